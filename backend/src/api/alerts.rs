@@ -1,6 +1,7 @@
 use actix_web::{web, HttpResponse, Responder, get, post, delete};
 use serde::{Deserialize, Serialize};
-use crate::services::alerts::{get_alerts, create_alert, delete_alert};
+use mongodb::Database;
+use crate::services::alerts::{get_alerts, get_alert_events, create_alert, delete_alert};
 use crate::utils::auth::AuthenticatedUser;
 
 #[derive(Debug, Deserialize)]
@@ -27,6 +28,20 @@ async fn list_alerts(
     }
 }
 
+#[get("/events")]
+async fn list_alert_events(
+    auth_user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> impl Responder {
+    match get_alert_events(&db, auth_user.user_id).await {
+        Ok(events) => HttpResponse::Ok().json(events),
+        Err(e) => {
+            let error_response = format!("Failed to fetch alert events: {}", e);
+            HttpResponse::InternalServerError().body(error_response)
+        }
+    }
+}
+
 #[post("")]
 async fn add_alert(
     auth_user: AuthenticatedUser,
@@ -62,6 +77,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/alerts")
             .service(list_alerts)
+            .service(list_alert_events)
             .service(add_alert)
             .service(remove_alert)
     );
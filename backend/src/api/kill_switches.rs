@@ -0,0 +1,77 @@
+use actix_web::{web, HttpResponse, Responder, get, post};
+use serde::Deserialize;
+use mongodb::Database;
+use crate::models::kill_switch::KillSwitchScope;
+use crate::services::kill_switch::{activate_kill_switch, get_kill_switches, reset_kill_switch};
+use crate::services::market_maker::MarketMakingEngine;
+use crate::utils::auth::AuthenticatedUser;
+
+#[derive(Debug, Deserialize)]
+pub struct KillSwitchRequest {
+    pub scope: KillSwitchScope,
+    // Strategy id or market pair; omitted for user-wide switches
+    pub target: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetKillSwitchRequest {
+    pub reason: String,
+}
+
+#[get("")]
+async fn list_kill_switches(
+    auth_user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> impl Responder {
+    match get_kill_switches(&db, auth_user.user_id).await {
+        Ok(kill_switches) => HttpResponse::Ok().json(kill_switches),
+        Err(e) => {
+            let error_response = format!("Failed to fetch kill switches: {}", e);
+            HttpResponse::InternalServerError().body(error_response)
+        }
+    }
+}
+
+#[post("")]
+async fn activate(
+    auth_user: AuthenticatedUser,
+    req: web::Json<KillSwitchRequest>,
+    db: web::Data<Database>,
+    engine: web::Data<MarketMakingEngine>,
+) -> impl Responder {
+    match activate_kill_switch(&db, &engine, auth_user.user_id, req.into_inner()).await {
+        Ok(kill_switch) => HttpResponse::Created().json(kill_switch),
+        Err(e) => {
+            let error_response = format!("Failed to activate kill switch: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+#[post("/{kill_switch_id}/reset")]
+async fn reset(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    req: web::Json<ResetKillSwitchRequest>,
+    db: web::Data<Database>,
+    engine: web::Data<MarketMakingEngine>,
+) -> impl Responder {
+    let kill_switch_id = path.into_inner();
+    match reset_kill_switch(&db, &engine, auth_user.user_id, kill_switch_id, &req.reason).await {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            let error_response = format!("Failed to reset kill switch: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/kill-switches")
+            .service(list_kill_switches)
+            .service(activate)
+            .service(reset)
+    );
+}
@@ -6,6 +6,9 @@ mod trading;
 mod orderbook;
 mod alerts;
 mod settings;
+mod kill_switches;
+mod pnl;
+mod rebalance;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -15,6 +18,9 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .configure(trading::config)
             .configure(orderbook::config)
             .configure(alerts::config)
-            .configure(settings::config),
+            .configure(settings::config)
+            .configure(kill_switches::config)
+            .configure(pnl::config)
+            .configure(rebalance::config),
     );
 } 
\ No newline at end of file
@@ -0,0 +1,45 @@
+use actix_web::{web, HttpResponse, Responder, get};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use mongodb::Database;
+use uuid::Uuid;
+use crate::services::pnl::{get_pnl, CostMethod, PnlGrouping};
+use crate::services::prices::PriceSource;
+use crate::utils::auth::AuthenticatedUser;
+
+#[derive(Debug, Deserialize)]
+pub struct PnlQuery {
+    // RFC 3339 bounds; fills before `from` still build the positions they close
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub group_by: PnlGrouping,
+    #[serde(default)]
+    pub method: CostMethod,
+    pub wallet_id: Option<Uuid>,
+    pub strategy_id: Option<Uuid>,
+    pub token: Option<String>,
+}
+
+#[get("")]
+async fn pnl_report(
+    auth_user: AuthenticatedUser,
+    query: web::Query<PnlQuery>,
+    db: web::Data<Database>,
+    prices: web::Data<PriceSource>,
+) -> impl Responder {
+    match get_pnl(&db, &prices, auth_user.user_id, query.into_inner()).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            let error_response = format!("Failed to compute PnL: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/pnl")
+            .service(pnl_report)
+    );
+}
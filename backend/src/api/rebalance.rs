@@ -0,0 +1,68 @@
+use actix_web::{web, HttpResponse, Responder, get, post};
+use serde::Deserialize;
+use mongodb::Database;
+use uuid::Uuid;
+use crate::models::rebalance::RebalanceTrigger;
+use crate::services::rebalancer::{get_report, list_reports, Rebalancer};
+use crate::utils::auth::AuthenticatedUser;
+
+#[derive(Debug, Deserialize)]
+pub struct RebalanceRequest {
+    // Runs preview the transfers unless this is explicitly false
+    pub dry_run: Option<bool>,
+}
+
+#[post("")]
+async fn rebalance(
+    auth_user: AuthenticatedUser,
+    req: web::Json<RebalanceRequest>,
+    rebalancer: web::Data<Rebalancer>,
+) -> impl Responder {
+    let dry_run = req.dry_run.unwrap_or(true);
+    match rebalancer.rebalance(auth_user.user_id, dry_run, RebalanceTrigger::Manual).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            let error_response = format!("Failed to rebalance wallets: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+#[get("/reports")]
+async fn reports(
+    auth_user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> impl Responder {
+    match list_reports(&db, auth_user.user_id).await {
+        Ok(reports) => HttpResponse::Ok().json(reports),
+        Err(e) => {
+            let error_response = format!("Failed to fetch rebalance reports: {}", e);
+            HttpResponse::InternalServerError().body(error_response)
+        }
+    }
+}
+
+#[get("/reports/{report_id}")]
+async fn report(
+    auth_user: AuthenticatedUser,
+    path: web::Path<Uuid>,
+    db: web::Data<Database>,
+) -> impl Responder {
+    let report_id = path.into_inner();
+    match get_report(&db, auth_user.user_id, report_id).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            let error_response = format!("Failed to fetch rebalance report: {}", e);
+            HttpResponse::NotFound().body(error_response)
+        }
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/rebalance")
+            .service(rebalance)
+            .service(reports)
+            .service(report)
+    );
+}
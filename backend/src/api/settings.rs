@@ -1,5 +1,7 @@
 use actix_web::{web, HttpResponse, Responder, get, put};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::models::settings::PriorityFeeLevel;
 use crate::services::settings::{get_user_settings, update_user_settings};
 use crate::utils::auth::AuthenticatedUser;
 
@@ -10,6 +12,16 @@ pub struct SettingsRequest {
     pub risk_level: Option<String>,
     pub notification_email: Option<String>,
     pub auto_rebalance: Option<bool>,
+    pub priority_fee_level: Option<PriorityFeeLevel>,
+    // Micro-lamports per compute unit
+    pub priority_fee_cap: Option<u64>,
+    pub max_slippage_bps: Option<u16>,
+    // Percentage points; unset uses REBALANCE_THRESHOLD_PCT
+    pub rebalance_threshold_pct: Option<f64>,
+    // One of the user's wallets
+    pub treasury_wallet_id: Option<Uuid>,
+    // SOL per UTC day; unset uses TOP_UP_DAILY_CAP
+    pub max_daily_top_up: Option<f64>,
 }
 
 #[get("")]
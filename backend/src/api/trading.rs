@@ -1,12 +1,29 @@
-use actix_web::{web, HttpResponse, Responder, get, post, put};
+use actix_web::{web, HttpResponse, Responder, ResponseError, delete, get, post, put};
 use serde::{Deserialize, Serialize};
-use crate::services::trading::{get_trading_strategies, create_strategy, update_strategy, execute_trade};
+use mongodb::Database;
+use crate::config::Config;
+use crate::services::aggregator::SwapAggregator;
+use crate::services::backtest::{get_backtest, list_backtests, start_backtest};
+use crate::services::market_maker::MarketMakingEngine;
+use crate::services::orders::{cancel_order_by_id, cancel_orders, get_order, get_orders};
+use crate::services::parent_orders::{cancel_parent_order, create_parent_order, get_parent_order, list_parent_orders};
+use crate::services::self_trade::get_self_trade_events;
+use crate::services::strategy_types::strategy_types;
+use crate::services::venues::paper::PaperVenue;
+use crate::models::order::OrderStatus;
+use crate::models::parent_order::{ExecutionAlgo, ParentOrder};
+use crate::models::strategy::{Strategy, StrategyKind};
+use crate::models::transaction::{StatusChange, Transaction, TransactionStatus};
+use crate::services::trading::{get_trading_strategies, get_strategy_detail, get_strategy_status, create_strategy, update_strategy, execute_trade, get_transaction_status};
 use crate::utils::auth::AuthenticatedUser;
 
 #[derive(Debug, Deserialize)]
 pub struct StrategyRequest {
     pub name: String,
-    pub strategy_type: String,
+    pub strategy_type: StrategyKind,
+    // Shape depends on `strategy_type`, see GET /api/trading/strategy-types
+    #[serde(default)]
+    pub params: Option<serde_json::Value>,
     pub trading_pair: String,
     pub execution_platform: String,
     pub min_trade_size: f64,
@@ -17,6 +34,10 @@ pub struct StrategyRequest {
     pub risk_alerts: bool,
     pub transaction_delay: i32,
     pub trade_frequency: i32,
+    #[serde(default)]
+    pub paper_trading: bool,
+    #[serde(default)]
+    pub max_slippage_bps: Option<u16>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -26,6 +47,83 @@ pub struct TradeRequest {
     pub amount: f64,
     pub token: String,
     pub price: Option<f64>,
+    pub strategy_id: Option<uuid::Uuid>,
+    // Tightens the strategy or user limit for this trade only
+    #[serde(default)]
+    pub max_slippage_bps: Option<u16>,
+    // Set on the children of a TWAP/VWAP parent order; never taken from the request body
+    #[serde(skip)]
+    pub parent_order_id: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderQuery {
+    pub strategy_id: Option<uuid::Uuid>,
+    pub market_pair: Option<String>,
+    pub status: Option<OrderStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkCancelQuery {
+    pub strategy_id: Option<uuid::Uuid>,
+    pub market_pair: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ParentOrderRequest {
+    pub wallet_id: uuid::Uuid,
+    pub action: String,  // "buy" or "sell"
+    pub amount: f64,
+    pub token: String,
+    pub algo: ExecutionAlgo,
+    pub duration_secs: u64,
+    pub slices: u32,
+    // Largest share of market volume each slice may take, e.g. 0.1 for 10%
+    pub participation_limit: f64,
+    pub limit_price: f64,
+    // VWAP weights, one per slice; defaults to an hourly intraday profile
+    pub volume_profile: Option<Vec<f64>>,
+    // Defaults to now
+    pub start_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub strategy_id: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BacktestRequest {
+    pub strategy_id: uuid::Uuid,
+    pub from: chrono::DateTime<chrono::Utc>,
+    pub to: chrono::DateTime<chrono::Utc>,
+    // Fall back to QUOTE_SPREAD_BPS and PAPER_MAKER_FEE_BPS
+    pub spread_bps: Option<f64>,
+    pub maker_fee_bps: Option<f64>,
+    #[serde(default)]
+    pub initial_base_balance: f64,
+    #[serde(default)]
+    pub initial_quote_balance: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StrategyDetailResponse {
+    #[serde(flatten)]
+    pub strategy: Strategy,
+    pub daily_volume_used: f64,
+    pub remaining_daily_volume: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ParentOrderDetailResponse {
+    #[serde(flatten)]
+    pub order: ParentOrder,
+    pub fills: Vec<Transaction>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionStatusResponse {
+    pub id: uuid::Uuid,
+    pub status: TransactionStatus,
+    pub transaction_hash: Option<String>,
+    pub error: Option<String>,
+    pub history: Vec<StatusChange>,
 }
 
 #[get("/strategies")]
@@ -74,27 +172,296 @@ async fn update_strategy_handler(
     }
 }
 
+#[get("/strategies/{strategy_id}")]
+async fn strategy_detail(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    db: web::Data<Database>,
+) -> impl Responder {
+    let strategy_id = path.into_inner();
+    match get_strategy_detail(&db, auth_user.user_id, strategy_id).await {
+        Ok((strategy, used)) => HttpResponse::Ok().json(StrategyDetailResponse {
+            remaining_daily_volume: (strategy.max_daily_volume - used).max(0.0),
+            daily_volume_used: used,
+            strategy,
+        }),
+        Err(e) => {
+            let error_response = format!("Failed to fetch strategy: {}", e);
+            HttpResponse::NotFound().body(error_response)
+        }
+    }
+}
+
+#[get("/strategies/{strategy_id}/status")]
+async fn strategy_status(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    db: web::Data<Database>,
+    engine: web::Data<MarketMakingEngine>,
+) -> impl Responder {
+    let strategy_id = path.into_inner();
+    match get_strategy_status(&db, &engine, auth_user.user_id, strategy_id).await {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => {
+            let error_response = format!("Failed to fetch strategy status: {}", e);
+            HttpResponse::NotFound().body(error_response)
+        }
+    }
+}
+
 #[post("/execute")]
 async fn execute_trade_handler(
     auth_user: AuthenticatedUser,
     req: web::Json<TradeRequest>,
-    db_pool: web::Data<sqlx::PgPool>,
+    db: web::Data<Database>,
+    config: web::Data<Config>,
+    engine: web::Data<MarketMakingEngine>,
+    aggregator: web::Data<dyn SwapAggregator>,
+    paper: web::Data<PaperVenue>,
 ) -> impl Responder {
-    match execute_trade(&db_pool, auth_user.user_id, req.into_inner()).await {
+    match execute_trade(&db, &config, &engine, aggregator.get_ref(), &paper, auth_user.user_id, req.into_inner()).await {
         Ok(transaction) => HttpResponse::Ok().json(transaction),
+        // Keep the status of the failure, e.g. 504 once submission retries run out
+        Err(e) => e.error_response(),
+    }
+}
+
+#[get("/orders")]
+async fn list_orders(
+    auth_user: AuthenticatedUser,
+    query: web::Query<OrderQuery>,
+    db: web::Data<Database>,
+) -> impl Responder {
+    let query = query.into_inner();
+    match get_orders(&db, auth_user.user_id, query.strategy_id, query.market_pair.as_deref(), query.status).await {
+        Ok(orders) => HttpResponse::Ok().json(orders),
+        Err(e) => {
+            let error_response = format!("Failed to fetch orders: {}", e);
+            HttpResponse::InternalServerError().body(error_response)
+        }
+    }
+}
+
+#[get("/orders/{order_id}")]
+async fn order_detail(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    db: web::Data<Database>,
+) -> impl Responder {
+    let order_id = path.into_inner();
+    match get_order(&db, auth_user.user_id, order_id).await {
+        Ok(order) => HttpResponse::Ok().json(order),
+        Err(e) => {
+            let error_response = format!("Failed to fetch order: {}", e);
+            HttpResponse::NotFound().body(error_response)
+        }
+    }
+}
+
+#[delete("/orders/{order_id}")]
+async fn cancel_order_handler(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    db: web::Data<Database>,
+    engine: web::Data<MarketMakingEngine>,
+) -> impl Responder {
+    let order_id = path.into_inner();
+    match cancel_order_by_id(&db, &engine, auth_user.user_id, order_id).await {
+        Ok(order) => HttpResponse::Ok().json(order),
+        Err(e) => {
+            let error_response = format!("Failed to cancel order: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+#[delete("/orders")]
+async fn cancel_orders_handler(
+    auth_user: AuthenticatedUser,
+    query: web::Query<BulkCancelQuery>,
+    db: web::Data<Database>,
+    engine: web::Data<MarketMakingEngine>,
+) -> impl Responder {
+    let query = query.into_inner();
+    match cancel_orders(&db, &engine, auth_user.user_id, query.strategy_id, query.market_pair.as_deref()).await {
+        Ok(orders) => HttpResponse::Ok().json(orders),
+        Err(e) => {
+            let error_response = format!("Failed to cancel orders: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+#[post("/parent-orders")]
+async fn add_parent_order(
+    auth_user: AuthenticatedUser,
+    req: web::Json<ParentOrderRequest>,
+    db: web::Data<Database>,
+) -> impl Responder {
+    match create_parent_order(&db, auth_user.user_id, req.into_inner()).await {
+        Ok(order) => HttpResponse::Created().json(order),
         Err(e) => {
-            let error_response = format!("Failed to execute trade: {}", e);
+            let error_response = format!("Failed to create parent order: {}", e);
             HttpResponse::BadRequest().body(error_response)
         }
     }
 }
 
+#[get("/parent-orders")]
+async fn list_parent_orders_handler(
+    auth_user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> impl Responder {
+    match list_parent_orders(&db, auth_user.user_id).await {
+        Ok(orders) => HttpResponse::Ok().json(orders),
+        Err(e) => {
+            let error_response = format!("Failed to fetch parent orders: {}", e);
+            HttpResponse::InternalServerError().body(error_response)
+        }
+    }
+}
+
+#[get("/parent-orders/{parent_order_id}")]
+async fn parent_order_detail(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    db: web::Data<Database>,
+) -> impl Responder {
+    let parent_order_id = path.into_inner();
+    match get_parent_order(&db, auth_user.user_id, parent_order_id).await {
+        Ok((order, fills)) => HttpResponse::Ok().json(ParentOrderDetailResponse { order, fills }),
+        Err(e) => {
+            let error_response = format!("Failed to fetch parent order: {}", e);
+            HttpResponse::NotFound().body(error_response)
+        }
+    }
+}
+
+#[post("/parent-orders/{parent_order_id}/cancel")]
+async fn cancel_parent_order_handler(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    db: web::Data<Database>,
+) -> impl Responder {
+    let parent_order_id = path.into_inner();
+    match cancel_parent_order(&db, auth_user.user_id, parent_order_id).await {
+        Ok(order) => HttpResponse::Ok().json(order),
+        Err(e) => {
+            let error_response = format!("Failed to cancel parent order: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+#[get("/transactions/{transaction_id}/status")]
+async fn transaction_status(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    db: web::Data<Database>,
+) -> impl Responder {
+    let transaction_id = path.into_inner();
+    match get_transaction_status(&db, auth_user.user_id, transaction_id).await {
+        Ok(transaction) => HttpResponse::Ok().json(TransactionStatusResponse {
+            id: transaction.id,
+            status: transaction.status,
+            transaction_hash: transaction.transaction_hash,
+            error: transaction.error,
+            history: transaction.status_history,
+        }),
+        Err(e) => {
+            let error_response = format!("Failed to fetch transaction status: {}", e);
+            HttpResponse::NotFound().body(error_response)
+        }
+    }
+}
+
+#[get("/strategy-types")]
+async fn list_strategy_types() -> impl Responder {
+    HttpResponse::Ok().json(strategy_types())
+}
+
+#[get("/self-trades")]
+async fn list_self_trades(
+    auth_user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> impl Responder {
+    match get_self_trade_events(&db, auth_user.user_id).await {
+        Ok(events) => HttpResponse::Ok().json(events),
+        Err(e) => {
+            let error_response = format!("Failed to fetch prevented self-trades: {}", e);
+            HttpResponse::InternalServerError().body(error_response)
+        }
+    }
+}
+
+#[post("/backtests")]
+async fn create_backtest(
+    auth_user: AuthenticatedUser,
+    req: web::Json<BacktestRequest>,
+    db: web::Data<Database>,
+    config: web::Data<Config>,
+) -> impl Responder {
+    match start_backtest(&db, &config, auth_user.user_id, req.into_inner()).await {
+        Ok(run) => HttpResponse::Accepted().json(run),
+        Err(e) => {
+            let error_response = format!("Failed to start backtest: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+#[get("/backtests")]
+async fn list_backtests_handler(
+    auth_user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> impl Responder {
+    match list_backtests(&db, auth_user.user_id).await {
+        Ok(runs) => HttpResponse::Ok().json(runs),
+        Err(e) => {
+            let error_response = format!("Failed to fetch backtests: {}", e);
+            HttpResponse::InternalServerError().body(error_response)
+        }
+    }
+}
+
+#[get("/backtests/{backtest_id}")]
+async fn backtest_detail(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    db: web::Data<Database>,
+) -> impl Responder {
+    let backtest_id = path.into_inner();
+    match get_backtest(&db, auth_user.user_id, backtest_id).await {
+        Ok(run) => HttpResponse::Ok().json(run),
+        Err(e) => {
+            let error_response = format!("Failed to fetch backtest: {}", e);
+            HttpResponse::NotFound().body(error_response)
+        }
+    }
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/trading")
             .service(list_strategies)
+            .service(list_strategy_types)
             .service(add_strategy)
             .service(update_strategy_handler)
+            .service(strategy_detail)
+            .service(strategy_status)
             .service(execute_trade_handler)
+            .service(list_orders)
+            .service(order_detail)
+            .service(cancel_order_handler)
+            .service(cancel_orders_handler)
+            .service(add_parent_order)
+            .service(list_parent_orders_handler)
+            .service(parent_order_detail)
+            .service(cancel_parent_order_handler)
+            .service(transaction_status)
+            .service(list_self_trades)
+            .service(create_backtest)
+            .service(list_backtests_handler)
+            .service(backtest_detail)
     );
 } 
\ No newline at end of file
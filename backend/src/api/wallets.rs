@@ -1,6 +1,16 @@
-use actix_web::{web, HttpResponse, Responder, get, post, delete};
+use actix_web::{web, HttpResponse, Responder, get, post, patch, delete};
 use serde::{Deserialize, Serialize};
-use crate::services::wallets::{get_wallets, add_wallet, remove_wallet, get_wallet_balance};
+use mongodb::Database;
+use std::fmt;
+use zeroize::Zeroize;
+use crate::config::Config;
+use crate::models::wallet::WalletStatus;
+use crate::services::market_maker::MarketMakingEngine;
+use crate::services::hd_wallets::{provision_wallets, recover_wallets};
+use crate::services::keystore::{export_mnemonic, export_wallet_key, import_wallet_key, Keystore};
+use crate::services::portfolio::{get_portfolio, get_wallet_portfolio};
+use crate::services::prices::PriceSource;
+use crate::services::wallets::{get_wallets, add_wallet, update_wallet, remove_wallet, get_wallet_balance};
 use crate::utils::auth::AuthenticatedUser;
 
 #[derive(Debug, Deserialize)]
@@ -10,6 +20,92 @@ pub struct AddWalletRequest {
     pub allocation_percentage: Option<f64>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct UpdateWalletRequest {
+    pub label: Option<String>,
+    pub allocation_percentage: Option<f64>,
+    pub status: Option<WalletStatus>,
+}
+
+/// A wallet's signing key, as a base58 secret key or the bytes of a `solana-keygen` JSON file.
+#[derive(Deserialize)]
+pub struct ImportKeyRequest {
+    pub secret_key: Option<String>,
+    pub keypair: Option<Vec<u8>>,
+}
+
+impl fmt::Debug for ImportKeyRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ImportKeyRequest { .. }")
+    }
+}
+
+impl Drop for ImportKeyRequest {
+    fn drop(&mut self) {
+        self.secret_key.zeroize();
+        self.keypair.zeroize();
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ExportKeyRequest {
+    pub password: String,
+}
+
+impl fmt::Debug for ExportKeyRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ExportKeyRequest { .. }")
+    }
+}
+
+impl Drop for ExportKeyRequest {
+    fn drop(&mut self) {
+        self.password.zeroize();
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProvisionWalletsRequest {
+    pub count: u32,
+    // Wallets are labelled "<label> #<index>"
+    pub label: Option<String>,
+    pub allocation_percentage: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct RecoverWalletsRequest {
+    // Only needed when the keystore does not hold the user's mnemonic
+    pub mnemonic: Option<String>,
+    // Derive at least this many wallets, from index 0; required until wallets have been derived for the user
+    pub count: Option<u32>,
+    // Labels and allocations for the wallets that have to be recreated; the rest are labelled
+    // "HD wallet #<index>" with no allocation
+    #[serde(default)]
+    pub wallets: Vec<RecoveredWallet>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecoveredWallet {
+    pub index: u32,
+    pub label: Option<String>,
+    pub allocation_percentage: Option<f64>,
+}
+
+impl fmt::Debug for RecoverWalletsRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecoverWalletsRequest")
+            .field("count", &self.count)
+            .field("wallets", &self.wallets)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for RecoverWalletsRequest {
+    fn drop(&mut self) {
+        self.mnemonic.zeroize();
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct WalletResponse {
     pub id: uuid::Uuid,
@@ -17,16 +113,16 @@ pub struct WalletResponse {
     pub label: Option<String>,
     pub balance: f64,
     pub allocation_percentage: f64,
-    pub status: String,
+    pub status: WalletStatus,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
 #[get("")]
 async fn list_wallets(
     auth_user: AuthenticatedUser,
-    db_pool: web::Data<sqlx::PgPool>,
+    db: web::Data<Database>,
 ) -> impl Responder {
-    match get_wallets(&db_pool, auth_user.user_id).await {
+    match get_wallets(&db, auth_user.user_id).await {
         Ok(wallets) => HttpResponse::Ok().json(wallets),
         Err(e) => {
             let error_response = format!("Failed to fetch wallets: {}", e);
@@ -39,10 +135,10 @@ async fn list_wallets(
 async fn create_wallet(
     auth_user: AuthenticatedUser,
     req: web::Json<AddWalletRequest>,
-    db_pool: web::Data<sqlx::PgPool>,
+    db: web::Data<Database>,
 ) -> impl Responder {
     match add_wallet(
-        &db_pool, 
+        &db, 
         auth_user.user_id, 
         &req.address, 
         req.label.as_deref(), 
@@ -56,14 +152,33 @@ async fn create_wallet(
     }
 }
 
+#[patch("/{wallet_id}")]
+async fn patch_wallet(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    req: web::Json<UpdateWalletRequest>,
+    db: web::Data<Database>,
+    engine: web::Data<MarketMakingEngine>,
+) -> impl Responder {
+    let wallet_id = path.into_inner();
+    match update_wallet(&db, &engine, auth_user.user_id, wallet_id, req.into_inner()).await {
+        Ok(wallet) => HttpResponse::Ok().json(wallet),
+        Err(e) => {
+            let error_response = format!("Failed to update wallet: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
 #[delete("/{wallet_id}")]
 async fn delete_wallet(
     auth_user: AuthenticatedUser,
     path: web::Path<uuid::Uuid>,
-    db_pool: web::Data<sqlx::PgPool>,
+    db: web::Data<Database>,
+    config: web::Data<Config>,
 ) -> impl Responder {
     let wallet_id = path.into_inner();
-    match remove_wallet(&db_pool, auth_user.user_id, wallet_id).await {
+    match remove_wallet(&db, &config, auth_user.user_id, wallet_id).await {
         Ok(_) => HttpResponse::NoContent().finish(),
         Err(e) => {
             let error_response = format!("Failed to remove wallet: {}", e);
@@ -76,10 +191,11 @@ async fn delete_wallet(
 async fn wallet_balance(
     auth_user: AuthenticatedUser,
     path: web::Path<uuid::Uuid>,
-    db_pool: web::Data<sqlx::PgPool>,
+    db: web::Data<Database>,
+    config: web::Data<Config>,
 ) -> impl Responder {
     let wallet_id = path.into_inner();
-    match get_wallet_balance(&db_pool, auth_user.user_id, wallet_id).await {
+    match get_wallet_balance(&db, &config, auth_user.user_id, wallet_id).await {
         Ok(balance) => HttpResponse::Ok().json(balance),
         Err(e) => {
             let error_response = format!("Failed to get wallet balance: {}", e);
@@ -88,12 +204,144 @@ async fn wallet_balance(
     }
 }
 
+#[get("/portfolio")]
+async fn portfolio(
+    auth_user: AuthenticatedUser,
+    db: web::Data<Database>,
+    config: web::Data<Config>,
+    prices: web::Data<PriceSource>,
+) -> impl Responder {
+    match get_portfolio(&db, &config, &prices, auth_user.user_id).await {
+        Ok(portfolio) => HttpResponse::Ok().json(portfolio),
+        Err(e) => {
+            let error_response = format!("Failed to get portfolio: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+#[get("/{wallet_id}/portfolio")]
+async fn wallet_portfolio(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    db: web::Data<Database>,
+    config: web::Data<Config>,
+    prices: web::Data<PriceSource>,
+) -> impl Responder {
+    let wallet_id = path.into_inner();
+    match get_wallet_portfolio(&db, &config, &prices, auth_user.user_id, wallet_id).await {
+        Ok(portfolio) => HttpResponse::Ok().json(portfolio),
+        Err(e) => {
+            let error_response = format!("Failed to get wallet portfolio: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+#[post("/hd")]
+async fn provision_hd_wallets(
+    auth_user: AuthenticatedUser,
+    req: web::Json<ProvisionWalletsRequest>,
+    db: web::Data<Database>,
+    keystore: web::Data<Keystore>,
+) -> impl Responder {
+    match provision_wallets(&db, &keystore, auth_user.user_id, req.into_inner()).await {
+        Ok(wallets) => HttpResponse::Created().json(wallets),
+        Err(e) => {
+            let error_response = format!("Failed to derive wallets: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+#[post("/hd/recover")]
+async fn recover_hd_wallets(
+    auth_user: AuthenticatedUser,
+    req: web::Json<RecoverWalletsRequest>,
+    db: web::Data<Database>,
+    keystore: web::Data<Keystore>,
+) -> impl Responder {
+    match recover_wallets(&db, &keystore, auth_user.user_id, req.into_inner()).await {
+        Ok(wallets) => HttpResponse::Ok().json(wallets),
+        Err(e) => {
+            let error_response = format!("Failed to recover wallets: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+#[post("/hd/mnemonic/export")]
+async fn export_hd_mnemonic(
+    auth_user: AuthenticatedUser,
+    req: web::Json<ExportKeyRequest>,
+    db: web::Data<Database>,
+    config: web::Data<Config>,
+    keystore: web::Data<Keystore>,
+) -> impl Responder {
+    match export_mnemonic(&db, &keystore, config.keystore_export_enabled, auth_user.user_id, &req.password).await {
+        Ok(exported) => HttpResponse::Ok()
+            .insert_header(("Cache-Control", "no-store"))
+            .json(exported),
+        Err(e) => {
+            let error_response = format!("Failed to export mnemonic: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+#[post("/{wallet_id}/key")]
+async fn import_key(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    req: web::Json<ImportKeyRequest>,
+    db: web::Data<Database>,
+    keystore: web::Data<Keystore>,
+) -> impl Responder {
+    let wallet_id = path.into_inner();
+    match import_wallet_key(&db, &keystore, auth_user.user_id, wallet_id, req.into_inner()).await {
+        Ok(imported) => HttpResponse::Ok().json(imported),
+        Err(e) => {
+            let error_response = format!("Failed to import wallet key: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
+#[post("/{wallet_id}/key/export")]
+async fn export_key(
+    auth_user: AuthenticatedUser,
+    path: web::Path<uuid::Uuid>,
+    req: web::Json<ExportKeyRequest>,
+    db: web::Data<Database>,
+    config: web::Data<Config>,
+    keystore: web::Data<Keystore>,
+) -> impl Responder {
+    let wallet_id = path.into_inner();
+    match export_wallet_key(&db, &keystore, config.keystore_export_enabled, auth_user.user_id, wallet_id, &req.password).await {
+        Ok(exported) => HttpResponse::Ok()
+            .insert_header(("Cache-Control", "no-store"))
+            .json(exported),
+        Err(e) => {
+            let error_response = format!("Failed to export wallet key: {}", e);
+            HttpResponse::BadRequest().body(error_response)
+        }
+    }
+}
+
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/wallets")
             .service(list_wallets)
+            .service(portfolio)
             .service(create_wallet)
+            .service(provision_hd_wallets)
+            .service(recover_hd_wallets)
+            .service(export_hd_mnemonic)
+            .service(patch_wallet)
             .service(delete_wallet)
             .service(wallet_balance)
+            .service(wallet_portfolio)
+            .service(import_key)
+            .service(export_key)
     );
 } 
\ No newline at end of file
@@ -0,0 +1,113 @@
+//! Replays recorded market data through a strategy and prints the resulting report as JSON.
+//!
+//! Usage:
+//!   backtest (--strategy <strategy.json> | --strategy-id <uuid>)
+//!            (--data <file.jsonl|file.csv> | --from <rfc3339> --to <rfc3339>)
+//!            [--spread-bps <bps>] [--maker-fee-bps <bps>]
+//!            [--base <amount>] [--quote <amount>] [--output <report.json>]
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use dotenv::dotenv;
+use std::collections::HashMap;
+use std::path::Path;
+use uuid::Uuid;
+
+use solana_market_nexus_backend::config::Config;
+use solana_market_nexus_backend::db;
+use solana_market_nexus_backend::models::backtest::BacktestParams;
+use solana_market_nexus_backend::models::strategy::Strategy;
+use solana_market_nexus_backend::services::backtest::{load_file, load_history, run_backtest};
+
+const USAGE: &str = "usage: backtest (--strategy <strategy.json> | --strategy-id <uuid>) \
+(--data <file.jsonl|file.csv> | --from <rfc3339> --to <rfc3339>) \
+[--spread-bps <bps>] [--maker-fee-bps <bps>] [--base <amount>] [--quote <amount>] [--output <report.json>]";
+
+fn parse_args() -> Result<HashMap<String, String>> {
+    let mut args = HashMap::new();
+    let mut iter = std::env::args().skip(1);
+    while let Some(flag) = iter.next() {
+        let name = flag
+            .strip_prefix("--")
+            .ok_or_else(|| anyhow!("Unexpected argument '{}'\n{}", flag, USAGE))?;
+        let value = iter.next().ok_or_else(|| anyhow!("Missing value for {}\n{}", flag, USAGE))?;
+        args.insert(name.to_string(), value);
+    }
+    Ok(args)
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .with_context(|| format!("Invalid timestamp '{}'", value))?
+        .with_timezone(&Utc))
+}
+
+fn parse_number(args: &HashMap<String, String>, name: &str, default: f64) -> Result<f64> {
+    match args.get(name) {
+        Some(value) => value.parse().with_context(|| format!("--{} must be a number", name)),
+        None => Ok(default),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+    let args = parse_args()?;
+    let config = Config::from_env();
+
+    // Only connect to MongoDB when the strategy or the market data lives there
+    let needs_db = args.contains_key("strategy-id") || !args.contains_key("data");
+    let database = if needs_db { Some(db::init_db(&config).await) } else { None };
+
+    let strategy = match (args.get("strategy"), args.get("strategy-id")) {
+        (Some(path), _) => {
+            let contents = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+            serde_json::from_str::<Strategy>(&contents).with_context(|| format!("Invalid strategy in {}", path))?
+        }
+        (None, Some(id)) => {
+            let id = Uuid::parse_str(id).context("--strategy-id must be a UUID")?;
+            Strategy::find_any_by_id(database.as_ref().expect("database is connected"), id)
+                .await?
+                .ok_or_else(|| anyhow!("Strategy {} not found", id))?
+        }
+        (None, None) => return Err(anyhow!("A strategy is required\n{}", USAGE)),
+    };
+
+    let snapshots = match args.get("data") {
+        Some(path) => load_file(Path::new(path), &strategy.trading_pair)?,
+        None => {
+            let from = parse_time(args.get("from").ok_or_else(|| anyhow!("--from is required\n{}", USAGE))?)?;
+            let to = parse_time(args.get("to").ok_or_else(|| anyhow!("--to is required\n{}", USAGE))?)?;
+            load_history(database.as_ref().expect("database is connected"), &strategy.trading_pair, from, to).await?
+        }
+    };
+    if snapshots.is_empty() {
+        return Err(anyhow!("No market data for {}", strategy.trading_pair));
+    }
+
+    let params = BacktestParams {
+        spread_bps: parse_number(&args, "spread-bps", config.quote_spread_bps)?,
+        maker_fee_bps: parse_number(&args, "maker-fee-bps", config.paper_maker_fee_bps)?,
+        initial_base_balance: parse_number(&args, "base", 0.0)?,
+        initial_quote_balance: parse_number(&args, "quote", 0.0)?,
+    };
+
+    let report = run_backtest(&strategy, &snapshots, &params);
+    eprintln!(
+        "{} snapshots, {} fills, volume {:.2}, total PnL {:.4}, max drawdown {:.4} ({:.2}%)",
+        snapshots.len(),
+        report.trade_count,
+        report.volume,
+        report.total_pnl,
+        report.max_drawdown,
+        report.max_drawdown_pct * 100.0
+    );
+
+    let json = serde_json::to_string_pretty(&report)?;
+    match args.get("output") {
+        Some(path) => std::fs::write(path, json).with_context(|| format!("Failed to write {}", path))?,
+        None => println!("{}", json),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,210 @@
+//! Reference remote signer. Holds keypairs and signs transactions for the backend over mutually
+//! authenticated TLS, checking every request against its signing policy first.
+//!
+//! Configured through the environment (or `.env`):
+//!   SIGNER_BIND                     address to listen on, default 127.0.0.1:8443
+//!   SIGNER_KEYPAIRS                 comma-separated `solana-keygen` keypair files
+//!   SIGNER_TLS_CERT                 server certificate chain (PEM)
+//!   SIGNER_TLS_KEY                  server private key (PKCS#8 PEM)
+//!   SIGNER_CLIENT_CA                CA that client certificates must be issued by (PEM)
+//!   SIGNER_ALLOWED_PURPOSES         comma-separated signing purposes, default all
+//!   SIGNER_ALLOWED_PROGRAMS         comma-separated program ids, default the backend's programs
+//!   SIGNER_MAX_COMPUTE_UNIT_PRICE   highest priority fee in micro-lamports per unit, default none
+//!   SIGNER_ALLOWED_DESTINATIONS     comma-separated addresses SOL and tokens may be sent to, default
+//!                                   the signer's own keys
+
+use actix_web::{get, middleware::Logger, post, web, App, HttpResponse, HttpServer, Responder};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use dotenv::dotenv;
+use log::{info, warn};
+use rustls::server::AllowAnyAuthenticatedClient;
+use rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::str::FromStr;
+
+use solana_market_nexus_backend::services::signer::{
+    KeysResponse, SignRequest, SignResponse, SigningPolicy, SigningPurpose, DEFAULT_ALLOWED_PROGRAMS,
+};
+
+const ALL_PURPOSES: &[SigningPurpose] = &[
+    SigningPurpose::Swap,
+    SigningPurpose::PlaceOrder,
+    SigningPurpose::CancelOrder,
+    SigningPurpose::SettleFunds,
+    SigningPurpose::Transfer,
+];
+
+struct SignerState {
+    keys: HashMap<Pubkey, Keypair>,
+    policy: SigningPolicy,
+}
+
+#[get("/keys")]
+async fn list_keys(state: web::Data<SignerState>) -> impl Responder {
+    HttpResponse::Ok().json(KeysResponse {
+        keys: state.keys.keys().map(|key| key.to_string()).collect(),
+    })
+}
+
+#[post("/sign")]
+async fn sign(state: web::Data<SignerState>, req: web::Json<SignRequest>) -> impl Responder {
+    let Ok(pubkey) = Pubkey::from_str(&req.pubkey) else {
+        return HttpResponse::BadRequest().body("Invalid public key");
+    };
+    let Some(keypair) = state.keys.get(&pubkey) else {
+        return HttpResponse::NotFound().body(format!("No key for {}", pubkey));
+    };
+    let message = match BASE64
+        .decode(&req.message)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| bincode::deserialize::<VersionedMessage>(&bytes).map_err(|e| e.to_string()))
+    {
+        Ok(message) => message,
+        Err(e) => return HttpResponse::BadRequest().body(format!("Invalid message: {}", e)),
+    };
+
+    if let Err(reason) = state.policy.check(&pubkey, &message, req.purpose) {
+        warn!("Refused to sign {} for {}: {}", req.purpose.as_str(), pubkey, reason);
+        return HttpResponse::Forbidden().body(reason);
+    }
+
+    // Sign the message as checked, not the bytes as received
+    let signature = keypair.sign_message(&message.serialize());
+    info!("Signed {} for {}: {}", req.purpose.as_str(), pubkey, signature);
+    HttpResponse::Ok().json(SignResponse {
+        signature: signature.to_string(),
+    })
+}
+
+fn required(name: &str) -> Result<String> {
+    env::var(name).map_err(|_| anyhow!("{} must be set", name))
+}
+
+fn list(name: &str) -> Option<Vec<String>> {
+    env::var(name).ok().map(|value| {
+        value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    })
+}
+
+fn load_keys() -> Result<HashMap<Pubkey, Keypair>> {
+    let paths = list("SIGNER_KEYPAIRS").ok_or_else(|| anyhow!("SIGNER_KEYPAIRS must be set"))?;
+    let mut keys = HashMap::new();
+    for path in paths {
+        let keypair = read_keypair_file(&path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))?;
+        keys.insert(keypair.pubkey(), keypair);
+    }
+    Ok(keys)
+}
+
+fn load_policy(keys: &HashMap<Pubkey, Keypair>) -> Result<SigningPolicy> {
+    let allowed_purposes = match list("SIGNER_ALLOWED_PURPOSES") {
+        Some(purposes) => purposes
+            .iter()
+            .map(|purpose| purpose.parse::<SigningPurpose>().map_err(|e| anyhow!(e)))
+            .collect::<Result<_>>()?,
+        None => ALL_PURPOSES.to_vec(),
+    };
+    let allowed_programs = match list("SIGNER_ALLOWED_PROGRAMS") {
+        Some(programs) => programs,
+        None => DEFAULT_ALLOWED_PROGRAMS.iter().map(|program| program.to_string()).collect(),
+    }
+    .iter()
+    .map(|program| Pubkey::from_str(program).with_context(|| format!("Invalid program id '{}'", program)))
+    .collect::<Result<_>>()?;
+    let max_compute_unit_price = match env::var("SIGNER_MAX_COMPUTE_UNIT_PRICE") {
+        Ok(value) => Some(value.parse().context("SIGNER_MAX_COMPUTE_UNIT_PRICE must be a valid integer")?),
+        Err(_) => None,
+    };
+    let allowed_destinations = match list("SIGNER_ALLOWED_DESTINATIONS") {
+        Some(destinations) => destinations
+            .iter()
+            .map(|address| Pubkey::from_str(address).with_context(|| format!("Invalid destination '{}'", address)))
+            .collect::<Result<_>>()?,
+        // Funds may move between the wallets this signer holds
+        None => keys.keys().copied().collect(),
+    };
+
+    Ok(SigningPolicy {
+        allowed_purposes,
+        allowed_programs,
+        max_compute_unit_price,
+        allowed_destinations,
+    })
+}
+
+fn read_certs(path: &str) -> Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(File::open(path).with_context(|| format!("Failed to open {}", path))?);
+    let certs = rustls_pemfile::certs(&mut reader).with_context(|| format!("Failed to read {}", path))?;
+    if certs.is_empty() {
+        return Err(anyhow!("No certificates in {}", path));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+/// Server TLS that only accepts clients presenting a certificate issued by `SIGNER_CLIENT_CA`.
+fn tls_config() -> Result<ServerConfig> {
+    let certs = read_certs(&required("SIGNER_TLS_CERT")?)?;
+    let key_path = required("SIGNER_TLS_KEY")?;
+    let mut reader = BufReader::new(File::open(&key_path).with_context(|| format!("Failed to open {}", key_path))?);
+    let key = rustls_pemfile::pkcs8_private_keys(&mut reader)?
+        .into_iter()
+        .next()
+        .map(PrivateKey)
+        .ok_or_else(|| anyhow!("No PKCS#8 private key in {}", key_path))?;
+
+    let mut client_roots = RootCertStore::empty();
+    for ca in read_certs(&required("SIGNER_CLIENT_CA")?)? {
+        client_roots.add(&ca)?;
+    }
+
+    Ok(ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(client_roots).boxed())
+        .with_single_cert(certs, key)?)
+}
+
+#[actix_web::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+
+    let keys = load_keys()?;
+    let policy = load_policy(&keys)?;
+    let tls = tls_config()?;
+    let bind = env::var("SIGNER_BIND").unwrap_or_else(|_| "127.0.0.1:8443".to_string());
+
+    for key in keys.keys() {
+        info!("Signing for {}", key);
+    }
+    info!(
+        "Allowing {} for programs {:?}",
+        policy.allowed_purposes.iter().map(|p| p.as_str()).collect::<Vec<_>>().join(", "),
+        policy.allowed_programs.iter().map(|p| p.to_string()).collect::<Vec<_>>()
+    );
+    info!("Starting remote signer at https://{}", bind);
+
+    let state = web::Data::new(SignerState { keys, policy });
+    HttpServer::new(move || {
+        App::new()
+            .wrap(Logger::default())
+            .app_data(state.clone())
+            .service(list_keys)
+            .service(sign)
+    })
+    .bind_rustls_021(bind, tls)?
+    .run()
+    .await?;
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
 
+use crate::services::self_trade::SelfTradePolicy;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub mongodb_uri: String,
@@ -8,6 +11,46 @@ pub struct Config {
     pub jwt_secret: String,
     pub jwt_expiration: i64,
     pub solana_rpc_url: String,
+    pub engine_sync_interval_secs: u64,
+    pub quote_spread_bps: f64,
+    pub aggregator_url: String,
+    // Oldest a cached token price may be before it is quoted again
+    pub price_max_age_secs: i64,
+    pub default_slippage_bps: u16,
+    pub trading_keypair_path: Option<String>,
+    pub openbook_markets: HashMap<String, String>,
+    pub confirmation_poll_interval_secs: u64,
+    pub paper_trading: bool,
+    pub paper_maker_fee_bps: f64,
+    pub paper_taker_fee_bps: f64,
+    pub circuit_breaker_interval_secs: u64,
+    pub breaker_max_consecutive_failures: usize,
+    // Daily loss per strategy, in the quote token, that trips its kill switch; 0 disables the check
+    pub breaker_max_daily_loss: f64,
+    pub breaker_max_market_data_age_secs: i64,
+    pub breaker_max_rpc_failures: u32,
+    // How often each traded market's book is added to the backtest history
+    pub market_data_record_interval_secs: u64,
+    pub self_trade_policy: SelfTradePolicy,
+    pub parent_order_interval_secs: u64,
+    // Signed transactions sent before giving up, each with its own blockhash
+    pub submit_max_attempts: u32,
+    pub rebroadcast_interval_ms: u64,
+    // Raise in the compute unit price for each new attempt, in percent; 0 keeps the price
+    pub resubmit_fee_bump_pct: f64,
+    // Lets owners download imported wallet keys; the keystore passphrase itself is never part of the config
+    pub keystore_export_enabled: bool,
+    pub rebalance_interval_secs: u64,
+    // Drift from a wallet's target allocation, in percentage points, that triggers a rebalance
+    pub rebalance_threshold_pct: f64,
+    pub top_up_interval_secs: u64,
+    // SOL a user's treasury may send per UTC day unless their settings say otherwise
+    pub top_up_daily_cap: f64,
+    // Signing service for keys the backend does not hold, reached over mutually authenticated TLS
+    pub remote_signer_url: Option<String>,
+    pub remote_signer_ca_cert: Option<String>,
+    pub remote_signer_client_cert: Option<String>,
+    pub remote_signer_client_key: Option<String>,
 }
 
 impl Config {
@@ -22,6 +65,117 @@ impl Config {
                 .expect("JWT_EXPIRATION must be a valid integer"),
             solana_rpc_url: env::var("SOLANA_RPC_URL")
                 .unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string()),
+            engine_sync_interval_secs: env::var("ENGINE_SYNC_INTERVAL_SECS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .expect("ENGINE_SYNC_INTERVAL_SECS must be a valid integer"),
+            quote_spread_bps: env::var("QUOTE_SPREAD_BPS")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .expect("QUOTE_SPREAD_BPS must be a valid number"),
+            aggregator_url: env::var("AGGREGATOR_URL")
+                .unwrap_or_else(|_| "https://quote-api.jup.ag/v6".to_string()),
+            price_max_age_secs: env::var("PRICE_MAX_AGE_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .expect("PRICE_MAX_AGE_SECS must be a valid integer"),
+            default_slippage_bps: env::var("DEFAULT_SLIPPAGE_BPS")
+                .unwrap_or_else(|_| "50".to_string())
+                .parse()
+                .expect("DEFAULT_SLIPPAGE_BPS must be a valid integer"),
+            trading_keypair_path: env::var("TRADING_KEYPAIR_PATH").ok(),
+            openbook_markets: parse_pairs(&env::var("OPENBOOK_MARKETS").unwrap_or_default()),
+            confirmation_poll_interval_secs: env::var("CONFIRMATION_POLL_INTERVAL_SECS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .expect("CONFIRMATION_POLL_INTERVAL_SECS must be a valid integer"),
+            paper_trading: env::var("PAPER_TRADING")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            paper_maker_fee_bps: env::var("PAPER_MAKER_FEE_BPS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("PAPER_MAKER_FEE_BPS must be a valid number"),
+            paper_taker_fee_bps: env::var("PAPER_TAKER_FEE_BPS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .expect("PAPER_TAKER_FEE_BPS must be a valid number"),
+            circuit_breaker_interval_secs: env::var("CIRCUIT_BREAKER_INTERVAL_SECS")
+                .unwrap_or_else(|_| "15".to_string())
+                .parse()
+                .expect("CIRCUIT_BREAKER_INTERVAL_SECS must be a valid integer"),
+            breaker_max_consecutive_failures: env::var("BREAKER_MAX_CONSECUTIVE_FAILURES")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .expect("BREAKER_MAX_CONSECUTIVE_FAILURES must be a valid integer"),
+            breaker_max_daily_loss: env::var("BREAKER_MAX_DAILY_LOSS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .expect("BREAKER_MAX_DAILY_LOSS must be a valid number"),
+            breaker_max_market_data_age_secs: env::var("BREAKER_MAX_MARKET_DATA_AGE_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .expect("BREAKER_MAX_MARKET_DATA_AGE_SECS must be a valid integer"),
+            breaker_max_rpc_failures: env::var("BREAKER_MAX_RPC_FAILURES")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .expect("BREAKER_MAX_RPC_FAILURES must be a valid integer"),
+            market_data_record_interval_secs: env::var("MARKET_DATA_RECORD_INTERVAL_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .expect("MARKET_DATA_RECORD_INTERVAL_SECS must be a valid integer"),
+            self_trade_policy: env::var("SELF_TRADE_POLICY")
+                .unwrap_or_else(|_| "reject".to_string())
+                .parse()
+                .expect("SELF_TRADE_POLICY must be one of reject, cancel_resting or reprice"),
+            parent_order_interval_secs: env::var("PARENT_ORDER_INTERVAL_SECS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .expect("PARENT_ORDER_INTERVAL_SECS must be a valid integer"),
+            submit_max_attempts: env::var("SUBMIT_MAX_ATTEMPTS")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .expect("SUBMIT_MAX_ATTEMPTS must be a valid integer"),
+            rebroadcast_interval_ms: env::var("REBROADCAST_INTERVAL_MS")
+                .unwrap_or_else(|_| "2000".to_string())
+                .parse()
+                .expect("REBROADCAST_INTERVAL_MS must be a valid integer"),
+            resubmit_fee_bump_pct: env::var("RESUBMIT_FEE_BUMP_PCT")
+                .unwrap_or_else(|_| "25".to_string())
+                .parse()
+                .expect("RESUBMIT_FEE_BUMP_PCT must be a valid number"),
+            keystore_export_enabled: env::var("KEYSTORE_EXPORT_ENABLED")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            rebalance_interval_secs: env::var("REBALANCE_INTERVAL_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .expect("REBALANCE_INTERVAL_SECS must be a valid integer"),
+            rebalance_threshold_pct: env::var("REBALANCE_THRESHOLD_PCT")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .expect("REBALANCE_THRESHOLD_PCT must be a valid number"),
+            top_up_interval_secs: env::var("TOP_UP_INTERVAL_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .expect("TOP_UP_INTERVAL_SECS must be a valid integer"),
+            top_up_daily_cap: env::var("TOP_UP_DAILY_CAP")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .expect("TOP_UP_DAILY_CAP must be a valid number"),
+            remote_signer_url: env::var("REMOTE_SIGNER_URL").ok(),
+            remote_signer_ca_cert: env::var("REMOTE_SIGNER_CA_CERT").ok(),
+            remote_signer_client_cert: env::var("REMOTE_SIGNER_CLIENT_CERT").ok(),
+            remote_signer_client_key: env::var("REMOTE_SIGNER_CLIENT_KEY").ok(),
         }
     }
+}
+
+// Parses "SOL/USDC=<address>,SOL/USDT=<address>" into a map keyed by market pair
+fn parse_pairs(value: &str) -> HashMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(pair, address)| (pair.trim().to_string(), address.trim().to_string()))
+        .collect()
 } 
\ No newline at end of file
@@ -1,15 +1,12 @@
 use actix_cors::Cors;
 use actix_web::{middleware::Logger, web, App, HttpServer};
 use dotenv::dotenv;
-use log::info;
+use log::{info, warn};
 use std::env;
+use std::sync::Arc;
+use zeroize::Zeroizing;
 
-mod api;
-mod config;
-mod db;
-mod models;
-mod services;
-mod utils;
+use solana_market_nexus_backend::{api, config, db, services};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -19,6 +16,70 @@ async fn main() -> std::io::Result<()> {
     let config = config::Config::from_env();
     let db = db::init_db(&config).await;
 
+    // Simulated venue shared by paper strategies and paper manual trades
+    let paper = Arc::new(services::venues::paper::PaperVenue::new(
+        db.clone(),
+        config.paper_maker_fee_bps,
+        config.paper_taker_fee_bps,
+    ));
+
+    // Imported wallet keys can only sign once the keystore is unlocked. The passphrase is read
+    // here rather than into the config so it never ends up in a log line.
+    let keystore = match env::var("KEYSTORE_PASSPHRASE") {
+        Ok(passphrase) => {
+            let passphrase = Zeroizing::new(passphrase);
+            env::remove_var("KEYSTORE_PASSPHRASE");
+            services::keystore::Keystore::unlock(db.clone(), passphrase.as_bytes())
+                .await
+                .expect("Failed to unlock the keystore")
+        }
+        Err(_) => {
+            warn!("KEYSTORE_PASSPHRASE is not set; imported wallet keys cannot be used");
+            services::keystore::Keystore::locked(db.clone())
+        }
+    };
+    let keystore = Arc::new(keystore);
+
+    // Transactions are signed by the keystore, the remote signer or the trading keypair
+    let signers = Arc::new(
+        services::signer::Signers::new(keystore.clone(), &config).expect("Failed to configure the remote signer"),
+    );
+
+    // Start the background market making runtime
+    let engine = services::market_maker::MarketMakingEngine::new(db.clone(), config.clone(), paper.clone(), signers.clone());
+    engine.start();
+
+    // Trip kill switches automatically when failures, losses, stale data or RPC outages pile up
+    services::kill_switch::CircuitBreaker::new(db.clone(), config.clone(), engine.clone()).start();
+
+    // Record each traded market's book so backtests can replay it
+    services::market_recorder::MarketDataRecorder::new(db.clone(), config.clone(), engine.clone()).start();
+
+    // Keep wallets above the user's minimum SOL balance from their treasury wallet
+    services::top_ups::TopUpMonitor::new(db.clone(), config.clone(), engine.clone()).start();
+
+    // Track submitted transactions until they are finalized, fail or expire
+    services::confirmations::ConfirmationTracker::new(db.clone(), &config).start();
+
+    let aggregator: Arc<dyn services::aggregator::SwapAggregator> =
+        Arc::new(services::aggregator::JupiterClient::new(&config.aggregator_url));
+
+    // Token prices for valuations, quoted from the aggregator's routes
+    let prices = Arc::new(services::prices::PriceSource::new(aggregator.clone(), config.price_max_age_secs));
+
+    // Work TWAP/VWAP parent orders as their child slices come due
+    services::parent_orders::ParentOrderExecutor::new(
+        db.clone(),
+        config.clone(),
+        engine.clone(),
+        aggregator.clone(),
+        paper.clone(),
+    ).start();
+
+    // Move funds between each user's wallets when they drift from their target allocations
+    let rebalancer = Arc::new(services::rebalancer::Rebalancer::new(db.clone(), config.clone(), signers, prices.clone()));
+    rebalancer.clone().start();
+
     let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
     let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
     let server_url = format!("{}:{}", host, port);
@@ -37,6 +98,12 @@ async fn main() -> std::io::Result<()> {
             .wrap(Logger::default())
             .app_data(web::Data::new(db.clone()))
             .app_data(web::Data::new(config.clone()))
+            .app_data(web::Data::new(engine.clone()))
+            .app_data(web::Data::from(aggregator.clone()))
+            .app_data(web::Data::from(prices.clone()))
+            .app_data(web::Data::from(paper.clone()))
+            .app_data(web::Data::from(keystore.clone()))
+            .app_data(web::Data::from(rebalancer.clone()))
             .configure(api::config)
     })
     .bind(server_url)?
@@ -0,0 +1,85 @@
+use bson::doc;
+use chrono::{DateTime, Utc};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertEventKind {
+    // A wallet fell below min_wallet_balance and could not be topped up
+    LowBalance,
+}
+
+impl AlertEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertEventKind::LowBalance => "low_balance",
+        }
+    }
+}
+
+/// Something the backend raised for the user's attention, as opposed to an `Alert` they configured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertEvent {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: AlertEventKind,
+    pub wallet_id: Option<Uuid>,
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AlertEvent {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("alert_events")
+    }
+
+    pub async fn find_by_user(db: &Database, user_id: Uuid, limit: i64) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "user_id": user_id };
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "created_at": -1 })
+            .limit(limit)
+            .build();
+
+        let cursor = Self::collection(db).find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn raised_since(
+        db: &Database,
+        user_id: Uuid,
+        kind: AlertEventKind,
+        wallet_id: Option<Uuid>,
+        since: DateTime<Utc>,
+    ) -> Result<bool, mongodb::error::Error> {
+        let filter = doc! {
+            "user_id": user_id,
+            "kind": kind.as_str(),
+            "wallet_id": wallet_id,
+            "created_at": { "$gte": since },
+        };
+        Ok(Self::collection(db).find_one(filter, None).await?.is_some())
+    }
+
+    pub async fn raise(
+        db: &Database,
+        user_id: Uuid,
+        kind: AlertEventKind,
+        wallet_id: Option<Uuid>,
+        message: &str,
+    ) -> Result<Self, mongodb::error::Error> {
+        let event = Self {
+            id: Uuid::new_v4(),
+            user_id,
+            kind,
+            wallet_id,
+            message: message.to_string(),
+            created_at: Utc::now(),
+        };
+
+        Self::collection(db).insert_one(&event, None).await?;
+        Ok(event)
+    }
+}
@@ -0,0 +1,175 @@
+use bson::{doc, Document};
+use chrono::{DateTime, Utc};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::services::venues::OrderSide;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BacktestStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl BacktestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BacktestStatus::Pending => "pending",
+            BacktestStatus::Running => "running",
+            BacktestStatus::Completed => "completed",
+            BacktestStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Knobs for a single backtest run that are not part of the strategy itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BacktestParams {
+    pub spread_bps: f64,
+    pub maker_fee_bps: f64,
+    pub initial_base_balance: f64,
+    pub initial_quote_balance: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BacktestFill {
+    pub timestamp: DateTime<Utc>,
+    pub side: OrderSide,
+    pub price: f64,
+    pub size: f64,
+    pub fee: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EquityPoint {
+    pub timestamp: DateTime<Utc>,
+    pub mid_price: f64,
+    pub equity: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BacktestReport {
+    // Left out of run listings, so absent when a report is read back from one
+    #[serde(default)]
+    pub fills: Vec<BacktestFill>,
+    #[serde(default)]
+    pub equity_curve: Vec<EquityPoint>,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub total_pnl: f64,
+    pub fees_paid: f64,
+    // Largest peak-to-trough fall in equity, in the quote token and as a fraction of the peak
+    pub max_drawdown: f64,
+    pub max_drawdown_pct: f64,
+    pub volume: f64,
+    pub trade_count: usize,
+    pub final_base_balance: f64,
+    pub final_quote_balance: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BacktestRun {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub strategy_id: Uuid,
+    pub market_pair: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub params: BacktestParams,
+    pub status: BacktestStatus,
+    pub report: Option<BacktestReport>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl BacktestRun {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("backtests")
+    }
+
+    pub async fn find_by_id(db: &Database, id: Uuid, user_id: Uuid) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! { "_id": id, "user_id": user_id };
+        Self::collection(db).find_one(filter, None).await
+    }
+
+    pub async fn find_by_user(db: &Database, user_id: Uuid) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "user_id": user_id };
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "created_at": -1 })
+            .projection(doc! { "report.fills": 0, "report.equity_curve": 0 })
+            .build();
+
+        let cursor = Self::collection(db).find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn create(
+        db: &Database,
+        user_id: Uuid,
+        strategy_id: Uuid,
+        market_pair: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        params: BacktestParams,
+    ) -> Result<Self, mongodb::error::Error> {
+        let run = Self {
+            id: Uuid::new_v4(),
+            user_id,
+            strategy_id,
+            market_pair: market_pair.to_string(),
+            from,
+            to,
+            params,
+            status: BacktestStatus::Pending,
+            report: None,
+            error: None,
+            created_at: Utc::now(),
+            completed_at: None,
+        };
+
+        Self::collection(db).insert_one(&run, None).await?;
+        Ok(run)
+    }
+
+    pub async fn set_running(db: &Database, id: Uuid) -> Result<(), mongodb::error::Error> {
+        let filter = doc! { "_id": id };
+        let update = doc! { "$set": { "status": BacktestStatus::Running.as_str() } };
+        Self::collection(db).update_one(filter, update, None).await?;
+        Ok(())
+    }
+
+    pub async fn complete(db: &Database, id: Uuid, report: &BacktestReport) -> Result<(), mongodb::error::Error> {
+        let report = bson::to_bson(report).map_err(|e| {
+            mongodb::error::Error::from(mongodb::error::ErrorKind::InvalidArgument { message: e.to_string() })
+        })?;
+        let filter = doc! { "_id": id };
+        let update = doc! {
+            "$set": {
+                "status": BacktestStatus::Completed.as_str(),
+                "report": report,
+                "completed_at": Utc::now(),
+            }
+        };
+        Self::collection(db).update_one(filter, update, None).await?;
+        Ok(())
+    }
+
+    pub async fn fail(db: &Database, id: Uuid, error: &str) -> Result<(), mongodb::error::Error> {
+        let filter = doc! { "_id": id };
+        let update = doc! {
+            "$set": {
+                "status": BacktestStatus::Failed.as_str(),
+                "error": error,
+                "completed_at": Utc::now(),
+            }
+        };
+        Self::collection(db).update_one(filter, update, None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use bson::doc;
+use chrono::{DateTime, Utc};
+use mongodb::options::{ReplaceOptions, UpdateOptions};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::strategy::GridParams;
+use crate::services::venues::OrderSide;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GridLevelStatus {
+    // The gap in the ladder, no order at this price
+    Idle,
+    // An order should rest here but has not been placed yet
+    Pending,
+    Live,
+    // The last order at this level filled and its replacement went to the neighbouring level
+    Filled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridLevel {
+    pub index: u32,
+    pub price: f64,
+    // Side of the order that rests, or should rest, at this level
+    pub side: Option<OrderSide>,
+    pub status: GridLevelStatus,
+    pub client_order_id: Option<u64>,
+    pub signature: Option<String>,
+    // Price of the fill that opened the position this level's order closes
+    pub entry_price: Option<f64>,
+    pub fill_count: u32,
+    pub last_filled_at: Option<DateTime<Utc>>,
+    pub realized_pnl: f64,
+}
+
+/// Ladder of a grid strategy's orders, persisted so the grid resumes where it left off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridState {
+    // One grid per strategy, keyed by the strategy id
+    #[serde(rename = "_id")]
+    pub strategy_id: Uuid,
+    pub user_id: Uuid,
+    pub market_pair: String,
+    // Parameters the ladder was built from; the grid is rebuilt when they change
+    pub params: GridParams,
+    pub levels: Vec<GridLevel>,
+    // Includes PnL from ladders this grid was rebuilt from
+    pub realized_pnl: f64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl GridState {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("grid_states")
+    }
+
+    pub async fn find_by_strategy(db: &Database, strategy_id: Uuid) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! { "_id": strategy_id };
+        Self::collection(db).find_one(filter, None).await
+    }
+
+    pub async fn save(db: &Database, state: &mut Self) -> Result<(), mongodb::error::Error> {
+        state.updated_at = Utc::now();
+        let filter = doc! { "_id": state.strategy_id };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        Self::collection(db).replace_one(filter, &*state, options).await?;
+        Ok(())
+    }
+
+    /// Marks live levels for re-placement after their orders were cancelled.
+    pub async fn requeue_live(db: &Database, strategy_id: Uuid) -> Result<(), mongodb::error::Error> {
+        let filter = doc! { "_id": strategy_id };
+        let update = doc! {
+            "$set": {
+                "levels.$[live].status": "pending",
+                "levels.$[live].client_order_id": null,
+                "levels.$[live].signature": null,
+                "updated_at": Utc::now(),
+            }
+        };
+        let options = UpdateOptions::builder()
+            .array_filters(vec![doc! { "live.status": "live" }])
+            .build();
+        Self::collection(db).update_one(filter, update, options).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use bson::doc;
+use chrono::{DateTime, Utc};
+use mongodb::options::{FindOneAndUpdateOptions, ReplaceOptions, ReturnDocument};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// The keystore has a single header document
+const HEADER_ID: &str = "keystore";
+
+/// How the keystore's master key is derived from the passphrase, plus a sealed known value
+/// that tells a wrong passphrase apart from the right one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeystoreHeader {
+    #[serde(rename = "_id")]
+    pub id: String,
+    // Base64; Argon2id salt
+    pub salt: String,
+    // Argon2id memory in KiB, iterations and lanes
+    pub memory_cost: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+    // Base64
+    pub check_nonce: String,
+    pub check_ciphertext: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl KeystoreHeader {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("keystore")
+    }
+
+    pub fn new(
+        salt: String,
+        memory_cost: u32,
+        time_cost: u32,
+        parallelism: u32,
+        check_nonce: String,
+        check_ciphertext: String,
+    ) -> Self {
+        Self {
+            id: HEADER_ID.to_string(),
+            salt,
+            memory_cost,
+            time_cost,
+            parallelism,
+            check_nonce,
+            check_ciphertext,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub async fn find(db: &Database) -> Result<Option<Self>, mongodb::error::Error> {
+        Self::collection(db).find_one(doc! { "_id": HEADER_ID }, None).await
+    }
+
+    pub async fn create(db: &Database, header: &Self) -> Result<(), mongodb::error::Error> {
+        Self::collection(db).insert_one(header, None).await?;
+        Ok(())
+    }
+}
+
+/// A wallet's keypair sealed with the keystore's master key. Deliberately not `Debug`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SealedKey {
+    #[serde(rename = "_id")]
+    pub wallet_id: Uuid,
+    pub user_id: Uuid,
+    pub public_key: String,
+    // Base64
+    pub nonce: String,
+    pub ciphertext: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SealedKey {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("wallet_keys")
+    }
+
+    pub async fn find_by_wallet(db: &Database, wallet_id: Uuid) -> Result<Option<Self>, mongodb::error::Error> {
+        Self::collection(db).find_one(doc! { "_id": wallet_id }, None).await
+    }
+
+    /// Stores the key, replacing one imported earlier for the same wallet.
+    pub async fn save(db: &Database, key: &Self) -> Result<(), mongodb::error::Error> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        Self::collection(db).replace_one(doc! { "_id": key.wallet_id }, key, options).await?;
+        Ok(())
+    }
+
+    pub async fn delete(db: &Database, wallet_id: Uuid) -> Result<(), mongodb::error::Error> {
+        Self::collection(db).delete_one(doc! { "_id": wallet_id }, None).await?;
+        Ok(())
+    }
+}
+
+/// A user's master mnemonic sealed with the keystore's master key, plus the next account index
+/// to derive wallets from. Deliberately not `Debug`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SealedMnemonic {
+    #[serde(rename = "_id")]
+    pub user_id: Uuid,
+    // Base64
+    pub nonce: String,
+    pub ciphertext: String,
+    pub next_index: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SealedMnemonic {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("wallet_mnemonics")
+    }
+
+    pub async fn find_by_user(db: &Database, user_id: Uuid) -> Result<Option<Self>, mongodb::error::Error> {
+        Self::collection(db).find_one(doc! { "_id": user_id }, None).await
+    }
+
+    /// Fails with a duplicate key error if the user already has a mnemonic.
+    pub async fn create(db: &Database, mnemonic: &Self) -> Result<(), mongodb::error::Error> {
+        Self::collection(db).insert_one(mnemonic, None).await?;
+        Ok(())
+    }
+
+    /// Claims `count` consecutive account indices and returns the first of them.
+    pub async fn reserve_indices(db: &Database, user_id: Uuid, count: u32) -> Result<Option<u32>, mongodb::error::Error> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::Before)
+            .build();
+        let before = Self::collection(db)
+            .find_one_and_update(doc! { "_id": user_id }, doc! { "$inc": { "next_index": count } }, options)
+            .await?;
+        Ok(before.map(|m| m.next_index))
+    }
+
+    /// Raises the next account index to `next_index` unless it is already past it.
+    pub async fn raise_next_index(db: &Database, user_id: Uuid, next_index: u32) -> Result<(), mongodb::error::Error> {
+        Self::collection(db)
+            .update_one(doc! { "_id": user_id }, doc! { "$max": { "next_index": next_index } }, None)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use bson::{doc, Document};
+use chrono::{DateTime, Utc};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::strategy::Strategy;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KillSwitchScope {
+    // Everything the user trades
+    User,
+    // A single strategy, `target` is its id
+    Strategy,
+    // Every strategy on one market, `target` is the pair, e.g. "SOL/USDC"
+    Market,
+}
+
+impl KillSwitchScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KillSwitchScope::User => "user",
+            KillSwitchScope::Strategy => "strategy",
+            KillSwitchScope::Market => "market",
+        }
+    }
+
+    /// The form targets are stored and looked up in, so a "sol/usdc" switch halts "SOL/USDC".
+    pub fn normalize_target(&self, target: &str) -> String {
+        match self {
+            KillSwitchScope::Market => target.trim().to_uppercase(),
+            KillSwitchScope::User | KillSwitchScope::Strategy => target.trim().to_string(),
+        }
+    }
+}
+
+/// What flipped the switch.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KillSwitchTrigger {
+    Manual,
+    ConsecutiveFailures,
+    LossThreshold,
+    StaleMarketData,
+    RpcUnavailable,
+    // The wallet the strategy trades from ran low on SOL and could not be topped up
+    LowBalance,
+}
+
+/// One activation of a kill switch. It stays in force until `reset_at` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KillSwitch {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub scope: KillSwitchScope,
+    pub target: Option<String>,
+    pub trigger: KillSwitchTrigger,
+    pub reason: String,
+    pub activated_at: DateTime<Utc>,
+    pub reset_at: Option<DateTime<Utc>>,
+    pub reset_reason: Option<String>,
+}
+
+impl KillSwitch {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("kill_switches")
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.reset_at.is_none()
+    }
+
+    /// Whether this switch stops `strategy` from trading.
+    pub fn halts(&self, strategy: &Strategy) -> bool {
+        if !self.is_active() || self.user_id != strategy.user_id {
+            return false;
+        }
+        match self.scope {
+            KillSwitchScope::User => true,
+            KillSwitchScope::Strategy => self.target.as_deref() == Some(strategy.id.to_string().as_str()),
+            KillSwitchScope::Market => self
+                .target
+                .as_deref()
+                .map_or(false, |target| target.eq_ignore_ascii_case(strategy.trading_pair.trim())),
+        }
+    }
+
+    pub async fn find_by_user(db: &Database, user_id: Uuid) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "user_id": user_id };
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "activated_at": -1 })
+            .build();
+
+        let cursor = Self::collection(db).find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn find_active(db: &Database) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "reset_at": null };
+        let cursor = Self::collection(db).find(filter, None).await?;
+        cursor.try_collect().await
+    }
+
+    /// The active switch that halts trading for this user, strategy or market, if any.
+    pub async fn find_halting(
+        db: &Database,
+        user_id: Uuid,
+        strategy_id: Option<Uuid>,
+        market_pair: &str,
+    ) -> Result<Option<Self>, mongodb::error::Error> {
+        let mut scopes = vec![
+            doc! { "scope": KillSwitchScope::User.as_str() },
+            doc! { "scope": KillSwitchScope::Market.as_str(), "target": KillSwitchScope::Market.normalize_target(market_pair) },
+        ];
+        if let Some(strategy_id) = strategy_id {
+            scopes.push(doc! { "scope": KillSwitchScope::Strategy.as_str(), "target": strategy_id.to_string() });
+        }
+
+        let filter = doc! { "user_id": user_id, "reset_at": null, "$or": scopes };
+        Self::collection(db).find_one(filter, None).await
+    }
+
+    pub async fn find_active_for_target(
+        db: &Database,
+        user_id: Uuid,
+        scope: KillSwitchScope,
+        target: Option<&str>,
+    ) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! {
+            "user_id": user_id,
+            "scope": scope.as_str(),
+            "target": target.map(|t| scope.normalize_target(t)),
+            "reset_at": null,
+        };
+        Self::collection(db).find_one(filter, None).await
+    }
+
+    pub async fn activate(
+        db: &Database,
+        user_id: Uuid,
+        scope: KillSwitchScope,
+        target: Option<&str>,
+        trigger: KillSwitchTrigger,
+        reason: &str,
+    ) -> Result<Self, mongodb::error::Error> {
+        let kill_switch = Self {
+            id: Uuid::new_v4(),
+            user_id,
+            scope,
+            target: target.map(|t| scope.normalize_target(t)),
+            trigger,
+            reason: reason.to_string(),
+            activated_at: Utc::now(),
+            reset_at: None,
+            reset_reason: None,
+        };
+
+        Self::collection(db).insert_one(&kill_switch, None).await?;
+        Ok(kill_switch)
+    }
+
+    /// Lifts an active switch. Returns false if it was not found or already reset.
+    pub async fn reset(db: &Database, id: Uuid, user_id: Uuid, reason: &str) -> Result<bool, mongodb::error::Error> {
+        let filter = doc! { "_id": id, "user_id": user_id, "reset_at": null };
+        let update = doc! { "$set": { "reset_at": Utc::now(), "reset_reason": reason } };
+        let result = Self::collection(db).update_one(filter, update, None).await?;
+        Ok(result.modified_count == 1)
+    }
+}
@@ -1,7 +1,19 @@
 pub mod user;
 pub mod wallet;
+pub mod wallet_allocation;
 pub mod strategy;
 pub mod transaction;
 pub mod alert;
 pub mod settings;
-pub mod orderbook; 
\ No newline at end of file
+pub mod orderbook;
+pub mod strategy_volume;
+pub mod backtest;
+pub mod kill_switch;
+pub mod self_trade_event;
+pub mod grid_state;
+pub mod parent_order;
+pub mod order;
+pub mod keystore;
+pub mod rebalance;
+pub mod top_up;
+pub mod alert_event;
\ No newline at end of file
@@ -0,0 +1,392 @@
+use bson::doc;
+use chrono::{DateTime, Utc};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::services::venues::{LimitOrder, OrderSide};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    // Recorded but not yet acknowledged by the venue
+    New,
+    Open,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+    // Disappeared from the venue without a fill we could attribute
+    Expired,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::New => "new",
+            OrderStatus::Open => "open",
+            OrderStatus::PartiallyFilled => "partially_filled",
+            OrderStatus::Filled => "filled",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Rejected => "rejected",
+            OrderStatus::Expired => "expired",
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Rejected | OrderStatus::Expired
+        )
+    }
+
+    /// Statuses of orders that may still be resting at the venue.
+    pub fn working() -> [OrderStatus; 3] {
+        [OrderStatus::New, OrderStatus::Open, OrderStatus::PartiallyFilled]
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeInForce {
+    // Rests until filled or cancelled
+    Gtc,
+    // Rests like GTC but is rejected instead of taking liquidity
+    PostOnly,
+}
+
+/// Something the venue reported about an order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderEvent {
+    Accepted { venue_order_id: Option<String>, signature: Option<String> },
+    // `fill_id` identifies the fill at the venue, so one reported twice is only applied once
+    Fill { size: f64, price: f64, fill_id: Option<String> },
+    Cancelled,
+    Rejected { reason: String },
+    Expired,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderStatusChange {
+    pub status: OrderStatus,
+    pub error: Option<String>,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// A limit order we sent to a venue, from submission until it stops resting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Order {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub wallet_id: Uuid,
+    pub strategy_id: Option<Uuid>,
+    // Public key the order rests under at the venue
+    pub owner: String,
+    pub venue: String,
+    pub market_pair: String,
+    pub client_order_id: u64,
+    // Assigned by the venue; a decimal string because it does not fit a BSON integer
+    pub venue_order_id: Option<String>,
+    pub side: OrderSide,
+    pub price: f64,
+    pub size: f64,
+    pub filled_size: f64,
+    pub average_fill_price: Option<f64>,
+    // Venue fills already counted in `filled_size`
+    #[serde(default)]
+    pub fill_ids: Vec<String>,
+    pub time_in_force: TimeInForce,
+    pub status: OrderStatus,
+    pub signature: Option<String>,
+    pub error: Option<String>,
+    pub status_history: Vec<OrderStatusChange>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Order {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("orders")
+    }
+
+    pub fn remaining_size(&self) -> f64 {
+        (self.size - self.filled_size).max(0.0)
+    }
+
+    /// Status the order moves to when `event` arrives, or `None` if the event does not apply.
+    pub fn next_status(&self, event: &OrderEvent) -> Option<OrderStatus> {
+        if self.status.is_terminal() {
+            return None;
+        }
+        if let OrderEvent::Fill { fill_id: Some(fill_id), .. } = event {
+            if self.fill_ids.contains(fill_id) {
+                return None;
+            }
+        }
+        match event {
+            OrderEvent::Accepted { .. } if self.status == OrderStatus::New => Some(OrderStatus::Open),
+            // A fill can arrive before the acknowledgement, which then only adds the venue details
+            OrderEvent::Accepted { .. } => Some(self.status),
+            OrderEvent::Fill { size, .. } if self.filled_size + size >= self.size - self.size * 1e-9 => {
+                Some(OrderStatus::Filled)
+            }
+            OrderEvent::Fill { .. } => Some(OrderStatus::PartiallyFilled),
+            OrderEvent::Cancelled => Some(OrderStatus::Cancelled),
+            OrderEvent::Rejected { .. } => Some(OrderStatus::Rejected),
+            OrderEvent::Expired => Some(OrderStatus::Expired),
+        }
+    }
+
+    pub async fn find_by_id(db: &Database, id: Uuid, user_id: Uuid) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! { "_id": id, "user_id": user_id };
+        Self::collection(db).find_one(filter, None).await
+    }
+
+    /// The user's orders, newest first, optionally narrowed by strategy, market and status.
+    pub async fn find_by_user(
+        db: &Database,
+        user_id: Uuid,
+        strategy_id: Option<Uuid>,
+        market_pair: Option<&str>,
+        status: Option<OrderStatus>,
+        limit: i64,
+    ) -> Result<Vec<Self>, mongodb::error::Error> {
+        let mut filter = doc! { "user_id": user_id };
+        if let Some(strategy_id) = strategy_id {
+            filter.insert("strategy_id", strategy_id);
+        }
+        if let Some(market_pair) = market_pair {
+            filter.insert("market_pair", market_pair);
+        }
+        if let Some(status) = status {
+            filter.insert("status", status.as_str());
+        }
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "created_at": -1 })
+            .limit(limit)
+            .build();
+
+        let cursor = Self::collection(db).find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn find_by_client_order_id(
+        db: &Database,
+        owner: &str,
+        venue: &str,
+        client_order_id: u64,
+    ) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! { "owner": owner, "venue": venue, "client_order_id": client_order_id as i64 };
+        Self::collection(db).find_one(filter, None).await
+    }
+
+    /// Orders `owner` may still have resting on `market_pair` at `venue`.
+    pub async fn find_working_for_owner(
+        db: &Database,
+        owner: &str,
+        venue: &str,
+        market_pair: &str,
+    ) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! {
+            "owner": owner,
+            "venue": venue,
+            "market_pair": market_pair,
+            "status": { "$in": working_statuses() },
+        };
+        let cursor = Self::collection(db).find(filter, None).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn create(
+        db: &Database,
+        order: &LimitOrder,
+        owner: &str,
+        venue: &str,
+    ) -> Result<Self, mongodb::error::Error> {
+        let now = Utc::now();
+        let record = Self {
+            id: Uuid::new_v4(),
+            user_id: order.user_id,
+            wallet_id: order.wallet_id,
+            strategy_id: order.strategy_id,
+            owner: owner.to_string(),
+            venue: venue.to_string(),
+            market_pair: order.market_pair.clone(),
+            client_order_id: order.client_order_id,
+            venue_order_id: None,
+            side: order.side,
+            price: order.price,
+            size: order.size,
+            filled_size: 0.0,
+            average_fill_price: None,
+            fill_ids: Vec::new(),
+            time_in_force: if order.post_only { TimeInForce::PostOnly } else { TimeInForce::Gtc },
+            status: OrderStatus::New,
+            signature: None,
+            error: None,
+            status_history: vec![OrderStatusChange {
+                status: OrderStatus::New,
+                error: None,
+                changed_at: now,
+            }],
+            created_at: now,
+            updated_at: now,
+        };
+
+        Self::collection(db).insert_one(&record, None).await?;
+        Ok(record)
+    }
+
+    /// Applies a venue event to the stored order. Returns false when the event does not apply
+    /// or the order changed underneath us.
+    pub async fn apply_event(db: &Database, order: &Self, event: &OrderEvent) -> Result<bool, mongodb::error::Error> {
+        let Some(status) = order.next_status(event) else {
+            return Ok(false);
+        };
+
+        let now = Utc::now();
+        let mut set = doc! { "status": status.as_str(), "updated_at": now };
+        let mut push = doc! {};
+        let mut error = None;
+        match event {
+            OrderEvent::Accepted { venue_order_id, signature } => {
+                if let Some(venue_order_id) = venue_order_id {
+                    set.insert("venue_order_id", venue_order_id.as_str());
+                }
+                if let Some(signature) = signature {
+                    set.insert("signature", signature.as_str());
+                }
+            }
+            OrderEvent::Fill { size, price, fill_id } => {
+                let filled_size = (order.filled_size + size).min(order.size);
+                let notional = order.average_fill_price.unwrap_or(0.0) * order.filled_size + price * size;
+                set.insert("filled_size", filled_size);
+                set.insert("average_fill_price", notional / (order.filled_size + size));
+                if let Some(fill_id) = fill_id {
+                    push.insert("fill_ids", fill_id.as_str());
+                }
+            }
+            OrderEvent::Rejected { reason } => {
+                set.insert("error", reason.as_str());
+                error = Some(reason.clone());
+            }
+            OrderEvent::Cancelled | OrderEvent::Expired => {}
+        }
+
+        if status != order.status {
+            let change = OrderStatusChange { status, error, changed_at: now };
+            push.insert(
+                "status_history",
+                bson::to_bson(&change).map_err(|e| mongodb::error::Error::from(mongodb::error::ErrorKind::InvalidArgument { message: e.to_string() }))?,
+            );
+        }
+        let mut update = doc! { "$set": set };
+        if !push.is_empty() {
+            update.insert("$push", push);
+        }
+
+        // Matching on the status and fill keeps concurrent events from being applied twice
+        let filter = doc! { "_id": order.id, "status": order.status.as_str(), "filled_size": order.filled_size };
+        let result = Self::collection(db).update_one(filter, update, None).await?;
+        Ok(result.modified_count == 1)
+    }
+}
+
+fn working_statuses() -> Vec<&'static str> {
+    OrderStatus::working().iter().map(|s| s.as_str()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(status: OrderStatus, size: f64, filled_size: f64) -> Order {
+        let now = Utc::now();
+        Order {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            wallet_id: Uuid::new_v4(),
+            strategy_id: None,
+            owner: String::new(),
+            venue: "paper".to_string(),
+            market_pair: "SOL/USDC".to_string(),
+            client_order_id: 1,
+            venue_order_id: None,
+            side: OrderSide::Buy,
+            price: 100.0,
+            size,
+            filled_size,
+            average_fill_price: None,
+            fill_ids: Vec::new(),
+            time_in_force: TimeInForce::Gtc,
+            status,
+            signature: None,
+            error: None,
+            status_history: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn fill(size: f64, fill_id: Option<&str>) -> OrderEvent {
+        OrderEvent::Fill { size, price: 100.0, fill_id: fill_id.map(str::to_string) }
+    }
+
+    fn accepted() -> OrderEvent {
+        OrderEvent::Accepted { venue_order_id: Some("42".to_string()), signature: None }
+    }
+
+    #[test]
+    fn acceptance_opens_a_new_order() {
+        assert_eq!(order(OrderStatus::New, 2.0, 0.0).next_status(&accepted()), Some(OrderStatus::Open));
+    }
+
+    #[test]
+    fn late_acceptance_keeps_the_fill_status() {
+        let partially_filled = order(OrderStatus::PartiallyFilled, 2.0, 1.0);
+        assert_eq!(partially_filled.next_status(&accepted()), Some(OrderStatus::PartiallyFilled));
+    }
+
+    #[test]
+    fn fills_move_towards_filled() {
+        let open = order(OrderStatus::Open, 2.0, 0.0);
+        assert_eq!(open.next_status(&fill(0.5, None)), Some(OrderStatus::PartiallyFilled));
+        assert_eq!(open.next_status(&fill(2.0, None)), Some(OrderStatus::Filled));
+        // Fills that leave only rounding dust behind complete the order
+        let partially_filled = order(OrderStatus::PartiallyFilled, 2.0, 1.5);
+        assert_eq!(partially_filled.next_status(&fill(0.5 - 1e-12, None)), Some(OrderStatus::Filled));
+        // A fill can arrive before the venue acknowledges the order
+        assert_eq!(order(OrderStatus::New, 2.0, 0.0).next_status(&fill(1.0, None)), Some(OrderStatus::PartiallyFilled));
+    }
+
+    #[test]
+    fn a_fill_reported_twice_is_applied_once() {
+        let mut open = order(OrderStatus::PartiallyFilled, 2.0, 0.5);
+        open.fill_ids.push("7".to_string());
+        assert_eq!(open.next_status(&fill(0.5, Some("7"))), None);
+        assert_eq!(open.next_status(&fill(0.5, Some("8"))), Some(OrderStatus::PartiallyFilled));
+    }
+
+    #[test]
+    fn working_orders_can_be_cancelled_rejected_or_expired() {
+        for status in OrderStatus::working() {
+            let working = order(status, 2.0, 0.0);
+            assert_eq!(working.next_status(&OrderEvent::Cancelled), Some(OrderStatus::Cancelled));
+            assert_eq!(working.next_status(&OrderEvent::Expired), Some(OrderStatus::Expired));
+            let rejected = OrderEvent::Rejected { reason: "post only would cross".to_string() };
+            assert_eq!(working.next_status(&rejected), Some(OrderStatus::Rejected));
+        }
+    }
+
+    #[test]
+    fn finished_orders_ignore_every_event() {
+        for status in [OrderStatus::Filled, OrderStatus::Cancelled, OrderStatus::Rejected, OrderStatus::Expired] {
+            let finished = order(status, 2.0, 2.0);
+            assert_eq!(finished.next_status(&accepted()), None);
+            assert_eq!(finished.next_status(&fill(1.0, None)), None);
+            assert_eq!(finished.next_status(&OrderEvent::Cancelled), None);
+            assert_eq!(finished.next_status(&OrderEvent::Expired), None);
+        }
+    }
+}
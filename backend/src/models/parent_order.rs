@@ -0,0 +1,161 @@
+use bson::doc;
+use chrono::{DateTime, Utc};
+use mongodb::options::UpdateOptions;
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::services::venues::OrderSide;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionAlgo {
+    // Equal slices
+    Twap,
+    // Slices weighted by a volume profile
+    Vwap,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ParentOrderStatus {
+    Working,
+    Completed,
+    // The window ended before the full size could be executed within the limits
+    Expired,
+    Cancelled,
+}
+
+impl ParentOrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParentOrderStatus::Working => "working",
+            ParentOrderStatus::Completed => "completed",
+            ParentOrderStatus::Expired => "expired",
+            ParentOrderStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChildOrderStatus {
+    Scheduled,
+    Filled,
+    // Held back by the price or participation limit; its size rolls into the next slice
+    Skipped,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChildOrder {
+    pub index: u32,
+    pub scheduled_at: DateTime<Utc>,
+    // Share of the parent size allotted to this slice by the schedule
+    pub target_size: f64,
+    pub executed_size: f64,
+    pub price: Option<f64>,
+    pub status: ChildOrderStatus,
+    pub transaction_id: Option<Uuid>,
+    pub error: Option<String>,
+}
+
+/// A large trade worked as a schedule of smaller child trades.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParentOrder {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub wallet_id: Uuid,
+    pub strategy_id: Option<Uuid>,
+    pub side: OrderSide,
+    pub token: String,
+    pub total_size: f64,
+    pub executed_size: f64,
+    // Volume weighted price of the children that filled
+    pub average_price: Option<f64>,
+    pub algo: ExecutionAlgo,
+    // Largest share of market volume a slice may take, between 0 and 1
+    pub participation_limit: f64,
+    // Highest price a buy, or lowest price a sell, may execute at
+    pub limit_price: f64,
+    pub start_at: DateTime<Utc>,
+    pub end_at: DateTime<Utc>,
+    pub children: Vec<ChildOrder>,
+    pub status: ParentOrderStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ParentOrder {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("parent_orders")
+    }
+
+    pub fn remaining_size(&self) -> f64 {
+        (self.total_size - self.executed_size).max(0.0)
+    }
+
+    pub async fn find_by_id(db: &Database, id: Uuid, user_id: Uuid) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! { "_id": id, "user_id": user_id };
+        Self::collection(db).find_one(filter, None).await
+    }
+
+    pub async fn find_by_user(db: &Database, user_id: Uuid) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "user_id": user_id };
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "created_at": -1 })
+            .build();
+
+        let cursor = Self::collection(db).find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn find_working(db: &Database) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "status": ParentOrderStatus::Working.as_str() };
+        let cursor = Self::collection(db).find(filter, None).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn create(db: &Database, order: &Self) -> Result<(), mongodb::error::Error> {
+        Self::collection(db).insert_one(order, None).await?;
+        Ok(())
+    }
+
+    /// Stores the outcome of one child along with the parent's running totals.
+    pub async fn record_child(db: &Database, order: &Self, child: &ChildOrder) -> Result<(), mongodb::error::Error> {
+        // Children are stored in schedule order, so a child's index is its position
+        let path = format!("children.{}", child.index);
+        let child = bson::to_bson(child)
+            .map_err(|e| mongodb::error::Error::from(mongodb::error::ErrorKind::InvalidArgument { message: e.to_string() }))?;
+        let filter = doc! { "_id": order.id };
+        let update = doc! {
+            "$set": {
+                path: child,
+                "executed_size": order.executed_size,
+                "average_price": order.average_price,
+                "updated_at": Utc::now(),
+            }
+        };
+        Self::collection(db).update_one(filter, update, None).await?;
+        Ok(())
+    }
+
+    /// Moves a working order to a final status. Returns false if it had already finished.
+    pub async fn finish(db: &Database, id: Uuid, status: ParentOrderStatus) -> Result<bool, mongodb::error::Error> {
+        let filter = doc! { "_id": id, "status": ParentOrderStatus::Working.as_str() };
+        let update = doc! {
+            "$set": {
+                "status": status.as_str(),
+                "children.$[scheduled].status": "cancelled",
+                "updated_at": Utc::now(),
+            }
+        };
+        let options = UpdateOptions::builder()
+            .array_filters(vec![doc! { "scheduled.status": "scheduled" }])
+            .build();
+        let result = Self::collection(db).update_one(filter, update, options).await?;
+        Ok(result.modified_count == 1)
+    }
+}
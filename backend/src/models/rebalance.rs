@@ -0,0 +1,93 @@
+use bson::doc;
+use chrono::{DateTime, Utc};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RebalanceTrigger {
+    Manual,
+    // Run by the background rebalancer for users with auto_rebalance on
+    Scheduled,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferStatus {
+    // Part of a dry run, or not attempted
+    Planned,
+    Completed,
+    Failed,
+}
+
+/// Where one wallet stood against its target when the run started.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletAllocation {
+    pub wallet_id: Uuid,
+    pub address: String,
+    pub label: Option<String>,
+    pub value_usd: f64,
+    // Shares of the rebalanced wallets' combined value, in percent
+    pub target_pct: f64,
+    pub actual_pct: f64,
+    // Actual minus target; positive when the wallet holds more than its share
+    pub drift_pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebalanceTransfer {
+    pub from_wallet_id: Uuid,
+    pub to_wallet_id: Uuid,
+    pub token: String,
+    pub amount: f64,
+    pub value_usd: f64,
+    pub status: TransferStatus,
+    pub transaction_id: Option<Uuid>,
+    pub signature: Option<String>,
+    pub error: Option<String>,
+}
+
+/// One run of the rebalancer, whether it moved funds or only previewed the moves.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebalanceReport {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub trigger: RebalanceTrigger,
+    pub dry_run: bool,
+    // Drift, in percentage points, a wallet had to exceed before funds were moved
+    pub threshold_pct: f64,
+    pub max_drift_pct: f64,
+    pub total_value_usd: f64,
+    pub wallets: Vec<WalletAllocation>,
+    pub transfers: Vec<RebalanceTransfer>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RebalanceReport {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("rebalance_reports")
+    }
+
+    pub async fn find_by_id(db: &Database, id: Uuid, user_id: Uuid) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! { "_id": id, "user_id": user_id };
+        Self::collection(db).find_one(filter, None).await
+    }
+
+    pub async fn find_by_user(db: &Database, user_id: Uuid, limit: i64) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "user_id": user_id };
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "created_at": -1 })
+            .limit(limit)
+            .build();
+
+        let cursor = Self::collection(db).find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn create(db: &Database, report: &Self) -> Result<(), mongodb::error::Error> {
+        Self::collection(db).insert_one(report, None).await?;
+        Ok(())
+    }
+}
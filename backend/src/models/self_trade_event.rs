@@ -0,0 +1,69 @@
+use bson::{doc, Document};
+use chrono::{DateTime, Utc};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::services::self_trade::{IncomingOrder, RestingQuote, SelfTradePolicy};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum SelfTradeAction {
+    Rejected,
+    CancelledResting,
+    Repriced { price: f64 },
+}
+
+/// Compliance record of an order that would have traded against the same user's resting order.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SelfTradeEvent {
+    #[serde(rename = "_id")]
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub market_pair: String,
+    pub policy: SelfTradePolicy,
+    pub incoming: IncomingOrder,
+    pub resting: Vec<RestingQuote>,
+    pub action: SelfTradeAction,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SelfTradeEvent {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("self_trade_events")
+    }
+
+    pub async fn find_by_user(db: &Database, user_id: Uuid) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "user_id": user_id };
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "created_at": -1 })
+            .build();
+
+        let cursor = Self::collection(db).find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn create(
+        db: &Database,
+        user_id: Uuid,
+        market_pair: &str,
+        policy: SelfTradePolicy,
+        incoming: IncomingOrder,
+        resting: Vec<RestingQuote>,
+        action: SelfTradeAction,
+    ) -> Result<Self, mongodb::error::Error> {
+        let event = Self {
+            id: Uuid::new_v4(),
+            user_id,
+            market_pair: market_pair.to_string(),
+            policy,
+            incoming,
+            resting,
+            action,
+            created_at: Utc::now(),
+        };
+
+        Self::collection(db).insert_one(&event, None).await?;
+        Ok(event)
+    }
+}
@@ -4,6 +4,29 @@ use mongodb::{Collection, Database};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// How aggressively transactions bid for block space.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PriorityFeeLevel {
+    Low,
+    #[default]
+    Medium,
+    High,
+    // Bids like `High` but never above the user's `priority_fee_cap`
+    Custom,
+}
+
+impl PriorityFeeLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PriorityFeeLevel::Low => "low",
+            PriorityFeeLevel::Medium => "medium",
+            PriorityFeeLevel::High => "high",
+            PriorityFeeLevel::Custom => "custom",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Settings {
     #[serde(rename = "_id")]
@@ -14,6 +37,23 @@ pub struct Settings {
     pub risk_level: String,
     pub notification_email: Option<String>,
     pub auto_rebalance: bool,
+    #[serde(default)]
+    pub priority_fee_level: PriorityFeeLevel,
+    // Highest compute unit price, in micro-lamports, paid at the custom level
+    #[serde(default)]
+    pub priority_fee_cap: Option<u64>,
+    // Worst slippage the user's trades may accept, unless a strategy sets its own
+    #[serde(default)]
+    pub max_slippage_bps: Option<u16>,
+    // Drift from a wallet's target allocation, in percentage points, that triggers a rebalance
+    #[serde(default)]
+    pub rebalance_threshold_pct: Option<f64>,
+    // Wallet that tops up the user's other wallets when they fall below min_wallet_balance
+    #[serde(default)]
+    pub treasury_wallet_id: Option<Uuid>,
+    // Most SOL the treasury may send per UTC day
+    #[serde(default)]
+    pub max_daily_top_up: Option<f64>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -28,6 +68,17 @@ impl Settings {
         Self::collection(db).find_one(filter, None).await
     }
 
+    pub async fn find_auto_rebalance(db: &Database) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "auto_rebalance": true };
+        let cursor = Self::collection(db).find(filter, None).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn find_all(db: &Database) -> Result<Vec<Self>, mongodb::error::Error> {
+        let cursor = Self::collection(db).find(doc! {}, None).await?;
+        cursor.try_collect().await
+    }
+
     pub async fn create_or_update(
         db: &Database,
         user_id: Uuid,
@@ -36,6 +87,12 @@ impl Settings {
         risk_level: Option<&str>,
         notification_email: Option<&str>,
         auto_rebalance: Option<bool>,
+        priority_fee_level: Option<PriorityFeeLevel>,
+        priority_fee_cap: Option<u64>,
+        max_slippage_bps: Option<u16>,
+        rebalance_threshold_pct: Option<f64>,
+        treasury_wallet_id: Option<Uuid>,
+        max_daily_top_up: Option<f64>,
     ) -> Result<Self, mongodb::error::Error> {
         // Check if settings exist for this user
         let existing = Self::find_by_user(db, user_id).await?;
@@ -67,6 +124,30 @@ impl Settings {
                 update_doc.insert("auto_rebalance", rebalance);
             }
             
+            if let Some(level) = priority_fee_level {
+                update_doc.insert("priority_fee_level", level.as_str());
+            }
+            
+            if let Some(cap) = priority_fee_cap {
+                update_doc.insert("priority_fee_cap", cap as i64);
+            }
+            
+            if let Some(bps) = max_slippage_bps {
+                update_doc.insert("max_slippage_bps", bps as i32);
+            }
+            
+            if let Some(threshold) = rebalance_threshold_pct {
+                update_doc.insert("rebalance_threshold_pct", threshold);
+            }
+            
+            if let Some(wallet_id) = treasury_wallet_id {
+                update_doc.insert("treasury_wallet_id", wallet_id);
+            }
+            
+            if let Some(cap) = max_daily_top_up {
+                update_doc.insert("max_daily_top_up", cap);
+            }
+            
             let update = doc! { "$set": update_doc };
             
             Self::collection(db).update_one(filter.clone(), update, None).await?;
@@ -87,6 +168,12 @@ impl Settings {
                 risk_level: risk_level.unwrap_or("medium").to_string(),
                 notification_email: notification_email.map(|s| s.to_string()),
                 auto_rebalance: auto_rebalance.unwrap_or(false),
+                priority_fee_level: priority_fee_level.unwrap_or_default(),
+                priority_fee_cap,
+                max_slippage_bps,
+                rebalance_threshold_pct,
+                treasury_wallet_id,
+                max_daily_top_up,
                 created_at: now,
                 updated_at: now,
             };
@@ -4,13 +4,130 @@ use mongodb::{Collection, Database};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum StrategyKind {
+    // Strategies created before kinds existed all quoted a fixed spread
+    #[serde(alias = "market-making", alias = "stealth-trading", alias = "price-influence")]
+    FixedSpread,
+    Grid,
+    InventorySkewed,
+    AvellanedaStoikov,
+}
+
+impl StrategyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StrategyKind::FixedSpread => "fixed-spread",
+            StrategyKind::Grid => "grid",
+            StrategyKind::InventorySkewed => "inventory-skewed",
+            StrategyKind::AvellanedaStoikov => "avellaneda-stoikov",
+        }
+    }
+
+    /// Kinds the market making engine keeps two-sided quotes out for.
+    pub fn quotes_continuously(&self) -> bool {
+        matches!(
+            self,
+            StrategyKind::FixedSpread | StrategyKind::InventorySkewed | StrategyKind::AvellanedaStoikov
+        )
+    }
+
+    /// Kinds the market making engine runs a worker for.
+    pub fn runs_on_engine(&self) -> bool {
+        self.quotes_continuously() || *self == StrategyKind::Grid
+    }
+}
+
+/// Symmetric quotes around the mid price.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FixedSpreadParams {
+    // Falls back to QUOTE_SPREAD_BPS when unset
+    pub spread_bps: Option<f64>,
+}
+
+/// Ladder of resting orders spaced evenly between two prices.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GridParams {
+    pub lower_price: f64,
+    pub upper_price: f64,
+    pub levels: u32,
+    pub order_size: f64,
+}
+
+/// Quotes whose centre shifts away from the side we are already long or short.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct InventorySkewedParams {
+    pub spread_bps: f64,
+    // Base token position the strategy tries to hold
+    pub target_inventory: f64,
+    // Distance from the target at which quoting on the growing side stops
+    pub max_inventory: f64,
+    // How far the quote centre moves when inventory is a full `max_inventory` away from target
+    pub skew_bps: f64,
+}
+
+/// Avellaneda–Stoikov market making: quotes around an inventory-adjusted reservation price
+/// with a spread derived from volatility, risk aversion and order arrival intensity.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AvellanedaStoikovParams {
+    // γ, higher values hold less inventory and quote wider
+    pub risk_aversion: f64,
+    // τ, the horizon over which inventory risk is priced
+    pub horizon_secs: u64,
+    // κ, how quickly fill probability decays with distance from the mid
+    pub order_arrival_intensity: f64,
+    // Number of mid price samples used to estimate volatility
+    #[serde(default = "default_volatility_window")]
+    pub volatility_window: u32,
+    #[serde(default)]
+    pub target_inventory: f64,
+}
+
+fn default_volatility_window() -> u32 {
+    30
+}
+
+/// Parameters for a strategy, tagged with the kind they belong to.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum StrategyParams {
+    FixedSpread(FixedSpreadParams),
+    Grid(GridParams),
+    InventorySkewed(InventorySkewedParams),
+    AvellanedaStoikov(AvellanedaStoikovParams),
+}
+
+impl Default for StrategyParams {
+    fn default() -> Self {
+        StrategyParams::FixedSpread(FixedSpreadParams::default())
+    }
+}
+
+impl StrategyParams {
+    pub fn kind(&self) -> StrategyKind {
+        match self {
+            StrategyParams::FixedSpread(_) => StrategyKind::FixedSpread,
+            StrategyParams::Grid(_) => StrategyKind::Grid,
+            StrategyParams::InventorySkewed(_) => StrategyKind::InventorySkewed,
+            StrategyParams::AvellanedaStoikov(_) => StrategyKind::AvellanedaStoikov,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Strategy {
     #[serde(rename = "_id")]
     pub id: Uuid,
     pub user_id: Uuid,
     pub name: String,
-    pub strategy_type: String,
+    pub strategy_type: StrategyKind,
+    #[serde(default)]
+    pub params: StrategyParams,
     pub trading_pair: String,
     pub execution_platform: String,
     pub min_trade_size: f64,
@@ -21,6 +138,11 @@ pub struct Strategy {
     pub risk_alerts: bool,
     pub transaction_delay: i32,
     pub trade_frequency: i32,
+    #[serde(default)]
+    pub paper_trading: bool,
+    // Worst slippage a trade for this strategy may accept; falls back to the user's setting
+    #[serde(default)]
+    pub max_slippage_bps: Option<u16>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -41,11 +163,23 @@ impl Strategy {
         Self::collection(db).find_one(filter, None).await
     }
 
+    /// Looks a strategy up without scoping it to a user, for operator tooling.
+    pub async fn find_any_by_id(db: &Database, id: Uuid) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! { "_id": id };
+        Self::collection(db).find_one(filter, None).await
+    }
+
+    pub async fn find_auto_trading(db: &Database) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "auto_trading": true };
+        let cursor = Self::collection(db).find(filter, None).await?;
+        cursor.try_collect().await
+    }
+
     pub async fn create(
         db: &Database,
         user_id: Uuid,
         name: &str,
-        strategy_type: &str,
+        params: &StrategyParams,
         trading_pair: &str,
         execution_platform: &str,
         min_trade_size: f64,
@@ -56,13 +190,16 @@ impl Strategy {
         risk_alerts: bool,
         transaction_delay: i32,
         trade_frequency: i32,
+        paper_trading: bool,
+        max_slippage_bps: Option<u16>,
     ) -> Result<Self, mongodb::error::Error> {
         let now = Utc::now();
         let strategy = Self {
             id: Uuid::new_v4(),
             user_id,
             name: name.to_string(),
-            strategy_type: strategy_type.to_string(),
+            strategy_type: params.kind(),
+            params: params.clone(),
             trading_pair: trading_pair.to_string(),
             execution_platform: execution_platform.to_string(),
             min_trade_size,
@@ -73,6 +210,8 @@ impl Strategy {
             risk_alerts,
             transaction_delay,
             trade_frequency,
+            paper_trading,
+            max_slippage_bps,
             created_at: now,
             updated_at: now,
         };
@@ -86,7 +225,7 @@ impl Strategy {
         id: Uuid,
         user_id: Uuid,
         name: &str,
-        strategy_type: &str,
+        params: &StrategyParams,
         trading_pair: &str,
         execution_platform: &str,
         min_trade_size: f64,
@@ -97,14 +236,18 @@ impl Strategy {
         risk_alerts: bool,
         transaction_delay: i32,
         trade_frequency: i32,
+        paper_trading: bool,
+        max_slippage_bps: Option<u16>,
     ) -> Result<Self, mongodb::error::Error> {
         let filter = doc! { "_id": id, "user_id": user_id };
         let now = Utc::now();
+        let params_bson = bson::to_bson(params).map_err(|e| mongodb::error::Error::from(mongodb::error::ErrorKind::InvalidArgument { message: e.to_string() }))?;
         
         let update = doc! {
             "$set": {
                 "name": name,
-                "strategy_type": strategy_type,
+                "strategy_type": params.kind().as_str(),
+                "params": params_bson,
                 "trading_pair": trading_pair,
                 "execution_platform": execution_platform,
                 "min_trade_size": min_trade_size,
@@ -115,6 +258,8 @@ impl Strategy {
                 "risk_alerts": risk_alerts,
                 "transaction_delay": transaction_delay,
                 "trade_frequency": trade_frequency,
+                "paper_trading": paper_trading,
+                "max_slippage_bps": max_slippage_bps.map(|bps| bps as i32),
                 "updated_at": now
             }
         };
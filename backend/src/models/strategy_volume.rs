@@ -0,0 +1,108 @@
+use bson::{doc, Document};
+use chrono::{DateTime, Utc};
+use mongodb::options::UpdateOptions;
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Notional traded by a strategy during one UTC day.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StrategyVolume {
+    pub strategy_id: Uuid,
+    pub user_id: Uuid,
+    // UTC calendar day formatted as YYYY-MM-DD
+    pub day: String,
+    pub notional: f64,
+    pub trade_count: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl StrategyVolume {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("strategy_volumes")
+    }
+
+    pub fn day_key(at: DateTime<Utc>) -> String {
+        at.format("%Y-%m-%d").to_string()
+    }
+
+    pub async fn find_for_day(db: &Database, strategy_id: Uuid, day: &str) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! { "strategy_id": strategy_id, "day": day };
+        Self::collection(db).find_one(filter, None).await
+    }
+
+    pub async fn used_today(db: &Database, strategy_id: Uuid) -> Result<f64, mongodb::error::Error> {
+        let day = Self::day_key(Utc::now());
+        Ok(Self::find_for_day(db, strategy_id, &day).await?.map_or(0.0, |v| v.notional))
+    }
+
+    /// Atomically adds `notional` to the day's total unless that would take it past `cap`.
+    /// Returns false when the reservation was refused.
+    pub async fn reserve(
+        db: &Database,
+        strategy_id: Uuid,
+        user_id: Uuid,
+        day: &str,
+        notional: f64,
+        cap: f64,
+    ) -> Result<bool, mongodb::error::Error> {
+        // Make sure the day's counter exists so the conditional update below has something to match
+        let filter = doc! { "strategy_id": strategy_id, "day": day };
+        let init = doc! {
+            "$setOnInsert": {
+                "user_id": user_id,
+                "notional": 0.0,
+                "trade_count": 0i64,
+                "updated_at": Utc::now(),
+            }
+        };
+        let options = UpdateOptions::builder().upsert(true).build();
+        Self::collection(db).update_one(filter, init, options).await?;
+
+        let filter = doc! {
+            "strategy_id": strategy_id,
+            "day": day,
+            "notional": { "$lte": cap - notional },
+        };
+        let update = doc! {
+            "$inc": { "notional": notional, "trade_count": 1i64 },
+            "$set": { "updated_at": Utc::now() },
+        };
+        let result = Self::collection(db).update_one(filter, update, None).await?;
+        Ok(result.modified_count == 1)
+    }
+
+    /// Adds volume that has already traded, without checking the cap.
+    pub async fn record(
+        db: &Database,
+        strategy_id: Uuid,
+        user_id: Uuid,
+        day: &str,
+        notional: f64,
+    ) -> Result<(), mongodb::error::Error> {
+        let filter = doc! { "strategy_id": strategy_id, "day": day };
+        let update = doc! {
+            "$inc": { "notional": notional, "trade_count": 1i64 },
+            "$set": { "user_id": user_id, "updated_at": Utc::now() },
+        };
+        let options = UpdateOptions::builder().upsert(true).build();
+        Self::collection(db).update_one(filter, update, options).await?;
+        Ok(())
+    }
+
+    /// Gives back a reservation for a trade that never executed.
+    pub async fn release(
+        db: &Database,
+        strategy_id: Uuid,
+        day: &str,
+        notional: f64,
+    ) -> Result<(), mongodb::error::Error> {
+        let filter = doc! { "strategy_id": strategy_id, "day": day };
+        let update = doc! {
+            "$inc": { "notional": -notional, "trade_count": -1i64 },
+            "$set": { "updated_at": Utc::now() },
+        };
+        Self::collection(db).update_one(filter, update, None).await?;
+        Ok(())
+    }
+}
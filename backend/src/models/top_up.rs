@@ -0,0 +1,68 @@
+use bson::doc;
+use chrono::{DateTime, Utc};
+use mongodb::options::UpdateOptions;
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// SOL sent from a user's treasury to top up their wallets during one UTC day.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TopUpUsage {
+    pub user_id: Uuid,
+    // UTC calendar day formatted as YYYY-MM-DD
+    pub day: String,
+    pub amount: f64,
+    pub top_up_count: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TopUpUsage {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("top_up_usage")
+    }
+
+    /// Atomically adds `amount` to the day's total unless that would take it past `cap`.
+    /// Returns false when the reservation was refused.
+    pub async fn reserve(
+        db: &Database,
+        user_id: Uuid,
+        day: &str,
+        amount: f64,
+        cap: f64,
+    ) -> Result<bool, mongodb::error::Error> {
+        // Make sure the day's counter exists so the conditional update below has something to match
+        let filter = doc! { "user_id": user_id, "day": day };
+        let init = doc! {
+            "$setOnInsert": {
+                "amount": 0.0,
+                "top_up_count": 0i64,
+                "updated_at": Utc::now(),
+            }
+        };
+        let options = UpdateOptions::builder().upsert(true).build();
+        Self::collection(db).update_one(filter, init, options).await?;
+
+        let filter = doc! {
+            "user_id": user_id,
+            "day": day,
+            "amount": { "$lte": cap - amount },
+        };
+        let update = doc! {
+            "$inc": { "amount": amount, "top_up_count": 1i64 },
+            "$set": { "updated_at": Utc::now() },
+        };
+        let result = Self::collection(db).update_one(filter, update, None).await?;
+        Ok(result.modified_count == 1)
+    }
+
+    /// Gives back a reservation for a top-up that was never sent.
+    pub async fn release(db: &Database, user_id: Uuid, day: &str, amount: f64) -> Result<(), mongodb::error::Error> {
+        let filter = doc! { "user_id": user_id, "day": day };
+        let update = doc! {
+            "$inc": { "amount": -amount, "top_up_count": -1i64 },
+            "$set": { "updated_at": Utc::now() },
+        };
+        Self::collection(db).update_one(filter, update, None).await?;
+        Ok(())
+    }
+}
@@ -4,34 +4,168 @@ use mongodb::{Collection, Database};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::models::order::Order;
+use crate::services::venues::OrderSide;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionStatus {
+    Built,
+    Simulated,
+    Submitted,
+    // Records written before the lifecycle existed used "completed" for landed trades
+    #[serde(alias = "completed")]
+    Confirmed,
+    Finalized,
+    Failed,
+    Expired,
+}
+
+impl TransactionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionStatus::Built => "built",
+            TransactionStatus::Simulated => "simulated",
+            TransactionStatus::Submitted => "submitted",
+            TransactionStatus::Confirmed => "confirmed",
+            TransactionStatus::Finalized => "finalized",
+            TransactionStatus::Failed => "failed",
+            TransactionStatus::Expired => "expired",
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            TransactionStatus::Finalized | TransactionStatus::Failed | TransactionStatus::Expired
+        )
+    }
+
+    pub fn can_transition_to(&self, next: TransactionStatus) -> bool {
+        use TransactionStatus::*;
+        match (self, next) {
+            (Built, Simulated) | (Built, Submitted) => true,
+            (Simulated, Submitted) => true,
+            (Submitted, Confirmed) | (Submitted, Finalized) | (Submitted, Expired) => true,
+            (Confirmed, Finalized) => true,
+            // A transaction can fail at any point before it is finalized
+            (from, Failed) => !from.is_terminal(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusChange {
+    pub status: TransactionStatus,
+    pub error: Option<String>,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// One signed version of a transaction, with its own blockhash, sent to the network.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmissionAttempt {
+    pub attempt: u32,
+    pub signature: String,
+    pub last_valid_block_height: u64,
+    pub compute_unit_price: u64,
+    pub sent_at: DateTime<Utc>,
+}
+
+/// Strategy daily volume held for a trade until it is known whether it landed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VolumeReservation {
+    // UTC day the volume was reserved against
+    pub day: String,
+    pub notional: f64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Transaction {
     #[serde(rename = "_id")]
     pub id: Uuid,
     pub user_id: Uuid,
     pub wallet_id: Uuid,
+    #[serde(default)]
+    pub strategy_id: Option<Uuid>,
     pub action: String,
     pub amount: f64,
     pub token: String,
     pub price: f64,
-    pub status: String,
+    pub status: TransactionStatus,
+    // Realized, in percent; positive when the fill was worse than the quote
     pub slippage: Option<f64>,
+    // Limit the swap was built with
+    #[serde(default)]
+    pub max_slippage_bps: Option<u16>,
     pub transaction_hash: Option<String>,
+    #[serde(default)]
+    pub last_valid_block_height: Option<u64>,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub status_history: Vec<StatusChange>,
+    // Fee paid to the venue, in the quote token
+    #[serde(default)]
+    pub fee: Option<f64>,
+    // Produced by the paper trading engine rather than on-chain
+    #[serde(default)]
+    pub simulated: bool,
+    // Set on the child trades of a TWAP/VWAP parent order
+    #[serde(default)]
+    pub parent_order_id: Option<Uuid>,
+    // Compute budget requested by the submitted transaction
+    #[serde(default)]
+    pub compute_unit_limit: Option<u32>,
+    // Micro-lamports per compute unit
+    #[serde(default)]
+    pub compute_unit_price: Option<u64>,
+    // Base plus priority fee charged by the network once the transaction landed, in lamports
+    #[serde(default)]
+    pub network_fee: Option<u64>,
+    #[serde(default)]
+    pub attempts: Vec<SubmissionAttempt>,
+    // The submitter is still rebroadcasting until then, so the confirmation tracker leaves expiry to it
+    #[serde(default)]
+    pub retrying_until: Option<DateTime<Utc>>,
+    // Cleared once the volume has been given back to the strategy
+    #[serde(default)]
+    pub volume_reservation: Option<VolumeReservation>,
     pub created_at: DateTime<Utc>,
 }
 
 impl Transaction {
+    pub fn notional(&self) -> f64 {
+        self.amount * self.price
+    }
+
     pub fn collection(db: &Database) -> Collection<Self> {
         db.collection::<Self>("transactions")
     }
 
+    pub async fn find_by_id(db: &Database, id: Uuid, user_id: Uuid) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! { "_id": id, "user_id": user_id };
+        Self::collection(db).find_one(filter, None).await
+    }
+
     pub async fn find_by_user(db: &Database, user_id: Uuid, limit: i64) -> Result<Vec<Self>, mongodb::error::Error> {
         let filter = doc! { "user_id": user_id };
         let options = mongodb::options::FindOptions::builder()
             .sort(doc! { "created_at": -1 })
             .limit(limit)
             .build();
-            
+
+        let cursor = Self::collection(db).find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn find_by_strategy(db: &Database, strategy_id: Uuid, user_id: Uuid, limit: i64) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "strategy_id": strategy_id, "user_id": user_id };
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "created_at": -1 })
+            .limit(limit)
+            .build();
+
         let cursor = Self::collection(db).find(filter, options).await?;
         cursor.try_collect().await
     }
@@ -42,38 +176,389 @@ impl Transaction {
             .sort(doc! { "created_at": -1 })
             .limit(limit)
             .build();
-            
+
         let cursor = Self::collection(db).find(filter, options).await?;
         cursor.try_collect().await
     }
 
+    pub async fn find_by_strategy_since(
+        db: &Database,
+        strategy_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "strategy_id": strategy_id, "created_at": { "$gte": since } };
+        let cursor = Self::collection(db).find(filter, None).await?;
+        cursor.try_collect().await
+    }
+
+    /// Base token bought minus sold by a strategy across its landed trades.
+    pub async fn net_position(db: &Database, strategy_id: Uuid) -> Result<f64, mongodb::error::Error> {
+        let filter = doc! {
+            "strategy_id": strategy_id,
+            "status": { "$in": [TransactionStatus::Confirmed.as_str(), TransactionStatus::Finalized.as_str()] },
+        };
+        let transactions: Vec<Self> = Self::collection(db).find(filter, None).await?.try_collect().await?;
+
+        Ok(transactions.iter().fold(0.0, |position, tx| match tx.action.to_lowercase().as_str() {
+            "buy" => position + tx.amount,
+            "sell" => position - tx.amount,
+            _ => position,
+        }))
+    }
+
+    /// Landed trades of the user's up to `until`, oldest first, optionally narrowed by
+    /// wallet, strategy and token.
+    pub async fn find_executed(
+        db: &Database,
+        user_id: Uuid,
+        wallet_id: Option<Uuid>,
+        strategy_id: Option<Uuid>,
+        token: Option<&str>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Self>, mongodb::error::Error> {
+        let mut filter = doc! {
+            "user_id": user_id,
+            "status": { "$in": [TransactionStatus::Confirmed.as_str(), TransactionStatus::Finalized.as_str()] },
+        };
+        if let Some(wallet_id) = wallet_id {
+            filter.insert("wallet_id", wallet_id);
+        }
+        if let Some(strategy_id) = strategy_id {
+            filter.insert("strategy_id", strategy_id);
+        }
+        if let Some(token) = token {
+            filter.insert("token", token);
+        }
+        if let Some(until) = until {
+            filter.insert("created_at", doc! { "$lt": until });
+        }
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "created_at": 1 })
+            .build();
+
+        let cursor = Self::collection(db).find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    pub async fn find_by_parent_order(db: &Database, parent_order_id: Uuid, user_id: Uuid) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "parent_order_id": parent_order_id, "user_id": user_id };
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "created_at": 1 })
+            .build();
+
+        let cursor = Self::collection(db).find(filter, options).await?;
+        cursor.try_collect().await
+    }
+
+    /// Transactions that have been sent but have not reached a terminal status.
+    pub async fn find_in_flight(db: &Database) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! {
+            "status": { "$in": [TransactionStatus::Submitted.as_str(), TransactionStatus::Confirmed.as_str()] },
+            "transaction_hash": { "$ne": null },
+        };
+        let cursor = Self::collection(db).find(filter, None).await?;
+        cursor.try_collect().await
+    }
+
+    /// Transactions recorded before `before` that the confirmation tracker cannot follow: never
+    /// sent (`Built` or `Simulated`), or `Submitted` without a signature.
+    pub async fn find_abandoned(db: &Database, before: DateTime<Utc>) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! {
+            "created_at": { "$lt": before },
+            "$or": [
+                { "status": { "$in": [TransactionStatus::Built.as_str(), TransactionStatus::Simulated.as_str()] } },
+                { "status": TransactionStatus::Submitted.as_str(), "transaction_hash": null },
+            ],
+        };
+        let cursor = Self::collection(db).find(filter, None).await?;
+        cursor.try_collect().await
+    }
+
     pub async fn create(
         db: &Database,
         user_id: Uuid,
         wallet_id: Uuid,
+        strategy_id: Option<Uuid>,
         action: &str,
         amount: f64,
         token: &str,
         price: f64,
-        status: &str,
+        status: TransactionStatus,
         slippage: Option<f64>,
+        max_slippage_bps: Option<u16>,
         transaction_hash: Option<&str>,
+        volume_reservation: Option<VolumeReservation>,
+        parent_order_id: Option<Uuid>,
     ) -> Result<Self, mongodb::error::Error> {
+        let now = Utc::now();
         let transaction = Self {
             id: Uuid::new_v4(),
             user_id,
             wallet_id,
+            strategy_id,
             action: action.to_string(),
             amount,
             token: token.to_string(),
             price,
-            status: status.to_string(),
+            status,
             slippage,
+            max_slippage_bps,
             transaction_hash: transaction_hash.map(|s| s.to_string()),
-            created_at: Utc::now(),
+            last_valid_block_height: None,
+            error: None,
+            status_history: vec![StatusChange {
+                status,
+                error: None,
+                changed_at: now,
+            }],
+            fee: None,
+            simulated: false,
+            parent_order_id,
+            compute_unit_limit: None,
+            compute_unit_price: None,
+            network_fee: None,
+            attempts: Vec::new(),
+            retrying_until: None,
+            volume_reservation,
+            created_at: now,
+        };
+
+        Self::collection(db).insert_one(&transaction, None).await?;
+        Ok(transaction)
+    }
+
+    /// Records a paper trading fill, which is final as soon as it happens.
+    pub async fn create_simulated(
+        db: &Database,
+        user_id: Uuid,
+        wallet_id: Uuid,
+        strategy_id: Option<Uuid>,
+        action: &str,
+        amount: f64,
+        token: &str,
+        price: f64,
+        fee: f64,
+        parent_order_id: Option<Uuid>,
+    ) -> Result<Self, mongodb::error::Error> {
+        let now = Utc::now();
+        let transaction = Self {
+            id: Uuid::new_v4(),
+            user_id,
+            wallet_id,
+            strategy_id,
+            action: action.to_string(),
+            amount,
+            token: token.to_string(),
+            price,
+            status: TransactionStatus::Finalized,
+            slippage: None,
+            max_slippage_bps: None,
+            transaction_hash: None,
+            last_valid_block_height: None,
+            error: None,
+            status_history: vec![StatusChange {
+                status: TransactionStatus::Finalized,
+                error: None,
+                changed_at: now,
+            }],
+            fee: Some(fee),
+            simulated: true,
+            parent_order_id,
+            compute_unit_limit: None,
+            compute_unit_price: None,
+            network_fee: None,
+            attempts: Vec::new(),
+            retrying_until: None,
+            volume_reservation: None,
+            created_at: now,
         };
 
         Self::collection(db).insert_one(&transaction, None).await?;
         Ok(transaction)
     }
-} 
\ No newline at end of file
+
+    /// Records a venue fill of one of our resting orders as a landed trade, so the PnL ledger and the
+    /// loss breaker see maker fills the way they see swaps.
+    pub async fn create_venue_fill(db: &Database, order: &Order, size: f64, price: f64) -> Result<Self, mongodb::error::Error> {
+        let transaction = Self::venue_fill(order, size, price, Utc::now());
+        Self::collection(db).insert_one(&transaction, None).await?;
+        Ok(transaction)
+    }
+
+    /// The unsaved trade `create_venue_fill` records. Maker fills are sent by whoever took the
+    /// order, so there is no signature of ours to keep.
+    pub fn venue_fill(order: &Order, size: f64, price: f64, at: DateTime<Utc>) -> Self {
+        let action = match order.side {
+            OrderSide::Buy => "buy",
+            OrderSide::Sell => "sell",
+        };
+        Self {
+            id: Uuid::new_v4(),
+            user_id: order.user_id,
+            wallet_id: order.wallet_id,
+            strategy_id: order.strategy_id,
+            action: action.to_string(),
+            amount: size,
+            // "SOL/USDC" trades SOL
+            token: order.market_pair.split('/').next().unwrap_or(&order.market_pair).to_string(),
+            price,
+            status: TransactionStatus::Finalized,
+            slippage: None,
+            max_slippage_bps: None,
+            transaction_hash: None,
+            last_valid_block_height: None,
+            error: None,
+            status_history: vec![StatusChange {
+                status: TransactionStatus::Finalized,
+                error: None,
+                changed_at: at,
+            }],
+            fee: None,
+            simulated: false,
+            parent_order_id: None,
+            compute_unit_limit: None,
+            compute_unit_price: None,
+            network_fee: None,
+            attempts: Vec::new(),
+            retrying_until: None,
+            volume_reservation: None,
+            created_at: at,
+        }
+    }
+
+    /// Moves the transaction to `status` if that is a legal transition from its current status.
+    /// Returns false when the transition was not applied.
+    pub async fn transition(
+        db: &Database,
+        id: Uuid,
+        from: TransactionStatus,
+        status: TransactionStatus,
+        error: Option<&str>,
+    ) -> Result<bool, mongodb::error::Error> {
+        if !from.can_transition_to(status) {
+            return Ok(false);
+        }
+
+        let change = StatusChange {
+            status,
+            error: error.map(|s| s.to_string()),
+            changed_at: Utc::now(),
+        };
+        // Matching on the current status keeps concurrent updaters from skipping states
+        let filter = doc! { "_id": id, "status": from.as_str() };
+        let update = doc! {
+            "$set": { "status": status.as_str(), "error": error },
+            "$push": { "status_history": bson::to_bson(&change).map_err(|e| mongodb::error::Error::from(mongodb::error::ErrorKind::InvalidArgument { message: e.to_string() }))? },
+        };
+
+        let result = Self::collection(db).update_one(filter, update, None).await?;
+        Ok(result.modified_count == 1)
+    }
+
+    /// Clears the volume reservation of a transaction that failed or expired and returns the
+    /// transaction as it was, so each reservation is given back exactly once.
+    pub async fn take_volume_reservation(db: &Database, id: Uuid) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! {
+            "_id": id,
+            "status": { "$in": [TransactionStatus::Failed.as_str(), TransactionStatus::Expired.as_str()] },
+            "volume_reservation": { "$ne": null },
+        };
+        let update = doc! { "$set": { "volume_reservation": null } };
+        Self::collection(db).find_one_and_update(filter, update, None).await
+    }
+
+    /// Makes `attempt` the transaction's current signature and adds it to the attempt history.
+    pub async fn record_attempt(
+        db: &Database,
+        id: Uuid,
+        attempt: &SubmissionAttempt,
+        retrying_until: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error> {
+        let filter = doc! { "_id": id };
+        let update = doc! {
+            "$set": {
+                "transaction_hash": attempt.signature.as_str(),
+                "last_valid_block_height": attempt.last_valid_block_height as i64,
+                "compute_unit_price": attempt.compute_unit_price as i64,
+                "retrying_until": retrying_until,
+            },
+            "$push": { "attempts": bson::to_bson(attempt).map_err(|e| mongodb::error::Error::from(mongodb::error::ErrorKind::InvalidArgument { message: e.to_string() }))? },
+        };
+        Self::collection(db).update_one(filter, update, None).await?;
+        Ok(())
+    }
+
+    pub async fn set_execution(
+        db: &Database,
+        id: Uuid,
+        price: f64,
+        slippage: Option<f64>,
+    ) -> Result<(), mongodb::error::Error> {
+        let filter = doc! { "_id": id };
+        let update = doc! { "$set": { "price": price, "slippage": slippage } };
+        Self::collection(db).update_one(filter, update, None).await?;
+        Ok(())
+    }
+
+    pub async fn set_compute_budget(
+        db: &Database,
+        id: Uuid,
+        compute_unit_limit: u32,
+        compute_unit_price: u64,
+    ) -> Result<(), mongodb::error::Error> {
+        let filter = doc! { "_id": id };
+        let update = doc! {
+            "$set": {
+                "compute_unit_limit": compute_unit_limit as i64,
+                "compute_unit_price": compute_unit_price as i64,
+            }
+        };
+        Self::collection(db).update_one(filter, update, None).await?;
+        Ok(())
+    }
+
+    pub async fn set_network_fee(db: &Database, id: Uuid, network_fee: u64) -> Result<(), mongodb::error::Error> {
+        let filter = doc! { "_id": id };
+        let update = doc! { "$set": { "network_fee": network_fee as i64 } };
+        Self::collection(db).update_one(filter, update, None).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TransactionStatus::*;
+
+    #[test]
+    fn lifecycle_moves_forward() {
+        assert!(Built.can_transition_to(Simulated));
+        assert!(Built.can_transition_to(Submitted));
+        assert!(Simulated.can_transition_to(Submitted));
+        assert!(Submitted.can_transition_to(Confirmed));
+        assert!(Submitted.can_transition_to(Finalized));
+        assert!(Submitted.can_transition_to(Expired));
+        assert!(Confirmed.can_transition_to(Finalized));
+    }
+
+    #[test]
+    fn lifecycle_never_moves_backwards_or_skips_submission() {
+        assert!(!Submitted.can_transition_to(Built));
+        assert!(!Confirmed.can_transition_to(Submitted));
+        assert!(!Built.can_transition_to(Confirmed));
+        assert!(!Simulated.can_transition_to(Expired));
+        assert!(!Confirmed.can_transition_to(Expired));
+        assert!(!Submitted.can_transition_to(Submitted));
+    }
+
+    #[test]
+    fn only_unfinished_transactions_can_fail() {
+        for from in [Built, Simulated, Submitted, Confirmed] {
+            assert!(from.can_transition_to(Failed), "{} should be able to fail", from.as_str());
+        }
+        for from in [Finalized, Failed, Expired] {
+            assert!(!from.can_transition_to(Failed), "{} should stay final", from.as_str());
+            assert!(!from.can_transition_to(Finalized), "{} should stay final", from.as_str());
+        }
+    }
+}
@@ -11,6 +11,9 @@ pub struct User {
     pub email: String,
     pub name: String,
     pub password_hash: String,
+    // Users in the same organization are kept from trading against each other's quotes
+    #[serde(default)]
+    pub organization_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -30,6 +33,22 @@ impl User {
         Self::collection(db).find_one(filter, None).await
     }
 
+    /// The user plus everyone in their organization, if they belong to one.
+    pub async fn organization_members(db: &Database, user_id: Uuid) -> Result<Vec<Uuid>, mongodb::error::Error> {
+        let Some(organization_id) = Self::find_by_id(db, user_id).await?.and_then(|u| u.organization_id) else {
+            return Ok(vec![user_id]);
+        };
+        let mut cursor = Self::collection(db).find(doc! { "organization_id": organization_id }, None).await?;
+        let mut members = vec![user_id];
+        while cursor.advance().await? {
+            let member = cursor.deserialize_current()?;
+            if member.id != user_id {
+                members.push(member.id);
+            }
+        }
+        Ok(members)
+    }
+
     pub async fn create(
         db: &Database,
         email: &str,
@@ -42,6 +61,7 @@ impl User {
             email: email.to_string(),
             name: name.to_string(),
             password_hash: password_hash.to_string(),
+            organization_id: None,
             created_at: now,
             updated_at: now,
         };
@@ -4,6 +4,40 @@ use mongodb::{Collection, Database};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Where a wallet is in its lifecycle. Serialized as written, which is how wallets created
+/// before the lifecycle existed stored "Active".
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum WalletStatus {
+    Active,
+    // Kept out of trading, rebalancing and top-ups until it is made active again
+    Paused,
+    // Not traded from; the rebalancer moves its funds to the user's other wallets
+    Draining,
+    // Retired for good, with no allocation
+    Archived,
+}
+
+impl WalletStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WalletStatus::Active => "Active",
+            WalletStatus::Paused => "Paused",
+            WalletStatus::Draining => "Draining",
+            WalletStatus::Archived => "Archived",
+        }
+    }
+
+    pub fn can_transition_to(&self, next: WalletStatus) -> bool {
+        use WalletStatus::*;
+        match (self, next) {
+            (Active, Paused) | (Active, Draining) => true,
+            (Paused, Active) | (Paused, Draining) | (Paused, Archived) => true,
+            (Draining, Active) | (Draining, Paused) | (Draining, Archived) => true,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Wallet {
     #[serde(rename = "_id")]
@@ -12,7 +46,10 @@ pub struct Wallet {
     pub address: String,
     pub label: Option<String>,
     pub allocation_percentage: f64,
-    pub status: String,
+    pub status: WalletStatus,
+    // Account index under the user's master mnemonic, for wallets derived from it
+    #[serde(default)]
+    pub derivation_index: Option<u32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -33,12 +70,18 @@ impl Wallet {
         Self::collection(db).find_one(filter, None).await
     }
 
+    pub async fn find_by_address(db: &Database, user_id: Uuid, address: &str) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! { "user_id": user_id, "address": address };
+        Self::collection(db).find_one(filter, None).await
+    }
+
     pub async fn create(
         db: &Database,
         user_id: Uuid,
         address: &str,
         label: Option<&str>,
         allocation_percentage: Option<f64>,
+        derivation_index: Option<u32>,
     ) -> Result<Self, mongodb::error::Error> {
         let now = Utc::now();
         let wallet = Self {
@@ -47,7 +90,8 @@ impl Wallet {
             address: address.to_string(),
             label: label.map(|s| s.to_string()),
             allocation_percentage: allocation_percentage.unwrap_or(0.0),
-            status: "Active".to_string(),
+            status: WalletStatus::Active,
+            derivation_index,
             created_at: now,
             updated_at: now,
         };
@@ -56,6 +100,65 @@ impl Wallet {
         Ok(wallet)
     }
 
+    /// Every user's wallets that are not active.
+    pub async fn find_inactive(db: &Database) -> Result<Vec<Self>, mongodb::error::Error> {
+        let filter = doc! { "status": { "$ne": WalletStatus::Active.as_str() } };
+        let cursor = Self::collection(db).find(filter, None).await?;
+        cursor.try_collect().await
+    }
+
+    /// Sum of the allocations of the user's wallets, leaving out `except`.
+    pub async fn total_allocation(db: &Database, user_id: Uuid, except: Option<Uuid>) -> Result<f64, mongodb::error::Error> {
+        Ok(Self::find_by_user(db, user_id)
+            .await?
+            .iter()
+            .filter(|w| Some(w.id) != except)
+            .map(|w| w.allocation_percentage)
+            .sum())
+    }
+
+    pub async fn update(
+        db: &Database,
+        id: Uuid,
+        user_id: Uuid,
+        label: Option<&str>,
+        allocation_percentage: Option<f64>,
+        status: Option<WalletStatus>,
+    ) -> Result<Option<Self>, mongodb::error::Error> {
+        let filter = doc! { "_id": id, "user_id": user_id };
+        let mut update_doc = doc! { "updated_at": Utc::now() };
+
+        if let Some(label) = label {
+            update_doc.insert("label", label);
+        }
+
+        if let Some(allocation) = allocation_percentage {
+            update_doc.insert("allocation_percentage", allocation);
+        }
+
+        if let Some(status) = status {
+            update_doc.insert("status", status.as_str());
+        }
+
+        let update = doc! { "$set": update_doc };
+        Self::collection(db).update_one(filter.clone(), update, None).await?;
+        Self::collection(db).find_one(filter, None).await
+    }
+
+    /// Sets the wallet's allocation if it is still `from`. Returns false when it had changed.
+    pub async fn set_allocation(
+        db: &Database,
+        id: Uuid,
+        user_id: Uuid,
+        from: f64,
+        to: f64,
+    ) -> Result<bool, mongodb::error::Error> {
+        let filter = doc! { "_id": id, "user_id": user_id, "allocation_percentage": from };
+        let update = doc! { "$set": { "allocation_percentage": to, "updated_at": Utc::now() } };
+        let result = Self::collection(db).update_one(filter, update, None).await?;
+        Ok(result.matched_count == 1)
+    }
+
     pub async fn delete(db: &Database, id: Uuid, user_id: Uuid) -> Result<(), mongodb::error::Error> {
         let filter = doc! { "_id": id, "user_id": user_id };
         Self::collection(db).delete_one(filter, None).await?;
@@ -0,0 +1,68 @@
+use bson::doc;
+use chrono::{DateTime, Utc};
+use mongodb::options::UpdateOptions;
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::wallet::Wallet;
+
+// Leaves room for rounding in percentages meant to add up to exactly 100
+pub const ALLOCATION_TOLERANCE: f64 = 1e-9;
+
+/// Combined allocation of a user's wallets, in percent, kept alongside the wallets so changes to
+/// it can be checked against the 100% cap atomically.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WalletAllocation {
+    pub user_id: Uuid,
+    pub allocated: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WalletAllocation {
+    pub fn collection(db: &Database) -> Collection<Self> {
+        db.collection::<Self>("wallet_allocations")
+    }
+
+    pub async fn find_by_user(db: &Database, user_id: Uuid) -> Result<Option<Self>, mongodb::error::Error> {
+        Self::collection(db).find_one(doc! { "user_id": user_id }, None).await
+    }
+
+    /// Atomically moves the user's total by `delta` unless an increase would take it past 100%.
+    /// Decreases always apply. Returns false when the change was refused.
+    pub async fn reserve(db: &Database, user_id: Uuid, delta: f64) -> Result<bool, mongodb::error::Error> {
+        // Start the counter from the wallets the user already has so the conditional update has
+        // something to match
+        let existing = Wallet::total_allocation(db, user_id, None).await?;
+        let init = doc! {
+            "$setOnInsert": {
+                "allocated": existing,
+                "updated_at": Utc::now(),
+            }
+        };
+        let options = UpdateOptions::builder().upsert(true).build();
+        Self::collection(db).update_one(doc! { "user_id": user_id }, init, options).await?;
+
+        let mut filter = doc! { "user_id": user_id };
+        if delta > 0.0 {
+            filter.insert("allocated", doc! { "$lte": 100.0 + ALLOCATION_TOLERANCE - delta });
+        }
+        let update = doc! {
+            "$inc": { "allocated": delta },
+            "$set": { "updated_at": Utc::now() },
+        };
+        let result = Self::collection(db).update_one(filter, update, None).await?;
+        Ok(result.modified_count == 1)
+    }
+
+    /// Takes back a change made by `reserve`, e.g. when the wallets it was for were not written,
+    /// or frees a deleted wallet's share.
+    pub async fn release(db: &Database, user_id: Uuid, delta: f64) -> Result<(), mongodb::error::Error> {
+        let update = doc! {
+            "$inc": { "allocated": -delta },
+            "$set": { "updated_at": Utc::now() },
+        };
+        Self::collection(db).update_one(doc! { "user_id": user_id }, update, None).await?;
+        Ok(())
+    }
+}
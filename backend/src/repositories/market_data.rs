@@ -1,3 +1,6 @@
+use bson::doc;
+use futures::TryStreamExt;
+use mongodb::options::{FindOptions, UpdateOptions};
 use mongodb::{Collection, Database};
 use crate::models::orderbook::{OrderBook, MarketPrice};
 use anyhow::Result;
@@ -16,22 +19,49 @@ pub struct MarketDataRecord {
 
 pub struct MarketDataRepository {
     collection: Collection<MarketDataRecord>,
+    // Every snapshot ever saved, kept for replaying through the backtester
+    history: Collection<MarketDataRecord>,
 }
 
 impl MarketDataRepository {
     pub fn new(db: Database) -> Self {
         Self {
             collection: db.collection("market_data"),
+            history: db.collection("market_data_history"),
         }
     }
 
     pub async fn save_market_data(&self, data: MarketDataRecord) -> Result<()> {
         let filter = doc! { "symbol": &data.symbol };
         let update = doc! { "$set": bson::to_document(&data)? };
-        self.collection.update_one(filter, update, None).await?;
+        let options = UpdateOptions::builder().upsert(true).build();
+        self.collection.update_one(filter, update, options).await?;
+        self.history.insert_one(&data, None).await?;
         Ok(())
     }
 
+    /// Adds a snapshot to the history only, leaving the latest market data as it is.
+    pub async fn append_history(&self, data: &MarketDataRecord) -> Result<()> {
+        self.history.insert_one(data, None).await?;
+        Ok(())
+    }
+
+    /// Snapshots for `symbol` between `from` and `to`, oldest first.
+    pub async fn get_history(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<MarketDataRecord>> {
+        let filter = doc! {
+            "symbol": symbol,
+            "timestamp": { "$gte": from, "$lte": to },
+        };
+        let options = FindOptions::builder().sort(doc! { "timestamp": 1 }).build();
+        let cursor = self.history.find(filter, options).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
     pub async fn get_market_data(&self, symbol: &str) -> Result<Option<MarketDataRecord>> {
         let filter = doc! { "symbol": symbol };
         let result = self.collection.find_one(filter, None).await?;
@@ -0,0 +1 @@
+pub mod market_data;
\ No newline at end of file
@@ -0,0 +1,157 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::VersionedTransaction;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SwapMode {
+    ExactIn,
+    ExactOut,
+}
+
+#[derive(Debug, Clone)]
+pub struct QuoteRequest {
+    pub input_mint: String,
+    pub output_mint: String,
+    // In base units of the input mint for ExactIn, of the output mint for ExactOut
+    pub amount: u64,
+    pub swap_mode: SwapMode,
+    pub slippage_bps: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct SwapQuote {
+    pub input_mint: String,
+    pub output_mint: String,
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub price_impact_pct: f64,
+    pub slippage_bps: u16,
+    // Least the swap may output for ExactIn, most it may take in for ExactOut
+    pub other_amount_threshold: u64,
+    // The aggregator expects its own quote back verbatim when building the swap
+    pub raw: serde_json::Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct BuiltSwap {
+    pub transaction: VersionedTransaction,
+    // Block height after which the transaction's blockhash can no longer land
+    pub last_valid_block_height: Option<u64>,
+}
+
+/// Route quoting and swap building, implemented by DEX aggregators.
+#[async_trait]
+pub trait SwapAggregator: Send + Sync {
+    async fn quote(&self, request: &QuoteRequest) -> Result<SwapQuote>;
+
+    /// Returns an unsigned swap transaction for `user` that executes `quote`.
+    async fn build_swap(&self, quote: &SwapQuote, user: &Pubkey) -> Result<BuiltSwap>;
+}
+
+/// Client for the Jupiter v6 swap API. Point `base_url` at a local server to stub it out.
+pub struct JupiterClient {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JupiterQuoteResponse {
+    in_amount: String,
+    out_amount: String,
+    other_amount_threshold: String,
+    price_impact_pct: String,
+    slippage_bps: u16,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JupiterSwapRequest<'a> {
+    quote_response: &'a serde_json::Value,
+    user_public_key: String,
+    wrap_and_unwrap_sol: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JupiterSwapResponse {
+    swap_transaction: String,
+    last_valid_block_height: Option<u64>,
+}
+
+impl JupiterClient {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl SwapAggregator for JupiterClient {
+    async fn quote(&self, request: &QuoteRequest) -> Result<SwapQuote> {
+        let swap_mode = match request.swap_mode {
+            SwapMode::ExactIn => "ExactIn",
+            SwapMode::ExactOut => "ExactOut",
+        };
+        let url = format!("{}/quote", self.base_url);
+        let response = self
+            .client
+            .get(&url)
+            .query(&[
+                ("inputMint", request.input_mint.clone()),
+                ("outputMint", request.output_mint.clone()),
+                ("amount", request.amount.to_string()),
+                ("swapMode", swap_mode.to_string()),
+                ("slippageBps", request.slippage_bps.to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let raw = response.json::<serde_json::Value>().await?;
+        let parsed: JupiterQuoteResponse = serde_json::from_value(raw.clone())?;
+
+        Ok(SwapQuote {
+            input_mint: request.input_mint.clone(),
+            output_mint: request.output_mint.clone(),
+            in_amount: parsed.in_amount.parse()?,
+            out_amount: parsed.out_amount.parse()?,
+            price_impact_pct: parsed.price_impact_pct.parse().unwrap_or_default(),
+            slippage_bps: parsed.slippage_bps,
+            other_amount_threshold: parsed.other_amount_threshold.parse()?,
+            raw,
+        })
+    }
+
+    async fn build_swap(&self, quote: &SwapQuote, user: &Pubkey) -> Result<BuiltSwap> {
+        let url = format!("{}/swap", self.base_url);
+        let body = JupiterSwapRequest {
+            quote_response: &quote.raw,
+            user_public_key: user.to_string(),
+            wrap_and_unwrap_sol: true,
+        };
+        let response = self
+            .client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JupiterSwapResponse>()
+            .await?;
+
+        let bytes = BASE64
+            .decode(response.swap_transaction)
+            .map_err(|e| anyhow!("Invalid swap transaction encoding: {}", e))?;
+        let transaction: VersionedTransaction = bincode::deserialize(&bytes)?;
+        Ok(BuiltSwap {
+            transaction,
+            last_valid_block_height: response.last_valid_block_height,
+        })
+    }
+}
@@ -3,8 +3,11 @@ use uuid::Uuid;
 
 use crate::api::alerts::AlertRequest;
 use crate::models::alert::Alert;
+use crate::models::alert_event::AlertEvent;
 use crate::utils::errors::ServiceError;
 
+const ALERT_EVENT_LIMIT: i64 = 100;
+
 pub async fn get_alerts(
     db: &Database,
     user_id: Uuid,
@@ -13,6 +16,15 @@ pub async fn get_alerts(
     Ok(alerts)
 }
 
+/// Alerts the backend raised for the user, newest first.
+pub async fn get_alert_events(
+    db: &Database,
+    user_id: Uuid,
+) -> Result<Vec<AlertEvent>, ServiceError> {
+    let events = AlertEvent::find_by_user(db, user_id, ALERT_EVENT_LIMIT).await?;
+    Ok(events)
+}
+
 pub async fn create_alert(
     db: &Database,
     user_id: Uuid,
@@ -0,0 +1,189 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+
+use crate::models::strategy::{AvellanedaStoikovParams, Strategy, StrategyParams};
+
+// Mid price samples kept when a strategy does not configure its own window
+const DEFAULT_VOLATILITY_WINDOW: usize = 30;
+
+/// Output of the Avellaneda–Stoikov model for one quoting cycle, kept for inspection.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReservationQuote {
+    pub mid_price: f64,
+    // Variance of the mid price per second, in price units squared
+    pub variance: f64,
+    // Base token position relative to the strategy's target
+    pub inventory: f64,
+    pub reservation_price: f64,
+    // Full distance between bid and ask
+    pub spread: f64,
+    pub computed_at: DateTime<Utc>,
+}
+
+/// Rolling estimate of mid price variance from the samples seen while quoting.
+#[derive(Debug, Clone)]
+pub struct VolatilityEstimator {
+    window: usize,
+    samples: VecDeque<(DateTime<Utc>, f64)>,
+}
+
+impl VolatilityEstimator {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(2),
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Estimator sized to the strategy's configured volatility window.
+    pub fn for_strategy(strategy: &Strategy) -> Self {
+        Self::new(match &strategy.params {
+            StrategyParams::AvellanedaStoikov(p) => p.volatility_window as usize,
+            _ => DEFAULT_VOLATILITY_WINDOW,
+        })
+    }
+
+    pub fn push(&mut self, at: DateTime<Utc>, mid: f64) {
+        if mid <= 0.0 || !mid.is_finite() {
+            return;
+        }
+        self.samples.push_back((at, mid));
+        while self.samples.len() > self.window {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Variance of the mid price per second, or `None` until there are enough samples.
+    pub fn variance(&self) -> Option<f64> {
+        if self.samples.len() < 3 {
+            return None;
+        }
+
+        // Log returns scaled to a one second horizon so uneven sampling does not bias the estimate
+        let returns: Vec<f64> = self
+            .samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .filter_map(|((t0, p0), (t1, p1))| {
+                let dt = (*t1 - *t0).num_milliseconds() as f64 / 1000.0;
+                (dt > 0.0).then(|| (p1 / p0).ln() / dt.sqrt())
+            })
+            .collect();
+        if returns.len() < 2 {
+            return None;
+        }
+
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        let return_variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+
+        // Convert from log-return variance to price variance around the latest mid
+        let (_, last) = self.samples.back()?;
+        Some(return_variance * last * last)
+    }
+}
+
+/// Reservation price r = s - q·γ·σ²·τ and optimal spread δ = γ·σ²·τ + (2/γ)·ln(1 + γ/κ).
+pub fn reservation_quote(params: &AvellanedaStoikovParams, mid: f64, variance: f64, position: f64) -> ReservationQuote {
+    let gamma = params.risk_aversion;
+    let tau = params.horizon_secs as f64;
+    let inventory = position - params.target_inventory;
+
+    let reservation_price = mid - inventory * gamma * variance * tau;
+    let spread = gamma * variance * tau + (2.0 / gamma) * (1.0 + gamma / params.order_arrival_intensity).ln();
+
+    ReservationQuote {
+        mid_price: mid,
+        variance,
+        inventory,
+        reservation_price,
+        spread,
+        computed_at: Utc::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn params() -> AvellanedaStoikovParams {
+        AvellanedaStoikovParams {
+            risk_aversion: 0.1,
+            horizon_secs: 60,
+            order_arrival_intensity: 1.5,
+            volatility_window: 30,
+            target_inventory: 2.0,
+        }
+    }
+
+    fn estimator(window: usize, samples: &[(i64, f64)]) -> VolatilityEstimator {
+        let start = Utc::now();
+        let mut estimator = VolatilityEstimator::new(window);
+        for (secs, mid) in samples {
+            estimator.push(start + Duration::seconds(*secs), *mid);
+        }
+        estimator
+    }
+
+    #[test]
+    fn target_inventory_quotes_around_the_mid() {
+        let quote = reservation_quote(&params(), 100.0, 0.01, 2.0);
+        assert_eq!(quote.inventory, 0.0);
+        assert_eq!(quote.reservation_price, 100.0);
+
+        let expected = 0.1 * 0.01 * 60.0 + (2.0 / 0.1) * (1.0 + 0.1 / 1.5f64).ln();
+        assert!((quote.spread - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn inventory_moves_the_reservation_price_against_it() {
+        let long = reservation_quote(&params(), 100.0, 0.01, 5.0);
+        assert!((long.reservation_price - (100.0 - 3.0 * 0.1 * 0.01 * 60.0)).abs() < 1e-12);
+
+        let short = reservation_quote(&params(), 100.0, 0.01, -1.0);
+        assert!(short.reservation_price > 100.0);
+        assert!((long.spread - short.spread).abs() < 1e-12);
+    }
+
+    #[test]
+    fn volatility_widens_the_spread() {
+        let calm = reservation_quote(&params(), 100.0, 0.01, 2.0);
+        let volatile = reservation_quote(&params(), 100.0, 0.04, 2.0);
+        assert!(volatile.spread > calm.spread);
+    }
+
+    #[test]
+    fn variance_needs_three_samples() {
+        assert_eq!(estimator(10, &[(0, 100.0), (1, 101.0)]).variance(), None);
+        assert!(estimator(10, &[(0, 100.0), (1, 101.0), (2, 100.0)]).variance().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn variance_is_of_log_returns_per_second_in_price_terms() {
+        let expected = 2.0 * 1.1f64.ln().powi(2) * 100.0 * 100.0;
+        let variance = estimator(10, &[(0, 100.0), (1, 110.0), (2, 100.0)]).variance().unwrap();
+        assert!((variance - expected).abs() < 1e-9);
+
+        // Samples four seconds apart carry a quarter of the per-second variance
+        let spaced = estimator(10, &[(0, 100.0), (4, 110.0), (8, 100.0)]).variance().unwrap();
+        assert!((spaced - expected / 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn steady_mid_has_no_variance() {
+        assert_eq!(estimator(10, &[(0, 100.0), (1, 100.0), (2, 100.0), (3, 100.0)]).variance(), Some(0.0));
+    }
+
+    #[test]
+    fn only_the_window_is_kept() {
+        let samples = [(0, 100.0), (1, 200.0), (2, 100.0), (3, 100.0), (4, 100.0)];
+        assert_eq!(estimator(3, &samples).variance(), Some(0.0));
+    }
+
+    #[test]
+    fn invalid_mids_are_ignored() {
+        let samples = [(0, 100.0), (1, 0.0), (2, f64::NAN), (3, -5.0), (4, 100.0)];
+        assert_eq!(estimator(10, &samples).variance(), None);
+    }
+}
@@ -0,0 +1,409 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use mongodb::Database;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::api::trading::BacktestRequest;
+use crate::config::Config;
+use crate::models::backtest::{BacktestFill, BacktestParams, BacktestReport, BacktestRun, EquityPoint};
+use crate::models::orderbook::{OrderBook, OrderBookEntry};
+use crate::models::strategy::Strategy;
+use crate::models::strategy_volume::StrategyVolume;
+use crate::repositories::market_data::{MarketDataRecord, MarketDataRepository};
+use crate::services::avellaneda_stoikov::VolatilityEstimator;
+use crate::services::market_maker::{compute_quotes, mid_price, Quote, QuoteInputs};
+use crate::services::venues::OrderSide;
+use crate::utils::errors::ServiceError;
+
+// Depth assumed at the top of book for snapshots that only carry prices
+pub const DEFAULT_LEVEL_SIZE: f64 = 1_000_000.0;
+
+/// Lines of a JSONL export can be full market data records or bare order books.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonlSnapshot {
+    Record(MarketDataRecord),
+    OrderBook(OrderBook),
+}
+
+/// Recorded snapshots for `market_pair`, oldest first.
+pub async fn load_history(
+    db: &Database,
+    market_pair: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<OrderBook>> {
+    let repository = MarketDataRepository::new(db.clone());
+    let records = repository.get_history(market_pair, from, to).await?;
+    Ok(records.into_iter().map(record_to_snapshot).collect())
+}
+
+/// Reads snapshots from an exported `.jsonl` file or a `timestamp,bid,ask[,bid_size,ask_size]` `.csv` file.
+pub fn load_file(path: &Path, market_pair: &str) -> Result<Vec<OrderBook>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let mut snapshots = match path.extension().and_then(|e| e.to_str()) {
+        Some("jsonl") | Some("ndjson") => parse_jsonl(&contents)?,
+        Some("csv") => parse_csv(&contents, market_pair)?,
+        _ => return Err(anyhow!("Unsupported market data file {}, expected .jsonl or .csv", path.display())),
+    };
+
+    snapshots.retain(|s| s.market_pair == market_pair);
+    snapshots.sort_by_key(|s| s.timestamp);
+    Ok(snapshots)
+}
+
+fn parse_jsonl(contents: &str) -> Result<Vec<OrderBook>> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let snapshot: JsonlSnapshot = serde_json::from_str(line)
+                .with_context(|| format!("Invalid market data on line {}", i + 1))?;
+            Ok(match snapshot {
+                JsonlSnapshot::Record(record) => record_to_snapshot(record),
+                JsonlSnapshot::OrderBook(order_book) => order_book,
+            })
+        })
+        .collect()
+}
+
+fn parse_csv(contents: &str, market_pair: &str) -> Result<Vec<OrderBook>> {
+    let mut snapshots = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        // Skip the header and blank lines
+        if fields[0].is_empty() || fields[0].eq_ignore_ascii_case("timestamp") {
+            continue;
+        }
+        if fields.len() < 3 {
+            return Err(anyhow!("Expected at least timestamp,bid,ask on line {}", i + 1));
+        }
+
+        let timestamp = DateTime::parse_from_rfc3339(fields[0])
+            .with_context(|| format!("Invalid timestamp on line {}", i + 1))?
+            .with_timezone(&Utc);
+        let number = |index: usize, default: f64| -> Result<f64> {
+            match fields.get(index).filter(|f| !f.is_empty()) {
+                Some(field) => field.parse().with_context(|| format!("Invalid number on line {}", i + 1)),
+                None => Ok(default),
+            }
+        };
+        let bid = number(1, f64::NAN)?;
+        let ask = number(2, f64::NAN)?;
+        let bid_size = number(3, DEFAULT_LEVEL_SIZE)?;
+        let ask_size = number(4, DEFAULT_LEVEL_SIZE)?;
+
+        snapshots.push(OrderBook {
+            market_pair: market_pair.to_string(),
+            bids: vec![OrderBookEntry { price: bid, size: bid_size, total: bid * bid_size, is_bot: false }],
+            asks: vec![OrderBookEntry { price: ask, size: ask_size, total: ask * ask_size, is_bot: false }],
+            last_price: (bid + ask) / 2.0,
+            timestamp,
+        });
+    }
+
+    Ok(snapshots)
+}
+
+fn record_to_snapshot(record: MarketDataRecord) -> OrderBook {
+    let mut order_book = record.order_book;
+    order_book.market_pair = record.symbol;
+    order_book.timestamp = record.timestamp;
+    order_book
+}
+
+/// Replays `snapshots` through the same quoting logic the live engine uses.
+/// Resting quotes fill at their own price as the maker once the book trades through them.
+pub fn run_backtest(strategy: &Strategy, snapshots: &[OrderBook], params: &BacktestParams) -> BacktestReport {
+    let cadence = chrono::Duration::seconds(strategy.trade_frequency.max(1) as i64);
+
+    let mut report = BacktestReport::default();
+    let mut base = params.initial_base_balance;
+    let mut quote = params.initial_quote_balance;
+    // Average cost of the base we hold; starting inventory is valued at the first mid price
+    let mut cost_basis: Option<f64> = None;
+    let mut working: Vec<Quote> = Vec::new();
+    let mut last_quote_at: Option<DateTime<Utc>> = None;
+    let mut daily_volume: HashMap<String, f64> = HashMap::new();
+    let mut peak_equity = f64::MIN;
+    let mut last_mid = None;
+    let mut volatility = VolatilityEstimator::for_strategy(strategy);
+
+    for snapshot in snapshots {
+        let mid = match mid_price(snapshot) {
+            Some(mid) if mid.is_finite() => mid,
+            _ => continue,
+        };
+        let avg_cost = cost_basis.get_or_insert(mid);
+        volatility.push(snapshot.timestamp, mid);
+        let day = StrategyVolume::day_key(snapshot.timestamp);
+
+        let best_bid = snapshot.bids.iter().map(|e| e.price).fold(f64::NAN, f64::max);
+        let best_ask = snapshot.asks.iter().map(|e| e.price).fold(f64::NAN, f64::min);
+
+        // Fill quotes the market has traded through since they were placed
+        for resting in std::mem::take(&mut working) {
+            let crossed = match resting.side {
+                OrderSide::Buy => best_ask <= resting.price,
+                OrderSide::Sell => best_bid >= resting.price,
+            };
+            if !crossed {
+                working.push(resting);
+                continue;
+            }
+
+            let notional = resting.price * resting.size;
+            let fee = notional * params.maker_fee_bps / 10_000.0;
+            match resting.side {
+                OrderSide::Buy => {
+                    // The venue would have rejected an order we could not pay for
+                    if quote < notional + fee {
+                        continue;
+                    }
+                    *avg_cost = (base * *avg_cost + notional) / (base + resting.size);
+                    base += resting.size;
+                    quote -= notional + fee;
+                    report.realized_pnl -= fee;
+                }
+                OrderSide::Sell => {
+                    if base < resting.size {
+                        continue;
+                    }
+                    report.realized_pnl += (resting.price - *avg_cost) * resting.size - fee;
+                    base -= resting.size;
+                    quote += notional - fee;
+                }
+            }
+
+            report.fees_paid += fee;
+            report.volume += notional;
+            *daily_volume.entry(day.clone()).or_default() += notional;
+            report.fills.push(BacktestFill {
+                timestamp: snapshot.timestamp,
+                side: resting.side,
+                price: resting.price,
+                size: resting.size,
+                fee,
+            });
+        }
+
+        // Requote on the strategy's cadence, staying out of the market once the daily cap is used up
+        if last_quote_at.map_or(true, |at| snapshot.timestamp - at >= cadence) {
+            let (quotes, _) = compute_quotes(
+                strategy,
+                &QuoteInputs {
+                    mid,
+                    // The simulated balance stands in for the wallet's, as inventory is defined live
+                    inventory: base,
+                    variance: volatility.variance(),
+                    default_spread_bps: params.spread_bps,
+                },
+            );
+            let remaining = strategy.max_daily_volume - daily_volume.get(&day).copied().unwrap_or(0.0);
+            let largest_fill = quotes.iter().map(|q| q.price * q.size).fold(0.0, f64::max);
+            working = if largest_fill > remaining { Vec::new() } else { quotes };
+            last_quote_at = Some(snapshot.timestamp);
+        }
+
+        let equity = quote + base * mid;
+        peak_equity = peak_equity.max(equity);
+        let drawdown = peak_equity - equity;
+        if drawdown > report.max_drawdown {
+            report.max_drawdown = drawdown;
+            report.max_drawdown_pct = if peak_equity > 0.0 { drawdown / peak_equity } else { 0.0 };
+        }
+        report.equity_curve.push(EquityPoint {
+            timestamp: snapshot.timestamp,
+            mid_price: mid,
+            equity,
+        });
+        last_mid = Some(mid);
+    }
+
+    if let (Some(mid), Some(avg_cost)) = (last_mid, cost_basis) {
+        report.unrealized_pnl = base * (mid - avg_cost);
+    }
+    report.total_pnl = report.realized_pnl + report.unrealized_pnl;
+    report.trade_count = report.fills.len();
+    report.final_base_balance = base;
+    report.final_quote_balance = quote;
+    report
+}
+
+pub async fn start_backtest(
+    db: &Database,
+    config: &Config,
+    user_id: Uuid,
+    req: BacktestRequest,
+) -> Result<BacktestRun, ServiceError> {
+    // Check if strategy exists and belongs to user
+    let strategy = Strategy::find_by_id(db, req.strategy_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Strategy not found".into()))?;
+
+    if !strategy.strategy_type.quotes_continuously() {
+        return Err(ServiceError::BadRequest(format!(
+            "Backtesting {} strategies is not supported",
+            strategy.strategy_type.as_str()
+        )));
+    }
+    if req.from >= req.to {
+        return Err(ServiceError::BadRequest("Backtest start must be before its end".into()));
+    }
+    if req.initial_base_balance < 0.0 || req.initial_quote_balance < 0.0 {
+        return Err(ServiceError::BadRequest("Initial balances cannot be negative".into()));
+    }
+
+    let params = BacktestParams {
+        spread_bps: req.spread_bps.unwrap_or(config.quote_spread_bps),
+        maker_fee_bps: req.maker_fee_bps.unwrap_or(config.paper_maker_fee_bps),
+        initial_base_balance: req.initial_base_balance,
+        initial_quote_balance: req.initial_quote_balance,
+    };
+
+    let run = BacktestRun::create(db, user_id, strategy.id, &strategy.trading_pair, req.from, req.to, params).await?;
+
+    let db = db.clone();
+    let run_id = run.id;
+    let (from, to, params) = (run.from, run.to, run.params.clone());
+    tokio::spawn(async move {
+        let result = async {
+            BacktestRun::set_running(&db, run_id).await?;
+            let snapshots = load_history(&db, &strategy.trading_pair, from, to).await?;
+            if snapshots.is_empty() {
+                return Err(anyhow!("No recorded market data for {} in the requested window", strategy.trading_pair));
+            }
+            let report = run_backtest(&strategy, &snapshots, &params);
+            BacktestRun::complete(&db, run_id, &report).await?;
+            Ok(report)
+        }
+        .await;
+
+        match result {
+            Ok(report) => info!("Backtest {} finished with {} fills", run_id, report.trade_count),
+            Err(e) => {
+                error!("Backtest {} failed: {}", run_id, e);
+                if let Err(e) = BacktestRun::fail(&db, run_id, &e.to_string()).await {
+                    error!("Failed to record backtest {} failure: {}", run_id, e);
+                }
+            }
+        }
+    });
+
+    Ok(run)
+}
+
+pub async fn get_backtest(db: &Database, user_id: Uuid, backtest_id: Uuid) -> Result<BacktestRun, ServiceError> {
+    BacktestRun::find_by_id(db, backtest_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Backtest not found".into()))
+}
+
+pub async fn list_backtests(db: &Database, user_id: Uuid) -> Result<Vec<BacktestRun>, ServiceError> {
+    Ok(BacktestRun::find_by_user(db, user_id).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::strategy::{FixedSpreadParams, StrategyKind, StrategyParams};
+    use chrono::TimeZone;
+
+    // Quotes 1% either side of the mid, one unit at a time, requoting every second
+    fn strategy(max_daily_volume: f64) -> Strategy {
+        let now = Utc::now();
+        Strategy {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            name: "backtest".to_string(),
+            strategy_type: StrategyKind::FixedSpread,
+            params: StrategyParams::FixedSpread(FixedSpreadParams { spread_bps: Some(200.0) }),
+            trading_pair: "SOL/USDC".to_string(),
+            execution_platform: "paper".to_string(),
+            min_trade_size: 1.0,
+            max_trade_size: 1.0,
+            max_daily_volume,
+            auto_trading: true,
+            stealth_mode: false,
+            risk_alerts: false,
+            transaction_delay: 0,
+            trade_frequency: 1,
+            paper_trading: true,
+            max_slippage_bps: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn params(initial_quote_balance: f64) -> BacktestParams {
+        BacktestParams {
+            spread_bps: 50.0,
+            maker_fee_bps: 10.0,
+            initial_base_balance: 0.0,
+            initial_quote_balance,
+        }
+    }
+
+    fn snapshot(secs: i64, bid: f64, ask: f64) -> OrderBook {
+        OrderBook {
+            market_pair: "SOL/USDC".to_string(),
+            bids: vec![OrderBookEntry { price: bid, size: 10.0, total: bid * 10.0, is_bot: false }],
+            asks: vec![OrderBookEntry { price: ask, size: 10.0, total: ask * 10.0, is_bot: false }],
+            last_price: (bid + ask) / 2.0,
+            timestamp: Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn quote_fills_at_its_own_price_once_the_book_trades_through_it() {
+        // The bid goes out at 99; the next book offers below it
+        let snapshots = [snapshot(0, 99.9, 100.1), snapshot(1, 98.0, 98.5)];
+        let report = run_backtest(&strategy(1_000_000.0), &snapshots, &params(1_000.0));
+
+        assert_eq!(report.trade_count, 1);
+        let fill = &report.fills[0];
+        assert_eq!(fill.side, OrderSide::Buy);
+        assert!((fill.price - 99.0).abs() < 1e-9);
+        assert!((fill.fee - 0.099).abs() < 1e-9);
+
+        assert!((report.final_base_balance - 1.0).abs() < 1e-12);
+        assert!((report.final_quote_balance - (1_000.0 - 99.0 - 0.099)).abs() < 1e-9);
+        assert!((report.realized_pnl + 0.099).abs() < 1e-9);
+        // Bought at 99, marked at the last mid of 98.25
+        assert!((report.unrealized_pnl + 0.75).abs() < 1e-9);
+        assert!((report.total_pnl + 0.849).abs() < 1e-9);
+        assert!((report.max_drawdown - 0.849).abs() < 1e-9);
+        assert_eq!(report.equity_curve.len(), 2);
+    }
+
+    #[test]
+    fn quotes_that_cannot_be_paid_for_do_not_fill() {
+        let snapshots = [snapshot(0, 99.9, 100.1), snapshot(1, 98.0, 98.5)];
+        let report = run_backtest(&strategy(1_000_000.0), &snapshots, &params(50.0));
+        assert!(report.fills.is_empty());
+        assert!((report.final_quote_balance - 50.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn no_quotes_once_the_daily_volume_cap_would_be_exceeded() {
+        let snapshots = [snapshot(0, 99.9, 100.1), snapshot(1, 98.0, 98.5), snapshot(2, 101.5, 102.0)];
+        let report = run_backtest(&strategy(50.0), &snapshots, &params(1_000.0));
+        assert!(report.fills.is_empty());
+        assert_eq!(report.volume, 0.0);
+    }
+
+    #[test]
+    fn snapshots_without_a_mid_price_are_skipped() {
+        let mut empty = snapshot(1, 99.0, 101.0);
+        empty.bids.clear();
+        empty.asks.clear();
+        let report = run_backtest(&strategy(1_000_000.0), &[snapshot(0, 99.9, 100.1), empty], &params(1_000.0));
+        assert_eq!(report.equity_curve.len(), 1);
+    }
+}
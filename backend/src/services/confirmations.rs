@@ -0,0 +1,258 @@
+use chrono::{Duration as ChronoDuration, Utc};
+use log::{error, info, warn};
+use mongodb::Database;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus as RpcTransactionStatus, UiTransactionEncoding, UiTransactionStatusMeta};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::strategy_volume::StrategyVolume;
+use crate::models::transaction::{Transaction, TransactionStatus};
+
+// getSignatureStatuses accepts at most this many signatures per request
+const MAX_SIGNATURES_PER_REQUEST: usize = 256;
+// Well past both how long a trade takes to reach its first broadcast and how long a blockhash
+// stays valid, so a transaction this old that was never sent, or cannot be tracked, will not land
+const ABANDONED_AFTER_SECS: i64 = 600;
+
+/// Maps an RPC signature status onto our lifecycle. `None` means nothing has landed yet.
+pub fn observed_status(status: &RpcTransactionStatus) -> Option<(TransactionStatus, Option<String>)> {
+    if let Some(err) = &status.err {
+        return Some((TransactionStatus::Failed, Some(err.to_string())));
+    }
+
+    match status.confirmation_status {
+        Some(TransactionConfirmationStatus::Finalized) => Some((TransactionStatus::Finalized, None)),
+        Some(TransactionConfirmationStatus::Confirmed) => Some((TransactionStatus::Confirmed, None)),
+        // Processed transactions can still be dropped by a fork
+        Some(TransactionConfirmationStatus::Processed) => None,
+        // Old nodes omit confirmation_status; rooted transactions have no confirmation count
+        None if status.confirmations.is_none() => Some((TransactionStatus::Finalized, None)),
+        None => Some((TransactionStatus::Confirmed, None)),
+    }
+}
+
+/// Status metadata of a landed transaction, including the fee it was charged and the
+/// balances it changed.
+pub async fn landed_meta(rpc: &RpcClient, signature: &Signature) -> Option<UiTransactionStatusMeta> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+    match rpc.get_transaction_with_config(signature, config).await {
+        Ok(landed) => landed.transaction.meta,
+        Err(e) => {
+            warn!("Failed to fetch landed transaction {}: {}", signature, e);
+            None
+        }
+    }
+}
+
+/// Gives a failed or expired trade's reserved volume back to its strategy, exactly as much as
+/// was reserved and only once. Does nothing while the trade may still land.
+pub async fn release_volume(db: &Database, transaction_id: Uuid) -> Result<(), mongodb::error::Error> {
+    if let Some(transaction) = Transaction::take_volume_reservation(db, transaction_id).await? {
+        if let (Some(strategy_id), Some(reservation)) = (transaction.strategy_id, transaction.volume_reservation) {
+            StrategyVolume::release(db, strategy_id, &reservation.day, reservation.notional).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Background poller that drives submitted transactions to a terminal status.
+pub struct ConfirmationTracker {
+    db: Database,
+    rpc: RpcClient,
+    interval: Duration,
+}
+
+impl ConfirmationTracker {
+    pub fn new(db: Database, config: &Config) -> Self {
+        Self {
+            db,
+            rpc: RpcClient::new(config.solana_rpc_url.clone()),
+            interval: Duration::from_secs(config.confirmation_poll_interval_secs.max(1)),
+        }
+    }
+
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.poll_once().await {
+                    error!("Confirmation tracker failed to poll signatures: {}", e);
+                }
+                tokio::time::sleep(self.interval).await;
+            }
+        })
+    }
+
+    async fn poll_once(&self) -> Result<(), anyhow::Error> {
+        self.sweep_abandoned().await?;
+
+        let in_flight = Transaction::find_in_flight(&self.db).await?;
+        if in_flight.is_empty() {
+            return Ok(());
+        }
+
+        let block_height = self.rpc.get_block_height().await?;
+
+        for batch in in_flight.chunks(MAX_SIGNATURES_PER_REQUEST) {
+            let signatures: Vec<Signature> = batch
+                .iter()
+                .filter_map(|tx| tx.transaction_hash.as_deref())
+                .filter_map(|hash| Signature::from_str(hash).ok())
+                .collect();
+            let statuses = self.rpc.get_signature_statuses_with_history(&signatures).await?.value;
+
+            let mut statuses = statuses.into_iter();
+            for transaction in batch {
+                // Records with an unparseable hash were skipped when building the request
+                let parsed = transaction
+                    .transaction_hash
+                    .as_deref()
+                    .and_then(|hash| Signature::from_str(hash).ok());
+                if parsed.is_none() {
+                    warn!("Transaction {} has an invalid signature, skipping", transaction.id);
+                    continue;
+                }
+                let status = statuses.next().flatten();
+                self.apply(transaction, status.as_ref(), block_height).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Settles transactions a crashed process left behind. Nothing is broadcast before a
+    /// transaction is `Submitted`, so unsent ones fail; submitted ones without a signature expire.
+    async fn sweep_abandoned(&self) -> Result<(), mongodb::error::Error> {
+        let before = Utc::now() - ChronoDuration::seconds(ABANDONED_AFTER_SECS);
+        for transaction in Transaction::find_abandoned(&self.db, before).await? {
+            let (next, error) = match transaction.status {
+                TransactionStatus::Submitted => (TransactionStatus::Expired, "Submitted without a signature to track"),
+                _ => (TransactionStatus::Failed, "Abandoned before it was sent"),
+            };
+            if Transaction::transition(&self.db, transaction.id, transaction.status, next, Some(error)).await? {
+                warn!(
+                    "Transaction {} was left {}; marked {}",
+                    transaction.id,
+                    transaction.status.as_str(),
+                    next.as_str()
+                );
+                release_volume(&self.db, transaction.id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn apply(
+        &self,
+        transaction: &Transaction,
+        status: Option<&RpcTransactionStatus>,
+        block_height: u64,
+    ) -> Result<(), mongodb::error::Error> {
+        let (next, error) = match status.and_then(observed_status) {
+            Some(observed) => observed,
+            None => {
+                // The blockhash has expired, so the transaction can no longer land. A submitter
+                // that is still retrying will sign a new attempt instead. Records without a last
+                // valid height are given until they are abandoned.
+                let retrying = transaction.retrying_until.map_or(false, |until| until > Utc::now());
+                let abandoned = Utc::now() - transaction.created_at > ChronoDuration::seconds(ABANDONED_AFTER_SECS);
+                let expired = transaction.status == TransactionStatus::Submitted
+                    && !retrying
+                    && transaction
+                        .last_valid_block_height
+                        .map_or(abandoned, |last_valid| block_height > last_valid);
+                if !expired {
+                    return Ok(());
+                }
+                (TransactionStatus::Expired, None)
+            }
+        };
+
+        if next != transaction.status
+            && Transaction::transition(&self.db, transaction.id, transaction.status, next, error.as_deref()).await?
+        {
+            info!(
+                "Transaction {} moved from {} to {}",
+                transaction.id,
+                transaction.status.as_str(),
+                next.as_str()
+            );
+
+            // Failed transactions that landed were still charged
+            if transaction.network_fee.is_none() && status.is_some() {
+                let fee = match transaction.transaction_hash.as_deref().map(Signature::from_str) {
+                    Some(Ok(signature)) => landed_meta(&self.rpc, &signature).await.map(|meta| meta.fee),
+                    _ => None,
+                };
+                if let Some(fee) = fee {
+                    Transaction::set_network_fee(&self.db, transaction.id, fee).await?;
+                }
+            }
+
+            // Volume reserved for a trade that never landed goes back to the strategy's budget
+            if matches!(next, TransactionStatus::Failed | TransactionStatus::Expired) {
+                release_volume(&self.db, transaction.id).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::transaction::TransactionError;
+
+    fn rpc_status(
+        confirmations: Option<usize>,
+        err: Option<TransactionError>,
+        confirmation_status: Option<TransactionConfirmationStatus>,
+    ) -> RpcTransactionStatus {
+        RpcTransactionStatus {
+            slot: 1,
+            confirmations,
+            status: err.clone().map_or(Ok(()), Err),
+            err,
+            confirmation_status,
+        }
+    }
+
+    #[test]
+    fn confirmation_levels_map_onto_the_lifecycle() {
+        let finalized = rpc_status(None, None, Some(TransactionConfirmationStatus::Finalized));
+        assert_eq!(observed_status(&finalized), Some((TransactionStatus::Finalized, None)));
+        let confirmed = rpc_status(Some(3), None, Some(TransactionConfirmationStatus::Confirmed));
+        assert_eq!(observed_status(&confirmed), Some((TransactionStatus::Confirmed, None)));
+    }
+
+    #[test]
+    fn processed_transactions_have_not_landed_yet() {
+        let processed = rpc_status(Some(0), None, Some(TransactionConfirmationStatus::Processed));
+        assert_eq!(observed_status(&processed), None);
+    }
+
+    #[test]
+    fn errors_fail_the_transaction_at_any_level() {
+        let failed = rpc_status(None, Some(TransactionError::AccountNotFound), Some(TransactionConfirmationStatus::Processed));
+        let (status, error) = observed_status(&failed).unwrap();
+        assert_eq!(status, TransactionStatus::Failed);
+        assert!(error.is_some());
+    }
+
+    #[test]
+    fn old_nodes_are_read_from_the_confirmation_count() {
+        assert_eq!(observed_status(&rpc_status(None, None, None)), Some((TransactionStatus::Finalized, None)));
+        assert_eq!(observed_status(&rpc_status(Some(5), None, None)), Some((TransactionStatus::Confirmed, None)));
+    }
+}
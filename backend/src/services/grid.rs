@@ -0,0 +1,183 @@
+use chrono::{DateTime, Utc};
+use log::warn;
+use uuid::Uuid;
+
+use crate::models::grid_state::{GridLevel, GridLevelStatus, GridState};
+use crate::models::strategy::GridParams;
+use crate::services::venues::OrderSide;
+
+/// Lays out a fresh ladder: buys below `mid`, sells above it and a gap at the level nearest to it.
+pub fn build_grid(strategy_id: Uuid, user_id: Uuid, market_pair: &str, params: &GridParams, mid: f64) -> GridState {
+    let step = (params.upper_price - params.lower_price) / (params.levels - 1) as f64;
+    let prices: Vec<f64> = (0..params.levels)
+        .map(|i| params.lower_price + step * i as f64)
+        .collect();
+    let gap = prices
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| (*a - mid).abs().total_cmp(&(*b - mid).abs()))
+        .map(|(i, _)| i)
+        .unwrap_or_default();
+
+    let levels = prices
+        .into_iter()
+        .enumerate()
+        .map(|(i, price)| {
+            let side = if i < gap {
+                Some(OrderSide::Buy)
+            } else if i > gap {
+                Some(OrderSide::Sell)
+            } else {
+                None
+            };
+            GridLevel {
+                index: i as u32,
+                price,
+                side,
+                status: if side.is_some() { GridLevelStatus::Pending } else { GridLevelStatus::Idle },
+                client_order_id: None,
+                signature: None,
+                entry_price: None,
+                fill_count: 0,
+                last_filled_at: None,
+                realized_pnl: 0.0,
+            }
+        })
+        .collect();
+
+    let now = Utc::now();
+    GridState {
+        strategy_id,
+        user_id,
+        market_pair: market_pair.to_string(),
+        params: params.clone(),
+        levels,
+        realized_pnl: 0.0,
+        created_at: now,
+        updated_at: now,
+    }
+}
+
+/// Records a fill at level `index`, executed at `fill_price`, and queues the opposite order one
+/// level away, entered at that price.
+/// Returns the PnL realised by the fill.
+pub fn apply_fill(state: &mut GridState, index: usize, fill_price: f64, at: DateTime<Utc>) -> f64 {
+    let size = state.params.order_size;
+    let level = &mut state.levels[index];
+    let Some(side) = level.side.take() else { return 0.0 };
+
+    // A fill closes the position opened by the neighbouring level's fill, if there was one
+    let pnl = match (side, level.entry_price.take()) {
+        (OrderSide::Sell, Some(entry)) => (fill_price - entry) * size,
+        (OrderSide::Buy, Some(entry)) => (entry - fill_price) * size,
+        (_, None) => 0.0,
+    };
+    level.status = GridLevelStatus::Filled;
+    level.client_order_id = None;
+    level.signature = None;
+    level.fill_count += 1;
+    level.last_filled_at = Some(at);
+    level.realized_pnl += pnl;
+    state.realized_pnl += pnl;
+
+    // A filled buy is sold one level up, a filled sell bought back one level down
+    let (neighbour, opposite) = match side {
+        OrderSide::Buy => (index.checked_add(1), OrderSide::Sell),
+        OrderSide::Sell => (index.checked_sub(1), OrderSide::Buy),
+    };
+    match neighbour.and_then(|i| state.levels.get_mut(i)) {
+        Some(next) if next.side.is_none() => {
+            next.side = Some(opposite);
+            next.status = GridLevelStatus::Pending;
+            next.entry_price = Some(fill_price);
+        }
+        Some(next) => warn!(
+            "Grid level {} already has a {:?} order, not replacing the fill at level {}",
+            next.index, next.side, index
+        ),
+        // Fills at the edge of the ladder have nowhere to go until the price comes back
+        None => {}
+    }
+
+    pnl
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(mid: f64) -> GridState {
+        let params = GridParams { lower_price: 90.0, upper_price: 110.0, levels: 5, order_size: 2.0 };
+        build_grid(Uuid::new_v4(), Uuid::new_v4(), "SOL/USDC", &params, mid)
+    }
+
+    #[test]
+    fn levels_are_evenly_spaced_around_a_gap_at_the_mid() {
+        let state = grid(101.0);
+        let prices: Vec<f64> = state.levels.iter().map(|l| l.price).collect();
+        assert_eq!(prices, vec![90.0, 95.0, 100.0, 105.0, 110.0]);
+
+        let sides: Vec<Option<OrderSide>> = state.levels.iter().map(|l| l.side).collect();
+        assert_eq!(
+            sides,
+            vec![Some(OrderSide::Buy), Some(OrderSide::Buy), None, Some(OrderSide::Sell), Some(OrderSide::Sell)]
+        );
+        assert_eq!(state.levels[2].status, GridLevelStatus::Idle);
+        assert!(state.levels.iter().filter(|l| l.side.is_some()).all(|l| l.status == GridLevelStatus::Pending));
+    }
+
+    #[test]
+    fn mid_outside_the_range_puts_the_gap_at_the_edge() {
+        let state = grid(80.0);
+        assert_eq!(state.levels[0].side, None);
+        assert!(state.levels[1..].iter().all(|l| l.side == Some(OrderSide::Sell)));
+    }
+
+    #[test]
+    fn filled_buy_queues_a_sell_one_level_up() {
+        let mut state = grid(101.0);
+        let pnl = apply_fill(&mut state, 1, 94.9, Utc::now());
+
+        assert_eq!(pnl, 0.0);
+        assert_eq!(state.levels[1].side, None);
+        assert_eq!(state.levels[1].status, GridLevelStatus::Filled);
+        assert_eq!(state.levels[1].fill_count, 1);
+        assert_eq!(state.levels[2].side, Some(OrderSide::Sell));
+        assert_eq!(state.levels[2].status, GridLevelStatus::Pending);
+        assert_eq!(state.levels[2].entry_price, Some(94.9));
+    }
+
+    #[test]
+    fn round_trip_realises_pnl_at_the_fill_prices() {
+        let mut state = grid(101.0);
+        apply_fill(&mut state, 1, 94.9, Utc::now());
+        // The sell level is at 100 but filled better
+        let pnl = apply_fill(&mut state, 2, 100.2, Utc::now());
+
+        assert!((pnl - (100.2 - 94.9) * 2.0).abs() < 1e-9);
+        assert!((state.levels[2].realized_pnl - pnl).abs() < 1e-12);
+        assert!((state.realized_pnl - pnl).abs() < 1e-12);
+        assert_eq!(state.levels[1].side, Some(OrderSide::Buy));
+        assert_eq!(state.levels[1].entry_price, Some(100.2));
+
+        // Buying back below the sell closes the short leg the same way
+        let pnl = apply_fill(&mut state, 1, 95.0, Utc::now());
+        assert!((pnl - (100.2 - 95.0) * 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn neighbour_with_an_order_keeps_it() {
+        let mut state = grid(101.0);
+        apply_fill(&mut state, 0, 90.0, Utc::now());
+        assert_eq!(state.levels[1].side, Some(OrderSide::Buy));
+        assert_eq!(state.levels[1].entry_price, None);
+    }
+
+    #[test]
+    fn level_without_an_order_ignores_fills() {
+        let mut state = grid(101.0);
+        assert_eq!(apply_fill(&mut state, 2, 100.0, Utc::now()), 0.0);
+        assert_eq!(state.levels[2].fill_count, 0);
+        assert_eq!(state.levels[2].status, GridLevelStatus::Idle);
+    }
+}
@@ -0,0 +1,248 @@
+use bip39::{Language, Mnemonic, MnemonicType, Seed};
+use log::info;
+use mongodb::Database;
+use solana_sdk::derivation_path::DerivationPath;
+use solana_sdk::signature::{keypair_from_seed_and_derivation_path, Keypair, Signer};
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::api::wallets::{ProvisionWalletsRequest, RecoverWalletsRequest};
+use crate::models::keystore::SealedMnemonic;
+use crate::models::wallet::Wallet;
+use crate::models::wallet_allocation::WalletAllocation;
+use crate::services::keystore::Keystore;
+use crate::services::wallets::reserve_allocation;
+use crate::utils::errors::ServiceError;
+
+// Most wallets derived by a single request
+pub const MAX_PROVISION: u32 = 100;
+
+/// The user's master seed, from which wallet `i` is derived along m/44'/501'/i'/0', the path
+/// used by `solana-keygen` and common wallets.
+struct MasterSeed(Zeroizing<Vec<u8>>);
+
+impl MasterSeed {
+    fn from_phrase(phrase: &str) -> Result<Self, ServiceError> {
+        let mnemonic = Mnemonic::from_phrase(phrase.trim(), Language::English)
+            .map_err(|_| ServiceError::BadRequest("Invalid BIP39 mnemonic".into()))?;
+        let seed = Seed::new(&mnemonic, "");
+        Ok(Self(Zeroizing::new(seed.as_bytes().to_vec())))
+    }
+
+    fn keypair(&self, index: u32) -> Result<Keypair, ServiceError> {
+        let path = DerivationPath::new_bip44(Some(index), Some(0));
+        keypair_from_seed_and_derivation_path(&self.0, Some(path))
+            .map_err(|e| ServiceError::InternalServerError(format!("Failed to derive wallet {}: {}", index, e)))
+    }
+}
+
+/// Derives `count` new wallets from the user's master mnemonic, creating the mnemonic on first use,
+/// and seals their keys in the keystore.
+pub async fn provision_wallets(
+    db: &Database,
+    keystore: &Keystore,
+    user_id: Uuid,
+    req: ProvisionWalletsRequest,
+) -> Result<Vec<Wallet>, ServiceError> {
+    if req.count == 0 || req.count > MAX_PROVISION {
+        return Err(ServiceError::BadRequest(format!("Count must be between 1 and {}", MAX_PROVISION)));
+    }
+    let allocation = req.allocation_percentage.unwrap_or(0.0);
+    if !(0.0..=100.0).contains(&allocation) {
+        return Err(ServiceError::BadRequest("Allocation percentage must be between 0 and 100".into()));
+    }
+    if !keystore.is_unlocked() {
+        return Err(ServiceError::InternalServerError("The keystore is locked; set KEYSTORE_PASSPHRASE".into()));
+    }
+    reserve_allocation(db, user_id, 0.0, allocation * req.count as f64).await?;
+
+    let mut wallets = Vec::new();
+    let mut created = 0;
+    let derived = async {
+        let seed = MasterSeed::from_phrase(&master_mnemonic(keystore, user_id).await?)?;
+        let first = SealedMnemonic::reserve_indices(db, user_id, req.count).await?
+            .ok_or_else(|| ServiceError::InternalServerError("Master mnemonic disappeared".into()))?;
+
+        for index in first..first + req.count {
+            let keypair = seed.keypair(index)?;
+            let label = format!("{} #{}", req.label.as_deref().unwrap_or("HD wallet"), index);
+            let wallet = Wallet::create(
+                db,
+                user_id,
+                &keypair.pubkey().to_string(),
+                Some(&label),
+                req.allocation_percentage,
+                Some(index),
+            ).await?;
+            created += 1;
+            keystore.store(&wallet, &keypair).await?;
+            wallets.push(wallet);
+        }
+        Ok::<u32, ServiceError>(first)
+    }.await;
+    let first = match derived {
+        Ok(first) => first,
+        Err(e) => {
+            // Wallets that were created keep their share of the allocation
+            WalletAllocation::release(db, user_id, allocation * (req.count - created) as f64).await?;
+            return Err(e);
+        }
+    };
+
+    info!("Derived wallets {}..{} for user {}", first, first + req.count, user_id);
+    Ok(wallets)
+}
+
+/// Re-derives the user's wallets from their master mnemonic, or from `req.mnemonic` when restoring
+/// onto a keystore that does not hold it. Missing wallets are recreated, with the labels and
+/// allocations `req.wallets` gives them, and every key is sealed again.
+pub async fn recover_wallets(
+    db: &Database,
+    keystore: &Keystore,
+    user_id: Uuid,
+    req: RecoverWalletsRequest,
+) -> Result<Vec<Wallet>, ServiceError> {
+    if let Some(count) = req.count {
+        if count > MAX_PROVISION {
+            return Err(ServiceError::BadRequest(format!("Count cannot exceed {}", MAX_PROVISION)));
+        }
+    }
+
+    let next_index = SealedMnemonic::find_by_user(db, user_id).await?
+        .map(|m| m.next_index)
+        .unwrap_or(0);
+    // Nothing records how many wallets a supplied mnemonic derived elsewhere, so the caller has to say
+    if req.mnemonic.is_some() && next_index == 0 && req.count.is_none() {
+        return Err(ServiceError::BadRequest("Provide count: how many wallets to derive from the mnemonic".into()));
+    }
+
+    let stored = keystore.mnemonic(user_id).await?;
+    let seed = match (&req.mnemonic, &stored) {
+        (Some(phrase), Some(stored)) => {
+            if phrase.split_whitespace().ne(stored.split_whitespace()) {
+                return Err(ServiceError::Conflict("A different mnemonic is already stored for this user".into()));
+            }
+            MasterSeed::from_phrase(stored)?
+        }
+        (Some(phrase), None) => {
+            let seed = MasterSeed::from_phrase(phrase)?;
+            let normalized = Zeroizing::new(phrase.split_whitespace().collect::<Vec<_>>().join(" "));
+            keystore.store_mnemonic(user_id, &normalized).await?;
+            seed
+        }
+        (None, Some(stored)) => MasterSeed::from_phrase(stored)?,
+        (None, None) => return Err(ServiceError::NotFound("No mnemonic is stored for this user; provide one".into())),
+    };
+
+    let count = req.count.unwrap_or(next_index).max(next_index);
+    for restored in &req.wallets {
+        if restored.index >= count {
+            return Err(ServiceError::BadRequest(format!(
+                "Wallet #{} is beyond the {} wallets being recovered",
+                restored.index, count
+            )));
+        }
+        if !restored.allocation_percentage.map_or(true, |a| (0.0..=100.0).contains(&a)) {
+            return Err(ServiceError::BadRequest("Allocation percentage must be between 0 and 100".into()));
+        }
+    }
+    SealedMnemonic::raise_next_index(db, user_id, count).await?;
+
+    let mut wallets = Vec::new();
+    for index in 0..count {
+        let keypair = seed.keypair(index)?;
+        let address = keypair.pubkey().to_string();
+        let wallet = match Wallet::find_by_address(db, user_id, &address).await? {
+            Some(wallet) => wallet,
+            None => {
+                let restored = req.wallets.iter().find(|w| w.index == index);
+                let label = restored
+                    .and_then(|w| w.label.clone())
+                    .unwrap_or_else(|| format!("HD wallet #{}", index));
+                let allocation = restored.and_then(|w| w.allocation_percentage);
+
+                // A restored allocation counts against the user's 100% like any other
+                if let Some(allocation) = allocation {
+                    reserve_allocation(db, user_id, 0.0, allocation).await?;
+                }
+                match Wallet::create(db, user_id, &address, Some(&label), allocation, Some(index)).await {
+                    Ok(wallet) => wallet,
+                    Err(e) => {
+                        if let Some(allocation) = allocation {
+                            WalletAllocation::release(db, user_id, allocation).await?;
+                        }
+                        return Err(e.into());
+                    }
+                }
+            }
+        };
+        keystore.store(&wallet, &keypair).await?;
+        wallets.push(wallet);
+    }
+
+    info!("Recovered {} derived wallets for user {}", wallets.len(), user_id);
+    Ok(wallets)
+}
+
+async fn master_mnemonic(keystore: &Keystore, user_id: Uuid) -> Result<Zeroizing<String>, ServiceError> {
+    if let Some(phrase) = keystore.mnemonic(user_id).await? {
+        return Ok(phrase);
+    }
+
+    let phrase = Zeroizing::new(Mnemonic::new(MnemonicType::Words24, Language::English).into_phrase());
+    match keystore.store_mnemonic(user_id, &phrase).await {
+        Ok(()) => {
+            info!("Created a master mnemonic for user {}", user_id);
+            Ok(phrase)
+        }
+        // Another request created one first; use that instead
+        Err(ServiceError::Conflict(_)) => keystore.mnemonic(user_id).await?
+            .ok_or_else(|| ServiceError::InternalServerError("Master mnemonic disappeared".into())),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn the_same_phrase_derives_the_same_wallets() {
+        let (first, second) = (MasterSeed::from_phrase(PHRASE).unwrap(), MasterSeed::from_phrase(PHRASE).unwrap());
+        for index in [0, 1, 7] {
+            assert_eq!(first.keypair(index).unwrap().to_bytes(), second.keypair(index).unwrap().to_bytes());
+        }
+    }
+
+    #[test]
+    fn each_index_derives_a_different_wallet() {
+        let seed = MasterSeed::from_phrase(PHRASE).unwrap();
+        let mut pubkeys: Vec<_> = (0..10).map(|index| seed.keypair(index).unwrap().pubkey()).collect();
+        pubkeys.sort();
+        pubkeys.dedup();
+        assert_eq!(pubkeys.len(), 10);
+    }
+
+    #[test]
+    fn different_phrases_derive_different_wallets() {
+        let other = Mnemonic::new(MnemonicType::Words24, Language::English);
+        let (seed, other) = (MasterSeed::from_phrase(PHRASE).unwrap(), MasterSeed::from_phrase(other.phrase()).unwrap());
+        assert_ne!(seed.keypair(0).unwrap().pubkey(), other.keypair(0).unwrap().pubkey());
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_ignored() {
+        let padded = MasterSeed::from_phrase(&format!("  {}\n", PHRASE)).unwrap();
+        let seed = MasterSeed::from_phrase(PHRASE).unwrap();
+        assert_eq!(padded.keypair(0).unwrap().pubkey(), seed.keypair(0).unwrap().pubkey());
+    }
+
+    #[test]
+    fn invalid_phrases_are_rejected() {
+        assert!(MasterSeed::from_phrase("abandon abandon abandon").is_err());
+        // Valid words, wrong checksum
+        assert!(MasterSeed::from_phrase(&PHRASE.replace("about", "abandon")).is_err());
+    }
+}
@@ -0,0 +1,419 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use chrono::Utc;
+use log::{info, warn};
+use mongodb::Database;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::Serialize;
+use solana_sdk::signature::{Keypair, Signer};
+use std::fmt;
+use uuid::Uuid;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::api::wallets::ImportKeyRequest;
+use crate::models::keystore::{KeystoreHeader, SealedKey, SealedMnemonic};
+use crate::models::user::User;
+use crate::models::wallet::Wallet;
+use crate::utils::errors::ServiceError;
+
+// Argon2id cost for new keystores: 64 MiB, 3 passes, 1 lane
+const MEMORY_COST_KIB: u32 = 64 * 1024;
+const TIME_COST: u32 = 3;
+const PARALLELISM: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+// Sealed into the header so unlocking can tell a wrong passphrase from a right one
+const CHECK_PLAINTEXT: &[u8] = b"solana-market-nexus keystore";
+const CHECK_AAD: &[u8] = b"keystore-check";
+
+/// Wallet keypairs sealed at rest with a master key derived from a passphrase.
+pub struct Keystore {
+    db: Database,
+    // None while locked
+    key: Option<Zeroizing<[u8; 32]>>,
+}
+
+impl fmt::Debug for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keystore").field("unlocked", &self.is_unlocked()).finish()
+    }
+}
+
+/// A wallet's secret key handed out by the export endpoint. Wiped once the response is written.
+#[derive(Serialize)]
+pub struct ExportedKey {
+    pub wallet_id: Uuid,
+    pub public_key: String,
+    // Base58, as accepted by common wallets
+    pub secret_key: String,
+}
+
+impl fmt::Debug for ExportedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExportedKey")
+            .field("wallet_id", &self.wallet_id)
+            .field("public_key", &self.public_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for ExportedKey {
+    fn drop(&mut self) {
+        self.secret_key.zeroize();
+    }
+}
+
+/// The master mnemonic handed out by the export endpoint. Wiped once the response is written.
+#[derive(Serialize)]
+pub struct ExportedMnemonic {
+    pub mnemonic: String,
+}
+
+impl fmt::Debug for ExportedMnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ExportedMnemonic { .. }")
+    }
+}
+
+impl Drop for ExportedMnemonic {
+    fn drop(&mut self) {
+        self.mnemonic.zeroize();
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedKey {
+    pub wallet_id: Uuid,
+    pub public_key: String,
+}
+
+impl Keystore {
+    /// A keystore that holds sealed keys but cannot open them.
+    pub fn locked(db: Database) -> Self {
+        Self { db, key: None }
+    }
+
+    /// Derives the master key from `passphrase`, creating the keystore on first use.
+    pub async fn unlock(db: Database, passphrase: &[u8]) -> Result<Self, ServiceError> {
+        let key = match KeystoreHeader::find(&db).await? {
+            Some(header) => {
+                let salt = decode(&header.salt)?;
+                let key = derive_key(passphrase, &salt, header.memory_cost, header.time_cost, header.parallelism)?;
+                open(&key, CHECK_AAD, &decode(&header.check_nonce)?, &decode(&header.check_ciphertext)?)
+                    .map_err(|_| ServiceError::Unauthorized("Wrong keystore passphrase".into()))?;
+                key
+            }
+            None => {
+                let mut salt = [0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                let key = derive_key(passphrase, &salt, MEMORY_COST_KIB, TIME_COST, PARALLELISM)?;
+                let (nonce, ciphertext) = seal(&key, CHECK_AAD, CHECK_PLAINTEXT)?;
+                let header = KeystoreHeader::new(
+                    BASE64.encode(salt),
+                    MEMORY_COST_KIB,
+                    TIME_COST,
+                    PARALLELISM,
+                    BASE64.encode(nonce),
+                    BASE64.encode(ciphertext),
+                );
+                KeystoreHeader::create(&db, &header).await?;
+                info!("Created a new keystore");
+                key
+            }
+        };
+
+        Ok(Self { db, key: Some(key) })
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.key.is_some()
+    }
+
+    fn master_key(&self) -> Result<&[u8; 32], ServiceError> {
+        self.key
+            .as_deref()
+            .ok_or_else(|| ServiceError::InternalServerError("The keystore is locked; set KEYSTORE_PASSPHRASE".into()))
+    }
+
+    /// Seals `keypair` as the signing key of `wallet`.
+    pub async fn store(&self, wallet: &Wallet, keypair: &Keypair) -> Result<(), ServiceError> {
+        if keypair.pubkey().to_string() != wallet.address {
+            return Err(ServiceError::BadRequest("Key does not belong to this wallet's address".into()));
+        }
+
+        let secret = Zeroizing::new(keypair.to_bytes());
+        let (nonce, ciphertext) = seal(self.master_key()?, &associated_data(wallet), &secret[..])?;
+        let sealed = SealedKey {
+            wallet_id: wallet.id,
+            user_id: wallet.user_id,
+            public_key: wallet.address.clone(),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+            created_at: Utc::now(),
+        };
+        SealedKey::save(&self.db, &sealed).await?;
+        Ok(())
+    }
+
+    /// Opens the keypair sealed for `wallet`, or `None` if none was imported.
+    pub async fn keypair(&self, wallet: &Wallet) -> Result<Option<Keypair>, ServiceError> {
+        let Some(sealed) = SealedKey::find_by_wallet(&self.db, wallet.id).await? else {
+            return Ok(None);
+        };
+
+        let secret = open(
+            self.master_key()?,
+            &associated_data(wallet),
+            &decode(&sealed.nonce)?,
+            &decode(&sealed.ciphertext)?,
+        )
+        .map_err(|_| ServiceError::InternalServerError(format!("Sealed key for wallet {} cannot be opened", wallet.id)))?;
+        let keypair = Keypair::from_bytes(&secret)
+            .map_err(|_| ServiceError::InternalServerError(format!("Sealed key for wallet {} is malformed", wallet.id)))?;
+        Ok(Some(keypair))
+    }
+
+    /// Seals `phrase` as the user's master mnemonic. A user has at most one.
+    pub async fn store_mnemonic(&self, user_id: Uuid, phrase: &str) -> Result<(), ServiceError> {
+        let (nonce, ciphertext) = seal(self.master_key()?, &mnemonic_associated_data(user_id), phrase.as_bytes())?;
+        let sealed = SealedMnemonic {
+            user_id,
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+            next_index: 0,
+            created_at: Utc::now(),
+        };
+        SealedMnemonic::create(&self.db, &sealed).await?;
+        Ok(())
+    }
+
+    /// Opens the user's master mnemonic, or `None` if they do not have one yet.
+    pub async fn mnemonic(&self, user_id: Uuid) -> Result<Option<Zeroizing<String>>, ServiceError> {
+        let Some(sealed) = SealedMnemonic::find_by_user(&self.db, user_id).await? else {
+            return Ok(None);
+        };
+
+        let phrase = open(
+            self.master_key()?,
+            &mnemonic_associated_data(user_id),
+            &decode(&sealed.nonce)?,
+            &decode(&sealed.ciphertext)?,
+        )
+        .map_err(|_| ServiceError::InternalServerError(format!("Mnemonic of user {} cannot be opened", user_id)))?;
+        let phrase = String::from_utf8(phrase.to_vec())
+            .map_err(|_| ServiceError::InternalServerError(format!("Mnemonic of user {} is malformed", user_id)))?;
+        Ok(Some(Zeroizing::new(phrase)))
+    }
+}
+
+pub async fn import_wallet_key(
+    db: &Database,
+    keystore: &Keystore,
+    user_id: Uuid,
+    wallet_id: Uuid,
+    req: ImportKeyRequest,
+) -> Result<ImportedKey, ServiceError> {
+    // Check if wallet exists and belongs to user
+    let wallet = Wallet::find_by_id(db, wallet_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Wallet not found".into()))?;
+
+    // The request wipes itself when dropped
+    let keypair = parse_keypair(&req)?;
+    drop(req);
+    keystore.store(&wallet, &keypair).await?;
+
+    info!("Imported the signing key of wallet {}", wallet.id);
+    Ok(ImportedKey { wallet_id: wallet.id, public_key: wallet.address })
+}
+
+/// Hands a wallet's secret key back to its owner after they confirm their password.
+pub async fn export_wallet_key(
+    db: &Database,
+    keystore: &Keystore,
+    export_enabled: bool,
+    user_id: Uuid,
+    wallet_id: Uuid,
+    password: &str,
+) -> Result<ExportedKey, ServiceError> {
+    authorize_export(db, export_enabled, user_id, password).await?;
+
+    // Check if wallet exists and belongs to user
+    let wallet = Wallet::find_by_id(db, wallet_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Wallet not found".into()))?;
+    let keypair = keystore.keypair(&wallet).await?
+        .ok_or_else(|| ServiceError::NotFound("No key has been imported for this wallet".into()))?;
+
+    warn!("Exported the signing key of wallet {} for user {}", wallet.id, user_id);
+    Ok(ExportedKey {
+        wallet_id: wallet.id,
+        public_key: wallet.address.clone(),
+        secret_key: keypair.to_base58_string(),
+    })
+}
+
+/// Hands the user's master mnemonic back to them after they confirm their password.
+pub async fn export_mnemonic(
+    db: &Database,
+    keystore: &Keystore,
+    export_enabled: bool,
+    user_id: Uuid,
+    password: &str,
+) -> Result<ExportedMnemonic, ServiceError> {
+    authorize_export(db, export_enabled, user_id, password).await?;
+
+    let mnemonic = keystore.mnemonic(user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("No wallets have been derived for this user".into()))?;
+
+    warn!("Exported the master mnemonic of user {}", user_id);
+    Ok(ExportedMnemonic { mnemonic: mnemonic.to_string() })
+}
+
+// Secrets only leave the keystore when export is switched on and the owner re-enters their password
+async fn authorize_export(db: &Database, export_enabled: bool, user_id: Uuid, password: &str) -> Result<(), ServiceError> {
+    if !export_enabled {
+        return Err(ServiceError::Unauthorized("Key export is disabled".into()));
+    }
+
+    let user = User::find_by_id(db, user_id).await?
+        .ok_or_else(|| ServiceError::Unauthorized("User not found".into()))?;
+    if !bcrypt::verify(password, &user.password_hash)? {
+        warn!("Rejected export for user {}: wrong password", user_id);
+        return Err(ServiceError::Unauthorized("Invalid password".into()));
+    }
+    Ok(())
+}
+
+/// Reads a base58 secret key or the byte array of a `solana-keygen` JSON keypair file.
+fn parse_keypair(req: &ImportKeyRequest) -> Result<Keypair, ServiceError> {
+    let bytes = match (&req.secret_key, &req.keypair) {
+        (Some(secret_key), None) => Zeroizing::new(
+            solana_sdk::bs58::decode(secret_key.trim())
+                .into_vec()
+                .map_err(|_| ServiceError::BadRequest("Secret key is not valid base58".into()))?,
+        ),
+        (None, Some(keypair)) => Zeroizing::new(keypair.clone()),
+        _ => return Err(ServiceError::BadRequest("Provide either secret_key or keypair".into())),
+    };
+
+    Keypair::from_bytes(&bytes).map_err(|_| ServiceError::BadRequest("Not a valid 64-byte Solana keypair".into()))
+}
+
+// Binds a sealed key to its wallet so ciphertexts cannot be swapped between wallets
+fn associated_data(wallet: &Wallet) -> Vec<u8> {
+    [wallet.id.as_bytes().as_slice(), wallet.address.as_bytes()].concat()
+}
+
+fn mnemonic_associated_data(user_id: Uuid) -> Vec<u8> {
+    [user_id.as_bytes().as_slice(), b"mnemonic"].concat()
+}
+
+fn derive_key(
+    passphrase: &[u8],
+    salt: &[u8],
+    memory_cost: u32,
+    time_cost: u32,
+    parallelism: u32,
+) -> Result<Zeroizing<[u8; 32]>, ServiceError> {
+    let params = Params::new(memory_cost, time_cost, parallelism, Some(32))
+        .map_err(|e| ServiceError::InternalServerError(format!("Invalid keystore parameters: {}", e)))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, &mut key[..])
+        .map_err(|e| ServiceError::InternalServerError(format!("Failed to derive the keystore key: {}", e)))?;
+    Ok(key)
+}
+
+fn seal(key: &[u8; 32], aad: &[u8], plaintext: &[u8]) -> Result<([u8; NONCE_LEN], Vec<u8>), ServiceError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = XChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+        .map_err(|_| ServiceError::InternalServerError("Failed to seal key".into()))?;
+    Ok((nonce, ciphertext))
+}
+
+fn open(key: &[u8; 32], aad: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Result<Zeroizing<Vec<u8>>, chacha20poly1305::aead::Error> {
+    if nonce.len() != NONCE_LEN {
+        return Err(chacha20poly1305::aead::Error);
+    }
+    XChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map(Zeroizing::new)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, ServiceError> {
+    BASE64
+        .decode(value)
+        .map_err(|e| ServiceError::InternalServerError(format!("Corrupt keystore record: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Far cheaper than the real cost so the tests stay fast
+    fn test_key(passphrase: &[u8], salt: &[u8]) -> Zeroizing<[u8; 32]> {
+        derive_key(passphrase, salt, 1024, 1, 1).unwrap()
+    }
+
+    #[test]
+    fn sealed_keys_open_with_the_same_key_and_associated_data() {
+        let key = test_key(b"correct horse", &[7u8; SALT_LEN]);
+        let (nonce, ciphertext) = seal(&key, b"wallet", b"secret bytes").unwrap();
+        assert_ne!(ciphertext.as_slice(), b"secret bytes".as_slice());
+        let opened = open(&key, b"wallet", &nonce, &ciphertext).unwrap();
+        assert_eq!(opened.as_slice(), b"secret bytes".as_slice());
+    }
+
+    #[test]
+    fn sealing_twice_uses_fresh_nonces() {
+        let key = test_key(b"correct horse", &[7u8; SALT_LEN]);
+        let (first, _) = seal(&key, b"wallet", b"secret bytes").unwrap();
+        let (second, _) = seal(&key, b"wallet", b"secret bytes").unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn opening_fails_with_the_wrong_passphrase_or_wallet() {
+        let key = test_key(b"correct horse", &[7u8; SALT_LEN]);
+        let (nonce, ciphertext) = seal(&key, b"wallet", b"secret bytes").unwrap();
+
+        let wrong_key = test_key(b"battery staple", &[7u8; SALT_LEN]);
+        assert!(open(&wrong_key, b"wallet", &nonce, &ciphertext).is_err());
+        assert!(open(&key, b"another wallet", &nonce, &ciphertext).is_err());
+    }
+
+    #[test]
+    fn tampered_records_do_not_open() {
+        let key = test_key(b"correct horse", &[7u8; SALT_LEN]);
+        let (nonce, mut ciphertext) = seal(&key, b"wallet", b"secret bytes").unwrap();
+        assert!(open(&key, b"wallet", &nonce[..NONCE_LEN - 1], &ciphertext).is_err());
+        ciphertext[0] ^= 1;
+        assert!(open(&key, b"wallet", &nonce, &ciphertext).is_err());
+    }
+
+    #[test]
+    fn derived_keys_depend_on_passphrase_and_salt() {
+        let key = test_key(b"correct horse", &[7u8; SALT_LEN]);
+        assert_eq!(*key, *test_key(b"correct horse", &[7u8; SALT_LEN]));
+        assert_ne!(*key, *test_key(b"correct horse", &[8u8; SALT_LEN]));
+        assert_ne!(*key, *test_key(b"correct horsf", &[7u8; SALT_LEN]));
+    }
+
+    #[test]
+    fn keypairs_import_from_base58_or_bytes() {
+        let keypair = Keypair::new();
+        let from_base58 = ImportKeyRequest { secret_key: Some(keypair.to_base58_string()), keypair: None };
+        assert_eq!(parse_keypair(&from_base58).unwrap().pubkey(), keypair.pubkey());
+        let from_bytes = ImportKeyRequest { secret_key: None, keypair: Some(keypair.to_bytes().to_vec()) };
+        assert_eq!(parse_keypair(&from_bytes).unwrap().pubkey(), keypair.pubkey());
+
+        assert!(parse_keypair(&ImportKeyRequest { secret_key: None, keypair: None }).is_err());
+        let both = ImportKeyRequest { secret_key: Some(keypair.to_base58_string()), keypair: Some(keypair.to_bytes().to_vec()) };
+        assert!(parse_keypair(&both).is_err());
+        assert!(parse_keypair(&ImportKeyRequest { secret_key: None, keypair: Some(vec![1; 10]) }).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use chrono::{Duration as ChronoDuration, TimeZone, Utc};
+use log::{error, warn};
+use mongodb::Database;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::api::kill_switches::KillSwitchRequest;
+use crate::config::Config;
+use crate::models::kill_switch::{KillSwitch, KillSwitchScope, KillSwitchTrigger};
+use crate::models::strategy::Strategy;
+use crate::models::transaction::{Transaction, TransactionStatus};
+use crate::services::market_maker::MarketMakingEngine;
+use crate::services::venues::{is_paper, BookTop};
+use crate::utils::errors::ServiceError;
+
+pub async fn get_kill_switches(db: &Database, user_id: Uuid) -> Result<Vec<KillSwitch>, ServiceError> {
+    Ok(KillSwitch::find_by_user(db, user_id).await?)
+}
+
+pub async fn activate_kill_switch(
+    db: &Database,
+    engine: &MarketMakingEngine,
+    user_id: Uuid,
+    req: KillSwitchRequest,
+) -> Result<KillSwitch, ServiceError> {
+    if req.reason.trim().is_empty() {
+        return Err(ServiceError::BadRequest("A reason is required".into()));
+    }
+
+    // Normalise the target so lookups match regardless of how it was typed
+    let target = match (req.scope, req.target.as_deref().map(str::trim)) {
+        (KillSwitchScope::User, None) => None,
+        (KillSwitchScope::User, Some(_)) => {
+            return Err(ServiceError::BadRequest("User kill switches do not take a target".into()));
+        }
+        (KillSwitchScope::Strategy, Some(target)) => {
+            let strategy_id = Uuid::parse_str(target)
+                .map_err(|_| ServiceError::BadRequest("Strategy target must be a strategy id".into()))?;
+            // Check if strategy exists and belongs to user
+            Strategy::find_by_id(db, strategy_id, user_id).await?
+                .ok_or_else(|| ServiceError::NotFound("Strategy not found".into()))?;
+            Some(strategy_id.to_string())
+        }
+        (KillSwitchScope::Market, Some(target)) if !target.is_empty() => Some(KillSwitchScope::Market.normalize_target(target)),
+        (scope, _) => {
+            return Err(ServiceError::BadRequest(format!("A target is required for {} kill switches", scope.as_str())));
+        }
+    };
+
+    if KillSwitch::find_active_for_target(db, user_id, req.scope, target.as_deref()).await?.is_some() {
+        return Err(ServiceError::Conflict("This kill switch is already active".into()));
+    }
+
+    let kill_switch = KillSwitch::activate(
+        db,
+        user_id,
+        req.scope,
+        target.as_deref(),
+        KillSwitchTrigger::Manual,
+        req.reason.trim(),
+    ).await?;
+
+    // Stop the affected workers now rather than on the next supervisor tick
+    engine.sync_workers().await?;
+
+    Ok(kill_switch)
+}
+
+pub async fn reset_kill_switch(
+    db: &Database,
+    engine: &MarketMakingEngine,
+    user_id: Uuid,
+    kill_switch_id: Uuid,
+    reason: &str,
+) -> Result<(), ServiceError> {
+    if reason.trim().is_empty() {
+        return Err(ServiceError::BadRequest("A reason is required".into()));
+    }
+
+    if !KillSwitch::reset(db, kill_switch_id, user_id, reason.trim()).await? {
+        return Err(ServiceError::NotFound("Active kill switch not found".into()));
+    }
+
+    engine.sync_workers().await?;
+
+    Ok(())
+}
+
+/// Refuses to trade while a kill switch covers the user, strategy or market.
+pub async fn ensure_trading_allowed(
+    db: &Database,
+    user_id: Uuid,
+    strategy_id: Option<Uuid>,
+    market_pair: &str,
+) -> Result<(), ServiceError> {
+    match KillSwitch::find_halting(db, user_id, strategy_id, market_pair).await? {
+        Some(kill_switch) => Err(ServiceError::Conflict(format!(
+            "Trading is halted by a {} kill switch: {}",
+            kill_switch.scope.as_str(),
+            kill_switch.reason
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Background monitor that trips kill switches when trading looks unsafe.
+pub struct CircuitBreaker {
+    db: Database,
+    config: Config,
+    engine: MarketMakingEngine,
+    rpc: RpcClient,
+    rpc_failures: u32,
+}
+
+impl CircuitBreaker {
+    pub fn new(db: Database, config: Config, engine: MarketMakingEngine) -> Self {
+        Self {
+            rpc: RpcClient::new(config.solana_rpc_url.clone()),
+            db,
+            config,
+            engine,
+            rpc_failures: 0,
+        }
+    }
+
+    pub fn start(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let interval = Duration::from_secs(self.config.circuit_breaker_interval_secs.max(1));
+            loop {
+                if let Err(e) = self.check_once().await {
+                    error!("Circuit breaker check failed: {}", e);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    async fn check_once(&mut self) -> Result<(), anyhow::Error> {
+        let strategies = Strategy::find_auto_trading(&self.db).await?;
+        let mut active = KillSwitch::find_active(&self.db).await?;
+        let tripped_before = active.len();
+
+        // RPC health affects every strategy that trades on-chain
+        match self.rpc.get_health().await {
+            Ok(()) => self.rpc_failures = 0,
+            Err(e) => {
+                self.rpc_failures += 1;
+                warn!("RPC health check failed ({} in a row): {}", self.rpc_failures, e);
+            }
+        }
+        if self.rpc_failures >= self.config.breaker_max_rpc_failures.max(1) {
+            let users: HashSet<Uuid> = strategies
+                .iter()
+                .filter(|s| !is_paper(s, &self.config))
+                .map(|s| s.user_id)
+                .collect();
+            let reason = format!("RPC unavailable for {} consecutive checks", self.rpc_failures);
+            for user_id in users {
+                self.trip(&mut active, user_id, KillSwitchScope::User, None, KillSwitchTrigger::RpcUnavailable, &reason).await?;
+            }
+        }
+
+        // Market data is shared between strategies on the same pair and venue
+        let mut markets: HashMap<(bool, String), Option<BookTop>> = HashMap::new();
+
+        for strategy in &strategies {
+            if active.iter().any(|k| k.halts(strategy)) {
+                continue;
+            }
+
+            let market = (is_paper(strategy, &self.config), strategy.trading_pair.clone());
+            if !markets.contains_key(&market) {
+                let top = match self.engine.book_top(strategy).await {
+                    Ok(top) => Some(top),
+                    Err(e) => {
+                        warn!("Failed to fetch market data for {}: {}", strategy.trading_pair, e);
+                        None
+                    }
+                };
+                markets.insert(market.clone(), top);
+            }
+            let top = markets[&market];
+
+            // Judged by when the venue last reported the book, not when we asked for it
+            let max_age = ChronoDuration::seconds(self.config.breaker_max_market_data_age_secs);
+            let stale = top.map_or(true, |top| Utc::now() - top.observed_at > max_age);
+            if stale {
+                let reason = format!(
+                    "No market data for {} newer than {}s",
+                    strategy.trading_pair, self.config.breaker_max_market_data_age_secs
+                );
+                self.trip(&mut active, strategy.user_id, KillSwitchScope::Market, Some(&strategy.trading_pair), KillSwitchTrigger::StaleMarketData, &reason).await?;
+                continue;
+            }
+
+            let strategy_target = strategy.id.to_string();
+
+            let max_failures = self.config.breaker_max_consecutive_failures.max(1);
+            let recent = Transaction::find_by_strategy(&self.db, strategy.id, strategy.user_id, max_failures as i64).await?;
+            let failed = recent
+                .iter()
+                .take_while(|tx| matches!(tx.status, TransactionStatus::Failed | TransactionStatus::Expired))
+                .count();
+            if failed >= max_failures {
+                let reason = format!("Last {} transactions failed or expired", failed);
+                self.trip(&mut active, strategy.user_id, KillSwitchScope::Strategy, Some(&strategy_target), KillSwitchTrigger::ConsecutiveFailures, &reason).await?;
+                continue;
+            }
+
+            if self.config.breaker_max_daily_loss > 0.0 {
+                if let Some(mid) = top.and_then(|top| top.mid()) {
+                    let pnl = self.daily_pnl(strategy, mid).await?;
+                    if pnl < -self.config.breaker_max_daily_loss {
+                        let reason = format!(
+                            "Loss of {:.2} today exceeds the limit of {:.2}",
+                            -pnl, self.config.breaker_max_daily_loss
+                        );
+                        self.trip(&mut active, strategy.user_id, KillSwitchScope::Strategy, Some(&strategy_target), KillSwitchTrigger::LossThreshold, &reason).await?;
+                    }
+                }
+            }
+        }
+
+        if active.len() > tripped_before {
+            self.engine.sync_workers().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Mark-to-market PnL of the strategy's trades since midnight UTC, swaps and maker fills alike.
+    async fn daily_pnl(&self, strategy: &Strategy, mid: f64) -> Result<f64, mongodb::error::Error> {
+        let midnight = Utc.from_utc_datetime(&Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap_or_default());
+        let transactions = Transaction::find_by_strategy_since(&self.db, strategy.id, midnight).await?;
+        Ok(mark_to_market(&transactions, mid))
+    }
+
+    async fn trip(
+        &self,
+        active: &mut Vec<KillSwitch>,
+        user_id: Uuid,
+        scope: KillSwitchScope,
+        target: Option<&str>,
+        trigger: KillSwitchTrigger,
+        reason: &str,
+    ) -> Result<(), mongodb::error::Error> {
+        let already_active = active
+            .iter()
+            .any(|k| k.user_id == user_id && k.scope == scope && k.target == target.map(|t| scope.normalize_target(t)));
+        if already_active {
+            return Ok(());
+        }
+
+        warn!("Circuit breaker tripped {} kill switch for user {}: {}", scope.as_str(), user_id, reason);
+        let kill_switch = KillSwitch::activate(&self.db, user_id, scope, target, trigger, reason).await?;
+        active.push(kill_switch);
+        Ok(())
+    }
+}
+
+/// Cash from the landed trades among `transactions`, net of venue fees, plus the base position
+/// they leave valued at `mid`.
+fn mark_to_market(transactions: &[Transaction], mid: f64) -> f64 {
+    let mut cash = 0.0;
+    let mut position = 0.0;
+    for tx in transactions
+        .iter()
+        .filter(|tx| matches!(tx.status, TransactionStatus::Confirmed | TransactionStatus::Finalized))
+    {
+        match tx.action.to_lowercase().as_str() {
+            "buy" => {
+                cash -= tx.notional();
+                position += tx.amount;
+            }
+            "sell" => {
+                cash += tx.notional();
+                position -= tx.amount;
+            }
+            _ => continue,
+        }
+        cash -= tx.fee.unwrap_or(0.0);
+    }
+
+    cash + position * mid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::order::{Order, OrderStatus, TimeInForce};
+    use crate::services::venues::OrderSide;
+
+    fn resting_order(side: OrderSide, price: f64, size: f64) -> Order {
+        let now = Utc::now();
+        Order {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            wallet_id: Uuid::new_v4(),
+            strategy_id: Some(Uuid::new_v4()),
+            owner: String::new(),
+            venue: crate::services::venues::OPENBOOK_V2.to_string(),
+            market_pair: "SOL/USDC".to_string(),
+            client_order_id: 1,
+            venue_order_id: None,
+            side,
+            price,
+            size,
+            filled_size: 0.0,
+            average_fill_price: None,
+            fill_ids: Vec::new(),
+            time_in_force: TimeInForce::PostOnly,
+            status: OrderStatus::Open,
+            signature: None,
+            error: None,
+            status_history: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn maker_fills_count_towards_the_daily_loss() {
+        let bid = resting_order(OrderSide::Buy, 100.0, 2.0);
+        let fills = [Transaction::venue_fill(&bid, 2.0, 100.0, Utc::now())];
+
+        // Bought 2 at 100 and the market fell to 90
+        assert!((mark_to_market(&fills, 90.0) + 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn round_trips_realise_the_spread_less_fees() {
+        let bid = resting_order(OrderSide::Buy, 99.0, 1.0);
+        let ask = resting_order(OrderSide::Sell, 101.0, 1.0);
+        let mut sell = Transaction::venue_fill(&ask, 1.0, 101.0, Utc::now());
+        sell.fee = Some(0.5);
+        let fills = [Transaction::venue_fill(&bid, 1.0, 99.0, Utc::now()), sell];
+
+        // Flat again, so the mid no longer matters
+        assert!((mark_to_market(&fills, 50.0) - 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn trades_that_did_not_land_are_ignored() {
+        let bid = resting_order(OrderSide::Buy, 100.0, 1.0);
+        let mut failed = Transaction::venue_fill(&bid, 1.0, 100.0, Utc::now());
+        failed.status = TransactionStatus::Failed;
+
+        assert_eq!(mark_to_market(&[failed], 50.0), 0.0);
+    }
+}
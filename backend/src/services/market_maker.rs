@@ -0,0 +1,863 @@
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use mongodb::Database;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::signature::keypair_from_seed;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::grid_state::{GridLevelStatus, GridState};
+use crate::models::kill_switch::KillSwitch;
+use crate::models::order::{Order, OrderStatus};
+use crate::models::orderbook::OrderBook;
+use crate::models::strategy::{GridParams, Strategy, StrategyKind, StrategyParams};
+use crate::models::strategy_volume::StrategyVolume;
+use crate::models::transaction::Transaction;
+use crate::models::user::User;
+use crate::models::wallet::{Wallet, WalletStatus};
+use crate::services::avellaneda_stoikov::{reservation_quote, ReservationQuote, VolatilityEstimator};
+use crate::services::grid::{apply_fill, build_grid};
+use crate::services::orders::{cancel_order, cancel_strategy_orders, place_order, reconcile_and_settle};
+use crate::services::priority_fees::PriorityFeePolicy;
+use crate::services::self_trade::{check_self_trade, IncomingOrder, RestingQuote, SelfTradeDecision};
+use crate::services::signer::{LocalSigner, Signers, TransactionSigner};
+use crate::services::venues::paper::PaperVenue;
+use crate::services::venues::{is_paper, select_venue, BookTop, ExecutionVenue, LimitOrder, OrderSide};
+use crate::services::wallets::token_balance;
+use crate::utils::errors::ServiceError;
+use crate::utils::tokens::token_by_symbol;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StrategyState {
+    Running,
+    Paused,
+    Errored,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Quote {
+    pub side: OrderSide,
+    pub price: f64,
+    pub size: f64,
+    pub client_order_id: u64,
+    pub signature: Option<String>,
+    pub placed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyStatus {
+    pub strategy_id: Uuid,
+    pub trading_pair: String,
+    pub paper_trading: bool,
+    // Wallet the quotes are attributed to, known once the worker has resolved its venue
+    pub wallet_id: Option<Uuid>,
+    pub state: StrategyState,
+    pub last_quote_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub working_quotes: Vec<Quote>,
+    // Reservation price and spread behind the latest quotes, for model-driven kinds
+    pub model: Option<ReservationQuote>,
+    // Ladder of a grid strategy, as persisted
+    pub grid: Option<GridState>,
+}
+
+impl StrategyStatus {
+    pub fn paused(strategy: &Strategy) -> Self {
+        Self {
+            strategy_id: strategy.id,
+            trading_pair: strategy.trading_pair.clone(),
+            paper_trading: strategy.paper_trading,
+            wallet_id: None,
+            state: StrategyState::Paused,
+            last_quote_at: None,
+            last_error: None,
+            working_quotes: Vec::new(),
+            model: None,
+            grid: None,
+        }
+    }
+}
+
+struct Worker {
+    handle: JoinHandle<()>,
+    status: Arc<RwLock<StrategyStatus>>,
+    // Used to detect edits to the strategy and to pull its quotes on shutdown
+    strategy: Strategy,
+}
+
+/// Where a strategy's orders go and who they belong to.
+struct QuotingContext {
+    venue: Arc<dyn ExecutionVenue>,
+    owner: Arc<dyn TransactionSigner>,
+    wallet_id: Uuid,
+}
+
+/// Background runtime that keeps one quoting worker alive per auto-trading strategy.
+#[derive(Clone)]
+pub struct MarketMakingEngine {
+    db: Database,
+    config: Config,
+    paper: Arc<PaperVenue>,
+    signers: Arc<Signers>,
+    rpc: Arc<RpcClient>,
+    workers: Arc<RwLock<HashMap<Uuid, Worker>>>,
+}
+
+impl MarketMakingEngine {
+    pub fn new(db: Database, config: Config, paper: Arc<PaperVenue>, signers: Arc<Signers>) -> Self {
+        let rpc = Arc::new(RpcClient::new(config.solana_rpc_url.clone()));
+        Self {
+            db,
+            config,
+            paper,
+            signers,
+            rpc,
+            workers: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Spawns the supervisor loop that reconciles workers with the stored strategies.
+    pub fn start(&self) -> JoinHandle<()> {
+        let engine = self.clone();
+        tokio::spawn(async move {
+            let interval = Duration::from_secs(engine.config.engine_sync_interval_secs.max(1));
+            loop {
+                if let Err(e) = engine.sync_workers().await {
+                    error!("Market making supervisor failed to sync strategies: {}", e);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    pub fn signers(&self) -> &Signers {
+        &self.signers
+    }
+
+    pub async fn status(&self, strategy_id: Uuid) -> Option<StrategyStatus> {
+        let workers = self.workers.read().await;
+        match workers.get(&strategy_id) {
+            Some(worker) => Some(worker.status.read().await.clone()),
+            None => None,
+        }
+    }
+
+    /// Quotes the user's workers currently have resting on `market_pair`.
+    pub async fn resting_quotes(&self, owners: &[Uuid], market_pair: &str) -> Vec<RestingQuote> {
+        let workers = self.workers.read().await;
+        let mut resting = Vec::new();
+        for worker in workers.values() {
+            if !owners.contains(&worker.strategy.user_id) || worker.strategy.trading_pair != market_pair {
+                continue;
+            }
+            let status = worker.status.read().await;
+            let Some(wallet_id) = status.wallet_id else { continue };
+            resting.extend(status.working_quotes.iter().map(|q| RestingQuote {
+                strategy_id: worker.strategy.id,
+                wallet_id,
+                side: q.side,
+                price: q.price,
+                size: q.size,
+                client_order_id: q.client_order_id,
+            }));
+        }
+        resting
+    }
+
+    /// Top of the book a strategy trades on, from its live venue or the paper one.
+    pub async fn book_top(&self, strategy: &Strategy) -> Result<BookTop, anyhow::Error> {
+        let venue = select_venue(strategy, &self.config, &self.paper, PriorityFeePolicy::default())?;
+        venue.top_of_book(&strategy.trading_pair).await
+    }
+
+    /// Pulls a strategy's resting quotes without stopping its worker; it requotes on its next cycle.
+    pub async fn cancel_strategy_quotes(&self, strategy_id: Uuid) -> Result<(), anyhow::Error> {
+        let (strategy, status) = {
+            let workers = self.workers.read().await;
+            match workers.get(&strategy_id) {
+                Some(worker) => (worker.strategy.clone(), worker.status.clone()),
+                None => return Ok(()),
+            }
+        };
+
+        let wallet_id = status.read().await.wallet_id;
+        let context = resolve_context(&self.db, &self.config, &self.paper, &self.signers, &strategy, wallet_id).await?;
+        cancel_strategy_orders(&self.db, context.venue.as_ref(), context.owner.as_ref(), &strategy.trading_pair, strategy.id).await?;
+        if strategy.strategy_type == StrategyKind::Grid {
+            GridState::requeue_live(&self.db, strategy.id).await?;
+        }
+        status.write().await.working_quotes.clear();
+        Ok(())
+    }
+
+    /// Cancels one of a strategy's resting orders; the worker requotes on its next cycle.
+    pub async fn cancel_order(&self, order: &Order) -> Result<(), anyhow::Error> {
+        let strategy_id = order.strategy_id
+            .ok_or_else(|| anyhow::anyhow!("Order {} was not placed by a strategy", order.id))?;
+        let strategy = Strategy::find_any_by_id(&self.db, strategy_id).await?
+            .ok_or_else(|| anyhow::anyhow!("Strategy {} no longer exists", strategy_id))?;
+
+        let context = resolve_context(&self.db, &self.config, &self.paper, &self.signers, &strategy, Some(order.wallet_id)).await?;
+        if context.owner.pubkey().to_string() != order.owner || context.venue.name() != order.venue {
+            return Err(anyhow::anyhow!("Order {} was placed through a venue or key the strategy no longer uses", order.id));
+        }
+        cancel_order(&self.db, context.venue.as_ref(), context.owner.as_ref(), order).await?;
+
+        if let Some(worker) = self.workers.read().await.get(&strategy_id) {
+            worker.status.write().await.working_quotes
+                .retain(|q| q.client_order_id != order.client_order_id);
+        }
+        Ok(())
+    }
+
+    /// Starts and stops workers to match the stored strategies and active kill switches.
+    pub async fn sync_workers(&self) -> Result<(), mongodb::error::Error> {
+        let kill_switches = KillSwitch::find_active(&self.db).await?;
+        let inactive_wallets: HashSet<Uuid> = Wallet::find_inactive(&self.db).await?
+            .into_iter()
+            .map(|w| w.id)
+            .collect();
+        let strategies: Vec<Strategy> = Strategy::find_auto_trading(&self.db).await?
+            .into_iter()
+            .filter(|s| s.strategy_type.runs_on_engine())
+            .filter(|s| !kill_switches.iter().any(|k| k.halts(s)))
+            .collect();
+        let mut workers = self.workers.write().await;
+
+        // Stop workers whose strategy was deleted, had auto trading switched off or was halted
+        let stopped: Vec<Uuid> = workers
+            .keys()
+            .filter(|id| !strategies.iter().any(|s| s.id == **id))
+            .copied()
+            .collect();
+        for id in stopped {
+            if let Some(worker) = workers.remove(&id) {
+                info!("Stopping market making worker for strategy {}", id);
+                let _ = self.stop_worker(worker);
+            }
+        }
+
+        for strategy in strategies {
+            let restart = match workers.get(&strategy.id) {
+                // Restart workers that exited unexpectedly, whose strategy was edited or whose wallet
+                // stopped being active, so they move to another wallet or wait for one
+                Some(worker) => {
+                    let wallet_inactive = worker.status.read().await.wallet_id
+                        .map_or(false, |wallet_id| inactive_wallets.contains(&wallet_id));
+                    worker.handle.is_finished() || worker.strategy.updated_at != strategy.updated_at || wallet_inactive
+                }
+                None => true,
+            };
+
+            if restart {
+                let previous = workers.remove(&strategy.id).map(|old| {
+                    if old.handle.is_finished() {
+                        warn!("Market making worker for strategy {} exited, restarting", strategy.id);
+                    }
+                    self.stop_worker(old)
+                });
+                info!("Starting market making worker for strategy {} ({})", strategy.id, strategy.trading_pair);
+                let strategy_id = strategy.id;
+                workers.insert(strategy_id, self.spawn_worker(strategy, previous));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `previous` is the shutdown of the worker this one replaces, which must finish before it quotes.
+    fn spawn_worker(&self, strategy: Strategy, previous: Option<JoinHandle<()>>) -> Worker {
+        let mut initial = StrategyStatus::paused(&strategy);
+        initial.state = StrategyState::Running;
+        initial.paper_trading = is_paper(&strategy, &self.config);
+        let status = Arc::new(RwLock::new(initial));
+
+        let engine = self.clone();
+        let worker_strategy = strategy.clone();
+        let worker_status = status.clone();
+        let handle = tokio::spawn(async move {
+            if let Some(previous) = previous {
+                let _ = previous.await;
+            }
+            run_strategy(engine, worker_strategy, worker_status).await;
+        });
+
+        Worker {
+            handle,
+            status,
+            strategy,
+        }
+    }
+
+    /// Aborts a worker and pulls its resting orders from the venue.
+    fn stop_worker(&self, worker: Worker) -> JoinHandle<()> {
+        worker.handle.abort();
+
+        let db = self.db.clone();
+        let config = self.config.clone();
+        let paper = self.paper.clone();
+        let signers = self.signers.clone();
+        let strategy = worker.strategy;
+        let status = worker.status;
+        tokio::spawn(async move {
+            // Cancel from the wallet the worker quoted from, even if it is no longer active
+            let wallet_id = status.read().await.wallet_id;
+            let result = match resolve_context(&db, &config, &paper, &signers, &strategy, wallet_id).await {
+                Ok(context) => match cancel_strategy_orders(&db, context.venue.as_ref(), context.owner.as_ref(), &strategy.trading_pair, strategy.id).await {
+                    // Cancelled grid levels are placed again when the grid next runs
+                    Ok(_) if strategy.strategy_type == StrategyKind::Grid => {
+                        GridState::requeue_live(&db, strategy.id).await.map_err(|e| e.to_string())
+                    }
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e.to_string()),
+                },
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = result {
+                warn!("Failed to cancel resting orders for strategy {}: {}", strategy.id, e);
+            }
+        })
+    }
+}
+
+/// Where `strategy` trades from. `wallet_id` pins the wallet, to wind down orders placed from it;
+/// otherwise the first of the user's active wallets that can be signed for is used.
+async fn resolve_context(
+    db: &Database,
+    config: &Config,
+    paper: &Arc<PaperVenue>,
+    signers: &Signers,
+    strategy: &Strategy,
+    wallet_id: Option<Uuid>,
+) -> Result<QuotingContext, ServiceError> {
+    let fees = PriorityFeePolicy::for_user(db, strategy.user_id).await?;
+    let venue = select_venue(strategy, config, paper, fees)?;
+    let wallets: Vec<Wallet> = Wallet::find_by_user(db, strategy.user_id).await?
+        .into_iter()
+        .filter(|w| match wallet_id {
+            Some(wallet_id) => w.id == wallet_id,
+            None => w.status == WalletStatus::Active,
+        })
+        .collect();
+
+    if is_paper(strategy, config) {
+        // Paper orders never touch the chain, so a per-strategy throwaway key identifies them
+        let seed = [strategy.id.as_bytes().as_slice(), strategy.id.as_bytes().as_slice()].concat();
+        let owner: Arc<dyn TransactionSigner> = Arc::new(LocalSigner::new(
+            keypair_from_seed(&seed).map_err(|e| ServiceError::InternalServerError(e.to_string()))?,
+        ));
+        let wallet = wallets.first()
+            .ok_or_else(|| ServiceError::BadRequest("Paper trading needs at least one active wallet".into()))?;
+        return Ok(QuotingContext { venue, owner, wallet_id: wallet.id });
+    }
+
+    // Quote from the first of the user's wallets the backend holds a key for. Strategies sharing it
+    // tell their orders apart by strategy and only ever cancel their own.
+    for wallet in &wallets {
+        match signers.for_wallet(wallet).await {
+            Ok(owner) => return Ok(QuotingContext { venue, owner, wallet_id: wallet.id }),
+            Err(ServiceError::BadRequest(_)) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(ServiceError::BadRequest("None of the user's active wallets can be signed for by this backend".into()))
+}
+
+async fn run_strategy(
+    engine: MarketMakingEngine,
+    strategy: Strategy,
+    status: Arc<RwLock<StrategyStatus>>,
+) {
+    let cadence = Duration::from_secs(strategy.trade_frequency.max(1) as u64);
+    let delay = Duration::from_millis(strategy.transaction_delay.max(0) as u64);
+    let mut volatility = VolatilityEstimator::for_strategy(&strategy);
+
+    // Resolve the venue adapter once, and only quote once the venue reports a book for the market;
+    // configuration problems are retried on the cadence
+    let context = loop {
+        let resolved = match resolve_context(&engine.db, &engine.config, &engine.paper, &engine.signers, &strategy, None).await {
+            Ok(context) => venue_mid(&engine, &context, &strategy).await.map(|_| context),
+            Err(e) => Err(e.into()),
+        };
+        match resolved {
+            Ok(context) => {
+                status.write().await.wallet_id = Some(context.wallet_id);
+                break context;
+            }
+            Err(e) => {
+                warn!("Strategy {} cannot start quoting: {}", strategy.id, e);
+                let mut status = status.write().await;
+                status.state = StrategyState::Errored;
+                status.last_error = Some(e.to_string());
+            }
+        }
+        tokio::time::sleep(cadence).await;
+    };
+
+    // Paper orders live in memory, so a grid's live levels did not survive a restart
+    if strategy.strategy_type == StrategyKind::Grid && is_paper(&strategy, &engine.config) {
+        if let Err(e) = GridState::requeue_live(&engine.db, strategy.id).await {
+            warn!("Failed to requeue paper grid levels for strategy {}: {}", strategy.id, e);
+        }
+    }
+
+    loop {
+        let result = match &strategy.params {
+            StrategyParams::Grid(params) => refresh_grid(&engine, &context, &strategy, params, &status).await,
+            _ => refresh_quotes(&engine, &context, &strategy, delay, &mut volatility, &status).await,
+        };
+        match result {
+            Ok(None) => {
+                let mut status = status.write().await;
+                status.state = StrategyState::Running;
+                status.last_error = None;
+            }
+            Ok(Some(reason)) => {
+                let mut status = status.write().await;
+                status.state = StrategyState::Paused;
+                status.last_error = Some(reason);
+            }
+            Err(e) => {
+                warn!("Strategy {} failed to refresh quotes: {}", strategy.id, e);
+                let mut status = status.write().await;
+                status.state = StrategyState::Errored;
+                status.last_error = Some(e.to_string());
+            }
+        }
+
+        tokio::time::sleep(cadence).await;
+    }
+}
+
+/// Cancels and replaces the strategy's quotes. Returns the reason when quoting is paused.
+async fn refresh_quotes(
+    engine: &MarketMakingEngine,
+    context: &QuotingContext,
+    strategy: &Strategy,
+    delay: Duration,
+    volatility: &mut VolatilityEstimator,
+    status: &Arc<RwLock<StrategyStatus>>,
+) -> Result<Option<String>, anyhow::Error> {
+    let db = &engine.db;
+    let spread_bps = engine.config.quote_spread_bps;
+    let mid = venue_mid(engine, context, strategy).await?;
+
+    volatility.push(Utc::now(), mid);
+
+    let inventory = match strategy.params {
+        StrategyParams::InventorySkewed(_) | StrategyParams::AvellanedaStoikov(_) => base_inventory(engine, context, strategy).await?,
+        _ => 0.0,
+    };
+    // The model prices inventory risk off volatility, so it does not quote on a guess
+    if matches!(strategy.params, StrategyParams::AvellanedaStoikov(_)) && volatility.variance().is_none() {
+        return Ok(Some("Waiting for enough mid price samples to estimate volatility".to_string()));
+    }
+    let (mut quotes, model) = compute_quotes(
+        strategy,
+        &QuoteInputs {
+            mid,
+            inventory,
+            variance: volatility.variance(),
+            default_spread_bps: spread_bps,
+        },
+    );
+    status.write().await.model = model;
+
+    // Reconcile first so fills since the last refresh are picked up before we cancel
+    let venue = context.venue.as_ref();
+    let owner = context.owner.as_ref();
+    let open = reconcile_and_settle(db, venue, owner, &strategy.trading_pair).await?;
+    status.write().await.working_quotes
+        .retain(|q| open.iter().any(|o| o.client_order_id == q.client_order_id));
+
+    // Cancel the resting quotes before replacing them
+    if !status.read().await.working_quotes.is_empty() {
+        cancel_strategy_orders(db, venue, owner, &strategy.trading_pair, strategy.id).await?;
+        status.write().await.working_quotes.clear();
+    }
+
+    // Stop quoting once a fill could take the strategy past its daily cap
+    let remaining = strategy.max_daily_volume - StrategyVolume::used_today(db, strategy.id).await?;
+    let largest_fill = quotes.iter().map(|q| q.price * q.size).fold(0.0, f64::max);
+    if largest_fill > remaining {
+        return Ok(Some(format!(
+            "Daily volume cap of {:.2} reached ({:.2} remaining)",
+            strategy.max_daily_volume,
+            remaining.max(0.0)
+        )));
+    }
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+
+    let owners = User::organization_members(db, strategy.user_id).await?;
+    for quote in quotes.iter_mut() {
+        // Never let this wallet's quote fill a resting quote from another of the organization's wallets
+        let resting = engine.resting_quotes(&owners, &strategy.trading_pair).await;
+        let incoming = IncomingOrder {
+            wallet_id: context.wallet_id,
+            strategy_id: Some(strategy.id),
+            side: quote.side,
+            price: quote.price,
+            size: quote.size,
+        };
+        match check_self_trade(db, engine.config.self_trade_policy, strategy.user_id, &strategy.trading_pair, &incoming, &resting).await? {
+            SelfTradeDecision::Proceed => {}
+            SelfTradeDecision::Reject => continue,
+            SelfTradeDecision::CancelResting(strategy_ids) => {
+                for strategy_id in strategy_ids {
+                    engine.cancel_strategy_quotes(strategy_id).await?;
+                }
+            }
+            SelfTradeDecision::Reprice(price) => quote.price = price,
+        }
+
+        let placed = place_order(
+            db,
+            venue,
+            owner,
+            &LimitOrder {
+                market_pair: strategy.trading_pair.clone(),
+                side: quote.side,
+                price: quote.price,
+                size: quote.size,
+                client_order_id: quote.client_order_id,
+                post_only: true,
+                user_id: strategy.user_id,
+                wallet_id: context.wallet_id,
+                strategy_id: Some(strategy.id),
+            },
+        )
+        .await?;
+        quote.signature = Some(placed.signature);
+        status.write().await.working_quotes.push(quote.clone());
+    }
+
+    status.write().await.last_quote_at = Some(Utc::now());
+    Ok(None)
+}
+
+/// Detects filled grid levels, queues their replacements and places every pending level.
+/// Returns the reason when the grid is paused.
+async fn refresh_grid(
+    engine: &MarketMakingEngine,
+    context: &QuotingContext,
+    strategy: &Strategy,
+    params: &GridParams,
+    status: &Arc<RwLock<StrategyStatus>>,
+) -> Result<Option<String>, anyhow::Error> {
+    let db = &engine.db;
+    let venue = context.venue.as_ref();
+    let owner = context.owner.as_ref();
+
+    let mut state = match GridState::find_by_strategy(db, strategy.id).await? {
+        Some(state) if state.params == *params => state,
+        existing => {
+            // First run, or the ladder was edited: start over, keeping the PnL already realised
+            let mid = venue_mid(engine, context, strategy).await?;
+            if existing.is_some() {
+                cancel_strategy_orders(db, venue, owner, &strategy.trading_pair, strategy.id).await?;
+            }
+            let mut state = build_grid(strategy.id, strategy.user_id, &strategy.trading_pair, params, mid);
+            state.realized_pnl = existing.map_or(0.0, |e| e.realized_pnl);
+            GridState::save(db, &mut state).await?;
+            state
+        }
+    };
+
+    // Settle live levels whose order is no longer resting from what the venue reported
+    let open = reconcile_and_settle(db, venue, owner, &strategy.trading_pair).await?;
+    let owner_key = owner.pubkey().to_string();
+    let now = Utc::now();
+    for index in 0..state.levels.len() {
+        let level = &state.levels[index];
+        let client_order_id = match (level.status, level.client_order_id) {
+            (GridLevelStatus::Live, Some(id)) if !open.iter().any(|o| o.client_order_id == id) => id,
+            _ => continue,
+        };
+
+        let order = Order::find_by_client_order_id(db, &owner_key, venue.name(), client_order_id).await?;
+        match order {
+            // Only a fill the venue reported for the whole order moves the ladder
+            Some(order) if order.status == OrderStatus::Filled && order.filled_size > 0.0 => {
+                let fill_price = order.average_fill_price.unwrap_or(state.levels[index].price);
+                let pnl = apply_fill(&mut state, index, fill_price, now);
+                info!(
+                    "Grid level {} of strategy {} filled at {}, realised {:.4}",
+                    index, strategy.id, fill_price, pnl
+                );
+            }
+            // Cancelled outside the grid, lost with the paper book or gone without a fill we
+            // saw, so place it again
+            Some(order) if order.status.is_terminal() => {
+                let level = &mut state.levels[index];
+                level.status = GridLevelStatus::Pending;
+                level.client_order_id = None;
+                level.signature = None;
+            }
+            // Still working, or not recorded yet; the next cycle looks again
+            _ => {}
+        }
+    }
+    GridState::save(db, &mut state).await?;
+
+    let owners = User::organization_members(db, strategy.user_id).await?;
+    let mut paused = None;
+    for index in 0..state.levels.len() {
+        let level = &state.levels[index];
+        let side = match (level.status, level.side) {
+            (GridLevelStatus::Pending, Some(side)) => side,
+            _ => continue,
+        };
+
+        // Leave the level pending once a fill could take the strategy past its daily cap
+        let remaining = strategy.max_daily_volume - StrategyVolume::used_today(db, strategy.id).await?;
+        if level.price * params.order_size > remaining {
+            paused = Some(format!(
+                "Daily volume cap of {:.2} reached ({:.2} remaining)",
+                strategy.max_daily_volume,
+                remaining.max(0.0)
+            ));
+            break;
+        }
+
+        // Never let a grid order fill a resting quote from another of the organization's wallets
+        let resting = engine.resting_quotes(&owners, &strategy.trading_pair).await;
+        let incoming = IncomingOrder {
+            wallet_id: context.wallet_id,
+            strategy_id: Some(strategy.id),
+            side,
+            price: level.price,
+            size: params.order_size,
+        };
+        match check_self_trade(db, engine.config.self_trade_policy, strategy.user_id, &strategy.trading_pair, &incoming, &resting).await? {
+            SelfTradeDecision::Proceed => {}
+            // Grid prices are fixed, so a level that would self-trade waits for the next cycle
+            SelfTradeDecision::Reject | SelfTradeDecision::Reprice(_) => continue,
+            SelfTradeDecision::CancelResting(strategy_ids) => {
+                for strategy_id in strategy_ids {
+                    engine.cancel_strategy_quotes(strategy_id).await?;
+                }
+            }
+        }
+
+        let client_order_id = strategy_client_order_id(strategy.id, Utc::now(), index as u64);
+        let placed = place_order(
+            db,
+            venue,
+            owner,
+            &LimitOrder {
+                market_pair: strategy.trading_pair.clone(),
+                side,
+                price: level.price,
+                size: params.order_size,
+                client_order_id,
+                post_only: true,
+                user_id: strategy.user_id,
+                wallet_id: context.wallet_id,
+                strategy_id: Some(strategy.id),
+            },
+        )
+        .await?;
+
+        let level = &mut state.levels[index];
+        level.status = GridLevelStatus::Live;
+        level.client_order_id = Some(placed.client_order_id);
+        level.signature = Some(placed.signature);
+        // Persist each placement so a restart never loses track of a resting order
+        GridState::save(db, &mut state).await?;
+    }
+
+    // Report the live levels as working quotes so self-trade checks see them
+    let working_quotes = state
+        .levels
+        .iter()
+        .filter(|level| level.status == GridLevelStatus::Live)
+        .filter_map(|level| {
+            Some(Quote {
+                side: level.side?,
+                price: level.price,
+                size: params.order_size,
+                client_order_id: level.client_order_id?,
+                signature: level.signature.clone(),
+                placed_at: state.updated_at,
+            })
+        })
+        .collect();
+    let mut status = status.write().await;
+    status.working_quotes = working_quotes;
+    status.grid = Some(state);
+    status.last_quote_at = Some(Utc::now());
+    Ok(paused)
+}
+
+/// Mid price of the strategy's market on its venue. Books older than
+/// `BREAKER_MAX_MARKET_DATA_AGE_SECS` are not quoted against.
+async fn venue_mid(
+    engine: &MarketMakingEngine,
+    context: &QuotingContext,
+    strategy: &Strategy,
+) -> Result<f64, anyhow::Error> {
+    let top = context.venue.top_of_book(&strategy.trading_pair).await?;
+    let age_secs = (Utc::now() - top.observed_at).num_seconds();
+    if age_secs > engine.config.breaker_max_market_data_age_secs {
+        return Err(anyhow::anyhow!("Order book for {} is {}s old", strategy.trading_pair, age_secs));
+    }
+    top.mid().ok_or_else(|| anyhow::anyhow!("Order book for {} is empty", strategy.trading_pair))
+}
+
+/// Inventory of every inventory-aware kind: the base token held by the wallet the strategy quotes
+/// from, plus its simulated fills when paper trading. Backtests start from `initial_base_balance`
+/// in place of the wallet balance.
+async fn base_inventory(
+    engine: &MarketMakingEngine,
+    context: &QuotingContext,
+    strategy: &Strategy,
+) -> Result<f64, anyhow::Error> {
+    let base_symbol = strategy.trading_pair.split('/').next().unwrap_or_default();
+    let base = token_by_symbol(base_symbol)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported base token {}", base_symbol))?;
+    let balance = token_balance(&engine.rpc, &context.owner.pubkey(), &base).await?;
+
+    if is_paper(strategy, &engine.config) {
+        return Ok(balance + Transaction::net_position(&engine.db, strategy.id).await?);
+    }
+    Ok(balance)
+}
+
+pub fn mid_price(order_book: &OrderBook) -> Option<f64> {
+    let best_bid = order_book.bids.iter().map(|e| e.price).fold(None, |acc: Option<f64>, p| {
+        Some(acc.map_or(p, |a| a.max(p)))
+    });
+    let best_ask = order_book.asks.iter().map(|e| e.price).fold(None, |acc: Option<f64>, p| {
+        Some(acc.map_or(p, |a| a.min(p)))
+    });
+
+    match (best_bid, best_ask) {
+        (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+        _ => None,
+    }
+}
+
+// Low bits of a client order ID, counting milliseconds; they wrap after about 34 years
+const CLIENT_ORDER_SEQUENCE_BITS: u32 = 40;
+
+/// Client order ID for one of a strategy's orders placed at `at`. The top bits are taken from the
+/// strategy's ID, so strategies quoting from the same wallet in the same millisecond never share
+/// an ID; `offset` separates orders a strategy places together.
+pub fn strategy_client_order_id(strategy_id: Uuid, at: DateTime<Utc>, offset: u64) -> u64 {
+    let id = strategy_id.as_bytes();
+    let tag = u64::from_be_bytes([0, 0, 0, 0, 0, id[0], id[1], id[2]]);
+    let sequence = (at.timestamp_millis() as u64).wrapping_add(offset) & ((1 << CLIENT_ORDER_SEQUENCE_BITS) - 1);
+    (tag << CLIENT_ORDER_SEQUENCE_BITS) | sequence
+}
+
+/// Market state a quoting cycle is computed from.
+pub struct QuoteInputs {
+    pub mid: f64,
+    // Base token held by the strategy's wallet, used by inventory-aware kinds
+    pub inventory: f64,
+    // Mid price variance per second, once enough samples have been seen
+    pub variance: Option<f64>,
+    pub default_spread_bps: f64,
+}
+
+/// Builds a bid/ask pair for the strategy's kind, sized within its trade limits.
+/// Model-driven kinds also return the figures the quotes were derived from.
+pub fn compute_quotes(strategy: &Strategy, inputs: &QuoteInputs) -> (Vec<Quote>, Option<ReservationQuote>) {
+    let mid = inputs.mid;
+    let mut model = None;
+    let (bid_price, ask_price, quote_bid, quote_ask) = match &strategy.params {
+        StrategyParams::FixedSpread(p) => {
+            let half_spread = mid * p.spread_bps.unwrap_or(inputs.default_spread_bps) / 20_000.0;
+            (mid - half_spread, mid + half_spread, true, true)
+        }
+        StrategyParams::InventorySkewed(p) => {
+            // Lean the centre against our position so fills bring it back towards the target
+            let excess = (inputs.inventory - p.target_inventory) / p.max_inventory;
+            let centre = mid * (1.0 - excess.clamp(-1.0, 1.0) * p.skew_bps / 10_000.0);
+            let half_spread = centre * p.spread_bps / 20_000.0;
+            (centre - half_spread, centre + half_spread, excess < 1.0, excess > -1.0)
+        }
+        StrategyParams::AvellanedaStoikov(p) => {
+            // No quotes until the estimator has enough samples; zero variance would understate the risk
+            let Some(variance) = inputs.variance else { return (Vec::new(), None) };
+            let reservation = reservation_quote(p, mid, variance, inputs.inventory);
+            let bid = reservation.reservation_price - reservation.spread / 2.0;
+            let ask = reservation.reservation_price + reservation.spread / 2.0;
+            model = Some(reservation);
+            (bid, ask, bid > 0.0, true)
+        }
+        // Grid strategies are not quoted around the mid
+        _ => return (Vec::new(), None),
+    };
+
+    let size = if strategy.stealth_mode {
+        // Vary the size so our quotes are harder to fingerprint
+        strategy.min_trade_size + rand::random::<f64>() * (strategy.max_trade_size - strategy.min_trade_size)
+    } else {
+        strategy.min_trade_size
+    };
+    let size = size.clamp(strategy.min_trade_size, strategy.max_trade_size.max(strategy.min_trade_size));
+    let now = Utc::now();
+
+    let client_order_id = strategy_client_order_id(strategy.id, now, 0);
+
+    let mut quotes = Vec::new();
+    if quote_bid {
+        quotes.push(Quote {
+            side: OrderSide::Buy,
+            price: bid_price,
+            size,
+            client_order_id,
+            signature: None,
+            placed_at: now,
+        });
+    }
+    if quote_ask {
+        quotes.push(Quote {
+            side: OrderSide::Sell,
+            price: ask_price,
+            size,
+            client_order_id: client_order_id + 1,
+            signature: None,
+            placed_at: now,
+        });
+    }
+    (quotes, model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn client_order_ids_differ_between_strategies_quoting_at_once() {
+        let at = Utc.timestamp_millis_opt(1_700_000_000_000).unwrap();
+        let first = Uuid::parse_str("00000001-0000-4000-8000-000000000000").unwrap();
+        let second = Uuid::parse_str("00000002-0000-4000-8000-000000000000").unwrap();
+
+        assert_ne!(strategy_client_order_id(first, at, 0), strategy_client_order_id(second, at, 0));
+        assert_ne!(strategy_client_order_id(first, at, 1), strategy_client_order_id(second, at, 0));
+    }
+
+    #[test]
+    fn client_order_ids_of_one_strategy_count_up() {
+        let at = Utc.timestamp_millis_opt(1_700_000_000_000).unwrap();
+        let strategy_id = Uuid::new_v4();
+
+        let first = strategy_client_order_id(strategy_id, at, 0);
+        assert_eq!(strategy_client_order_id(strategy_id, at, 1), first + 1);
+        assert_eq!(strategy_client_order_id(strategy_id, at + chrono::Duration::seconds(1), 0), first + 1000);
+        // Every ID of a strategy carries the same tag
+        assert_eq!(first >> CLIENT_ORDER_SEQUENCE_BITS, (first + 1000) >> CLIENT_ORDER_SEQUENCE_BITS);
+    }
+}
@@ -0,0 +1,95 @@
+use log::{error, warn};
+use mongodb::Database;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::models::orderbook::{OrderBook, OrderBookEntry};
+use crate::models::strategy::Strategy;
+use crate::repositories::market_data::{MarketDataRecord, MarketDataRepository};
+use crate::services::backtest::DEFAULT_LEVEL_SIZE;
+use crate::services::market_maker::MarketMakingEngine;
+use crate::services::venues::{is_paper, BookTop};
+
+/// Background task that snapshots the book of every market a strategy trades into the history
+/// the backtester replays.
+pub struct MarketDataRecorder {
+    db: Database,
+    config: Config,
+    engine: MarketMakingEngine,
+    repository: MarketDataRepository,
+}
+
+impl MarketDataRecorder {
+    pub fn new(db: Database, config: Config, engine: MarketMakingEngine) -> Self {
+        Self {
+            repository: MarketDataRepository::new(db.clone()),
+            db,
+            config,
+            engine,
+        }
+    }
+
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let interval = Duration::from_secs(self.config.market_data_record_interval_secs.max(1));
+            loop {
+                if let Err(e) = self.record_once().await {
+                    error!("Market data recording failed: {}", e);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    async fn record_once(&self) -> Result<(), anyhow::Error> {
+        // One snapshot per pair, from the live venue whenever a strategy trades the pair there
+        let mut markets: HashMap<String, Strategy> = HashMap::new();
+        for strategy in Strategy::find_auto_trading(&self.db).await? {
+            let replace = markets
+                .get(&strategy.trading_pair)
+                .map_or(true, |current| is_paper(current, &self.config) && !is_paper(&strategy, &self.config));
+            if replace {
+                markets.insert(strategy.trading_pair.clone(), strategy);
+            }
+        }
+
+        for (market_pair, strategy) in markets {
+            let top = match self.engine.book_top(&strategy).await {
+                Ok(top) => top,
+                Err(e) => {
+                    warn!("Failed to read the book of {} for recording: {}", market_pair, e);
+                    continue;
+                }
+            };
+            if let Some(record) = snapshot_record(&market_pair, &top) {
+                self.repository.append_history(&record).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// History record for the top of a venue's book, or `None` while either side is empty. Venues
+/// only report prices here, so both levels get the same assumed depth as price-only CSV rows.
+pub fn snapshot_record(market_pair: &str, top: &BookTop) -> Option<MarketDataRecord> {
+    let (bid, ask) = (top.best_bid?, top.best_ask?);
+    let mid = (bid + ask) / 2.0;
+    Some(MarketDataRecord {
+        symbol: market_pair.to_string(),
+        price: mid,
+        // Not reported by the venue book
+        volume_24h: 0.0,
+        change_24h: 0.0,
+        timestamp: top.observed_at,
+        order_book: OrderBook {
+            market_pair: market_pair.to_string(),
+            bids: vec![OrderBookEntry { price: bid, size: DEFAULT_LEVEL_SIZE, total: bid * DEFAULT_LEVEL_SIZE, is_bot: false }],
+            asks: vec![OrderBookEntry { price: ask, size: DEFAULT_LEVEL_SIZE, total: ask * DEFAULT_LEVEL_SIZE, is_bot: false }],
+            last_price: mid,
+            timestamp: top.observed_at,
+        },
+    })
+}
@@ -3,4 +3,28 @@ pub mod wallets;
 pub mod trading;
 pub mod orderbook;
 pub mod alerts;
-pub mod settings; 
\ No newline at end of file
+pub mod settings;
+pub mod market_maker;
+pub mod aggregator;
+pub mod venues;
+pub mod confirmations;
+pub mod backtest;
+pub mod market_recorder;
+pub mod kill_switch;
+pub mod self_trade;
+pub mod strategy_types;
+pub mod avellaneda_stoikov;
+pub mod grid;
+pub mod parent_orders;
+pub mod orders;
+pub mod pnl;
+pub mod priority_fees;
+pub mod submission;
+pub mod slippage;
+pub mod keystore;
+pub mod signer;
+pub mod portfolio;
+pub mod prices;
+pub mod hd_wallets;
+pub mod rebalancer;
+pub mod top_ups;
\ No newline at end of file
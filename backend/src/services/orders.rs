@@ -0,0 +1,241 @@
+use anyhow::anyhow;
+use chrono::Utc;
+use log::warn;
+use mongodb::Database;
+use solana_sdk::pubkey::Pubkey;
+use uuid::Uuid;
+
+use crate::models::order::{Order, OrderEvent, OrderStatus};
+use crate::models::strategy_volume::StrategyVolume;
+use crate::models::transaction::Transaction;
+use crate::services::market_maker::MarketMakingEngine;
+use crate::services::signer::TransactionSigner;
+use crate::services::venues::{ExecutionVenue, LimitOrder, OpenOrder, PlacedOrder};
+use crate::utils::errors::ServiceError;
+
+// Most orders returned by a listing
+const ORDER_LIST_LIMIT: i64 = 500;
+
+/// Records an order, sends it to the venue and applies the venue's answer.
+pub async fn place_order(
+    db: &Database,
+    venue: &dyn ExecutionVenue,
+    owner: &dyn TransactionSigner,
+    order: &LimitOrder,
+) -> Result<PlacedOrder, anyhow::Error> {
+    let record = Order::create(db, order, &owner.pubkey().to_string(), venue.name()).await?;
+
+    match venue.place_limit_order(owner, order).await {
+        Ok(placed) => {
+            // Venues that fill on entry may already have moved the order on
+            let current = Order::find_by_id(db, record.id, record.user_id).await?.unwrap_or(record);
+            let accepted = OrderEvent::Accepted {
+                venue_order_id: None,
+                signature: Some(placed.signature.clone()),
+            };
+            Order::apply_event(db, &current, &accepted).await?;
+            Ok(placed)
+        }
+        Err(e) => {
+            let rejected = OrderEvent::Rejected { reason: e.to_string() };
+            Order::apply_event(db, &record, &rejected).await?;
+            Err(e)
+        }
+    }
+}
+
+/// Fetches the owner's resting orders, applies the fills the venue reports and settles stored
+/// orders that are no longer resting. Proceeds of any fills picked up are moved out of the venue
+/// and back into the owner's token accounts.
+pub async fn reconcile_and_settle(
+    db: &Database,
+    venue: &dyn ExecutionVenue,
+    owner: &dyn TransactionSigner,
+    market_pair: &str,
+) -> Result<Vec<OpenOrder>, anyhow::Error> {
+    let (open, filled) = reconcile(db, venue, &owner.pubkey(), market_pair).await?;
+    if filled > 0 {
+        settle(venue, owner, market_pair).await;
+    }
+    Ok(open)
+}
+
+/// Settles the owner's funds at the venue. A failure leaves them there until the next settlement,
+/// so it is logged rather than failing the caller.
+async fn settle(venue: &dyn ExecutionVenue, owner: &dyn TransactionSigner, market_pair: &str) {
+    if let Err(e) = venue.settle_funds(owner, market_pair).await {
+        warn!("Failed to settle funds of {} on {}: {}", owner.pubkey(), market_pair, e);
+    }
+}
+
+/// Fetches the owner's resting orders and brings the stored ones up to date with them and the
+/// venue's fills. Returns the resting orders and the number of fills applied.
+async fn reconcile(
+    db: &Database,
+    venue: &dyn ExecutionVenue,
+    owner: &Pubkey,
+    market_pair: &str,
+) -> Result<(Vec<OpenOrder>, usize), anyhow::Error> {
+    // Read the book before the fills, so any order missing from it has its fill in the later read
+    // unless the event was already cranked away
+    let open = venue.open_orders(owner, market_pair).await?;
+    let fills = venue.fills(owner, market_pair).await?;
+
+    let mut filled = 0;
+    for mut order in Order::find_working_for_owner(db, &owner.to_string(), venue.name(), market_pair).await? {
+        for fill in fills.iter().filter(|f| f.client_order_id == order.client_order_id) {
+            let event = OrderEvent::Fill {
+                size: fill.size,
+                price: fill.price,
+                fill_id: Some(fill.fill_id.clone()),
+            };
+            if Order::apply_event(db, &order, &event).await? {
+                // Maker fills are trades like any other, for PnL and the strategy's daily volume
+                Transaction::create_venue_fill(db, &order, fill.size, fill.price).await?;
+                filled += 1;
+                if let Some(strategy_id) = order.strategy_id {
+                    let day = StrategyVolume::day_key(Utc::now());
+                    StrategyVolume::record(db, strategy_id, order.user_id, &day, fill.price * fill.size).await?;
+                }
+                order = Order::find_by_id(db, order.id, order.user_id).await?
+                    .ok_or_else(|| anyhow!("Order {} disappeared", order.id))?;
+            }
+        }
+
+        // Orders still being submitted are settled by `place_order`
+        if order.status == OrderStatus::New || order.status.is_terminal() {
+            continue;
+        }
+
+        let event = match open.iter().find(|o| o.client_order_id == order.client_order_id) {
+            Some(resting) if order.venue_order_id.is_none() => OrderEvent::Accepted {
+                venue_order_id: Some(resting.order_id.to_string()),
+                signature: None,
+            },
+            Some(_) => continue,
+            // Gone without a fill we saw, e.g. its fill was cranked out of the event heap between
+            // polls or a paper book was lost on restart. Whatever did fill stays recorded.
+            None => OrderEvent::Expired,
+        };
+        Order::apply_event(db, &order, &event).await?;
+    }
+
+    Ok((open, filled))
+}
+
+/// Cancels everything `strategy_id` has resting on `market_pair`, picking up fills first, and
+/// settles what the cancelled and filled orders held. Orders other strategies rest from the same
+/// owner are left alone.
+pub async fn cancel_strategy_orders(
+    db: &Database,
+    venue: &dyn ExecutionVenue,
+    owner: &dyn TransactionSigner,
+    market_pair: &str,
+    strategy_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let (open, filled) = reconcile(db, venue, &owner.pubkey(), market_pair).await?;
+    let orders: Vec<Order> = Order::find_working_for_owner(db, &owner.pubkey().to_string(), venue.name(), market_pair)
+        .await?
+        .into_iter()
+        .filter(|order| order.strategy_id == Some(strategy_id))
+        .collect();
+
+    let order_ids: Vec<u128> = open
+        .iter()
+        .filter(|o| orders.iter().any(|order| order.client_order_id == o.client_order_id))
+        .map(|o| o.order_id)
+        .collect();
+    if !order_ids.is_empty() {
+        venue.cancel_orders(owner, market_pair, &order_ids).await?;
+    }
+    if filled > 0 || !order_ids.is_empty() {
+        settle(venue, owner, market_pair).await;
+    }
+
+    for order in orders {
+        Order::apply_event(db, &order, &OrderEvent::Cancelled).await?;
+    }
+    Ok(())
+}
+
+/// Cancels a single resting order, picking up fills first, and settles what it held.
+pub async fn cancel_order(
+    db: &Database,
+    venue: &dyn ExecutionVenue,
+    owner: &dyn TransactionSigner,
+    order: &Order,
+) -> Result<(), anyhow::Error> {
+    let (open, _) = reconcile(db, venue, &owner.pubkey(), &order.market_pair).await?;
+    let resting = open
+        .iter()
+        .find(|o| o.client_order_id == order.client_order_id)
+        .ok_or_else(|| anyhow!("Order {} is no longer resting", order.id))?;
+
+    venue.cancel_order(owner, &order.market_pair, resting.order_id).await?;
+    settle(venue, owner, &order.market_pair).await;
+
+    let current = Order::find_by_id(db, order.id, order.user_id).await?
+        .ok_or_else(|| anyhow!("Order {} disappeared", order.id))?;
+    Order::apply_event(db, &current, &OrderEvent::Cancelled).await?;
+    Ok(())
+}
+
+pub async fn get_orders(
+    db: &Database,
+    user_id: Uuid,
+    strategy_id: Option<Uuid>,
+    market_pair: Option<&str>,
+    status: Option<OrderStatus>,
+) -> Result<Vec<Order>, ServiceError> {
+    Ok(Order::find_by_user(db, user_id, strategy_id, market_pair, status, ORDER_LIST_LIMIT).await?)
+}
+
+pub async fn get_order(db: &Database, user_id: Uuid, order_id: Uuid) -> Result<Order, ServiceError> {
+    Order::find_by_id(db, order_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Order not found".into()))
+}
+
+pub async fn cancel_order_by_id(
+    db: &Database,
+    engine: &MarketMakingEngine,
+    user_id: Uuid,
+    order_id: Uuid,
+) -> Result<Order, ServiceError> {
+    // Check if order exists and belongs to user
+    let order = get_order(db, user_id, order_id).await?;
+    if order.status.is_terminal() {
+        return Err(ServiceError::Conflict(format!("Order is already {}", order.status.as_str())));
+    }
+
+    engine.cancel_order(&order).await
+        .map_err(|e| ServiceError::BadRequest(format!("Failed to cancel order: {}", e)))?;
+
+    get_order(db, user_id, order_id).await
+}
+
+/// Cancels every working order of the user's on a strategy, a market, or both.
+pub async fn cancel_orders(
+    db: &Database,
+    engine: &MarketMakingEngine,
+    user_id: Uuid,
+    strategy_id: Option<Uuid>,
+    market_pair: Option<&str>,
+) -> Result<Vec<Order>, ServiceError> {
+    if strategy_id.is_none() && market_pair.is_none() {
+        return Err(ServiceError::BadRequest("Bulk cancel needs a strategy_id or a market_pair".into()));
+    }
+
+    let mut cancelled = Vec::new();
+    for status in OrderStatus::working() {
+        let orders = Order::find_by_user(db, user_id, strategy_id, market_pair, Some(status), ORDER_LIST_LIMIT).await?;
+        for order in orders {
+            // Orders that filled in the meantime are reported with their final status
+            if let Err(e) = engine.cancel_order(&order).await {
+                warn!("Failed to cancel order {}: {}", order.id, e);
+            }
+            cancelled.push(get_order(db, user_id, order.id).await?);
+        }
+    }
+
+    Ok(cancelled)
+}
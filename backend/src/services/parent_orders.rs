@@ -0,0 +1,399 @@
+use chrono::{DateTime, Duration as ChronoDuration, Timelike, Utc};
+use log::{error, info, warn};
+use mongodb::Database;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::api::trading::{ParentOrderRequest, TradeRequest};
+use crate::config::Config;
+use crate::models::orderbook::OrderBook;
+use crate::models::parent_order::{ChildOrder, ChildOrderStatus, ExecutionAlgo, ParentOrder, ParentOrderStatus};
+use crate::models::strategy::Strategy;
+use crate::models::transaction::Transaction;
+use crate::models::wallet::Wallet;
+use crate::services::aggregator::SwapAggregator;
+use crate::services::market_maker::{mid_price, MarketMakingEngine};
+use crate::services::trading::{execute_trade, QUOTE_TOKEN};
+use crate::services::venues::paper::PaperVenue;
+use crate::services::venues::OrderSide;
+use crate::services::wallets::ensure_wallet_active;
+use crate::utils::errors::ServiceError;
+use crate::utils::tokens::token_by_symbol;
+
+// Children are spaced at least this far apart so each one can land before the next
+const MIN_SLICE_SECS: i64 = 5;
+const MAX_SLICES: u32 = 1_000;
+
+// Share of daily volume traded in each UTC hour, used by VWAP when no profile is given.
+// Activity peaks while the European and US sessions overlap.
+const DEFAULT_VOLUME_PROFILE: [f64; 24] = [
+    0.034, 0.032, 0.030, 0.029, 0.028, 0.029, 0.031, 0.034, 0.039, 0.042, 0.044, 0.045,
+    0.047, 0.052, 0.057, 0.059, 0.056, 0.050, 0.045, 0.041, 0.039, 0.038, 0.036, 0.035,
+];
+
+/// Splits `total_size` into `slices` children spread evenly over the window.
+/// TWAP gives every child the same size; VWAP weights them by `volume_profile`,
+/// one weight per slice, or by the default hourly profile.
+pub fn build_schedule(
+    algo: ExecutionAlgo,
+    total_size: f64,
+    start_at: DateTime<Utc>,
+    duration_secs: u64,
+    slices: u32,
+    volume_profile: Option<&[f64]>,
+) -> Vec<ChildOrder> {
+    let spacing = ChronoDuration::milliseconds((duration_secs * 1000 / slices as u64) as i64);
+    let times: Vec<DateTime<Utc>> = (0..slices).map(|i| start_at + spacing * i as i32).collect();
+
+    let weights: Vec<f64> = match (algo, volume_profile) {
+        (ExecutionAlgo::Twap, _) => vec![1.0; slices as usize],
+        (ExecutionAlgo::Vwap, Some(profile)) => profile.to_vec(),
+        (ExecutionAlgo::Vwap, None) => times
+            .iter()
+            .map(|at| DEFAULT_VOLUME_PROFILE[at.hour() as usize])
+            .collect(),
+    };
+    let total_weight: f64 = weights.iter().sum();
+
+    times
+        .into_iter()
+        .zip(weights)
+        .enumerate()
+        .map(|(i, (scheduled_at, weight))| ChildOrder {
+            index: i as u32,
+            scheduled_at,
+            target_size: total_size * weight / total_weight,
+            executed_size: 0.0,
+            price: None,
+            status: ChildOrderStatus::Scheduled,
+            transaction_id: None,
+            error: None,
+        })
+        .collect()
+}
+
+pub async fn create_parent_order(
+    db: &Database,
+    user_id: Uuid,
+    req: ParentOrderRequest,
+) -> Result<ParentOrder, ServiceError> {
+    // Check if wallet exists and belongs to user
+    let wallet = Wallet::find_by_id(db, req.wallet_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Wallet not found".into()))?;
+    ensure_wallet_active(&wallet)?;
+
+    // Check if strategy exists and belongs to user
+    if let Some(strategy_id) = req.strategy_id {
+        Strategy::find_by_id(db, strategy_id, user_id).await?
+            .ok_or_else(|| ServiceError::NotFound("Strategy not found".into()))?;
+    }
+
+    let side = match req.action.to_lowercase().as_str() {
+        "buy" => OrderSide::Buy,
+        "sell" => OrderSide::Sell,
+        _ => return Err(ServiceError::BadRequest("Action must be 'buy' or 'sell'".into())),
+    };
+    let token = token_by_symbol(&req.token)
+        .ok_or_else(|| ServiceError::BadRequest(format!("Unsupported token: {}", req.token)))?;
+
+    if req.amount <= 0.0 {
+        return Err(ServiceError::BadRequest("Parent order amount must be positive".into()));
+    }
+    if req.slices == 0 || req.slices > MAX_SLICES {
+        return Err(ServiceError::BadRequest(format!("Slices must be between 1 and {}", MAX_SLICES)));
+    }
+    if (req.duration_secs as i64) < MIN_SLICE_SECS * req.slices as i64 {
+        return Err(ServiceError::BadRequest(format!(
+            "Duration is too short for {} slices, allow at least {} seconds per slice",
+            req.slices, MIN_SLICE_SECS
+        )));
+    }
+    if req.participation_limit <= 0.0 || req.participation_limit > 1.0 {
+        return Err(ServiceError::BadRequest("Participation limit must be above 0 and at most 1".into()));
+    }
+    if req.limit_price <= 0.0 {
+        return Err(ServiceError::BadRequest("Limit price must be positive".into()));
+    }
+    if let Some(profile) = &req.volume_profile {
+        if req.algo != ExecutionAlgo::Vwap {
+            return Err(ServiceError::BadRequest("A volume profile only applies to VWAP orders".into()));
+        }
+        if profile.len() != req.slices as usize {
+            return Err(ServiceError::BadRequest("Volume profile needs one weight per slice".into()));
+        }
+        if profile.iter().any(|w| *w < 0.0) || profile.iter().sum::<f64>() <= 0.0 {
+            return Err(ServiceError::BadRequest("Volume profile weights must be non-negative and not all zero".into()));
+        }
+    }
+
+    let now = Utc::now();
+    let start_at = req.start_at.unwrap_or(now).max(now);
+    let order = ParentOrder {
+        id: Uuid::new_v4(),
+        user_id,
+        wallet_id: req.wallet_id,
+        strategy_id: req.strategy_id,
+        side,
+        token: token.symbol.to_string(),
+        total_size: req.amount,
+        executed_size: 0.0,
+        average_price: None,
+        algo: req.algo,
+        participation_limit: req.participation_limit,
+        limit_price: req.limit_price,
+        start_at,
+        end_at: start_at + ChronoDuration::seconds(req.duration_secs as i64),
+        children: build_schedule(req.algo, req.amount, start_at, req.duration_secs, req.slices, req.volume_profile.as_deref()),
+        status: ParentOrderStatus::Working,
+        created_at: now,
+        updated_at: now,
+    };
+    ParentOrder::create(db, &order).await?;
+
+    Ok(order)
+}
+
+pub async fn get_parent_order(
+    db: &Database,
+    user_id: Uuid,
+    parent_order_id: Uuid,
+) -> Result<(ParentOrder, Vec<Transaction>), ServiceError> {
+    let order = ParentOrder::find_by_id(db, parent_order_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Parent order not found".into()))?;
+    let fills = Transaction::find_by_parent_order(db, order.id, user_id).await?;
+
+    Ok((order, fills))
+}
+
+pub async fn list_parent_orders(db: &Database, user_id: Uuid) -> Result<Vec<ParentOrder>, ServiceError> {
+    Ok(ParentOrder::find_by_user(db, user_id).await?)
+}
+
+/// Stops scheduling children. A child that is already executing still completes.
+pub async fn cancel_parent_order(
+    db: &Database,
+    user_id: Uuid,
+    parent_order_id: Uuid,
+) -> Result<ParentOrder, ServiceError> {
+    let order = ParentOrder::find_by_id(db, parent_order_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Parent order not found".into()))?;
+
+    if !ParentOrder::finish(db, order.id, ParentOrderStatus::Cancelled).await? {
+        return Err(ServiceError::Conflict(format!(
+            "Parent order is already {}",
+            order.status.as_str()
+        )));
+    }
+
+    ParentOrder::find_by_id(db, order.id, user_id).await?
+        .ok_or_else(|| ServiceError::InternalServerError("Parent order record disappeared".into()))
+}
+
+/// Background task that sends each working parent order's children as they come due.
+pub struct ParentOrderExecutor {
+    db: Database,
+    config: Config,
+    engine: MarketMakingEngine,
+    aggregator: Arc<dyn SwapAggregator>,
+    paper: Arc<PaperVenue>,
+}
+
+impl ParentOrderExecutor {
+    pub fn new(
+        db: Database,
+        config: Config,
+        engine: MarketMakingEngine,
+        aggregator: Arc<dyn SwapAggregator>,
+        paper: Arc<PaperVenue>,
+    ) -> Self {
+        Self {
+            db,
+            config,
+            engine,
+            aggregator,
+            paper,
+        }
+    }
+
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let interval = Duration::from_secs(self.config.parent_order_interval_secs.max(1));
+            loop {
+                match ParentOrder::find_working(&self.db).await {
+                    Ok(orders) => {
+                        for order in orders {
+                            if let Err(e) = self.work(order).await {
+                                error!("Failed to work parent order: {}", e);
+                            }
+                        }
+                    }
+                    Err(e) => error!("Failed to load working parent orders: {}", e),
+                }
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    /// Sends the earliest due child, if any, and finishes the order once its schedule is done.
+    /// Once the window has closed, children that were never sent are cancelled instead.
+    async fn work(&self, mut order: ParentOrder) -> Result<(), ServiceError> {
+        let now = Utc::now();
+        if now >= order.end_at {
+            return self.finish(&order, ParentOrderStatus::Expired).await;
+        }
+        let Some(index) = order
+            .children
+            .iter()
+            .position(|c| c.status == ChildOrderStatus::Scheduled && c.scheduled_at <= now)
+        else {
+            return Ok(());
+        };
+
+        // Whatever earlier children left unexecuted rolls into this one
+        let scheduled: f64 = order.children[..=index].iter().map(|c| c.target_size).sum();
+        let wanted = (scheduled - order.executed_size).min(order.remaining_size()).max(0.0);
+
+        let mut child = order.children[index].clone();
+        match self.child_size(&order, wanted).await {
+            Ok(size) if size > 0.0 => {
+                let req = TradeRequest {
+                    wallet_id: order.wallet_id,
+                    action: match order.side {
+                        OrderSide::Buy => "buy".to_string(),
+                        OrderSide::Sell => "sell".to_string(),
+                    },
+                    amount: size,
+                    token: order.token.clone(),
+                    price: Some(order.limit_price),
+                    strategy_id: order.strategy_id,
+                    max_slippage_bps: None,
+                    parent_order_id: Some(order.id),
+                };
+                match execute_trade(&self.db, &self.config, &self.engine, self.aggregator.as_ref(), &self.paper, order.user_id, req).await {
+                    Ok(transaction) => {
+                        let filled_notional = order.average_price.unwrap_or(0.0) * order.executed_size
+                            + transaction.price * transaction.amount;
+                        order.executed_size += transaction.amount;
+                        order.average_price = Some(filled_notional / order.executed_size);
+
+                        child.status = ChildOrderStatus::Filled;
+                        child.executed_size = transaction.amount;
+                        child.price = Some(transaction.price);
+                        child.transaction_id = Some(transaction.id);
+                    }
+                    Err(e) => {
+                        warn!("Child {} of parent order {} failed: {}", index, order.id, e);
+                        child.status = ChildOrderStatus::Failed;
+                        child.error = Some(e.to_string());
+                    }
+                }
+            }
+            Ok(_) => child.status = ChildOrderStatus::Skipped,
+            Err(reason) => {
+                child.status = ChildOrderStatus::Skipped;
+                child.error = Some(reason);
+            }
+        }
+        ParentOrder::record_child(&self.db, &order, &child).await?;
+        order.children[index] = child;
+
+        let finished = if order.remaining_size() <= order.total_size * 1e-9 {
+            Some(ParentOrderStatus::Completed)
+        } else if order.children.iter().all(|c| c.status != ChildOrderStatus::Scheduled) {
+            Some(ParentOrderStatus::Expired)
+        } else {
+            None
+        };
+        if let Some(status) = finished {
+            self.finish(&order, status).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn finish(&self, order: &ParentOrder, status: ParentOrderStatus) -> Result<(), ServiceError> {
+        if ParentOrder::finish(&self.db, order.id, status).await? {
+            info!(
+                "Parent order {} {}: {} of {} {} executed",
+                order.id,
+                status.as_str(),
+                order.executed_size,
+                order.total_size,
+                order.token
+            );
+        }
+        Ok(())
+    }
+
+    /// Caps `wanted` by the order's participation limit. Returns why the slice is held back
+    /// when the market is beyond the order's limit price.
+    async fn child_size(&self, order: &ParentOrder, wanted: f64) -> Result<f64, String> {
+        let market_pair = format!("{}/{}", order.token, QUOTE_TOKEN);
+        let order_book = OrderBook::get_order_book(&self.db, &market_pair).await.map_err(|e| e.to_string())?;
+        let mid = mid_price(&order_book).ok_or_else(|| format!("Order book for {} is empty", market_pair))?;
+
+        let beyond_limit = match order.side {
+            OrderSide::Buy => mid > order.limit_price,
+            OrderSide::Sell => mid < order.limit_price,
+        };
+        if beyond_limit {
+            return Err(format!("Market price {:.4} is beyond the limit price {:.4}", mid, order.limit_price));
+        }
+
+        // Daily volume is quoted in the quote token; take our share of what trades during one slice
+        let market = OrderBook::get_market_price(&self.db, &market_pair).await.map_err(|e| e.to_string())?;
+        let slice_secs = (order.end_at - order.start_at).num_seconds() as f64 / order.children.len() as f64;
+        let slice_volume = market.volume_24h / mid * slice_secs / 86_400.0;
+        Ok(wanted.min(order.participation_limit * slice_volume))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn twap_splits_evenly_across_the_window() {
+        let children = build_schedule(ExecutionAlgo::Twap, 10.0, start(), 100, 4, None);
+        assert_eq!(children.len(), 4);
+        for (i, child) in children.iter().enumerate() {
+            assert_eq!(child.index, i as u32);
+            assert_eq!(child.scheduled_at, start() + ChronoDuration::seconds(25 * i as i64));
+            assert!((child.target_size - 2.5).abs() < 1e-12);
+            assert_eq!(child.status, ChildOrderStatus::Scheduled);
+        }
+    }
+
+    #[test]
+    fn vwap_follows_the_given_profile() {
+        let children = build_schedule(ExecutionAlgo::Vwap, 10.0, start(), 60, 2, Some(&[1.0, 3.0]));
+        let sizes: Vec<f64> = children.iter().map(|c| c.target_size).collect();
+        assert_eq!(sizes, vec![2.5, 7.5]);
+    }
+
+    #[test]
+    fn vwap_defaults_to_the_hourly_profile() {
+        // One slice at midnight and one at 01:00 UTC
+        let children = build_schedule(ExecutionAlgo::Vwap, 1.0, start(), 7_200, 2, None);
+        let expected = DEFAULT_VOLUME_PROFILE[0] / (DEFAULT_VOLUME_PROFILE[0] + DEFAULT_VOLUME_PROFILE[1]);
+        assert!((children[0].target_size - expected).abs() < 1e-12);
+        assert!((children[0].target_size + children[1].target_size - 1.0).abs() < 1e-12);
+        assert_eq!(children[1].scheduled_at, start() + ChronoDuration::hours(1));
+    }
+
+    #[test]
+    fn every_child_is_scheduled_before_the_window_closes() {
+        let children = build_schedule(ExecutionAlgo::Twap, 3.0, start(), 10, 3, None);
+        let end_at = start() + ChronoDuration::seconds(10);
+        assert!(children.iter().all(|c| c.scheduled_at < end_at));
+        let total: f64 = children.iter().map(|c| c.target_size).sum();
+        assert!((total - 3.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,434 @@
+use chrono::{DateTime, Utc};
+use mongodb::Database;
+use serde::{Deserialize, Serialize};
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use uuid::Uuid;
+
+use crate::api::pnl::PnlQuery;
+use crate::models::transaction::Transaction;
+use crate::services::prices::PriceSource;
+use crate::utils::errors::ServiceError;
+
+/// How the cost of a closed position is matched against the fills that opened it.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CostMethod {
+    // Oldest open lots are closed first
+    #[default]
+    Fifo,
+    // Every open unit carries the running average entry price
+    AverageCost,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PnlGrouping {
+    // Positions per (wallet, token)
+    Wallet,
+    // Positions per (strategy, token); manual trades have no strategy
+    #[default]
+    Strategy,
+    // Positions per token across all of the user's wallets
+    Token,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Lot {
+    // Positive for long lots, negative for short lots
+    quantity: f64,
+    price: f64,
+}
+
+/// Open lots for one position, in the order they were opened.
+#[derive(Debug, Default)]
+struct PositionBook {
+    lots: VecDeque<Lot>,
+}
+
+impl PositionBook {
+    fn quantity(&self) -> f64 {
+        self.lots.iter().map(|l| l.quantity).sum()
+    }
+
+    fn average_price(&self) -> Option<f64> {
+        let quantity = self.quantity();
+        (quantity.abs() > f64::EPSILON)
+            .then(|| self.lots.iter().map(|l| l.quantity * l.price).sum::<f64>() / quantity)
+    }
+
+    /// Applies a fill of signed `quantity` and returns the PnL it realises.
+    fn apply(&mut self, method: CostMethod, quantity: f64, price: f64) -> f64 {
+        // Average cost is FIFO over a single lot holding the running average
+        if method == CostMethod::AverageCost && self.lots.len() > 1 {
+            let merged = Lot {
+                quantity: self.quantity(),
+                price: self.average_price().unwrap_or(price),
+            };
+            self.lots = VecDeque::from([merged]);
+        }
+
+        let mut remaining = quantity;
+        let mut realized = 0.0;
+        while remaining.abs() > f64::EPSILON {
+            let Some(front) = self.lots.front_mut() else { break };
+            // A fill in the same direction as the position opens a new lot
+            if front.quantity.signum() == remaining.signum() {
+                break;
+            }
+
+            let closed = remaining.abs().min(front.quantity.abs());
+            realized += closed * (price - front.price) * front.quantity.signum();
+            front.quantity -= closed * front.quantity.signum();
+            remaining -= closed * remaining.signum();
+            if front.quantity.abs() <= f64::EPSILON {
+                self.lots.pop_front();
+            }
+        }
+
+        if remaining.abs() > f64::EPSILON {
+            self.lots.push_back(Lot { quantity: remaining, price });
+        }
+        realized
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionSummary {
+    pub wallet_id: Option<Uuid>,
+    pub strategy_id: Option<Uuid>,
+    pub token: String,
+    // Base token held; negative when short
+    pub quantity: f64,
+    pub average_entry_price: Option<f64>,
+    pub market_price: Option<f64>,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    // Venue fees plus network fees, in the quote token
+    pub fees: f64,
+    // Notional traded in the quote token
+    pub volume: f64,
+    pub trade_count: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PnlTotals {
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub fees: f64,
+    // Realized plus unrealized, less fees
+    pub net_pnl: f64,
+    pub volume: f64,
+    pub trade_count: usize,
+}
+
+impl PnlTotals {
+    fn add(&mut self, position: &PositionSummary) {
+        self.realized_pnl += position.realized_pnl;
+        self.unrealized_pnl += position.unrealized_pnl;
+        self.fees += position.fees;
+        self.net_pnl = self.realized_pnl + self.unrealized_pnl - self.fees;
+        self.volume += position.volume;
+        self.trade_count += position.trade_count;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PnlGroup {
+    pub wallet_id: Option<Uuid>,
+    pub strategy_id: Option<Uuid>,
+    pub token: Option<String>,
+    #[serde(flatten)]
+    pub totals: PnlTotals,
+    pub positions: Vec<PositionSummary>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PnlReport {
+    pub method: CostMethod,
+    pub group_by: PnlGrouping,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub totals: PnlTotals,
+    pub groups: Vec<PnlGroup>,
+}
+
+// (wallet, strategy, token), with the ids that do not apply to the grouping left empty
+type PositionKey = (Option<Uuid>, Option<Uuid>, String);
+
+/// Replays executed fills in order, keeping the open lots of every position.
+/// Positions are built from the full history up to `to`; realised PnL, fees and volume
+/// only count fills from `from` onwards. Network fees are converted to the quote token at the
+/// fill's own price for SOL trades and at `sol_price` for everything else.
+pub fn build_ledger(
+    transactions: &[Transaction],
+    method: CostMethod,
+    group_by: PnlGrouping,
+    from: Option<DateTime<Utc>>,
+    sol_price: Option<f64>,
+) -> Vec<(PositionSummary, Option<f64>)> {
+    let mut books: BTreeMap<PositionKey, (PositionBook, PositionSummary)> = BTreeMap::new();
+
+    for tx in transactions {
+        let quantity = match tx.action.to_lowercase().as_str() {
+            "buy" => tx.amount,
+            "sell" => -tx.amount,
+            _ => continue,
+        };
+        let key: PositionKey = match group_by {
+            PnlGrouping::Wallet => (Some(tx.wallet_id), None, tx.token.clone()),
+            PnlGrouping::Strategy => (None, tx.strategy_id, tx.token.clone()),
+            PnlGrouping::Token => (None, None, tx.token.clone()),
+        };
+        let (book, summary) = books.entry(key.clone()).or_insert_with(|| {
+            (
+                PositionBook::default(),
+                PositionSummary {
+                    wallet_id: key.0,
+                    strategy_id: key.1,
+                    token: key.2.clone(),
+                    quantity: 0.0,
+                    average_entry_price: None,
+                    market_price: None,
+                    realized_pnl: 0.0,
+                    unrealized_pnl: 0.0,
+                    fees: 0.0,
+                    volume: 0.0,
+                    trade_count: 0,
+                },
+            )
+        });
+
+        let realized = book.apply(method, quantity, tx.price);
+        if from.map_or(true, |from| tx.created_at >= from) {
+            summary.realized_pnl += realized;
+            summary.fees += tx.fee.unwrap_or(0.0) + network_fee(tx, sol_price);
+            summary.volume += tx.notional();
+            summary.trade_count += 1;
+        }
+    }
+
+    books
+        .into_values()
+        .map(|(book, mut summary)| {
+            summary.quantity = book.quantity();
+            summary.average_entry_price = book.average_price();
+            let basis = summary.average_entry_price;
+            (summary, basis)
+        })
+        .collect()
+}
+
+/// A fill's network fee in the quote token.
+fn network_fee(tx: &Transaction, sol_price: Option<f64>) -> f64 {
+    let Some(lamports) = tx.network_fee else { return 0.0 };
+    let price = if tx.token.eq_ignore_ascii_case("SOL") { Some(tx.price) } else { sol_price };
+    lamports as f64 / LAMPORTS_PER_SOL as f64 * price.unwrap_or(0.0)
+}
+
+pub async fn get_pnl(db: &Database, prices: &PriceSource, user_id: Uuid, query: PnlQuery) -> Result<PnlReport, ServiceError> {
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from >= to {
+            return Err(ServiceError::BadRequest("PnL range start must be before its end".into()));
+        }
+    }
+
+    let transactions = Transaction::find_executed(
+        db,
+        user_id,
+        query.wallet_id,
+        query.strategy_id,
+        query.token.as_deref(),
+        query.to,
+    ).await?;
+    // Fees paid in SOL on other tokens' trades need SOL's price to be counted
+    let needs_sol_price = transactions
+        .iter()
+        .any(|tx| tx.network_fee.is_some() && !tx.token.eq_ignore_ascii_case("SOL"));
+    let sol_price = if needs_sol_price { Some(prices.require("SOL").await?.value) } else { None };
+    let ledger = build_ledger(&transactions, query.method, query.group_by, query.from, sol_price);
+
+    // Mark open positions to the current price, once per token; positions with no price keep no mark
+    let mut marks: HashMap<String, Option<f64>> = HashMap::new();
+    let mut groups: BTreeMap<(Option<Uuid>, Option<Uuid>, Option<String>), PnlGroup> = BTreeMap::new();
+    let mut totals = PnlTotals::default();
+    for (mut position, basis) in ledger {
+        if !marks.contains_key(&position.token) {
+            let mark = prices.price(&position.token).await.map(|p| p.value);
+            marks.insert(position.token.clone(), mark);
+        }
+        position.market_price = marks[&position.token];
+        if let (Some(mark), Some(basis)) = (position.market_price, basis) {
+            position.unrealized_pnl = position.quantity * (mark - basis);
+        }
+
+        let token = (query.group_by == PnlGrouping::Token).then(|| position.token.clone());
+        let group = groups
+            .entry((position.wallet_id, position.strategy_id, token.clone()))
+            .or_insert_with(|| PnlGroup {
+                wallet_id: position.wallet_id,
+                strategy_id: position.strategy_id,
+                token,
+                totals: PnlTotals::default(),
+                positions: Vec::new(),
+            });
+        group.totals.add(&position);
+        totals.add(&position);
+        group.positions.push(position);
+    }
+
+    Ok(PnlReport {
+        method: query.method,
+        group_by: query.group_by,
+        from: query.from,
+        to: query.to,
+        totals,
+        groups: groups.into_values().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    use crate::models::transaction::TransactionStatus;
+
+    fn fill(wallet_id: Uuid, token: &str, action: &str, amount: f64, price: f64, at: DateTime<Utc>) -> Transaction {
+        Transaction {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            wallet_id,
+            strategy_id: None,
+            action: action.to_string(),
+            amount,
+            token: token.to_string(),
+            price,
+            status: TransactionStatus::Finalized,
+            slippage: None,
+            max_slippage_bps: None,
+            transaction_hash: None,
+            last_valid_block_height: None,
+            error: None,
+            status_history: Vec::new(),
+            fee: None,
+            simulated: false,
+            parent_order_id: None,
+            compute_unit_limit: None,
+            compute_unit_price: None,
+            network_fee: None,
+            attempts: Vec::new(),
+            retrying_until: None,
+            volume_reservation: None,
+            created_at: at,
+        }
+    }
+
+    fn single(transactions: &[Transaction], method: CostMethod, from: Option<DateTime<Utc>>) -> PositionSummary {
+        let mut ledger = build_ledger(transactions, method, PnlGrouping::Token, from, None);
+        assert_eq!(ledger.len(), 1);
+        ledger.remove(0).0
+    }
+
+    #[test]
+    fn fifo_closes_the_oldest_lot_first() {
+        let (wallet, now) = (Uuid::new_v4(), Utc::now());
+        let fills = [
+            fill(wallet, "SOL", "buy", 1.0, 100.0, now),
+            fill(wallet, "SOL", "buy", 1.0, 110.0, now),
+            fill(wallet, "SOL", "sell", 1.0, 120.0, now),
+        ];
+        let summary = single(&fills, CostMethod::Fifo, None);
+        assert!((summary.realized_pnl - 20.0).abs() < 1e-9);
+        assert!((summary.quantity - 1.0).abs() < 1e-9);
+        assert!((summary.average_entry_price.unwrap() - 110.0).abs() < 1e-9);
+        assert_eq!(summary.trade_count, 3);
+        assert!((summary.volume - 330.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn average_cost_closes_at_the_running_average() {
+        let (wallet, now) = (Uuid::new_v4(), Utc::now());
+        let fills = [
+            fill(wallet, "SOL", "buy", 1.0, 100.0, now),
+            fill(wallet, "SOL", "buy", 1.0, 110.0, now),
+            fill(wallet, "SOL", "sell", 1.0, 120.0, now),
+        ];
+        let summary = single(&fills, CostMethod::AverageCost, None);
+        assert!((summary.realized_pnl - 15.0).abs() < 1e-9);
+        assert!((summary.average_entry_price.unwrap() - 105.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn shorts_profit_when_bought_back_lower() {
+        let (wallet, now) = (Uuid::new_v4(), Utc::now());
+        let fills = [
+            fill(wallet, "SOL", "sell", 1.0, 100.0, now),
+            fill(wallet, "SOL", "buy", 1.0, 90.0, now),
+        ];
+        let summary = single(&fills, CostMethod::Fifo, None);
+        assert!((summary.realized_pnl - 10.0).abs() < 1e-9);
+        assert!(summary.quantity.abs() < 1e-9);
+        assert_eq!(summary.average_entry_price, None);
+    }
+
+    #[test]
+    fn selling_through_a_long_opens_a_short() {
+        let (wallet, now) = (Uuid::new_v4(), Utc::now());
+        let fills = [
+            fill(wallet, "SOL", "buy", 1.0, 100.0, now),
+            fill(wallet, "SOL", "sell", 3.0, 110.0, now),
+        ];
+        let summary = single(&fills, CostMethod::Fifo, None);
+        assert!((summary.realized_pnl - 10.0).abs() < 1e-9);
+        assert!((summary.quantity + 2.0).abs() < 1e-9);
+        assert!((summary.average_entry_price.unwrap() - 110.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fills_before_the_range_only_build_the_position() {
+        let (wallet, now) = (Uuid::new_v4(), Utc::now());
+        let from = now - Duration::hours(1);
+        let fills = [
+            fill(wallet, "SOL", "buy", 1.0, 100.0, now - Duration::hours(2)),
+            fill(wallet, "SOL", "sell", 0.5, 120.0, now),
+        ];
+        let summary = single(&fills, CostMethod::Fifo, Some(from));
+        assert!((summary.realized_pnl - 10.0).abs() < 1e-9);
+        assert_eq!(summary.trade_count, 1);
+        assert!((summary.volume - 60.0).abs() < 1e-9);
+        assert!((summary.quantity - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn positions_are_grouped_as_asked() {
+        let (first, second, now) = (Uuid::new_v4(), Uuid::new_v4(), Utc::now());
+        let fills = [
+            fill(first, "SOL", "buy", 1.0, 100.0, now),
+            fill(second, "SOL", "buy", 1.0, 100.0, now),
+            fill(second, "BTC", "buy", 0.1, 60_000.0, now),
+            fill(second, "SOL", "transfer", 1.0, 100.0, now),
+        ];
+        assert_eq!(build_ledger(&fills, CostMethod::Fifo, PnlGrouping::Wallet, None, None).len(), 3);
+        assert_eq!(build_ledger(&fills, CostMethod::Fifo, PnlGrouping::Token, None, None).len(), 2);
+    }
+
+    #[test]
+    fn network_fees_are_valued_in_the_quote_token() {
+        let (wallet, now) = (Uuid::new_v4(), Utc::now());
+        let mut sol = fill(wallet, "SOL", "buy", 1.0, 100.0, now);
+        sol.network_fee = Some(5_000);
+        sol.fee = Some(0.1);
+        let mut btc = fill(wallet, "BTC", "buy", 0.1, 60_000.0, now);
+        btc.network_fee = Some(5_000);
+
+        // SOL trades price their own fee; others need the SOL price
+        let summary = single(&[sol], CostMethod::Fifo, None);
+        assert!((summary.fees - (0.1 + 0.000_005 * 100.0)).abs() < 1e-12);
+
+        let ledger = build_ledger(std::slice::from_ref(&btc), CostMethod::Fifo, PnlGrouping::Token, None, Some(150.0));
+        assert!((ledger[0].0.fees - 0.000_005 * 150.0).abs() < 1e-12);
+        let ledger = build_ledger(std::slice::from_ref(&btc), CostMethod::Fifo, PnlGrouping::Token, None, None);
+        assert_eq!(ledger[0].0.fees, 0.0);
+    }
+}
@@ -0,0 +1,199 @@
+use chrono::{DateTime, Utc};
+use mongodb::Database;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_request::TokenAccountsFilter;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::wallet::Wallet;
+use crate::services::prices::PriceSource;
+use crate::utils::errors::ServiceError;
+use crate::utils::tokens::{token_by_mint, NATIVE_MINT, SPL_TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID};
+
+const SOL_DECIMALS: u8 = 9;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Holding {
+    pub mint: String,
+    pub symbol: Option<String>,
+    pub decimals: u8,
+    // Base units, as a string so large supplies keep their precision
+    pub amount: String,
+    pub ui_amount: f64,
+    // Absent when no route currently prices the token
+    pub price_usd: Option<f64>,
+    pub value_usd: Option<f64>,
+    pub priced_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WalletPortfolio {
+    pub wallet_id: Uuid,
+    pub address: String,
+    pub label: Option<String>,
+    pub holdings: Vec<Holding>,
+    // Sum over the holdings that have a price
+    pub total_value_usd: f64,
+    // Mints of holdings left out of the total because they have no current price
+    pub unpriced: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Portfolio {
+    pub wallets: Vec<WalletPortfolio>,
+    // Every wallet's holdings combined per mint
+    pub holdings: Vec<Holding>,
+    pub total_value_usd: f64,
+    pub unpriced: Vec<String>,
+}
+
+/// Raw balances per mint, before they are priced.
+struct Balance {
+    decimals: u8,
+    amount: u128,
+}
+
+pub async fn get_wallet_portfolio(
+    db: &Database,
+    config: &Config,
+    prices: &PriceSource,
+    user_id: Uuid,
+    wallet_id: Uuid,
+) -> Result<WalletPortfolio, ServiceError> {
+    // Check if wallet exists and belongs to user
+    let wallet = Wallet::find_by_id(db, wallet_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Wallet not found".into()))?;
+
+    let rpc = RpcClient::new(config.solana_rpc_url.clone());
+    let balances = fetch_balances(&rpc, &wallet.address).await?;
+    Ok(wallet_portfolio(prices, wallet, balances).await)
+}
+
+/// Holdings of every one of the user's wallets, and their total per mint.
+pub async fn get_portfolio(db: &Database, config: &Config, prices: &PriceSource, user_id: Uuid) -> Result<Portfolio, ServiceError> {
+    let rpc = RpcClient::new(config.solana_rpc_url.clone());
+    let mut combined: BTreeMap<String, Balance> = BTreeMap::new();
+    let mut wallets = Vec::new();
+
+    for wallet in Wallet::find_by_user(db, user_id).await? {
+        let balances = fetch_balances(&rpc, &wallet.address).await?;
+        for (mint, balance) in &balances {
+            let entry = combined.entry(mint.clone()).or_insert(Balance { decimals: balance.decimals, amount: 0 });
+            entry.amount += balance.amount;
+        }
+        wallets.push(wallet_portfolio(prices, wallet, balances).await);
+    }
+
+    let mut holdings = Vec::new();
+    for (mint, balance) in combined {
+        holdings.push(holding(prices, mint, balance).await);
+    }
+    sort_by_value(&mut holdings);
+
+    Ok(Portfolio {
+        total_value_usd: holdings.iter().filter_map(|h| h.value_usd).sum(),
+        unpriced: unpriced(&holdings),
+        wallets,
+        holdings,
+    })
+}
+
+async fn wallet_portfolio(prices: &PriceSource, wallet: Wallet, balances: BTreeMap<String, Balance>) -> WalletPortfolio {
+    let mut holdings = Vec::new();
+    for (mint, balance) in balances {
+        holdings.push(holding(prices, mint, balance).await);
+    }
+    sort_by_value(&mut holdings);
+
+    WalletPortfolio {
+        wallet_id: wallet.id,
+        address: wallet.address,
+        label: wallet.label,
+        total_value_usd: holdings.iter().filter_map(|h| h.value_usd).sum(),
+        unpriced: unpriced(&holdings),
+        holdings,
+    }
+}
+
+/// Whether the address holds no SOL and no tokens at all.
+pub async fn is_empty(rpc: &RpcClient, address: &str) -> Result<bool, ServiceError> {
+    Ok(fetch_balances(rpc, address).await?.values().all(|b| b.amount == 0))
+}
+
+/// SOL plus every non-empty SPL and Token-2022 account the address owns, summed per mint.
+async fn fetch_balances(rpc: &RpcClient, address: &str) -> Result<BTreeMap<String, Balance>, ServiceError> {
+    let owner = address.parse::<Pubkey>()
+        .map_err(|_| ServiceError::BadRequest("Invalid Solana address".into()))?;
+    let mut balances = BTreeMap::new();
+
+    let lamports = rpc.get_balance(&owner).await
+        .map_err(|e| ServiceError::InternalServerError(format!("Failed to fetch SOL balance of {}: {}", address, e)))?;
+    balances.insert(NATIVE_MINT.to_string(), Balance { decimals: SOL_DECIMALS, amount: lamports as u128 });
+
+    for program in [SPL_TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID] {
+        let program_id = Pubkey::from_str(program)
+            .map_err(|e| ServiceError::InternalServerError(e.to_string()))?;
+        let accounts = rpc.get_token_accounts_by_owner(&owner, TokenAccountsFilter::ProgramId(program_id)).await
+            .map_err(|e| ServiceError::InternalServerError(format!("Failed to fetch token accounts of {}: {}", address, e)))?;
+
+        for keyed in accounts {
+            // Accounts come back jsonParsed: { "parsed": { "info": { "mint", "tokenAmount": { "amount", "decimals" } } } }
+            let data = serde_json::to_value(&keyed.account.data)
+                .map_err(|e| ServiceError::InternalServerError(e.to_string()))?;
+            let info = &data["parsed"]["info"];
+            let (Some(mint), Some(amount), Some(decimals)) = (
+                info["mint"].as_str(),
+                info["tokenAmount"]["amount"].as_str().and_then(|a| a.parse::<u128>().ok()),
+                info["tokenAmount"]["decimals"].as_u64(),
+            ) else {
+                return Err(ServiceError::InternalServerError(format!("Unreadable token account {}", keyed.pubkey)));
+            };
+            if amount == 0 {
+                continue;
+            }
+
+            // Wrapped SOL is counted with native SOL
+            let entry = balances.entry(mint.to_string()).or_insert(Balance { decimals: decimals as u8, amount: 0 });
+            entry.amount += amount;
+        }
+    }
+
+    Ok(balances)
+}
+
+async fn holding(prices: &PriceSource, mint: String, balance: Balance) -> Holding {
+    let symbol = token_by_mint(&mint).map(|t| t.symbol.to_string());
+    let price = match &symbol {
+        Some(symbol) => prices.price(symbol).await,
+        None => None,
+    };
+    let ui_amount = balance.amount as f64 / 10f64.powi(balance.decimals as i32);
+
+    Holding {
+        mint,
+        symbol,
+        decimals: balance.decimals,
+        amount: balance.amount.to_string(),
+        ui_amount,
+        price_usd: price.map(|p| p.value),
+        value_usd: price.map(|p| p.value * ui_amount),
+        priced_at: price.map(|p| p.at),
+    }
+}
+
+fn unpriced(holdings: &[Holding]) -> Vec<String> {
+    holdings.iter().filter(|h| h.price_usd.is_none()).map(|h| h.mint.clone()).collect()
+}
+
+fn sort_by_value(holdings: &mut [Holding]) {
+    holdings.sort_by(|a, b| {
+        b.value_usd
+            .unwrap_or(0.0)
+            .partial_cmp(&a.value_usd.unwrap_or(0.0))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
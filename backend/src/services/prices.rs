@@ -0,0 +1,93 @@
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::services::aggregator::{QuoteRequest, SwapAggregator, SwapMode};
+use crate::services::trading::QUOTE_TOKEN;
+use crate::utils::errors::ServiceError;
+use crate::utils::tokens::{from_base_units, to_base_units, token_by_symbol};
+
+// Slippage sent with price quotes; no swap is ever built from them
+const PRICE_QUOTE_SLIPPAGE_BPS: u16 = 50;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct Price {
+    // Quote token per unit of the priced token
+    pub value: f64,
+    pub at: DateTime<Utc>,
+}
+
+/// Prices known tokens in the quote token from what the aggregator would pay for one unit of
+/// them right now. The quote token is taken at par with USD.
+pub struct PriceSource {
+    aggregator: Arc<dyn SwapAggregator>,
+    max_age: Duration,
+    cache: Mutex<HashMap<String, Price>>,
+}
+
+impl PriceSource {
+    pub fn new(aggregator: Arc<dyn SwapAggregator>, max_age_secs: i64) -> Self {
+        Self {
+            aggregator,
+            max_age: Duration::seconds(max_age_secs),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Price of `symbol` no older than `PRICE_MAX_AGE_SECS`, or `None` when there is no route
+    /// that prices it. Stale prices are never returned in place of a fresh one.
+    pub async fn price(&self, symbol: &str) -> Option<Price> {
+        let symbol = symbol.to_uppercase();
+        if symbol == QUOTE_TOKEN {
+            return Some(Price { value: 1.0, at: Utc::now() });
+        }
+        if let Some(price) = self.cache.lock().await.get(&symbol) {
+            if Utc::now() - price.at <= self.max_age {
+                return Some(*price);
+            }
+        }
+
+        match self.fetch(&symbol).await {
+            Ok(price) => {
+                self.cache.lock().await.insert(symbol, price);
+                Some(price)
+            }
+            Err(e) => {
+                warn!("No price for {}: {}", symbol, e);
+                None
+            }
+        }
+    }
+
+    /// Like `price`, for callers that cannot go on without one.
+    pub async fn require(&self, symbol: &str) -> Result<Price, ServiceError> {
+        self.price(symbol)
+            .await
+            .ok_or_else(|| ServiceError::InternalServerError(format!("No current price for {}", symbol)))
+    }
+
+    async fn fetch(&self, symbol: &str) -> Result<Price, ServiceError> {
+        let token = token_by_symbol(symbol)
+            .ok_or_else(|| ServiceError::BadRequest(format!("Unknown token {}", symbol)))?;
+        let quote_token = token_by_symbol(QUOTE_TOKEN)
+            .ok_or_else(|| ServiceError::InternalServerError(format!("Unknown quote token {}", QUOTE_TOKEN)))?;
+
+        let quote = self.aggregator.quote(&QuoteRequest {
+            input_mint: token.mint.to_string(),
+            output_mint: quote_token.mint.to_string(),
+            amount: to_base_units(1.0, token.decimals),
+            swap_mode: SwapMode::ExactIn,
+            slippage_bps: PRICE_QUOTE_SLIPPAGE_BPS,
+        }).await
+            .map_err(|e| ServiceError::InternalServerError(format!("Failed to quote {}: {}", symbol, e)))?;
+        if quote.in_amount == 0 || quote.out_amount == 0 {
+            return Err(ServiceError::InternalServerError(format!("Empty quote for {}", symbol)));
+        }
+
+        let value = from_base_units(quote.out_amount, quote_token.decimals) / from_base_units(quote.in_amount, token.decimals);
+        Ok(Price { value, at: Utc::now() })
+    }
+}
@@ -0,0 +1,237 @@
+use anyhow::{anyhow, Result};
+use mongodb::Database;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
+use solana_sdk::address_lookup_table::state::AddressLookupTable;
+use solana_sdk::compute_budget::{self, ComputeBudgetInstruction};
+use solana_sdk::instruction::CompiledInstruction;
+use solana_sdk::message::{MessageHeader, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+use uuid::Uuid;
+
+use crate::models::settings::{PriorityFeeLevel, Settings};
+
+// Most compute units a transaction may request
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+// Headroom over the simulated usage, since accounts can change before the transaction lands
+const COMPUTE_UNIT_MARGIN: f64 = 1.15;
+// getRecentPrioritizationFees accepts at most this many accounts
+const MAX_FEE_ACCOUNTS: usize = 128;
+
+/// A user's choice of how much to bid for block space.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PriorityFeePolicy {
+    pub level: PriorityFeeLevel,
+    // Micro-lamports per compute unit; only used at the custom level
+    pub cap: Option<u64>,
+}
+
+impl PriorityFeePolicy {
+    pub async fn for_user(db: &Database, user_id: Uuid) -> Result<Self, mongodb::error::Error> {
+        Ok(Settings::find_by_user(db, user_id)
+            .await?
+            .map(|s| Self { level: s.priority_fee_level, cap: s.priority_fee_cap })
+            .unwrap_or_default())
+    }
+
+    // Percentile of recent prioritization fees to bid at
+    fn percentile(&self) -> f64 {
+        match self.level {
+            PriorityFeeLevel::Low => 25.0,
+            PriorityFeeLevel::Medium => 50.0,
+            PriorityFeeLevel::High | PriorityFeeLevel::Custom => 75.0,
+        }
+    }
+
+    /// Compute unit price to bid given the fees recently paid for the same accounts.
+    pub fn unit_price(&self, recent_fees: &[u64]) -> u64 {
+        let mut fees = recent_fees.to_vec();
+        fees.sort_unstable();
+        let price = match fees.len() {
+            0 => 0,
+            n => fees[((n - 1) as f64 * self.percentile() / 100.0).round() as usize],
+        };
+        self.capped(price)
+    }
+
+    /// Holds a compute unit price to the user's cap at the custom level.
+    pub fn capped(&self, unit_price: u64) -> u64 {
+        match (self.level, self.cap) {
+            (PriorityFeeLevel::Custom, Some(cap)) => unit_price.min(cap),
+            _ => unit_price,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ComputeBudget {
+    pub unit_limit: u32,
+    // Micro-lamports per compute unit
+    pub unit_price: u64,
+}
+
+/// Simulates `message` to size its compute unit limit and prices its units from the
+/// fees recently paid for the accounts it writes. Returns the message with its compute
+/// budget instructions replaced, or the simulation error.
+pub async fn with_priority_fee(
+    rpc: &RpcClient,
+    message: VersionedMessage,
+    policy: &PriorityFeePolicy,
+) -> Result<(VersionedMessage, ComputeBudget)> {
+    // Simulate at the maximum limit so the measurement is not cut short
+    let unbounded = with_compute_budget(message.clone(), &ComputeBudget { unit_limit: MAX_COMPUTE_UNIT_LIMIT, unit_price: 0 });
+    let unsigned = VersionedTransaction {
+        signatures: vec![Signature::default(); unbounded.header().num_required_signatures as usize],
+        message: unbounded,
+    };
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        ..Default::default()
+    };
+    let simulation = rpc.simulate_transaction_with_config(&unsigned, config).await?.value;
+    if let Some(err) = simulation.err {
+        return Err(anyhow!("{}", err));
+    }
+
+    let unit_limit = simulation
+        .units_consumed
+        .map(|units| ((units as f64 * COMPUTE_UNIT_MARGIN).ceil() as u32).min(MAX_COMPUTE_UNIT_LIMIT))
+        .unwrap_or(MAX_COMPUTE_UNIT_LIMIT);
+
+    let mut accounts = writable_accounts(rpc, &message).await?;
+    accounts.truncate(MAX_FEE_ACCOUNTS);
+    let recent_fees: Vec<u64> = rpc
+        .get_recent_prioritization_fees(&accounts)
+        .await?
+        .into_iter()
+        .map(|fee| fee.prioritization_fee)
+        .collect();
+    let budget = ComputeBudget { unit_limit, unit_price: policy.unit_price(&recent_fees) };
+
+    Ok((with_compute_budget(message, &budget), budget))
+}
+
+/// Accounts the message writes, including those it loads from address lookup tables. Swaps
+/// keep most of their pool accounts in tables, and those are the accounts fees compete for.
+pub async fn writable_accounts(rpc: &RpcClient, message: &VersionedMessage) -> Result<Vec<Pubkey>> {
+    let mut writable: Vec<Pubkey> = message
+        .static_account_keys()
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| message.is_maybe_writable(*index))
+        .map(|(_, key)| *key)
+        .collect();
+
+    let lookups = match message.address_table_lookups() {
+        Some(lookups) if !lookups.is_empty() => lookups,
+        _ => return Ok(writable),
+    };
+    let table_keys: Vec<Pubkey> = lookups.iter().map(|lookup| lookup.account_key).collect();
+    let tables = rpc.get_multiple_accounts(&table_keys).await?;
+
+    for (lookup, table) in lookups.iter().zip(tables) {
+        let table = table.ok_or_else(|| anyhow!("Address lookup table {} does not exist", lookup.account_key))?;
+        let table = AddressLookupTable::deserialize(&table.data)
+            .map_err(|e| anyhow!("Invalid address lookup table {}: {}", lookup.account_key, e))?;
+        for index in &lookup.writable_indexes {
+            let address = table.addresses.get(*index as usize).ok_or_else(|| {
+                anyhow!("Address lookup table {} has no entry {}", lookup.account_key, index)
+            })?;
+            writable.push(*address);
+        }
+    }
+
+    Ok(writable)
+}
+
+/// Replaces any compute budget instructions in `message` with ones requesting `budget`.
+pub fn with_compute_budget(message: VersionedMessage, budget: &ComputeBudget) -> VersionedMessage {
+    match message {
+        VersionedMessage::Legacy(mut message) => {
+            insert_compute_budget(&mut message.header, &mut message.account_keys, &mut message.instructions, budget);
+            VersionedMessage::Legacy(message)
+        }
+        VersionedMessage::V0(mut message) => {
+            insert_compute_budget(&mut message.header, &mut message.account_keys, &mut message.instructions, budget);
+            VersionedMessage::V0(message)
+        }
+    }
+}
+
+fn insert_compute_budget(
+    header: &mut MessageHeader,
+    account_keys: &mut Vec<Pubkey>,
+    instructions: &mut Vec<CompiledInstruction>,
+    budget: &ComputeBudget,
+) {
+    let program_index = match account_keys.iter().position(|key| *key == compute_budget::id()) {
+        Some(index) => index,
+        None => {
+            // Added as the last read-only unsigned key. Addresses loaded from lookup tables
+            // are indexed after the static keys, so they move up by one.
+            let index = account_keys.len();
+            for instruction in instructions.iter_mut() {
+                for account in std::iter::once(&mut instruction.program_id_index).chain(instruction.accounts.iter_mut()) {
+                    if *account as usize >= index {
+                        *account += 1;
+                    }
+                }
+            }
+            account_keys.push(compute_budget::id());
+            header.num_readonly_unsigned_accounts += 1;
+            index
+        }
+    };
+
+    instructions.retain(|instruction| instruction.program_id_index as usize != program_index);
+    let budget_instructions = [
+        ComputeBudgetInstruction::set_compute_unit_limit(budget.unit_limit),
+        ComputeBudgetInstruction::set_compute_unit_price(budget.unit_price),
+    ];
+    instructions.splice(
+        0..0,
+        budget_instructions
+            .into_iter()
+            .map(|instruction| CompiledInstruction::new_from_raw_parts(program_index as u8, instruction.data, vec![])),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(level: PriorityFeeLevel, cap: Option<u64>) -> PriorityFeePolicy {
+        PriorityFeePolicy { level, cap }
+    }
+
+    // Deliberately out of order
+    const RECENT_FEES: [u64; 10] = [100, 10, 90, 20, 80, 30, 70, 40, 60, 50];
+
+    #[test]
+    fn levels_bid_at_their_percentile_of_recent_fees() {
+        assert_eq!(policy(PriorityFeeLevel::Low, None).unit_price(&RECENT_FEES), 30);
+        assert_eq!(policy(PriorityFeeLevel::Medium, None).unit_price(&RECENT_FEES), 60);
+        assert_eq!(policy(PriorityFeeLevel::High, None).unit_price(&RECENT_FEES), 80);
+    }
+
+    #[test]
+    fn no_recent_fees_bids_nothing() {
+        assert_eq!(policy(PriorityFeeLevel::High, None).unit_price(&[]), 0);
+    }
+
+    #[test]
+    fn a_single_recent_fee_is_every_percentile() {
+        assert_eq!(policy(PriorityFeeLevel::Low, None).unit_price(&[1_234]), 1_234);
+        assert_eq!(policy(PriorityFeeLevel::High, None).unit_price(&[1_234]), 1_234);
+    }
+
+    #[test]
+    fn only_the_custom_level_is_capped() {
+        assert_eq!(policy(PriorityFeeLevel::Custom, Some(50)).unit_price(&RECENT_FEES), 50);
+        assert_eq!(policy(PriorityFeeLevel::Custom, None).unit_price(&RECENT_FEES), 80);
+        assert_eq!(policy(PriorityFeeLevel::High, Some(50)).unit_price(&RECENT_FEES), 80);
+    }
+}
@@ -0,0 +1,423 @@
+use chrono::Utc;
+use log::{error, info, warn};
+use mongodb::Database;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::rebalance::{RebalanceReport, RebalanceTransfer, RebalanceTrigger, TransferStatus, WalletAllocation};
+use crate::models::settings::Settings;
+use crate::models::transaction::{Transaction, TransactionStatus};
+use crate::models::wallet::{Wallet, WalletStatus};
+use crate::services::portfolio::{get_wallet_portfolio, WalletPortfolio};
+use crate::services::prices::PriceSource;
+use crate::services::signer::Signers;
+use crate::services::submission::submit_transfer;
+use crate::services::wallets::token_balance;
+use crate::utils::errors::ServiceError;
+use crate::utils::tokens::{token_by_mint, token_by_symbol, TokenInfo, NATIVE_MINT};
+
+// SOL left in a sending wallet for fees and rent when the user has no min_wallet_balance setting
+const DEFAULT_SOL_RESERVE: f64 = 0.1;
+// Transfers worth less than this are not worth their fees
+const MIN_TRANSFER_USD: f64 = 1.0;
+const REPORT_LIMIT: i64 = 50;
+
+/// Funds a wallet could send: a priced token and the amount of it above any reserve.
+struct Spendable {
+    token: TokenInfo,
+    price: f64,
+    amount: f64,
+}
+
+/// Moves funds between a user's wallets until each holds its `allocation_percentage` of their
+/// combined value. Runs on a timer for users with `auto_rebalance` on, and on demand.
+pub struct Rebalancer {
+    db: Database,
+    config: Config,
+    signers: Arc<Signers>,
+    prices: Arc<PriceSource>,
+    // Users with a run in progress, so scheduled and manual runs never overlap
+    running: Mutex<HashSet<Uuid>>,
+}
+
+impl Rebalancer {
+    pub fn new(db: Database, config: Config, signers: Arc<Signers>, prices: Arc<PriceSource>) -> Self {
+        Self {
+            db,
+            config,
+            signers,
+            prices,
+            running: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn start(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let interval = Duration::from_secs(self.config.rebalance_interval_secs.max(1));
+            loop {
+                tokio::time::sleep(interval).await;
+                match Settings::find_auto_rebalance(&self.db).await {
+                    Ok(settings) => {
+                        for settings in settings {
+                            if let Err(e) = self.rebalance(settings.user_id, false, RebalanceTrigger::Scheduled).await {
+                                error!("Failed to rebalance wallets of user {}: {}", settings.user_id, e);
+                            }
+                        }
+                    }
+                    Err(e) => error!("Failed to load users with auto rebalance on: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Compares each wallet's share of the user's capital with its target and, unless
+    /// `dry_run`, sends the transfers that close the gap. The run is recorded either way.
+    pub async fn rebalance(
+        &self,
+        user_id: Uuid,
+        dry_run: bool,
+        trigger: RebalanceTrigger,
+    ) -> Result<RebalanceReport, ServiceError> {
+        if !self.running.lock().await.insert(user_id) {
+            return Err(ServiceError::Conflict("A rebalance is already running for this user".into()));
+        }
+        let result = self.run(user_id, dry_run, trigger).await;
+        self.running.lock().await.remove(&user_id);
+        result
+    }
+
+    async fn run(&self, user_id: Uuid, dry_run: bool, trigger: RebalanceTrigger) -> Result<RebalanceReport, ServiceError> {
+        let settings = Settings::find_by_user(&self.db, user_id).await?;
+        let threshold_pct = settings
+            .as_ref()
+            .and_then(|s| s.rebalance_threshold_pct)
+            .unwrap_or(self.config.rebalance_threshold_pct);
+        let sol_reserve = settings.as_ref().map_or(DEFAULT_SOL_RESERVE, |s| s.min_wallet_balance);
+
+        // Draining wallets take part with a target of nothing; paused, archived and unallocated
+        // wallets are left alone rather than drained
+        let wallets: Vec<Wallet> = Wallet::find_by_user(&self.db, user_id).await?
+            .into_iter()
+            .filter(|w| match w.status {
+                WalletStatus::Active => w.allocation_percentage > 0.0,
+                WalletStatus::Draining => true,
+                WalletStatus::Paused | WalletStatus::Archived => false,
+            })
+            .collect();
+        let targeted = wallets.iter().filter(|w| w.status == WalletStatus::Active).count();
+        if targeted == 0 || wallets.len() < 2 {
+            return Err(ServiceError::BadRequest("At least two wallets need an allocation percentage, or one a draining wallet to take from, to rebalance".into()));
+        }
+
+        let mut portfolios = Vec::new();
+        for wallet in &wallets {
+            portfolios.push(get_wallet_portfolio(&self.db, &self.config, &self.prices, user_id, wallet.id).await?);
+        }
+        // Shares computed without some holdings would move funds towards the wrong targets
+        let unpriced: Vec<&str> = portfolios.iter().flat_map(|p| p.unpriced.iter().map(String::as_str)).collect();
+        if !unpriced.is_empty() {
+            return Err(ServiceError::BadRequest(format!(
+                "Cannot rebalance while these holdings have no current price: {}",
+                unpriced.join(", ")
+            )));
+        }
+        let allocations = allocations(&wallets, &portfolios);
+        let total_value_usd: f64 = portfolios.iter().map(|p| p.total_value_usd).sum();
+        let max_drift_pct = allocations.iter().map(|a| a.drift_pct.abs()).fold(0.0, f64::max);
+
+        let mut transfers = Vec::new();
+        if max_drift_pct > threshold_pct {
+            let rpc = RpcClient::new(self.config.solana_rpc_url.clone());
+            let mut spendable = Vec::new();
+            for portfolio in &portfolios {
+                spendable.push(spendable_funds(&rpc, portfolio, sol_reserve).await?);
+            }
+            transfers = plan_transfers(&allocations, total_value_usd, &mut spendable);
+        }
+
+        if !dry_run {
+            for transfer in &mut transfers {
+                let from = wallets.iter().find(|w| w.id == transfer.from_wallet_id);
+                let to = wallets.iter().find(|w| w.id == transfer.to_wallet_id);
+                let (Some(from), Some(to)) = (from, to) else { continue };
+                match self.send(from, to, transfer).await {
+                    Ok(()) => transfer.status = TransferStatus::Completed,
+                    Err(e) => {
+                        warn!("Rebalance transfer from wallet {} to {} failed: {}", from.id, to.id, e);
+                        transfer.status = TransferStatus::Failed;
+                        transfer.error = Some(e.to_string());
+                    }
+                }
+            }
+        }
+
+        let report = RebalanceReport {
+            id: Uuid::new_v4(),
+            user_id,
+            trigger,
+            dry_run,
+            threshold_pct,
+            max_drift_pct,
+            total_value_usd,
+            wallets: allocations,
+            transfers,
+            created_at: Utc::now(),
+        };
+        RebalanceReport::create(&self.db, &report).await?;
+        info!(
+            "Rebalance of user {} ({}): max drift {:.2}%, {} transfers",
+            user_id,
+            if dry_run { "dry run" } else { "live" },
+            max_drift_pct,
+            report.transfers.len()
+        );
+
+        Ok(report)
+    }
+
+    /// Signs and sends one planned transfer, recording it as a transaction of the sending wallet.
+    async fn send(&self, from: &Wallet, to: &Wallet, transfer: &mut RebalanceTransfer) -> Result<(), ServiceError> {
+        let token = token_by_symbol(&transfer.token)
+            .ok_or_else(|| ServiceError::InternalServerError(format!("Unsupported token: {}", transfer.token)))?;
+        let recipient = to.address.parse::<Pubkey>()
+            .map_err(|_| ServiceError::BadRequest("Invalid Solana address".into()))?;
+        let signer = self.signers.for_wallet(from).await?;
+
+        let price = if transfer.amount > 0.0 { transfer.value_usd / transfer.amount } else { 0.0 };
+        let transaction = Transaction::create(
+            &self.db,
+            from.user_id,
+            from.id,
+            None,
+            "transfer",
+            transfer.amount,
+            &transfer.token,
+            price,
+            TransactionStatus::Built,
+            None,
+            None,
+            None,
+            None,
+            None,
+        ).await?;
+        transfer.transaction_id = Some(transaction.id);
+
+        let signature = submit_transfer(
+            &self.db,
+            &self.config,
+            from.user_id,
+            transaction.id,
+            signer.as_ref(),
+            &recipient,
+            &token,
+            transfer.amount,
+        ).await?;
+        transfer.signature = Some(signature.to_string());
+
+        Ok(())
+    }
+}
+
+pub async fn list_reports(db: &Database, user_id: Uuid) -> Result<Vec<RebalanceReport>, ServiceError> {
+    let reports = RebalanceReport::find_by_user(db, user_id, REPORT_LIMIT).await?;
+    Ok(reports)
+}
+
+pub async fn get_report(db: &Database, user_id: Uuid, report_id: Uuid) -> Result<RebalanceReport, ServiceError> {
+    let report = RebalanceReport::find_by_id(db, report_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Rebalance report not found".into()))?;
+
+    Ok(report)
+}
+
+/// Each wallet's target and actual share of the wallets' combined value. Targets are the active
+/// wallets' allocation percentages scaled to add up to 100; draining wallets target nothing.
+fn allocations(wallets: &[Wallet], portfolios: &[WalletPortfolio]) -> Vec<WalletAllocation> {
+    let target = |w: &Wallet| if w.status == WalletStatus::Active { w.allocation_percentage } else { 0.0 };
+    let total_allocation: f64 = wallets.iter().map(target).sum();
+    let total_value: f64 = portfolios.iter().map(|p| p.total_value_usd).sum();
+
+    wallets
+        .iter()
+        .zip(portfolios)
+        .map(|(wallet, portfolio)| {
+            let target_pct = target(wallet) / total_allocation * 100.0;
+            let actual_pct = if total_value > 0.0 { portfolio.total_value_usd / total_value * 100.0 } else { target_pct };
+            WalletAllocation {
+                wallet_id: wallet.id,
+                address: wallet.address.clone(),
+                label: wallet.label.clone(),
+                value_usd: portfolio.total_value_usd,
+                target_pct,
+                actual_pct,
+                drift_pct: actual_pct - target_pct,
+            }
+        })
+        .collect()
+}
+
+/// Known, priced tokens the wallet can send, most valuable first. Transfers only draw from the
+/// native SOL balance and the associated token accounts, so other token accounts the portfolio
+/// counts are left out, and SOL is limited to what is above `sol_reserve`.
+async fn spendable_funds(rpc: &RpcClient, portfolio: &WalletPortfolio, sol_reserve: f64) -> Result<Vec<Spendable>, ServiceError> {
+    let owner = portfolio.address.parse::<Pubkey>()
+        .map_err(|_| ServiceError::BadRequest("Invalid Solana address".into()))?;
+    let mut funds = Vec::new();
+    for holding in &portfolio.holdings {
+        let (Some(token), Some(price)) = (token_by_mint(&holding.mint), holding.price_usd) else { continue };
+        if price <= 0.0 {
+            continue;
+        }
+
+        let balance = token_balance(rpc, &owner, &token).await?.min(holding.ui_amount);
+        let amount = if token.mint == NATIVE_MINT { balance - sol_reserve } else { balance };
+        if amount > 0.0 {
+            funds.push(Spendable { token, price, amount });
+        }
+    }
+    Ok(funds)
+}
+
+/// Pairs the wallets over their target with those under it, largest gaps first, and draws
+/// each move from the sending wallet's most valuable spendable tokens.
+fn plan_transfers(
+    allocations: &[WalletAllocation],
+    total_value_usd: f64,
+    spendable: &mut [Vec<Spendable>],
+) -> Vec<RebalanceTransfer> {
+    let gap = |a: &WalletAllocation| a.value_usd - a.target_pct / 100.0 * total_value_usd;
+    let mut surplus: Vec<(usize, f64)> = allocations.iter().enumerate().map(|(i, a)| (i, gap(a))).filter(|(_, g)| *g > 0.0).collect();
+    let mut deficit: Vec<(usize, f64)> = allocations.iter().enumerate().map(|(i, a)| (i, -gap(a))).filter(|(_, g)| *g > 0.0).collect();
+    surplus.sort_by(|a, b| b.1.total_cmp(&a.1));
+    deficit.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut transfers = Vec::new();
+    let (mut s, mut d) = (0, 0);
+    while s < surplus.len() && d < deficit.len() {
+        let (from, to) = (surplus[s].0, deficit[d].0);
+        let wanted = surplus[s].1.min(deficit[d].1);
+
+        let mut moved = 0.0;
+        for funds in spendable[from].iter_mut() {
+            let value = (wanted - moved).min(funds.amount * funds.price);
+            if value < MIN_TRANSFER_USD {
+                continue;
+            }
+            let amount = value / funds.price;
+            funds.amount -= amount;
+            moved += value;
+            transfers.push(RebalanceTransfer {
+                from_wallet_id: allocations[from].wallet_id,
+                to_wallet_id: allocations[to].wallet_id,
+                token: funds.token.symbol.to_string(),
+                amount,
+                value_usd: value,
+                status: TransferStatus::Planned,
+                transaction_id: None,
+                signature: None,
+                error: None,
+            });
+        }
+
+        // A sender that runs out of spendable funds leaves the rest of its surplus in place
+        surplus[s].1 -= wanted;
+        deficit[d].1 -= wanted;
+        if surplus[s].1 <= MIN_TRANSFER_USD {
+            s += 1;
+        }
+        if deficit[d].1 <= MIN_TRANSFER_USD {
+            d += 1;
+        }
+    }
+
+    transfers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocation(value_usd: f64, target_pct: f64) -> WalletAllocation {
+        WalletAllocation {
+            wallet_id: Uuid::new_v4(),
+            address: String::new(),
+            label: None,
+            value_usd,
+            target_pct,
+            actual_pct: 0.0,
+            drift_pct: 0.0,
+        }
+    }
+
+    fn funds(symbol: &str, price: f64, amount: f64) -> Spendable {
+        Spendable { token: token_by_symbol(symbol).unwrap(), price, amount }
+    }
+
+    #[test]
+    fn moves_the_surplus_to_the_wallet_short_of_its_share() {
+        let allocations = [allocation(75.0, 50.0), allocation(25.0, 50.0)];
+        let mut spendable = vec![vec![funds("USDC", 1.0, 75.0)], vec![funds("USDC", 1.0, 25.0)]];
+
+        let transfers = plan_transfers(&allocations, 100.0, &mut spendable);
+
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].from_wallet_id, allocations[0].wallet_id);
+        assert_eq!(transfers[0].to_wallet_id, allocations[1].wallet_id);
+        assert_eq!(transfers[0].token, "USDC");
+        assert!((transfers[0].value_usd - 25.0).abs() < 1e-9);
+        assert!((spendable[0][0].amount - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn draws_on_funds_in_the_order_given() {
+        let allocations = [allocation(75.0, 50.0), allocation(25.0, 50.0)];
+        let mut spendable = vec![vec![funds("SOL", 100.0, 0.1), funds("USDC", 1.0, 65.0)], vec![]];
+
+        let transfers = plan_transfers(&allocations, 100.0, &mut spendable);
+
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].token, "SOL");
+        assert!((transfers[0].amount - 0.1).abs() < 1e-9);
+        assert!((transfers[0].value_usd - 10.0).abs() < 1e-9);
+        assert_eq!(transfers[1].token, "USDC");
+        assert!((transfers[1].amount - 15.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn small_gaps_are_left_alone() {
+        let allocations = [allocation(50.5, 50.0), allocation(49.5, 50.0)];
+        let mut spendable = vec![vec![funds("USDC", 1.0, 50.5)], vec![funds("USDC", 1.0, 49.5)]];
+
+        assert!(plan_transfers(&allocations, 100.0, &mut spendable).is_empty());
+    }
+
+    #[test]
+    fn senders_without_spendable_funds_move_nothing() {
+        let allocations = [allocation(75.0, 50.0), allocation(25.0, 50.0)];
+        let mut spendable = vec![vec![], vec![funds("USDC", 1.0, 25.0)]];
+
+        assert!(plan_transfers(&allocations, 100.0, &mut spendable).is_empty());
+    }
+
+    #[test]
+    fn one_surplus_covers_several_deficits_largest_first() {
+        let third = 100.0 / 3.0;
+        let allocations = [allocation(60.0, third), allocation(30.0, third), allocation(10.0, third)];
+        let mut spendable = vec![vec![funds("USDC", 1.0, 60.0)], vec![], vec![]];
+
+        let transfers = plan_transfers(&allocations, 100.0, &mut spendable);
+
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].to_wallet_id, allocations[2].wallet_id);
+        assert!((transfers[0].value_usd - (third - 10.0)).abs() < 1e-9);
+        assert_eq!(transfers[1].to_wallet_id, allocations[1].wallet_id);
+        assert!((transfers[1].value_usd - (third - 30.0)).abs() < 1e-9);
+    }
+}
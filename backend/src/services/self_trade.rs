@@ -0,0 +1,224 @@
+use log::warn;
+use mongodb::Database;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::models::self_trade_event::{SelfTradeAction, SelfTradeEvent};
+use crate::services::venues::OrderSide;
+use crate::utils::errors::ServiceError;
+
+// Repriced orders are moved this far behind the resting order they would have hit
+const REPRICE_BPS: f64 = 1.0;
+
+/// What to do when an order from one wallet would trade against a resting order from another
+/// wallet of the same user or organization.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SelfTradePolicy {
+    // Drop the incoming order
+    Reject,
+    // Pull the resting order, then send the incoming one
+    CancelResting,
+    // Move the incoming limit price so it no longer crosses
+    Reprice,
+}
+
+impl SelfTradePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SelfTradePolicy::Reject => "reject",
+            SelfTradePolicy::CancelResting => "cancel_resting",
+            SelfTradePolicy::Reprice => "reprice",
+        }
+    }
+}
+
+impl FromStr for SelfTradePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "reject" => Ok(SelfTradePolicy::Reject),
+            "cancel_resting" => Ok(SelfTradePolicy::CancelResting),
+            "reprice" => Ok(SelfTradePolicy::Reprice),
+            other => Err(format!("unknown self-trade policy '{}'", other)),
+        }
+    }
+}
+
+/// A quote the engine currently has resting on a venue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestingQuote {
+    pub strategy_id: Uuid,
+    pub wallet_id: Uuid,
+    pub side: OrderSide,
+    pub price: f64,
+    pub size: f64,
+    pub client_order_id: u64,
+}
+
+/// An order about to be sent, checked against the resting quotes of the user's organization.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncomingOrder {
+    pub wallet_id: Uuid,
+    pub strategy_id: Option<Uuid>,
+    pub side: OrderSide,
+    // Worst price the order may execute at
+    pub price: f64,
+    pub size: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelfTradeDecision {
+    Proceed,
+    Reject,
+    CancelResting(Vec<Uuid>),
+    Reprice(f64),
+}
+
+/// Resting quotes from other wallets the incoming order would trade against. A wallet's own
+/// quotes are left to the venue, which never matches an owner against itself.
+pub fn find_crossing<'a>(incoming: &IncomingOrder, resting: &'a [RestingQuote]) -> Vec<&'a RestingQuote> {
+    resting
+        .iter()
+        .filter(|r| r.wallet_id != incoming.wallet_id)
+        .filter(|r| match incoming.side {
+            OrderSide::Buy => r.side == OrderSide::Sell && incoming.price >= r.price,
+            OrderSide::Sell => r.side == OrderSide::Buy && incoming.price <= r.price,
+        })
+        .collect()
+}
+
+/// Applies `policy` to an incoming order and records any prevented match.
+pub async fn check_self_trade(
+    db: &Database,
+    policy: SelfTradePolicy,
+    user_id: Uuid,
+    market_pair: &str,
+    incoming: &IncomingOrder,
+    resting: &[RestingQuote],
+) -> Result<SelfTradeDecision, ServiceError> {
+    let crossing = find_crossing(incoming, resting);
+    if crossing.is_empty() {
+        return Ok(SelfTradeDecision::Proceed);
+    }
+
+    let (decision, action) = match policy {
+        SelfTradePolicy::Reject => (SelfTradeDecision::Reject, SelfTradeAction::Rejected),
+        SelfTradePolicy::CancelResting => {
+            let mut strategies: Vec<Uuid> = crossing.iter().map(|r| r.strategy_id).collect();
+            strategies.sort();
+            strategies.dedup();
+            (SelfTradeDecision::CancelResting(strategies), SelfTradeAction::CancelledResting)
+        }
+        SelfTradePolicy::Reprice => {
+            // Step behind the most aggressive resting order we would have hit
+            let price = match incoming.side {
+                OrderSide::Buy => {
+                    let best = crossing.iter().map(|r| r.price).fold(f64::INFINITY, f64::min);
+                    best * (1.0 - REPRICE_BPS / 10_000.0)
+                }
+                OrderSide::Sell => {
+                    let best = crossing.iter().map(|r| r.price).fold(f64::NEG_INFINITY, f64::max);
+                    best * (1.0 + REPRICE_BPS / 10_000.0)
+                }
+            };
+            (SelfTradeDecision::Reprice(price), SelfTradeAction::Repriced { price })
+        }
+    };
+
+    warn!(
+        "Prevented self-trade for user {} on {}: {:?} {} @ {} would cross {} resting quote(s), {}",
+        user_id,
+        market_pair,
+        incoming.side,
+        incoming.size,
+        incoming.price,
+        crossing.len(),
+        policy.as_str()
+    );
+    SelfTradeEvent::create(
+        db,
+        user_id,
+        market_pair,
+        policy,
+        incoming.clone(),
+        crossing.into_iter().cloned().collect(),
+        action,
+    ).await?;
+
+    Ok(decision)
+}
+
+pub async fn get_self_trade_events(db: &Database, user_id: Uuid) -> Result<Vec<SelfTradeEvent>, ServiceError> {
+    Ok(SelfTradeEvent::find_by_user(db, user_id).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resting(wallet_id: Uuid, side: OrderSide, price: f64) -> RestingQuote {
+        RestingQuote {
+            strategy_id: Uuid::new_v4(),
+            wallet_id,
+            side,
+            price,
+            size: 1.0,
+            client_order_id: 1,
+        }
+    }
+
+    fn incoming(wallet_id: Uuid, side: OrderSide, price: f64) -> IncomingOrder {
+        IncomingOrder {
+            wallet_id,
+            strategy_id: None,
+            side,
+            price,
+            size: 1.0,
+        }
+    }
+
+    #[test]
+    fn buy_crosses_asks_at_or_below_its_price() {
+        let (ours, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let book = [
+            resting(other, OrderSide::Sell, 100.0),
+            resting(other, OrderSide::Sell, 101.0),
+            resting(other, OrderSide::Sell, 102.0),
+            resting(other, OrderSide::Buy, 99.0),
+        ];
+        let crossing = find_crossing(&incoming(ours, OrderSide::Buy, 101.0), &book);
+        let prices: Vec<f64> = crossing.iter().map(|r| r.price).collect();
+        assert_eq!(prices, vec![100.0, 101.0]);
+    }
+
+    #[test]
+    fn sell_crosses_bids_at_or_above_its_price() {
+        let (ours, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let book = [
+            resting(other, OrderSide::Buy, 99.0),
+            resting(other, OrderSide::Buy, 98.0),
+            resting(other, OrderSide::Sell, 97.0),
+        ];
+        let crossing = find_crossing(&incoming(ours, OrderSide::Sell, 99.0), &book);
+        let prices: Vec<f64> = crossing.iter().map(|r| r.price).collect();
+        assert_eq!(prices, vec![99.0]);
+    }
+
+    #[test]
+    fn a_wallets_own_quotes_are_left_to_the_venue() {
+        let ours = Uuid::new_v4();
+        let book = [resting(ours, OrderSide::Sell, 100.0)];
+        assert!(find_crossing(&incoming(ours, OrderSide::Buy, 105.0), &book).is_empty());
+    }
+
+    #[test]
+    fn orders_behind_the_book_do_not_cross() {
+        let (ours, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let book = [resting(other, OrderSide::Sell, 100.0), resting(other, OrderSide::Buy, 98.0)];
+        assert!(find_crossing(&incoming(ours, OrderSide::Buy, 99.9), &book).is_empty());
+        assert!(find_crossing(&incoming(ours, OrderSide::Sell, 98.1), &book).is_empty());
+    }
+}
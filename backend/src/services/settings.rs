@@ -2,7 +2,9 @@ use mongodb::Database;
 use uuid::Uuid;
 
 use crate::api::settings::SettingsRequest;
-use crate::models::settings::Settings;
+use crate::models::settings::{PriorityFeeLevel, Settings};
+use crate::models::wallet::Wallet;
+use crate::services::slippage::validate_max_slippage;
 use crate::utils::errors::ServiceError;
 
 pub async fn get_user_settings(
@@ -39,6 +41,35 @@ pub async fn update_user_settings(
         }
     }
     
+    if let Some(max_slippage_bps) = req.max_slippage_bps {
+        validate_max_slippage(max_slippage_bps)?;
+    }
+    
+    if let Some(threshold) = req.rebalance_threshold_pct {
+        if threshold <= 0.0 || threshold > 100.0 {
+            return Err(ServiceError::BadRequest("Rebalance threshold must be above 0 and at most 100".into()));
+        }
+    }
+    
+    if let Some(cap) = req.max_daily_top_up {
+        if cap < 0.0 {
+            return Err(ServiceError::BadRequest("Daily top-up cap cannot be negative".into()));
+        }
+    }
+    
+    if let Some(wallet_id) = req.treasury_wallet_id {
+        // Check if wallet exists and belongs to user
+        Wallet::find_by_id(db, wallet_id, user_id).await?
+            .ok_or_else(|| ServiceError::NotFound("Treasury wallet not found".into()))?;
+    }
+    
+    if req.priority_fee_level == Some(PriorityFeeLevel::Custom) && req.priority_fee_cap.is_none() {
+        let existing_cap = Settings::find_by_user(db, user_id).await?.and_then(|s| s.priority_fee_cap);
+        if existing_cap.is_none() {
+            return Err(ServiceError::BadRequest("The custom priority fee level needs a priority_fee_cap".into()));
+        }
+    }
+    
     // Update settings
     let settings = Settings::create_or_update(
         db,
@@ -48,6 +79,12 @@ pub async fn update_user_settings(
         req.risk_level.as_deref(),
         req.notification_email.as_deref(),
         req.auto_rebalance,
+        req.priority_fee_level,
+        req.priority_fee_cap,
+        req.max_slippage_bps,
+        req.rebalance_threshold_pct,
+        req.treasury_wallet_id,
+        req.max_daily_top_up,
     ).await?;
     
     Ok(settings)
@@ -0,0 +1,604 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use solana_sdk::compute_budget;
+use solana_sdk::instruction::CompiledInstruction;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature, Signer};
+use solana_sdk::system_program;
+use solana_sdk::transaction::VersionedTransaction;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::config::Config;
+use crate::models::wallet::Wallet;
+use crate::services::keystore::Keystore;
+use crate::services::venues::openbook_v2::OPENBOOK_V2_PROGRAM_ID;
+use crate::utils::errors::ServiceError;
+use crate::utils::tokens::{associated_token_address, ASSOCIATED_TOKEN_PROGRAM_ID, KNOWN_TOKENS, SPL_TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID};
+
+pub const JUPITER_PROGRAM_ID: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+
+// Programs the backend's own transactions invoke, which the reference signer allows by default
+pub const DEFAULT_ALLOWED_PROGRAMS: &[&str] = &[
+    "11111111111111111111111111111111",
+    "ComputeBudget111111111111111111111111111111",
+    SPL_TOKEN_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_TOKEN_PROGRAM_ID,
+    JUPITER_PROGRAM_ID,
+    OPENBOOK_V2_PROGRAM_ID,
+];
+
+// Programs a Jupiter swap transaction may invoke. The system program wraps SOL into the
+// signer's wrapped SOL account, which the transfer destination check already covers.
+const SWAP_PROGRAMS: &[&str] = &[
+    "11111111111111111111111111111111",
+    "ComputeBudget111111111111111111111111111111",
+    SPL_TOKEN_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_TOKEN_PROGRAM_ID,
+    JUPITER_PROGRAM_ID,
+];
+
+// SetComputeUnitPrice is variant 3 of the compute budget program's instructions, followed by a u64
+const SET_COMPUTE_UNIT_PRICE_TAG: u8 = 3;
+// System program instructions start with a u32 tag: Transfer is 2, TransferWithSeed 11
+const SYSTEM_TRANSFER_TAG: u32 = 2;
+const SYSTEM_TRANSFER_WITH_SEED_TAG: u32 = 11;
+// Token program Transfer is instruction 3, TransferChecked instruction 12
+const TOKEN_TRANSFER_TAG: u8 = 3;
+const TOKEN_TRANSFER_CHECKED_TAG: u8 = 12;
+// How long the remote signer's key list is used before it is fetched again
+const REMOTE_KEYS_TTL: Duration = Duration::from_secs(60);
+
+/// What a transaction is signed for. Sent along with every remote signing request so the
+/// signer's policy can allow some kinds of transactions and not others.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SigningPurpose {
+    Swap,
+    PlaceOrder,
+    CancelOrder,
+    SettleFunds,
+    // Moving funds between the user's own wallets
+    Transfer,
+}
+
+impl SigningPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SigningPurpose::Swap => "swap",
+            SigningPurpose::PlaceOrder => "place_order",
+            SigningPurpose::CancelOrder => "cancel_order",
+            SigningPurpose::SettleFunds => "settle_funds",
+            SigningPurpose::Transfer => "transfer",
+        }
+    }
+}
+
+impl FromStr for SigningPurpose {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "swap" => Ok(SigningPurpose::Swap),
+            "place_order" => Ok(SigningPurpose::PlaceOrder),
+            "cancel_order" => Ok(SigningPurpose::CancelOrder),
+            "settle_funds" => Ok(SigningPurpose::SettleFunds),
+            "transfer" => Ok(SigningPurpose::Transfer),
+            other => Err(format!("Unknown signing purpose '{}'", other)),
+        }
+    }
+}
+
+/// Signs transaction messages for one key, wherever that key lives.
+#[async_trait]
+pub trait TransactionSigner: Send + Sync {
+    fn pubkey(&self) -> Pubkey;
+
+    /// Returns the key's signature over the serialized `message`.
+    async fn sign_message(&self, message: &VersionedMessage, purpose: SigningPurpose) -> Result<Signature>;
+}
+
+/// Signs `message` with `signer`, which must be its fee payer and only required signer.
+pub async fn sign_transaction(
+    signer: &dyn TransactionSigner,
+    message: VersionedMessage,
+    purpose: SigningPurpose,
+) -> Result<VersionedTransaction> {
+    let pubkey = signer.pubkey();
+    if message.header().num_required_signatures != 1 || message.static_account_keys().first() != Some(&pubkey) {
+        return Err(anyhow!("Transaction must be signed by {} alone", pubkey));
+    }
+
+    let signature = signer.sign_message(&message, purpose).await?;
+    Ok(VersionedTransaction {
+        signatures: vec![signature],
+        message,
+    })
+}
+
+/// A key held in this process.
+pub struct LocalSigner {
+    keypair: Keypair,
+}
+
+impl LocalSigner {
+    pub fn new(keypair: Keypair) -> Self {
+        Self { keypair }
+    }
+}
+
+#[async_trait]
+impl TransactionSigner for LocalSigner {
+    fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+
+    async fn sign_message(&self, message: &VersionedMessage, _purpose: SigningPurpose) -> Result<Signature> {
+        Ok(self.keypair.sign_message(&message.serialize()))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignRequest {
+    pub pubkey: String,
+    // Base64 of the serialized message
+    pub message: String,
+    pub purpose: SigningPurpose,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignResponse {
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeysResponse {
+    pub keys: Vec<String>,
+}
+
+/// Client for a remote signing service, authenticated in both directions with TLS certificates.
+pub struct RemoteSignerClient {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl RemoteSignerClient {
+    /// Builds the client when `REMOTE_SIGNER_URL` is set.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Some(base_url) = &config.remote_signer_url else {
+            return Ok(None);
+        };
+        let (ca_path, cert_path, key_path) = match (
+            &config.remote_signer_ca_cert,
+            &config.remote_signer_client_cert,
+            &config.remote_signer_client_key,
+        ) {
+            (Some(ca), Some(cert), Some(key)) => (ca, cert, key),
+            _ => return Err(anyhow!(
+                "REMOTE_SIGNER_CA_CERT, REMOTE_SIGNER_CLIENT_CERT and REMOTE_SIGNER_CLIENT_KEY must be set with REMOTE_SIGNER_URL"
+            )),
+        };
+
+        let ca = std::fs::read(ca_path).with_context(|| format!("Failed to read {}", ca_path))?;
+        let mut identity = std::fs::read(cert_path).with_context(|| format!("Failed to read {}", cert_path))?;
+        identity.extend(std::fs::read(key_path).with_context(|| format!("Failed to read {}", key_path))?);
+
+        let client = reqwest::Client::builder()
+            .use_rustls_tls()
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(reqwest::Certificate::from_pem(&ca)?)
+            .identity(reqwest::Identity::from_pem(&identity)?)
+            .timeout(Duration::from_secs(10))
+            .build()?;
+
+        Ok(Some(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }))
+    }
+
+    pub async fn keys(&self) -> Result<Vec<Pubkey>> {
+        let response = self
+            .client
+            .get(format!("{}/keys", self.base_url))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<KeysResponse>()
+            .await?;
+        response
+            .keys
+            .iter()
+            .map(|key| Pubkey::from_str(key).map_err(|e| anyhow!("Remote signer listed an invalid key: {}", e)))
+            .collect()
+    }
+
+    pub async fn sign(&self, pubkey: &Pubkey, message: &VersionedMessage, purpose: SigningPurpose) -> Result<Signature> {
+        let bytes = message.serialize();
+        let request = SignRequest {
+            pubkey: pubkey.to_string(),
+            message: BASE64.encode(&bytes),
+            purpose,
+        };
+        let response = self.client.post(format!("{}/sign", self.base_url)).json(&request).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let reason = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Remote signer refused to sign ({}): {}", status, reason));
+        }
+
+        let signature = Signature::from_str(&response.json::<SignResponse>().await?.signature)?;
+        // Never hand the network a signature that is not the key's over this exact message
+        if !signature.verify(pubkey.as_ref(), &bytes) {
+            return Err(anyhow!("Remote signer returned an invalid signature for {}", pubkey));
+        }
+        Ok(signature)
+    }
+}
+
+/// A key held by the remote signing service.
+pub struct RemoteSigner {
+    client: Arc<RemoteSignerClient>,
+    pubkey: Pubkey,
+}
+
+#[async_trait]
+impl TransactionSigner for RemoteSigner {
+    fn pubkey(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    async fn sign_message(&self, message: &VersionedMessage, purpose: SigningPurpose) -> Result<Signature> {
+        self.client.sign(&self.pubkey, message, purpose).await
+    }
+}
+
+/// Rules the remote signer applies to every request before signing it.
+#[derive(Debug, Clone)]
+pub struct SigningPolicy {
+    pub allowed_purposes: Vec<SigningPurpose>,
+    pub allowed_programs: Vec<Pubkey>,
+    // Highest compute unit price, in micro-lamports, a transaction may pay
+    pub max_compute_unit_price: Option<u64>,
+    // Where SOL and token transfers may go, besides the signing key itself; token transfers may
+    // also go to these owners' associated token accounts
+    pub allowed_destinations: Vec<Pubkey>,
+}
+
+impl SigningPolicy {
+    pub fn check(&self, signer: &Pubkey, message: &VersionedMessage, purpose: SigningPurpose) -> Result<(), String> {
+        if !self.allowed_purposes.contains(&purpose) {
+            return Err(format!("Signing for {} is not allowed", purpose.as_str()));
+        }
+
+        let keys = message.static_account_keys();
+        if keys.first() != Some(signer) {
+            return Err(format!("{} must pay the transaction's fees", signer));
+        }
+
+        // Invoked programs are always static keys, never loaded from lookup tables
+        for instruction in message.instructions() {
+            let program = keys
+                .get(instruction.program_id_index as usize)
+                .ok_or_else(|| "Instruction references a missing program".to_string())?;
+            if !self.allowed_programs.contains(program) {
+                return Err(format!("Program {} is not allowed", program));
+            }
+
+            if *program == compute_budget::id() && instruction.data.first() == Some(&SET_COMPUTE_UNIT_PRICE_TAG) {
+                let price = instruction
+                    .data
+                    .get(1..9)
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .map(u64::from_le_bytes)
+                    .ok_or_else(|| "Malformed compute unit price".to_string())?;
+                if let Some(max) = self.max_compute_unit_price {
+                    if price > max {
+                        return Err(format!("Compute unit price {} exceeds the limit of {}", price, max));
+                    }
+                }
+            }
+
+            if let Some((destination, mint)) = transfer_destination(keys, program, instruction)? {
+                if !self.destination_allowed(signer, &destination, mint.as_ref()) {
+                    return Err(format!("Transfers to {} are not allowed", destination));
+                }
+            }
+        }
+
+        // A swap invokes Jupiter and only the programs a Jupiter swap needs around it
+        if purpose == SigningPurpose::Swap {
+            for instruction in message.instructions() {
+                let program = keys[instruction.program_id_index as usize];
+                if !SWAP_PROGRAMS.iter().any(|id| Pubkey::from_str(id).ok() == Some(program)) {
+                    return Err(format!("Program {} may not be invoked by a swap", program));
+                }
+            }
+
+            let jupiter = Pubkey::from_str(JUPITER_PROGRAM_ID).map_err(|e| e.to_string())?;
+            let swaps = message
+                .instructions()
+                .iter()
+                .any(|instruction| keys.get(instruction.program_id_index as usize) == Some(&jupiter));
+            if !swaps {
+                return Err("A swap must invoke the Jupiter aggregator".to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The signer itself, an allowlisted destination, or an associated token account of either.
+    /// `mint` narrows the token accounts checked when the instruction names it.
+    fn destination_allowed(&self, signer: &Pubkey, destination: &Pubkey, mint: Option<&Pubkey>) -> bool {
+        let owners = || std::iter::once(signer).chain(self.allowed_destinations.iter());
+        if owners().any(|owner| owner == destination) {
+            return true;
+        }
+
+        let mints: Vec<Pubkey> = match mint {
+            Some(mint) => vec![*mint],
+            None => KNOWN_TOKENS.iter().filter_map(|t| Pubkey::from_str(t.mint).ok()).collect(),
+        };
+        owners().any(|owner| mints.iter().any(|mint| associated_token_address(owner, mint) == *destination))
+    }
+}
+
+/// Account an instruction moves SOL or tokens to, with the mint when the instruction names it.
+/// `None` for instructions that are not transfers.
+fn transfer_destination(
+    keys: &[Pubkey],
+    program: &Pubkey,
+    instruction: &CompiledInstruction,
+) -> Result<Option<(Pubkey, Option<Pubkey>)>, String> {
+    let is_token_program = [SPL_TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID]
+        .iter()
+        .any(|id| Pubkey::from_str(id).ok().as_ref() == Some(program));
+
+    let (destination, mint) = if *program == system_program::id() {
+        let tag = instruction.data.get(..4).and_then(|bytes| bytes.try_into().ok()).map(u32::from_le_bytes);
+        match tag {
+            Some(SYSTEM_TRANSFER_TAG) => (1, None),
+            Some(SYSTEM_TRANSFER_WITH_SEED_TAG) => (2, None),
+            _ => return Ok(None),
+        }
+    } else if is_token_program {
+        match instruction.data.first() {
+            Some(&TOKEN_TRANSFER_TAG) => (1, None),
+            Some(&TOKEN_TRANSFER_CHECKED_TAG) => (2, Some(1)),
+            _ => return Ok(None),
+        }
+    } else {
+        return Ok(None);
+    };
+
+    // Accounts loaded from lookup tables cannot be checked without fetching the tables
+    let account = |position: usize| -> Result<Pubkey, String> {
+        instruction
+            .accounts
+            .get(position)
+            .and_then(|index| keys.get(*index as usize))
+            .copied()
+            .ok_or_else(|| "Transfer accounts must be static keys".to_string())
+    };
+    Ok(Some((account(destination)?, mint.map(account).transpose()?)))
+}
+
+/// Finds the signer for a wallet among the keystore, the remote signer and the configured
+/// trading keypair, in that order.
+pub struct Signers {
+    keystore: Arc<Keystore>,
+    remote: Option<Arc<RemoteSignerClient>>,
+    // Keys the remote signer holds, and when they were listed
+    remote_keys: Mutex<Option<(Instant, Vec<Pubkey>)>>,
+    trading_keypair_path: Option<String>,
+}
+
+impl Signers {
+    pub fn new(keystore: Arc<Keystore>, config: &Config) -> Result<Self> {
+        Ok(Self {
+            keystore,
+            remote: RemoteSignerClient::from_config(config)?.map(Arc::new),
+            remote_keys: Mutex::new(None),
+            trading_keypair_path: config.trading_keypair_path.clone(),
+        })
+    }
+
+    pub async fn for_wallet(&self, wallet: &Wallet) -> Result<Arc<dyn TransactionSigner>, ServiceError> {
+        if let Some(keypair) = self.keystore.keypair(wallet).await? {
+            return Ok(Arc::new(LocalSigner::new(keypair)));
+        }
+
+        let pubkey = Pubkey::from_str(&wallet.address)
+            .map_err(|_| ServiceError::BadRequest("Invalid Solana address".into()))?;
+
+        if let Some(remote) = &self.remote {
+            if self.remote_holds(remote, &pubkey).await? {
+                return Ok(Arc::new(RemoteSigner { client: remote.clone(), pubkey }));
+            }
+        }
+
+        if let Some(path) = &self.trading_keypair_path {
+            let keypair = read_keypair_file(path)
+                .map_err(|e| ServiceError::InternalServerError(format!("Failed to read trading keypair: {}", e)))?;
+            if keypair.pubkey() == pubkey {
+                return Ok(Arc::new(LocalSigner::new(keypair)));
+            }
+        }
+
+        Err(ServiceError::BadRequest("Wallet cannot be signed for by this backend; import its key first".into()))
+    }
+
+    /// Whether the remote signer holds `pubkey`, from its key list as of at most `REMOTE_KEYS_TTL` ago.
+    async fn remote_holds(&self, remote: &RemoteSignerClient, pubkey: &Pubkey) -> Result<bool, ServiceError> {
+        let mut cached = self.remote_keys.lock().await;
+        if !cached.as_ref().map_or(false, |(listed_at, _)| listed_at.elapsed() < REMOTE_KEYS_TTL) {
+            let keys = remote.keys().await
+                .map_err(|e| ServiceError::InternalServerError(format!("Failed to reach the remote signer: {}", e)))?;
+            *cached = Some((Instant::now(), keys));
+        }
+        Ok(cached.as_ref().map_or(false, |(_, keys)| keys.contains(pubkey)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::compute_budget::ComputeBudgetInstruction;
+    use solana_sdk::instruction::{AccountMeta, Instruction};
+    use solana_sdk::message::Message;
+    use solana_sdk::system_instruction;
+
+    const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn policy(allowed_destinations: Vec<Pubkey>) -> SigningPolicy {
+        SigningPolicy {
+            allowed_purposes: vec![SigningPurpose::Swap, SigningPurpose::Transfer],
+            allowed_programs: DEFAULT_ALLOWED_PROGRAMS.iter().map(|p| Pubkey::from_str(p).unwrap()).collect(),
+            max_compute_unit_price: Some(10_000),
+            allowed_destinations,
+        }
+    }
+
+    fn message(payer: &Pubkey, instructions: &[Instruction]) -> VersionedMessage {
+        VersionedMessage::Legacy(Message::new(instructions, Some(payer)))
+    }
+
+    fn jupiter_swap(user: &Pubkey) -> Instruction {
+        Instruction::new_with_bytes(Pubkey::from_str(JUPITER_PROGRAM_ID).unwrap(), &[1], vec![AccountMeta::new(*user, true)])
+    }
+
+    fn token_transfer_checked(owner: &Pubkey, mint: &Pubkey, destination: &Pubkey) -> Instruction {
+        let mut data = vec![TOKEN_TRANSFER_CHECKED_TAG];
+        data.extend_from_slice(&1_000_000u64.to_le_bytes());
+        data.push(6);
+        Instruction::new_with_bytes(
+            Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap(),
+            &data,
+            vec![
+                AccountMeta::new(associated_token_address(owner, mint), false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(*owner, true),
+            ],
+        )
+    }
+
+    fn token_transfer(owner: &Pubkey, destination: &Pubkey) -> Instruction {
+        let mut data = vec![TOKEN_TRANSFER_TAG];
+        data.extend_from_slice(&1_000_000u64.to_le_bytes());
+        Instruction::new_with_bytes(
+            Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap(),
+            &data,
+            vec![
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(*owner, true),
+            ],
+        )
+    }
+
+    #[test]
+    fn swap_through_jupiter_is_signed() {
+        let signer = Pubkey::new_unique();
+        let swap = message(&signer, &[ComputeBudgetInstruction::set_compute_unit_price(5_000), jupiter_swap(&signer)]);
+        assert_eq!(policy(vec![]).check(&signer, &swap, SigningPurpose::Swap), Ok(()));
+    }
+
+    #[test]
+    fn swap_must_invoke_jupiter() {
+        let signer = Pubkey::new_unique();
+        let transfer = message(&signer, &[system_instruction::transfer(&signer, &signer, 1)]);
+        assert!(policy(vec![]).check(&signer, &transfer, SigningPurpose::Swap).is_err());
+    }
+
+    #[test]
+    fn swap_may_not_invoke_other_allowed_programs() {
+        let signer = Pubkey::new_unique();
+        let openbook = Instruction::new_with_bytes(
+            Pubkey::from_str(OPENBOOK_V2_PROGRAM_ID).unwrap(),
+            &[],
+            vec![AccountMeta::new(signer, true)],
+        );
+        let swap = message(&signer, &[jupiter_swap(&signer), openbook]);
+        assert!(policy(vec![]).check(&signer, &swap, SigningPurpose::Swap).is_err());
+    }
+
+    #[test]
+    fn purposes_outside_the_policy_are_refused() {
+        let signer = Pubkey::new_unique();
+        let swap = message(&signer, &[jupiter_swap(&signer)]);
+        assert!(policy(vec![]).check(&signer, &swap, SigningPurpose::PlaceOrder).is_err());
+    }
+
+    #[test]
+    fn signer_must_pay_the_fees() {
+        let (signer, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let swap = message(&payer, &[jupiter_swap(&signer)]);
+        assert!(policy(vec![]).check(&signer, &swap, SigningPurpose::Swap).is_err());
+    }
+
+    #[test]
+    fn unknown_programs_are_refused() {
+        let signer = Pubkey::new_unique();
+        let unknown = Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![AccountMeta::new(signer, true)]);
+        let swap = message(&signer, &[unknown, jupiter_swap(&signer)]);
+        assert!(policy(vec![]).check(&signer, &swap, SigningPurpose::Swap).is_err());
+    }
+
+    #[test]
+    fn compute_unit_price_is_capped() {
+        let signer = Pubkey::new_unique();
+        let swap = message(&signer, &[ComputeBudgetInstruction::set_compute_unit_price(20_000), jupiter_swap(&signer)]);
+        assert!(policy(vec![]).check(&signer, &swap, SigningPurpose::Swap).is_err());
+    }
+
+    #[test]
+    fn sol_only_goes_to_the_signer_or_allowed_destinations() {
+        let (signer, allowed, stranger) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let policy = policy(vec![allowed]);
+        let to = |destination: &Pubkey| message(&signer, &[system_instruction::transfer(&signer, destination, 1)]);
+
+        assert_eq!(policy.check(&signer, &to(&signer), SigningPurpose::Transfer), Ok(()));
+        assert_eq!(policy.check(&signer, &to(&allowed), SigningPurpose::Transfer), Ok(()));
+        assert!(policy.check(&signer, &to(&stranger), SigningPurpose::Transfer).is_err());
+    }
+
+    #[test]
+    fn tokens_only_go_to_associated_accounts_of_allowed_owners() {
+        let (signer, allowed, stranger) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mint = Pubkey::from_str(USDC_MINT).unwrap();
+        let policy = policy(vec![allowed]);
+
+        let to_allowed = message(&signer, &[token_transfer_checked(&signer, &mint, &associated_token_address(&allowed, &mint))]);
+        assert_eq!(policy.check(&signer, &to_allowed, SigningPurpose::Transfer), Ok(()));
+
+        let to_stranger = message(&signer, &[token_transfer_checked(&signer, &mint, &associated_token_address(&stranger, &mint))]);
+        assert!(policy.check(&signer, &to_stranger, SigningPurpose::Transfer).is_err());
+
+        // The account of a different mint than the one named does not count
+        let other = Pubkey::new_unique();
+        let wrong_mint = message(&signer, &[token_transfer_checked(&signer, &mint, &associated_token_address(&allowed, &other))]);
+        assert!(policy.check(&signer, &wrong_mint, SigningPurpose::Transfer).is_err());
+    }
+
+    #[test]
+    fn plain_token_transfers_are_checked_against_known_mints() {
+        let (signer, allowed) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mint = Pubkey::from_str(USDC_MINT).unwrap();
+        let policy = policy(vec![allowed]);
+
+        let known = message(&signer, &[token_transfer(&signer, &associated_token_address(&allowed, &mint))]);
+        assert_eq!(policy.check(&signer, &known, SigningPurpose::Transfer), Ok(()));
+
+        let unknown = message(&signer, &[token_transfer(&signer, &Pubkey::new_unique())]);
+        assert!(policy.check(&signer, &unknown, SigningPurpose::Transfer).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use mongodb::Database;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{UiTransactionStatusMeta, UiTransactionTokenBalance};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::settings::Settings;
+use crate::models::strategy::Strategy;
+use crate::services::aggregator::{SwapMode, SwapQuote};
+use crate::utils::errors::ServiceError;
+use crate::utils::tokens::{TokenInfo, NATIVE_MINT};
+
+const BPS: f64 = 10_000.0;
+
+pub fn validate_max_slippage(max_slippage_bps: u16) -> Result<(), ServiceError> {
+    if max_slippage_bps as f64 > BPS {
+        return Err(ServiceError::BadRequest("Maximum slippage cannot exceed 10000 bps".into()));
+    }
+    Ok(())
+}
+
+/// Worst slippage a trade may accept. The strategy's limit wins over the user's, which wins
+/// over the configured default; a limit on the trade itself can only tighten the result.
+pub async fn max_slippage_bps(
+    db: &Database,
+    config: &Config,
+    user_id: Uuid,
+    strategy: Option<&Strategy>,
+    requested: Option<u16>,
+) -> Result<u16, ServiceError> {
+    let limit = match strategy.and_then(|s| s.max_slippage_bps) {
+        Some(bps) => bps,
+        None => Settings::find_by_user(db, user_id)
+            .await?
+            .and_then(|s| s.max_slippage_bps)
+            .unwrap_or(config.default_slippage_bps),
+    };
+
+    match requested {
+        Some(bps) => {
+            validate_max_slippage(bps)?;
+            Ok(bps.min(limit))
+        }
+        None => Ok(limit),
+    }
+}
+
+/// Rejects a quote whose price impact already exceeds the limit, or whose minimum-out
+/// (maximum-in for exact-out swaps) amount would let the swap slip further than it.
+pub fn check_quote(quote: &SwapQuote, swap_mode: SwapMode, max_slippage_bps: u16) -> Result<(), ServiceError> {
+    let limit = max_slippage_bps as f64 / BPS;
+    let impact = quote.price_impact_pct.abs() / 100.0;
+    if impact > limit {
+        return Err(ServiceError::BadRequest(format!(
+            "Quoted price impact of {:.2} bps exceeds the {} bps slippage limit",
+            impact * BPS,
+            max_slippage_bps
+        )));
+    }
+
+    let within_limit = match swap_mode {
+        SwapMode::ExactIn => quote.other_amount_threshold as f64 >= (quote.out_amount as f64 * (1.0 - limit)).floor(),
+        SwapMode::ExactOut => quote.other_amount_threshold as f64 <= (quote.in_amount as f64 * (1.0 + limit)).ceil(),
+    };
+    if !within_limit {
+        return Err(ServiceError::BadRequest(format!(
+            "The aggregator's swap allows more than the {} bps slippage limit",
+            max_slippage_bps
+        )));
+    }
+
+    Ok(())
+}
+
+/// How much of `token` the owner's accounts gained (negative when they lost it) in a landed
+/// transaction, from the balances recorded in its metadata.
+pub fn balance_change(meta: &UiTransactionStatusMeta, owner: &Pubkey, token: &TokenInfo) -> f64 {
+    let scale = 10f64.powi(token.decimals as i32);
+
+    if token.mint == NATIVE_MINT {
+        // SOL is wrapped and unwrapped within the swap, so it moves as lamports. The owner
+        // pays for the transaction and is its first account; the fee is not part of the trade.
+        return match (meta.pre_balances.first(), meta.post_balances.first()) {
+            (Some(pre), Some(post)) => (*post as f64 - *pre as f64 + meta.fee as f64) / scale,
+            _ => 0.0,
+        };
+    }
+
+    let owner = owner.to_string();
+    let held = |balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>| -> f64 {
+        match balances {
+            OptionSerializer::Some(balances) => balances
+                .iter()
+                .filter(|b| b.mint == token.mint && matches!(&b.owner, OptionSerializer::Some(o) if *o == owner))
+                .filter_map(|b| b.ui_token_amount.amount.parse::<u64>().ok())
+                .map(|amount| amount as f64)
+                .sum(),
+            _ => 0.0,
+        }
+    };
+
+    (held(&meta.post_token_balances) - held(&meta.pre_token_balances)) / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const USDC: TokenInfo = TokenInfo { symbol: "USDC", mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", decimals: 6 };
+    const SOL: TokenInfo = TokenInfo { symbol: "SOL", mint: NATIVE_MINT, decimals: 9 };
+
+    fn quote(in_amount: u64, out_amount: u64, other_amount_threshold: u64, price_impact_pct: f64) -> SwapQuote {
+        SwapQuote {
+            input_mint: NATIVE_MINT.to_string(),
+            output_mint: USDC.mint.to_string(),
+            in_amount,
+            out_amount,
+            price_impact_pct,
+            slippage_bps: 50,
+            other_amount_threshold,
+            raw: serde_json::Value::Null,
+        }
+    }
+
+    #[test]
+    fn exact_in_minimum_out_must_stay_within_the_limit() {
+        // 50 bps below 1_000_000 is 995_000
+        assert!(check_quote(&quote(1_000, 1_000_000, 995_000, 0.0), SwapMode::ExactIn, 50).is_ok());
+        assert!(check_quote(&quote(1_000, 1_000_000, 994_999, 0.0), SwapMode::ExactIn, 50).is_err());
+    }
+
+    #[test]
+    fn exact_out_maximum_in_must_stay_within_the_limit() {
+        assert!(check_quote(&quote(1_000_000, 1_000, 1_005_000, 0.0), SwapMode::ExactOut, 50).is_ok());
+        assert!(check_quote(&quote(1_000_000, 1_000, 1_005_001, 0.0), SwapMode::ExactOut, 50).is_err());
+    }
+
+    #[test]
+    fn price_impact_beyond_the_limit_is_rejected() {
+        // Impact is quoted in percent, 0.6% being 60 bps
+        assert!(check_quote(&quote(1_000, 1_000_000, 1_000_000, 0.4), SwapMode::ExactIn, 50).is_ok());
+        assert!(check_quote(&quote(1_000, 1_000_000, 1_000_000, 0.6), SwapMode::ExactIn, 50).is_err());
+        assert!(check_quote(&quote(1_000, 1_000_000, 1_000_000, -0.6), SwapMode::ExactIn, 50).is_err());
+    }
+
+    fn token_balance(mint: &str, owner: &Pubkey, amount: u64) -> serde_json::Value {
+        json!({
+            "accountIndex": 1,
+            "mint": mint,
+            "uiTokenAmount": {
+                "uiAmount": null,
+                "decimals": 6,
+                "amount": amount.to_string(),
+                "uiAmountString": "",
+            },
+            "owner": owner.to_string(),
+        })
+    }
+
+    fn meta(
+        fee: u64,
+        balances: (u64, u64),
+        pre_token_balances: Vec<serde_json::Value>,
+        post_token_balances: Vec<serde_json::Value>,
+    ) -> UiTransactionStatusMeta {
+        serde_json::from_value(json!({
+            "err": null,
+            "status": { "Ok": null },
+            "fee": fee,
+            "preBalances": [balances.0],
+            "postBalances": [balances.1],
+            "preTokenBalances": pre_token_balances,
+            "postTokenBalances": post_token_balances,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn token_change_counts_only_the_owners_accounts_of_that_mint() {
+        let (owner, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let meta = meta(
+            5_000,
+            (10_000_000, 10_000_000),
+            vec![token_balance(USDC.mint, &owner, 1_000_000), token_balance(USDC.mint, &other, 9_000_000)],
+            vec![
+                token_balance(USDC.mint, &owner, 3_500_000),
+                token_balance(USDC.mint, &other, 6_500_000),
+                token_balance("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB", &owner, 7_000_000),
+            ],
+        );
+        assert!((balance_change(&meta, &owner, &USDC) - 2.5).abs() < 1e-12);
+        assert!((balance_change(&meta, &other, &USDC) + 2.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sol_change_leaves_out_the_transaction_fee() {
+        let owner = Pubkey::new_unique();
+        // Spent 1 SOL on the swap and 5_000 lamports on the fee
+        let meta = meta(5_000, (3_000_000_000, 1_999_995_000), vec![], vec![]);
+        assert!((balance_change(&meta, &owner, &SOL) + 1.0).abs() < 1e-12);
+    }
+}
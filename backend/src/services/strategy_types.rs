@@ -0,0 +1,183 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::models::strategy::{
+    AvellanedaStoikovParams, FixedSpreadParams, GridParams, InventorySkewedParams, StrategyKind, StrategyParams,
+};
+use crate::utils::errors::ServiceError;
+
+/// A strategy kind as presented to clients, with a JSON schema for its parameter block.
+#[derive(Debug, Serialize)]
+pub struct StrategyTypeInfo {
+    pub kind: StrategyKind,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub schema: Value,
+}
+
+pub const STRATEGY_KINDS: &[StrategyKind] = &[
+    StrategyKind::FixedSpread,
+    StrategyKind::Grid,
+    StrategyKind::InventorySkewed,
+    StrategyKind::AvellanedaStoikov,
+];
+
+pub fn strategy_types() -> Vec<StrategyTypeInfo> {
+    STRATEGY_KINDS.iter().map(|kind| describe(*kind)).collect()
+}
+
+fn describe(kind: StrategyKind) -> StrategyTypeInfo {
+    match kind {
+        StrategyKind::FixedSpread => StrategyTypeInfo {
+            kind,
+            name: "Fixed Spread",
+            description: "Quotes a bid and an ask a fixed distance either side of the mid price.",
+            schema: object_schema(
+                json!({
+                    "spread_bps": number("Distance between bid and ask in basis points; defaults to the server setting", Some(0.0)),
+                }),
+                &[],
+            ),
+        },
+        StrategyKind::Grid => StrategyTypeInfo {
+            kind,
+            name: "Grid",
+            description: "Keeps a ladder of buy and sell orders between two prices and replaces each level as it fills.",
+            schema: object_schema(
+                json!({
+                    "lower_price": number("Lowest grid price", Some(0.0)),
+                    "upper_price": number("Highest grid price", Some(0.0)),
+                    "levels": integer("Number of price levels in the grid", 2),
+                    "order_size": number("Size of the order at each level, in the base token", Some(0.0)),
+                }),
+                &["lower_price", "upper_price", "levels", "order_size"],
+            ),
+        },
+        StrategyKind::InventorySkewed => StrategyTypeInfo {
+            kind,
+            name: "Inventory Skewed",
+            description: "Quotes around the mid price, leaning away from the side the strategy is already long or short.",
+            schema: object_schema(
+                json!({
+                    "spread_bps": number("Distance between bid and ask in basis points", Some(0.0)),
+                    "target_inventory": number("Base token position to hold", None),
+                    "max_inventory": number("Distance from the target at which the growing side stops quoting", Some(0.0)),
+                    "skew_bps": number("Shift of the quote centre at full inventory, in basis points", Some(0.0)),
+                }),
+                &["spread_bps", "target_inventory", "max_inventory", "skew_bps"],
+            ),
+        },
+        StrategyKind::AvellanedaStoikov => StrategyTypeInfo {
+            kind,
+            name: "Avellaneda–Stoikov",
+            description: "Quotes around a reservation price that moves against inventory, with a spread set by volatility and risk aversion.",
+            schema: object_schema(
+                json!({
+                    "risk_aversion": number("Risk aversion γ; higher values carry less inventory and quote wider", Some(0.0)),
+                    "horizon_secs": integer("Horizon τ over which inventory risk is priced, in seconds", 1),
+                    "order_arrival_intensity": number("Order arrival intensity κ; higher values mean fills drop off faster away from the mid", Some(0.0)),
+                    "volatility_window": integer("Mid price samples used to estimate volatility", 3),
+                    "target_inventory": number("Base token position to hold", None),
+                }),
+                &["risk_aversion", "horizon_secs", "order_arrival_intensity"],
+            ),
+        },
+    }
+}
+
+fn object_schema(properties: Value, required: &[&str]) -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn number(description: &str, exclusive_minimum: Option<f64>) -> Value {
+    let mut schema = json!({ "type": "number", "description": description });
+    if let Some(minimum) = exclusive_minimum {
+        schema["exclusiveMinimum"] = json!(minimum);
+    }
+    schema
+}
+
+fn integer(description: &str, minimum: u64) -> Value {
+    json!({ "type": "integer", "description": description, "minimum": minimum })
+}
+
+/// Parses and validates the parameter block submitted for `kind`.
+pub fn parse_params(kind: StrategyKind, params: Option<Value>) -> Result<StrategyParams, ServiceError> {
+    let params = params.unwrap_or_else(|| json!({}));
+    let invalid = |e: serde_json::Error| {
+        ServiceError::BadRequest(format!("Invalid parameters for {} strategy: {}", kind.as_str(), e))
+    };
+
+    let parsed = match kind {
+        StrategyKind::FixedSpread => {
+            StrategyParams::FixedSpread(serde_json::from_value::<FixedSpreadParams>(params).map_err(invalid)?)
+        }
+        StrategyKind::Grid => StrategyParams::Grid(serde_json::from_value::<GridParams>(params).map_err(invalid)?),
+        StrategyKind::InventorySkewed => {
+            StrategyParams::InventorySkewed(serde_json::from_value::<InventorySkewedParams>(params).map_err(invalid)?)
+        }
+        StrategyKind::AvellanedaStoikov => StrategyParams::AvellanedaStoikov(
+            serde_json::from_value::<AvellanedaStoikovParams>(params).map_err(invalid)?,
+        ),
+    };
+
+    validate_params(&parsed)?;
+    Ok(parsed)
+}
+
+pub fn validate_params(params: &StrategyParams) -> Result<(), ServiceError> {
+    let error = match params {
+        StrategyParams::FixedSpread(p) => match p.spread_bps {
+            Some(spread) if spread <= 0.0 => Some("Spread must be positive"),
+            _ => None,
+        },
+        StrategyParams::Grid(p) => {
+            if p.lower_price <= 0.0 {
+                Some("Grid lower price must be positive")
+            } else if p.upper_price <= p.lower_price {
+                Some("Grid upper price must be above the lower price")
+            } else if p.levels < 2 {
+                Some("A grid needs at least 2 levels")
+            } else if p.order_size <= 0.0 {
+                Some("Grid order size must be positive")
+            } else {
+                None
+            }
+        }
+        StrategyParams::InventorySkewed(p) => {
+            if p.spread_bps <= 0.0 {
+                Some("Spread must be positive")
+            } else if p.max_inventory <= 0.0 {
+                Some("Maximum inventory must be positive")
+            } else if p.skew_bps < 0.0 {
+                Some("Skew cannot be negative")
+            } else {
+                None
+            }
+        }
+        StrategyParams::AvellanedaStoikov(p) => {
+            if p.risk_aversion <= 0.0 {
+                Some("Risk aversion must be positive")
+            } else if p.horizon_secs == 0 {
+                Some("Horizon must be positive")
+            } else if p.order_arrival_intensity <= 0.0 {
+                Some("Order arrival intensity must be positive")
+            } else if p.volatility_window < 3 {
+                Some("Volatility window needs at least 3 samples")
+            } else {
+                None
+            }
+        }
+    };
+
+    match error {
+        Some(message) => Err(ServiceError::BadRequest(message.into())),
+        None => Ok(()),
+    }
+}
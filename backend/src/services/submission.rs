@@ -0,0 +1,278 @@
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use mongodb::Database;
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::message::{Message, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::transaction::{SubmissionAttempt, Transaction, TransactionStatus};
+use crate::services::confirmations::{landed_meta, observed_status};
+use crate::services::priority_fees::{with_compute_budget, with_priority_fee, ComputeBudget, PriorityFeePolicy};
+use crate::services::signer::{sign_transaction, SigningPurpose, TransactionSigner};
+use crate::utils::errors::ServiceError;
+use crate::utils::tokens::{to_base_units, transfer_instructions, TokenInfo};
+
+// How long the confirmation tracker leaves an attempt's expiry to the submitter; comfortably
+// longer than a blockhash stays valid
+const RETRY_LEASE_SECS: i64 = 180;
+
+/// How broadcasting one signed attempt ended.
+#[derive(Debug)]
+enum Broadcast {
+    Landed(Landed),
+    // Can no longer land
+    Expired,
+    // The RPC node could not tell us in time whether it landed
+    Unknown,
+}
+
+/// A submission that made it on-chain, successfully or not.
+#[derive(Debug)]
+pub struct Landed {
+    pub signature: Signature,
+    pub status: TransactionStatus,
+    pub error: Option<String>,
+}
+
+/// Sends `message` for the stored transaction `transaction_id`, which must be `Simulated`.
+///
+/// Each attempt is signed with a fresh blockhash and rebroadcast until it lands or the
+/// finalized block height passes its last valid height. Only then is the next attempt
+/// signed, so no two attempts can both land. Gives up with `RetriesExhausted` after
+/// `config.submit_max_attempts`, leaving the transaction `Expired`. An attempt whose fate is
+/// still unknown when its retry lease runs out ends with `StatusUnknown`, leaving the transaction
+/// `Submitted` for the confirmation tracker.
+pub async fn submit_with_retry(
+    db: &Database,
+    config: &Config,
+    rpc: &RpcClient,
+    transaction_id: Uuid,
+    signer: &dyn TransactionSigner,
+    purpose: SigningPurpose,
+    message: VersionedMessage,
+    budget: ComputeBudget,
+    fees: &PriorityFeePolicy,
+) -> Result<Landed, ServiceError> {
+    let max_attempts = config.submit_max_attempts.max(1);
+    let interval = Duration::from_millis(config.rebroadcast_interval_ms.max(100));
+    let mut message = message;
+    let mut budget = budget;
+    let mut status = TransactionStatus::Simulated;
+    let mut last_error = String::new();
+
+    for attempt in 1..=max_attempts {
+        if attempt > 1 && config.resubmit_fee_bump_pct > 0.0 {
+            budget.unit_price = bumped_price(budget.unit_price, config.resubmit_fee_bump_pct, fees);
+            message = with_compute_budget(message, &budget);
+        }
+
+        let (blockhash, last_valid_block_height) = match rpc.get_latest_blockhash_with_commitment(CommitmentConfig::confirmed()).await {
+            Ok(latest) => latest,
+            Err(e) => {
+                last_error = format!("Failed to fetch a blockhash: {}", e);
+                break;
+            }
+        };
+        message.set_recent_blockhash(blockhash);
+        let signed = match sign_transaction(signer, message.clone(), purpose).await {
+            Ok(signed) => signed,
+            Err(e) => {
+                last_error = format!("Failed to sign transaction: {}", e);
+                break;
+            }
+        };
+        let signature = signed.signatures[0];
+
+        let record = SubmissionAttempt {
+            attempt,
+            signature: signature.to_string(),
+            last_valid_block_height,
+            compute_unit_price: budget.unit_price,
+            sent_at: Utc::now(),
+        };
+        let lease_until = Utc::now() + chrono::Duration::seconds(RETRY_LEASE_SECS);
+        Transaction::record_attempt(db, transaction_id, &record, lease_until).await?;
+        if status == TransactionStatus::Simulated {
+            Transaction::transition(db, transaction_id, TransactionStatus::Simulated, TransactionStatus::Submitted, None).await?;
+            status = TransactionStatus::Submitted;
+        }
+
+        match broadcast_until_expired(rpc, &signed, last_valid_block_height, interval, lease_until).await {
+            Broadcast::Landed(landed) => return Ok(landed),
+            Broadcast::Expired => {}
+            Broadcast::Unknown => {
+                warn!("Transaction {} attempt {} has an unknown status, leaving it to the confirmation tracker", transaction_id, attempt);
+                return Err(ServiceError::StatusUnknown { signature: signature.to_string() });
+            }
+        }
+        info!("Transaction {} attempt {} expired without landing", transaction_id, attempt);
+        last_error = format!("Attempt {} expired at block height {}", attempt, last_valid_block_height);
+    }
+
+    let final_status = if status == TransactionStatus::Simulated {
+        TransactionStatus::Failed
+    } else {
+        TransactionStatus::Expired
+    };
+    Transaction::transition(db, transaction_id, status, final_status, Some(&last_error)).await?;
+    Err(ServiceError::RetriesExhausted { attempts: max_attempts, reason: last_error })
+}
+
+/// Sends `amount` of `token` from the signer's wallet to `recipient` for the stored transaction
+/// `transaction_id`, which must be `Built`. Returns the signature the transfer landed with.
+pub async fn submit_transfer(
+    db: &Database,
+    config: &Config,
+    user_id: Uuid,
+    transaction_id: Uuid,
+    signer: &dyn TransactionSigner,
+    recipient: &Pubkey,
+    token: &TokenInfo,
+    amount: f64,
+) -> Result<Signature, ServiceError> {
+    let owner = signer.pubkey();
+    let instructions = transfer_instructions(&owner, recipient, token, to_base_units(amount, token.decimals));
+    let message = VersionedMessage::Legacy(Message::new(&instructions, Some(&owner)));
+    let fee_policy = PriorityFeePolicy::for_user(db, user_id).await?;
+
+    let rpc = RpcClient::new(config.solana_rpc_url.clone());
+    let (message, budget) = match with_priority_fee(&rpc, message, &fee_policy).await {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            let error = e.to_string();
+            Transaction::transition(db, transaction_id, TransactionStatus::Built, TransactionStatus::Failed, Some(&error)).await?;
+            return Err(ServiceError::BadRequest(format!("Transfer simulation failed: {}", error)));
+        }
+    };
+    Transaction::set_compute_budget(db, transaction_id, budget.unit_limit, budget.unit_price).await?;
+    Transaction::transition(db, transaction_id, TransactionStatus::Built, TransactionStatus::Simulated, None).await?;
+
+    let landed = submit_with_retry(db, config, &rpc, transaction_id, signer, SigningPurpose::Transfer, message, budget, &fee_policy).await?;
+    if let Some(meta) = landed_meta(&rpc, &landed.signature).await {
+        Transaction::set_network_fee(db, transaction_id, meta.fee).await?;
+    }
+    if landed.status == TransactionStatus::Failed {
+        let error = landed.error.unwrap_or_default();
+        Transaction::transition(db, transaction_id, TransactionStatus::Submitted, TransactionStatus::Failed, Some(&error)).await?;
+        return Err(ServiceError::BadRequest(format!("Transfer failed on-chain: {}", error)));
+    }
+    Transaction::transition(db, transaction_id, TransactionStatus::Submitted, landed.status, None).await?;
+
+    Ok(landed.signature)
+}
+
+/// Resends `signed` every `interval` until it lands or can no longer land. Gives up with
+/// `Unknown` at `deadline`, when the confirmation tracker takes the transaction over, if the RPC
+/// node has not answered either way by then.
+async fn broadcast_until_expired(
+    rpc: &RpcClient,
+    signed: &VersionedTransaction,
+    last_valid_block_height: u64,
+    interval: Duration,
+    deadline: DateTime<Utc>,
+) -> Broadcast {
+    let signature = signed.signatures[0];
+    // The transaction was simulated already, and resending is our job rather than the node's
+    let send_config = RpcSendTransactionConfig {
+        skip_preflight: true,
+        max_retries: Some(0),
+        ..Default::default()
+    };
+
+    loop {
+        if let Err(e) = rpc.send_transaction_with_config(signed, send_config).await {
+            warn!("Failed to broadcast {}: {}", signature, e);
+        }
+        tokio::time::sleep(interval).await;
+
+        if let Ok(Some(landed)) = landed_status(rpc, &signature, false).await {
+            return Broadcast::Landed(landed);
+        }
+
+        // A block past the last valid height being finalized means no block can include it any more
+        match rpc.get_block_height_with_commitment(CommitmentConfig::finalized()).await {
+            Ok(height) if height > last_valid_block_height => break,
+            Ok(_) => {}
+            Err(e) => warn!("Failed to fetch the block height: {}", e),
+        }
+        if Utc::now() >= deadline {
+            return Broadcast::Unknown;
+        }
+    }
+
+    // It may have landed since the last check, in a block that is now finalized. Without a
+    // definite answer a new attempt could land alongside it, so keep asking until the deadline.
+    loop {
+        match landed_status(rpc, &signature, true).await {
+            Ok(Some(landed)) => return Broadcast::Landed(landed),
+            Ok(None) => return Broadcast::Expired,
+            Err(e) => warn!("Failed to fetch the status of {}: {}", signature, e),
+        }
+        if Utc::now() >= deadline {
+            return Broadcast::Unknown;
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+async fn landed_status(
+    rpc: &RpcClient,
+    signature: &Signature,
+    search_history: bool,
+) -> Result<Option<Landed>, ClientError> {
+    let response = if search_history {
+        rpc.get_signature_statuses_with_history(&[*signature]).await?
+    } else {
+        rpc.get_signature_statuses(&[*signature]).await?
+    };
+    Ok(response
+        .value
+        .into_iter()
+        .next()
+        .flatten()
+        .and_then(|status| observed_status(&status))
+        .map(|(status, error)| Landed { signature: *signature, status, error }))
+}
+
+fn bumped_price(unit_price: u64, bump_pct: f64, fees: &PriorityFeePolicy) -> u64 {
+    // Zero stays zero otherwise, and a bump that rounds away would make no difference
+    let bumped = ((unit_price.max(1) as f64) * (1.0 + bump_pct / 100.0)).ceil() as u64;
+    fees.capped(bumped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::settings::PriorityFeeLevel;
+
+    fn fees(level: PriorityFeeLevel, cap: Option<u64>) -> PriorityFeePolicy {
+        PriorityFeePolicy { level, cap }
+    }
+
+    #[test]
+    fn retries_bid_more_than_the_last_attempt() {
+        assert_eq!(bumped_price(1_000, 20.0, &fees(PriorityFeeLevel::Medium, None)), 1_200);
+        assert_eq!(bumped_price(999, 10.0, &fees(PriorityFeeLevel::Medium, None)), 1_099);
+    }
+
+    #[test]
+    fn small_prices_still_move_up() {
+        // Zero would stay zero and 10% of one unit would round away
+        assert_eq!(bumped_price(0, 20.0, &fees(PriorityFeeLevel::Low, None)), 2);
+        assert_eq!(bumped_price(1, 10.0, &fees(PriorityFeeLevel::Low, None)), 2);
+    }
+
+    #[test]
+    fn bumps_stop_at_the_custom_cap() {
+        assert_eq!(bumped_price(1_000, 20.0, &fees(PriorityFeeLevel::Custom, Some(1_100))), 1_100);
+        assert_eq!(bumped_price(1_000, 20.0, &fees(PriorityFeeLevel::High, Some(1_100))), 1_200);
+    }
+}
@@ -0,0 +1,221 @@
+use chrono::{TimeZone, Utc};
+use log::{error, info, warn};
+use mongodb::Database;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::alert_event::{AlertEvent, AlertEventKind};
+use crate::models::kill_switch::{KillSwitch, KillSwitchScope, KillSwitchTrigger};
+use crate::models::settings::Settings;
+use crate::models::strategy::Strategy;
+use crate::models::strategy_volume::StrategyVolume;
+use crate::models::top_up::TopUpUsage;
+use crate::models::transaction::{Transaction, TransactionStatus};
+use crate::models::wallet::{Wallet, WalletStatus};
+use crate::services::market_maker::MarketMakingEngine;
+use crate::services::signer::TransactionSigner;
+use crate::services::submission::submit_transfer;
+use crate::services::wallets::token_balance;
+use crate::utils::errors::ServiceError;
+use crate::utils::tokens::{token_by_mint, TokenInfo, NATIVE_MINT};
+
+// Wallets are topped up to this multiple of the minimum, so one top-up lasts more than a few trades
+const TOP_UP_TARGET_MULTIPLE: f64 = 2.0;
+
+/// Background monitor that keeps every wallet the backend signs for above the user's
+/// `min_wallet_balance` of SOL, topping it up from their treasury wallet. When the treasury
+/// cannot cover a top-up, the strategies trading from the wallet are paused and the user is alerted.
+pub struct TopUpMonitor {
+    db: Database,
+    config: Config,
+    engine: MarketMakingEngine,
+    rpc: RpcClient,
+}
+
+impl TopUpMonitor {
+    pub fn new(db: Database, config: Config, engine: MarketMakingEngine) -> Self {
+        Self {
+            rpc: RpcClient::new(config.solana_rpc_url.clone()),
+            db,
+            config,
+            engine,
+        }
+    }
+
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let interval = Duration::from_secs(self.config.top_up_interval_secs.max(1));
+            loop {
+                if let Err(e) = self.check_once().await {
+                    error!("Top-up monitor check failed: {}", e);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    async fn check_once(&self) -> Result<(), ServiceError> {
+        // Paper trading never spends SOL
+        if self.config.paper_trading {
+            return Ok(());
+        }
+
+        let sol = token_by_mint(NATIVE_MINT)
+            .ok_or_else(|| ServiceError::InternalServerError("SOL is not configured".into()))?;
+        for settings in Settings::find_all(&self.db).await? {
+            if settings.min_wallet_balance <= 0.0 {
+                continue;
+            }
+            if let Err(e) = self.check_user(&settings, &sol).await {
+                error!("Failed to check wallet balances of user {}: {}", settings.user_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn check_user(&self, settings: &Settings, sol: &TokenInfo) -> Result<(), ServiceError> {
+        let user_id = settings.user_id;
+        for wallet in Wallet::find_by_user(&self.db, user_id).await? {
+            if Some(wallet.id) == settings.treasury_wallet_id || wallet.status != WalletStatus::Active {
+                continue;
+            }
+            // Only wallets the backend signs for pay fees from here
+            if self.engine.signers().for_wallet(&wallet).await.is_err() {
+                continue;
+            }
+
+            let balance = token_balance(&self.rpc, &address(&wallet)?, sol).await?;
+            if balance >= settings.min_wallet_balance {
+                continue;
+            }
+
+            let amount = settings.min_wallet_balance * TOP_UP_TARGET_MULTIPLE - balance;
+            if let Err(reason) = self.top_up(settings, &wallet, sol, amount).await {
+                let message = format!(
+                    "Wallet {} holds {:.4} SOL, below the minimum of {:.4}, and could not be topped up: {}",
+                    wallet.address, balance, settings.min_wallet_balance, reason
+                );
+                self.shortfall(user_id, &wallet, &message).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends `amount` SOL from the user's treasury to `wallet` within the daily cap.
+    async fn top_up(&self, settings: &Settings, wallet: &Wallet, sol: &TokenInfo, amount: f64) -> Result<(), String> {
+        let user_id = settings.user_id;
+        let Some(treasury_id) = settings.treasury_wallet_id else {
+            return Err("no treasury wallet is set".into());
+        };
+        let treasury = Wallet::find_by_id(&self.db, treasury_id, user_id).await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "the treasury wallet no longer exists".to_string())?;
+        if treasury.status != WalletStatus::Active {
+            return Err(format!("the treasury wallet is {}", treasury.status.as_str()));
+        }
+        let signer = self.engine.signers().for_wallet(&treasury).await.map_err(|e| e.to_string())?;
+
+        // The treasury pays its own fees, so it keeps the same minimum
+        let available = token_balance(&self.rpc, &address(&treasury).map_err(|e| e.to_string())?, sol).await
+            .map_err(|e| e.to_string())?
+            - settings.min_wallet_balance;
+        if available < amount {
+            return Err(format!("the treasury has {:.4} SOL to spare, {:.4} is needed", available.max(0.0), amount));
+        }
+
+        let cap = settings.max_daily_top_up.unwrap_or(self.config.top_up_daily_cap);
+        let day = StrategyVolume::day_key(Utc::now());
+        if !TopUpUsage::reserve(&self.db, user_id, &day, amount, cap).await.map_err(|e| e.to_string())? {
+            return Err(format!("the daily top-up cap of {:.4} SOL has been reached", cap));
+        }
+
+        let result = self.send(user_id, &treasury, wallet, signer.as_ref(), sol, amount).await;
+        if let Err(e) = &result {
+            warn!("Top-up of wallet {} from treasury {} failed: {}", wallet.id, treasury.id, e);
+            if let Err(e) = TopUpUsage::release(&self.db, user_id, &day, amount).await {
+                error!("Failed to release top-up reservation of user {}: {}", user_id, e);
+            }
+        }
+        result.map_err(|e| e.to_string())
+    }
+
+    async fn send(
+        &self,
+        user_id: Uuid,
+        treasury: &Wallet,
+        wallet: &Wallet,
+        signer: &dyn TransactionSigner,
+        sol: &TokenInfo,
+        amount: f64,
+    ) -> Result<(), ServiceError> {
+        let transaction = Transaction::create(
+            &self.db,
+            user_id,
+            treasury.id,
+            None,
+            "top_up",
+            amount,
+            sol.symbol,
+            // Not a trade, so there is no price
+            0.0,
+            TransactionStatus::Built,
+            None,
+            None,
+            None,
+            None,
+            None,
+        ).await?;
+        let signature = submit_transfer(
+            &self.db,
+            &self.config,
+            user_id,
+            transaction.id,
+            signer,
+            &address(wallet)?,
+            sol,
+            amount,
+        ).await?;
+
+        info!("Topped up wallet {} with {:.4} SOL from treasury {}: {}", wallet.id, amount, treasury.id, signature);
+        Ok(())
+    }
+
+    /// Pauses the live strategies quoting from `wallet` and tells the user, once a day per wallet.
+    async fn shortfall(&self, user_id: Uuid, wallet: &Wallet, message: &str) -> Result<(), ServiceError> {
+        let mut paused = 0;
+        for strategy in Strategy::find_auto_trading(&self.db).await? {
+            if strategy.user_id != user_id {
+                continue;
+            }
+            let Some(status) = self.engine.status(strategy.id).await else { continue };
+            if status.paper_trading || status.wallet_id != Some(wallet.id) {
+                continue;
+            }
+
+            let target = strategy.id.to_string();
+            if KillSwitch::find_active_for_target(&self.db, user_id, KillSwitchScope::Strategy, Some(&target)).await?.is_none() {
+                KillSwitch::activate(&self.db, user_id, KillSwitchScope::Strategy, Some(&target), KillSwitchTrigger::LowBalance, message).await?;
+                paused += 1;
+            }
+        }
+        if paused > 0 {
+            self.engine.sync_workers().await?;
+        }
+
+        let midnight = Utc.from_utc_datetime(&Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap_or_default());
+        if paused > 0 || !AlertEvent::raised_since(&self.db, user_id, AlertEventKind::LowBalance, Some(wallet.id), midnight).await? {
+            warn!("{} ({} strategies paused)", message, paused);
+            AlertEvent::raise(&self.db, user_id, AlertEventKind::LowBalance, Some(wallet.id), message).await?;
+        }
+        Ok(())
+    }
+}
+
+fn address(wallet: &Wallet) -> Result<Pubkey, ServiceError> {
+    wallet.address.parse::<Pubkey>()
+        .map_err(|_| ServiceError::BadRequest("Invalid Solana address".into()))
+}
@@ -1,11 +1,36 @@
+use chrono::Utc;
+use mongodb::Database;
+use solana_client::nonblocking::rpc_client::RpcClient;
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::api::trading::{StrategyRequest, TradeRequest};
-use crate::models::strategy::Strategy;
-use crate::models::transaction::Transaction;
+use crate::config::Config;
+use crate::models::grid_state::GridState;
+use crate::models::kill_switch::KillSwitch;
+use crate::models::strategy::{Strategy, StrategyKind};
+use crate::models::strategy_volume::StrategyVolume;
+use crate::models::transaction::{Transaction, TransactionStatus, VolumeReservation};
+use crate::models::user::User;
 use crate::models::wallet::Wallet;
+use crate::services::aggregator::{QuoteRequest, SwapAggregator, SwapMode, SwapQuote};
+use crate::services::confirmations::{landed_meta, release_volume};
+use crate::services::kill_switch::ensure_trading_allowed;
+use crate::services::market_maker::{MarketMakingEngine, StrategyState, StrategyStatus};
+use crate::services::priority_fees::{with_priority_fee, PriorityFeePolicy};
+use crate::services::self_trade::{check_self_trade, IncomingOrder, SelfTradeDecision, SelfTradePolicy};
+use crate::services::signer::SigningPurpose;
+use crate::services::slippage::{balance_change, check_quote, max_slippage_bps, validate_max_slippage};
+use crate::services::strategy_types::parse_params;
+use crate::services::submission::submit_with_retry;
+use crate::services::venues::paper::PaperVenue;
+use crate::services::venues::{validate_platform, OrderSide};
+use crate::services::wallets::ensure_wallet_active;
 use crate::utils::errors::ServiceError;
+use crate::utils::tokens::{from_base_units, to_base_units, token_by_symbol, TokenInfo};
+
+// Trades are settled against this token
+pub const QUOTE_TOKEN: &str = "USDC";
 
 pub async fn get_trading_strategies(
     pool: &PgPool,
@@ -15,6 +40,44 @@ pub async fn get_trading_strategies(
     Ok(strategies)
 }
 
+pub async fn get_strategy_status(
+    db: &Database,
+    engine: &MarketMakingEngine,
+    user_id: Uuid,
+    strategy_id: Uuid,
+) -> Result<StrategyStatus, ServiceError> {
+    // Check if strategy exists and belongs to user
+    let strategy = Strategy::find_by_id(db, strategy_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Strategy not found".into()))?;
+    
+    // Strategies without a running worker are reported as paused
+    let mut status = engine.status(strategy.id).await
+        .unwrap_or_else(|| StrategyStatus::paused(&strategy));
+    if status.state == StrategyState::Paused {
+        if let Some(kill_switch) = KillSwitch::find_halting(db, user_id, Some(strategy.id), &strategy.trading_pair).await? {
+            status.last_error = Some(format!("Halted by a {} kill switch: {}", kill_switch.scope.as_str(), kill_switch.reason));
+        }
+    }
+    if strategy.strategy_type == StrategyKind::Grid {
+        status.grid = GridState::find_by_strategy(db, strategy.id).await?;
+    }
+    
+    Ok(status)
+}
+
+pub async fn get_strategy_detail(
+    db: &Database,
+    user_id: Uuid,
+    strategy_id: Uuid,
+) -> Result<(Strategy, f64), ServiceError> {
+    let strategy = Strategy::find_by_id(db, strategy_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Strategy not found".into()))?;
+    
+    let used_today = StrategyVolume::used_today(db, strategy.id).await?;
+    
+    Ok((strategy, used_today))
+}
+
 pub async fn create_strategy(
     pool: &PgPool,
     user_id: Uuid,
@@ -41,12 +104,19 @@ pub async fn create_strategy(
         return Err(ServiceError::BadRequest("Trade frequency must be positive".into()));
     }
     
+    if let Some(max_slippage_bps) = req.max_slippage_bps {
+        validate_max_slippage(max_slippage_bps)?;
+    }
+    
+    validate_platform(&req.execution_platform)?;
+    let params = parse_params(req.strategy_type, req.params.clone())?;
+    
     // Create the strategy
     let strategy = Strategy::create(
         pool,
         user_id,
         &req.name,
-        &req.strategy_type,
+        &params,
         &req.trading_pair,
         &req.execution_platform,
         req.min_trade_size,
@@ -57,6 +127,8 @@ pub async fn create_strategy(
         req.risk_alerts,
         req.transaction_delay,
         req.trade_frequency,
+        req.paper_trading,
+        req.max_slippage_bps,
     ).await?;
     
     Ok(strategy)
@@ -93,13 +165,20 @@ pub async fn update_strategy(
         return Err(ServiceError::BadRequest("Trade frequency must be positive".into()));
     }
     
+    if let Some(max_slippage_bps) = req.max_slippage_bps {
+        validate_max_slippage(max_slippage_bps)?;
+    }
+    
+    validate_platform(&req.execution_platform)?;
+    let params = parse_params(req.strategy_type, req.params.clone())?;
+    
     // Update the strategy
     let strategy = Strategy::update(
         pool,
         strategy_id,
         user_id,
         &req.name,
-        &req.strategy_type,
+        &params,
         &req.trading_pair,
         &req.execution_platform,
         req.min_trade_size,
@@ -110,55 +189,352 @@ pub async fn update_strategy(
         req.risk_alerts,
         req.transaction_delay,
         req.trade_frequency,
+        req.paper_trading,
+        req.max_slippage_bps,
     ).await?;
     
     Ok(strategy)
 }
 
 pub async fn execute_trade(
-    pool: &PgPool,
+    db: &Database,
+    config: &Config,
+    engine: &MarketMakingEngine,
+    aggregator: &dyn SwapAggregator,
+    paper: &PaperVenue,
     user_id: Uuid,
     req: TradeRequest,
 ) -> Result<Transaction, ServiceError> {
     // Check if wallet exists and belongs to user
-    let wallet = Wallet::find_by_id(pool, req.wallet_id, user_id).await?
+    let wallet = Wallet::find_by_id(db, req.wallet_id, user_id).await?
         .ok_or_else(|| ServiceError::NotFound("Wallet not found".into()))?;
+    ensure_wallet_active(&wallet)?;
     
     // Validate trade parameters
     if req.amount <= 0.0 {
         return Err(ServiceError::BadRequest("Trade amount must be positive".into()));
     }
     
-    if !["buy", "sell"].contains(&req.action.to_lowercase().as_str()) {
-        return Err(ServiceError::BadRequest("Action must be 'buy' or 'sell'".into()));
+    let is_buy = match req.action.to_lowercase().as_str() {
+        "buy" => true,
+        "sell" => false,
+        _ => return Err(ServiceError::BadRequest("Action must be 'buy' or 'sell'".into())),
+    };
+    
+    let token = token_by_symbol(&req.token)
+        .ok_or_else(|| ServiceError::BadRequest(format!("Unsupported token: {}", req.token)))?;
+    let quote_token = token_by_symbol(QUOTE_TOKEN)
+        .ok_or_else(|| ServiceError::InternalServerError("Quote token is not configured".into()))?;
+    if token.mint == quote_token.mint {
+        return Err(ServiceError::BadRequest(format!("Cannot trade {} against itself", QUOTE_TOKEN)));
     }
     
-    // In a real implementation, we would execute the trade on the blockchain
-    // For now, we'll just create a transaction record
+    // Trades made on behalf of a strategy count against its daily volume cap
+    let strategy = match req.strategy_id {
+        Some(strategy_id) => Some(Strategy::find_by_id(db, strategy_id, user_id).await?
+            .ok_or_else(|| ServiceError::NotFound("Strategy not found".into()))?),
+        None => None,
+    };
+    
+    let market_pair = format!("{}/{}", token.symbol, QUOTE_TOKEN);
+    ensure_trading_allowed(db, user_id, req.strategy_id, &market_pair).await?;
+    let max_slippage = max_slippage_bps(db, config, user_id, strategy.as_ref(), req.max_slippage_bps).await?;
+    
+    if config.paper_trading || strategy.as_ref().map_or(false, |s| s.paper_trading) {
+        return execute_paper_trade(db, config, engine, paper, &wallet, strategy.as_ref(), &req, is_buy, &market_pair, max_slippage).await;
+    }
+    
+    // Buys receive exactly `amount` of the token, sells spend exactly `amount` of it
+    let (input, output, swap_mode) = if is_buy {
+        (quote_token, token, SwapMode::ExactOut)
+    } else {
+        (token, quote_token, SwapMode::ExactIn)
+    };
+    
+    let quote = aggregator.quote(&QuoteRequest {
+        input_mint: input.mint.to_string(),
+        output_mint: output.mint.to_string(),
+        amount: to_base_units(req.amount, token.decimals),
+        swap_mode,
+        slippage_bps: max_slippage,
+    }).await
+        .map_err(|e| ServiceError::BadRequest(format!("Failed to get swap quote: {}", e)))?;
+    check_quote(&quote, swap_mode, max_slippage)?;
+    
+    let quoted_price = price_per_token(
+        is_buy,
+        from_base_units(quote.in_amount, input.decimals),
+        from_base_units(quote.out_amount, output.decimals),
+    );
+    // The swap may execute anywhere up to its slippage threshold, so that is what has to respect
+    // the limit price
+    let worst_price = if is_buy {
+        price_per_token(true, from_base_units(quote.other_amount_threshold, input.decimals), from_base_units(quote.out_amount, output.decimals))
+    } else {
+        price_per_token(false, from_base_units(quote.in_amount, input.decimals), from_base_units(quote.other_amount_threshold, output.decimals))
+    };
+    check_limit_price(is_buy, worst_price, req.price)?;
+    
+    prevent_self_trade(db, config, engine, &wallet, req.strategy_id, &market_pair, is_buy, req.amount, quoted_price, max_slippage).await?;
     
-    // Calculate price if not provided
-    let price = req.price.unwrap_or_else(|| {
-        // Mock price calculation
-        match req.token.as_str() {
-            "SOL" => 150.0,
-            "BTC" => 60000.0,
-            _ => 1.0,
+    let notional = req.amount * quoted_price;
+    let reservation = match &strategy {
+        Some(strategy) => {
+            let day = StrategyVolume::day_key(Utc::now());
+            if !StrategyVolume::reserve(db, strategy.id, user_id, &day, notional, strategy.max_daily_volume).await? {
+                let remaining = (strategy.max_daily_volume - StrategyVolume::used_today(db, strategy.id).await?).max(0.0);
+                return Err(ServiceError::BadRequest(format!(
+                    "Trade notional {:.2} exceeds the strategy's remaining daily volume of {:.2}",
+                    notional, remaining
+                )));
+            }
+            Some(VolumeReservation { day, notional })
         }
-    });
+        None => None,
+    };
     
-    // Create transaction record
-    let transaction = Transaction::create(
-        pool,
+    // Record the trade before anything is built so every attempt is traceable and the
+    // reservation travels with it
+    let transaction = match Transaction::create(
+        db,
         user_id,
         req.wallet_id,
+        req.strategy_id,
         &req.action,
         req.amount,
         &req.token,
-        price,
-        "completed", // Status
-        Some(0.1), // Mock slippage
-        Some("mock_transaction_hash"), // Mock transaction hash
-    ).await?;
+        quoted_price,
+        TransactionStatus::Built,
+        None,
+        Some(max_slippage),
+        None,
+        reservation.clone(),
+        req.parent_order_id,
+    ).await {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            if let (Some(strategy), Some(reservation)) = (&strategy, &reservation) {
+                StrategyVolume::release(db, strategy.id, &reservation.day, reservation.notional).await?;
+            }
+            return Err(e.into());
+        }
+    };
+    
+    let result = submit_swap(db, config, engine, aggregator, &wallet, &transaction, &quote, is_buy, input, output, quoted_price).await;
+    
+    // The reservation is only given back once the trade provably did not land. A trade still
+    // being prepared was never signed; anything that was sent is settled by the confirmation
+    // tracker, which releases the reservation if it fails or expires.
+    if let Err(e) = &result {
+        if let Some(current) = Transaction::find_by_id(db, transaction.id, user_id).await? {
+            if matches!(current.status, TransactionStatus::Built | TransactionStatus::Simulated) && current.attempts.is_empty() {
+                Transaction::transition(db, current.id, current.status, TransactionStatus::Failed, Some(&e.to_string())).await?;
+            }
+        }
+        release_volume(db, transaction.id).await?;
+    }
+    
+    result
+}
+
+/// Stops a market order from filling a resting quote from another wallet of the user's organization.
+async fn prevent_self_trade(
+    db: &Database,
+    config: &Config,
+    engine: &MarketMakingEngine,
+    wallet: &Wallet,
+    strategy_id: Option<Uuid>,
+    market_pair: &str,
+    is_buy: bool,
+    amount: f64,
+    price: f64,
+    max_slippage_bps: u16,
+) -> Result<(), ServiceError> {
+    // Swaps can execute anywhere within the slippage tolerance of the quote
+    let slippage = max_slippage_bps as f64 / 10_000.0;
+    let incoming = IncomingOrder {
+        wallet_id: wallet.id,
+        strategy_id,
+        side: if is_buy { OrderSide::Buy } else { OrderSide::Sell },
+        price: if is_buy { price * (1.0 + slippage) } else { price * (1.0 - slippage) },
+        size: amount,
+    };
+    
+    // A swap has no limit price to move, so repricing falls back to rejecting
+    let policy = match config.self_trade_policy {
+        SelfTradePolicy::Reprice => SelfTradePolicy::Reject,
+        policy => policy,
+    };
+    
+    let owners = User::organization_members(db, wallet.user_id).await?;
+    let resting = engine.resting_quotes(&owners, market_pair).await;
+    match check_self_trade(db, policy, wallet.user_id, market_pair, &incoming, &resting).await? {
+        SelfTradeDecision::Proceed => Ok(()),
+        SelfTradeDecision::CancelResting(strategy_ids) => {
+            for strategy_id in strategy_ids {
+                engine.cancel_strategy_quotes(strategy_id).await
+                    .map_err(|e| ServiceError::InternalServerError(format!("Failed to cancel resting quotes: {}", e)))?;
+            }
+            Ok(())
+        }
+        SelfTradeDecision::Reject | SelfTradeDecision::Reprice(_) => Err(ServiceError::Conflict(
+            "Trade would match a resting order from another of your organization's wallets".into(),
+        )),
+    }
+}
+
+async fn execute_paper_trade(
+    db: &Database,
+    config: &Config,
+    engine: &MarketMakingEngine,
+    paper: &PaperVenue,
+    wallet: &Wallet,
+    strategy: Option<&Strategy>,
+    req: &TradeRequest,
+    is_buy: bool,
+    market_pair: &str,
+    max_slippage_bps: u16,
+) -> Result<Transaction, ServiceError> {
+    let side = if is_buy { OrderSide::Buy } else { OrderSide::Sell };
+    
+    let estimated_price = paper.estimate_market_price(market_pair, side, req.amount).await
+        .map_err(|e| ServiceError::BadRequest(format!("Failed to price paper trade: {}", e)))?;
+    check_limit_price(is_buy, estimated_price, req.price)?;
+    
+    prevent_self_trade(db, config, engine, wallet, strategy.map(|s| s.id), market_pair, is_buy, req.amount, estimated_price, max_slippage_bps).await?;
+    
+    let notional = req.amount * estimated_price;
+    let reserved_day = StrategyVolume::day_key(Utc::now());
+    if let Some(strategy) = strategy {
+        if !StrategyVolume::reserve(db, strategy.id, wallet.user_id, &reserved_day, notional, strategy.max_daily_volume).await? {
+            let remaining = (strategy.max_daily_volume - StrategyVolume::used_today(db, strategy.id).await?).max(0.0);
+            return Err(ServiceError::BadRequest(format!(
+                "Trade notional {:.2} exceeds the strategy's remaining daily volume of {:.2}",
+                notional, remaining
+            )));
+        }
+    }
+    
+    let result = paper.execute_market(wallet.user_id, wallet.id, strategy.map(|s| s.id), market_pair, side, req.amount, max_slippage_bps, req.parent_order_id).await
+        .map_err(|e| ServiceError::BadRequest(format!("Paper trade failed: {}", e)));
+    
+    // A paper fill is written in a single insert, so an error means nothing was filled
+    if let (Err(_), Some(strategy)) = (&result, strategy) {
+        StrategyVolume::release(db, strategy.id, &reserved_day, notional).await?;
+    }
+    
+    result
+}
+
+async fn submit_swap(
+    db: &Database,
+    config: &Config,
+    engine: &MarketMakingEngine,
+    aggregator: &dyn SwapAggregator,
+    wallet: &Wallet,
+    transaction: &Transaction,
+    quote: &SwapQuote,
+    is_buy: bool,
+    input: TokenInfo,
+    output: TokenInfo,
+    quoted_price: f64,
+) -> Result<Transaction, ServiceError> {
+    let user_id = wallet.user_id;
+    
+    // Build the swap for the wallet's key
+    let signer = engine.signers().for_wallet(wallet).await?;
+    let built = aggregator.build_swap(quote, &signer.pubkey()).await
+        .map_err(|e| ServiceError::InternalServerError(format!("Failed to build swap transaction: {}", e)))?;
+    let fee_policy = PriorityFeePolicy::for_user(db, user_id).await?;
+    
+    // Simulate first so swaps that would fail never reach the network; the simulation also sizes the compute budget
+    let rpc = RpcClient::new(config.solana_rpc_url.clone());
+    let (message, budget) = match with_priority_fee(&rpc, built.transaction.message, &fee_policy).await {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            let error = e.to_string();
+            Transaction::transition(db, transaction.id, TransactionStatus::Built, TransactionStatus::Failed, Some(&error)).await?;
+            return Err(ServiceError::BadRequest(format!("Swap simulation failed: {}", error)));
+        }
+    };
+    Transaction::set_compute_budget(db, transaction.id, budget.unit_limit, budget.unit_price).await?;
+    Transaction::transition(db, transaction.id, TransactionStatus::Built, TransactionStatus::Simulated, None).await?;
+    
+    // Rebroadcasts, and re-signs with a fresh blockhash on expiry, until the swap lands
+    let landed = submit_with_retry(db, config, &rpc, transaction.id, signer.as_ref(), SigningPurpose::Swap, message, budget, &fee_policy).await?;
+    let meta = landed_meta(&rpc, &landed.signature).await;
+    if let Some(meta) = &meta {
+        Transaction::set_network_fee(db, transaction.id, meta.fee).await?;
+    }
+    if landed.status == TransactionStatus::Failed {
+        let error = landed.error.unwrap_or_default();
+        Transaction::transition(db, transaction.id, TransactionStatus::Submitted, TransactionStatus::Failed, Some(&error)).await?;
+        return Err(ServiceError::BadRequest(format!("Swap failed on-chain: {}", error)));
+    }
+    
+    // Measure what actually moved, from the transaction's own balance records, so slippage
+    // reflects the executed fill
+    if let Some(meta) = &meta {
+        let owner = signer.pubkey();
+        let spent = (-balance_change(meta, &owner, &input)).max(0.0);
+        let received = balance_change(meta, &owner, &output).max(0.0);
+        
+        if spent > 0.0 && received > 0.0 {
+            let executed_price = price_per_token(is_buy, spent, received);
+            let slippage = slippage_pct(is_buy, quoted_price, executed_price);
+            Transaction::set_execution(db, transaction.id, executed_price, Some(slippage)).await?;
+        }
+    }
+    Transaction::transition(db, transaction.id, TransactionStatus::Submitted, landed.status, None).await?;
+    
+    let transaction = Transaction::find_by_id(db, transaction.id, user_id).await?
+        .ok_or_else(|| ServiceError::InternalServerError("Transaction record disappeared".into()))?;
+    
+    Ok(transaction)
+}
+
+pub async fn get_transaction_status(
+    db: &Database,
+    user_id: Uuid,
+    transaction_id: Uuid,
+) -> Result<Transaction, ServiceError> {
+    let transaction = Transaction::find_by_id(db, transaction_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Transaction not found".into()))?;
     
     Ok(transaction)
-} 
\ No newline at end of file
+}
+
+// Refuses a trade whose price is worse than the caller's limit, if it gave one
+fn check_limit_price(is_buy: bool, price: f64, limit: Option<f64>) -> Result<(), ServiceError> {
+    let beyond = match limit {
+        Some(limit) if is_buy => price > limit,
+        Some(limit) => price < limit,
+        None => false,
+    };
+    if beyond {
+        return Err(ServiceError::BadRequest(format!(
+            "Price {:.4} is beyond the limit price {:.4}",
+            price,
+            limit.unwrap_or_default()
+        )));
+    }
+    Ok(())
+}
+
+// Price of the traded token in units of the quote token
+fn price_per_token(is_buy: bool, input_amount: f64, output_amount: f64) -> f64 {
+    if is_buy {
+        input_amount / output_amount
+    } else {
+        output_amount / input_amount
+    }
+}
+
+// Adverse price movement versus the quote, in percent
+fn slippage_pct(is_buy: bool, quoted_price: f64, executed_price: f64) -> f64 {
+    if is_buy {
+        (executed_price - quoted_price) / quoted_price * 100.0
+    } else {
+        (quoted_price - executed_price) / quoted_price * 100.0
+    }
+}
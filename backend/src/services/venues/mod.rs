@@ -0,0 +1,152 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::strategy::Strategy;
+use crate::services::priority_fees::PriorityFeePolicy;
+use crate::services::signer::TransactionSigner;
+use crate::utils::errors::ServiceError;
+
+pub mod openbook_v2;
+pub mod paper;
+
+pub const OPENBOOK_V2: &str = "openbook-v2";
+
+// Values accepted in `Strategy.execution_platform`
+pub const SUPPORTED_PLATFORMS: &[&str] = &[OPENBOOK_V2];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone)]
+pub struct LimitOrder {
+    pub market_pair: String,
+    pub side: OrderSide,
+    pub price: f64,
+    pub size: f64,
+    pub client_order_id: u64,
+    pub post_only: bool,
+    // Attribution for the fills this order produces
+    pub user_id: Uuid,
+    pub wallet_id: Uuid,
+    pub strategy_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenOrder {
+    pub order_id: u128,
+    pub client_order_id: u64,
+    pub side: OrderSide,
+    pub price: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlacedOrder {
+    pub client_order_id: u64,
+    pub signature: String,
+}
+
+/// A fill of one of our resting orders, as the venue reported it.
+#[derive(Debug, Clone, Serialize)]
+pub struct VenueFill {
+    // Unique per fill on the market
+    pub fill_id: String,
+    pub client_order_id: u64,
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Best prices resting on a venue's book, and when the venue last reported them.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BookTop {
+    pub best_bid: Option<f64>,
+    pub best_ask: Option<f64>,
+    pub observed_at: DateTime<Utc>,
+}
+
+impl BookTop {
+    pub fn mid(&self) -> Option<f64> {
+        match (self.best_bid, self.best_ask) {
+            (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+            _ => None,
+        }
+    }
+}
+
+/// A place where limit orders can rest, e.g. an on-chain central limit order book.
+#[async_trait]
+pub trait ExecutionVenue: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn place_limit_order(&self, owner: &dyn TransactionSigner, order: &LimitOrder) -> Result<PlacedOrder>;
+
+    async fn cancel_order(&self, owner: &dyn TransactionSigner, market_pair: &str, order_id: u128) -> Result<Signature>;
+
+    /// Cancels several of the owner's resting orders, in as few transactions as the venue allows.
+    async fn cancel_orders(&self, owner: &dyn TransactionSigner, market_pair: &str, order_ids: &[u128]) -> Result<Vec<Signature>>;
+
+    async fn open_orders(&self, owner: &Pubkey, market_pair: &str) -> Result<Vec<OpenOrder>>;
+
+    /// Fills of the owner's resting orders that the venue still has on record. A fill may be
+    /// reported on several calls until the venue discards it.
+    async fn fills(&self, owner: &Pubkey, market_pair: &str) -> Result<Vec<VenueFill>>;
+
+    /// The market's best bid and ask as the venue currently has them.
+    async fn top_of_book(&self, market_pair: &str) -> Result<BookTop>;
+
+    /// Moves filled proceeds held by the venue back into the owner's token accounts.
+    async fn settle_funds(&self, owner: &dyn TransactionSigner, market_pair: &str) -> Result<Signature>;
+}
+
+pub fn validate_platform(platform: &str) -> Result<(), ServiceError> {
+    if SUPPORTED_PLATFORMS.contains(&platform) {
+        Ok(())
+    } else {
+        Err(ServiceError::BadRequest(format!(
+            "Unsupported execution platform '{}', expected one of: {}",
+            platform,
+            SUPPORTED_PLATFORMS.join(", ")
+        )))
+    }
+}
+
+/// Returns the venue adapter for a strategy's `execution_platform`.
+pub fn venue_for(
+    platform: &str,
+    config: &Config,
+    fees: PriorityFeePolicy,
+) -> Result<Arc<dyn ExecutionVenue>, ServiceError> {
+    validate_platform(platform)?;
+    match platform {
+        OPENBOOK_V2 => Ok(Arc::new(openbook_v2::OpenBookV2::new(config, fees)?)),
+        _ => Err(ServiceError::BadRequest(format!("Unsupported execution platform '{}'", platform))),
+    }
+}
+
+pub fn is_paper(strategy: &Strategy, config: &Config) -> bool {
+    config.paper_trading || strategy.paper_trading
+}
+
+/// Picks the venue a strategy's orders go to, honouring paper mode.
+pub fn select_venue(
+    strategy: &Strategy,
+    config: &Config,
+    paper: &Arc<paper::PaperVenue>,
+    fees: PriorityFeePolicy,
+) -> Result<Arc<dyn ExecutionVenue>, ServiceError> {
+    if is_paper(strategy, config) {
+        Ok(paper.clone())
+    } else {
+        venue_for(&strategy.execution_platform, config, fees)
+    }
+}
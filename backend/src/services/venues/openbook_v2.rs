@@ -0,0 +1,679 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::message::{Message, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::system_program;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::{BookTop, ExecutionVenue, LimitOrder, OpenOrder, OrderSide, PlacedOrder, VenueFill};
+use crate::config::Config;
+use crate::services::priority_fees::{with_priority_fee, PriorityFeePolicy};
+use crate::services::signer::{sign_transaction, SigningPurpose, TransactionSigner};
+use crate::utils::errors::ServiceError;
+use crate::utils::tokens::{associated_token_address, SPL_TOKEN_PROGRAM_ID};
+
+pub const OPENBOOK_V2_PROGRAM_ID: &str = "opnb2LAfJYbRMAHHvqjCwQxanZn7ReEHp1k81EohpZb";
+
+// Anchor discriminators: first 8 bytes of sha256("global:<instruction name>")
+const PLACE_ORDER_DISCRIMINATOR: [u8; 8] = [51, 194, 155, 175, 109, 130, 96, 106];
+const CANCEL_ORDER_DISCRIMINATOR: [u8; 8] = [95, 129, 237, 240, 8, 49, 223, 132];
+const SETTLE_FUNDS_DISCRIMINATOR: [u8; 8] = [238, 64, 163, 96, 75, 171, 16, 33];
+
+const ORDER_TYPE_LIMIT: u8 = 0;
+const ORDER_TYPE_POST_ONLY: u8 = 2;
+// Cancel our own resting order instead of matching against it
+const SELF_TRADE_CANCEL_PROVIDE: u8 = 1;
+// Upper bound on resting orders matched or cancelled in one instruction
+const MATCH_LIMIT: u8 = 10;
+const OPEN_ORDER_SLOTS: usize = 24;
+// Cancel instructions that fit in one transaction next to the compute budget instructions
+const CANCEL_BATCH_SIZE: usize = 8;
+const FEE_SCALE: f64 = 1_000_000.0;
+
+// BookSide: discriminator (8) + roots (2 x 8) + reserved roots (4 x 8) + reserved (256), then the
+// node pool's header (528) and its fixed-size nodes
+const BOOK_FIXED_ROOT_OFFSET: usize = 8;
+const BOOK_NODES_OFFSET: usize = 840;
+const BOOK_NODE_SIZE: usize = 88;
+const BOOK_MAX_NODES: usize = 1024;
+const NODE_TAG_INNER: u8 = 1;
+const NODE_TAG_LEAF: u8 = 2;
+
+// EventHeap: discriminator (8) + header (free_head, used_head, count, padding, seq_num), then
+// 600 nodes of next/prev links (8) and a 144-byte event
+const EVENT_HEAP_HEADER_OFFSET: usize = 8;
+const EVENT_HEAP_NODES_OFFSET: usize = 24;
+const EVENT_NODE_SIZE: usize = 152;
+const EVENT_HEAP_MAX_EVENTS: usize = 600;
+const EVENT_TYPE_FILL: u8 = 0;
+
+/// Fields of the on-chain `Market` account needed to build instructions.
+#[derive(Debug, Clone)]
+struct MarketState {
+    address: Pubkey,
+    base_decimals: u8,
+    quote_decimals: u8,
+    market_authority: Pubkey,
+    bids: Pubkey,
+    asks: Pubkey,
+    event_heap: Pubkey,
+    oracle_a: Pubkey,
+    oracle_b: Pubkey,
+    quote_lot_size: i64,
+    base_lot_size: i64,
+    taker_fee: i64,
+    base_mint: Pubkey,
+    quote_mint: Pubkey,
+    market_base_vault: Pubkey,
+    market_quote_vault: Pubkey,
+}
+
+impl MarketState {
+    fn parse(address: Pubkey, data: &[u8]) -> Result<Self> {
+        if data.len() < 712 {
+            return Err(anyhow!("Account {} is not an OpenBook v2 market", address));
+        }
+
+        Ok(Self {
+            address,
+            base_decimals: data[9],
+            quote_decimals: data[10],
+            market_authority: read_pubkey(data, 16),
+            bids: read_pubkey(data, 200),
+            asks: read_pubkey(data, 232),
+            event_heap: read_pubkey(data, 264),
+            oracle_a: read_pubkey(data, 296),
+            oracle_b: read_pubkey(data, 328),
+            quote_lot_size: read_i64(data, 448),
+            base_lot_size: read_i64(data, 456),
+            taker_fee: read_i64(data, 488),
+            base_mint: read_pubkey(data, 576),
+            quote_mint: read_pubkey(data, 608),
+            market_base_vault: read_pubkey(data, 640),
+            market_quote_vault: read_pubkey(data, 680),
+        })
+    }
+
+    fn price_to_lots(&self, price: f64) -> i64 {
+        let native_price = price * 10f64.powi(self.quote_decimals as i32 - self.base_decimals as i32);
+        (native_price * self.base_lot_size as f64 / self.quote_lot_size as f64).round() as i64
+    }
+
+    fn lots_to_price(&self, price_lots: i64) -> f64 {
+        let native_price = price_lots as f64 * self.quote_lot_size as f64 / self.base_lot_size as f64;
+        native_price / 10f64.powi(self.quote_decimals as i32 - self.base_decimals as i32)
+    }
+
+    fn size_to_base_lots(&self, size: f64) -> i64 {
+        (size * 10f64.powi(self.base_decimals as i32) / self.base_lot_size as f64).floor() as i64
+    }
+
+    fn base_lots_to_size(&self, base_lots: i64) -> f64 {
+        base_lots as f64 * self.base_lot_size as f64 / 10f64.powi(self.base_decimals as i32)
+    }
+}
+
+/// Fill events in the event heap whose maker is `open_orders`, oldest first. Events stay in the
+/// heap until the market is cranked.
+fn maker_fills(market: &MarketState, data: &[u8], open_orders: &Pubkey) -> Result<Vec<VenueFill>> {
+    if data.len() < EVENT_HEAP_NODES_OFFSET + EVENT_HEAP_MAX_EVENTS * EVENT_NODE_SIZE {
+        return Err(anyhow!("Account is not an OpenBook v2 event heap"));
+    }
+    let header = &data[EVENT_HEAP_HEADER_OFFSET..EVENT_HEAP_NODES_OFFSET];
+    let used_head = u16::from_le_bytes(header[2..4].try_into()?) as usize;
+    let count = u16::from_le_bytes(header[4..6].try_into()?) as usize;
+
+    let mut fills = Vec::new();
+    let mut index = used_head;
+    for _ in 0..count.min(EVENT_HEAP_MAX_EVENTS) {
+        if index >= EVENT_HEAP_MAX_EVENTS {
+            return Err(anyhow!("Malformed OpenBook v2 event heap"));
+        }
+        let node = &data[EVENT_HEAP_NODES_OFFSET + index * EVENT_NODE_SIZE..][..EVENT_NODE_SIZE];
+        index = u16::from_le_bytes(node[0..2].try_into()?) as usize;
+
+        let event = &node[8..];
+        if event[0] != EVENT_TYPE_FILL || read_pubkey(event, 24) != *open_orders {
+            continue;
+        }
+        fills.push(VenueFill {
+            fill_id: u64::from_le_bytes(event[16..24].try_into()?).to_string(),
+            client_order_id: u64::from_le_bytes(event[128..136].try_into()?),
+            price: market.lots_to_price(read_i64(event, 104)),
+            size: market.base_lots_to_size(read_i64(event, 120)),
+        });
+    }
+    Ok(fills)
+}
+
+/// Open orders accounts listed in an `OpenOrdersIndexer`, or `None` if the account is shorter
+/// than the list it declares.
+fn indexed_open_orders(data: &[u8]) -> Option<Vec<Pubkey>> {
+    // discriminator (8) + bump (1) + created_counter (4) + Vec<Pubkey>
+    let count = u32::from_le_bytes(data.get(13..17)?.try_into().ok()?) as usize;
+    let end = count.checked_mul(32)?.checked_add(17)?;
+    if end > data.len() {
+        return None;
+    }
+    Some((0..count).map(|i| read_pubkey(data, 17 + i * 32)).collect())
+}
+
+/// Price in lots of every unexpired order in a BookSide's fixed-price tree.
+fn book_side_prices(data: &[u8], now_ts: u64) -> Result<Vec<i64>> {
+    if data.len() < BOOK_NODES_OFFSET + BOOK_MAX_NODES * BOOK_NODE_SIZE {
+        return Err(anyhow!("Account is not an OpenBook v2 book side"));
+    }
+    let root = u32::from_le_bytes(data[BOOK_FIXED_ROOT_OFFSET..BOOK_FIXED_ROOT_OFFSET + 4].try_into()?);
+    let leaf_count = u32::from_le_bytes(data[BOOK_FIXED_ROOT_OFFSET + 4..BOOK_FIXED_ROOT_OFFSET + 8].try_into()?);
+
+    let mut prices = Vec::new();
+    let mut stack = if leaf_count > 0 { vec![root] } else { Vec::new() };
+    while let Some(handle) = stack.pop() {
+        // Guard against a corrupt tree sending us in circles
+        if handle as usize >= BOOK_MAX_NODES || prices.len() + stack.len() > BOOK_MAX_NODES {
+            return Err(anyhow!("Malformed OpenBook v2 order tree"));
+        }
+        let node = &data[BOOK_NODES_OFFSET + handle as usize * BOOK_NODE_SIZE..][..BOOK_NODE_SIZE];
+        match node[0] {
+            NODE_TAG_INNER => {
+                stack.push(u32::from_le_bytes(node[24..28].try_into()?));
+                stack.push(u32::from_le_bytes(node[28..32].try_into()?));
+            }
+            NODE_TAG_LEAF => {
+                let time_in_force = u16::from_le_bytes(node[2..4].try_into()?) as u64;
+                let timestamp = u64::from_le_bytes(node[64..72].try_into()?);
+                if time_in_force != 0 && timestamp + time_in_force < now_ts {
+                    continue;
+                }
+                // The key's upper 64 bits are the price in lots
+                let key = u128::from_le_bytes(node[8..24].try_into()?);
+                prices.push((key >> 64) as i64);
+            }
+            tag => return Err(anyhow!("Unexpected node tag {} in OpenBook v2 order tree", tag)),
+        }
+    }
+    Ok(prices)
+}
+
+/// Adapter for the OpenBook v2 on-chain order book.
+pub struct OpenBookV2 {
+    rpc: Arc<RpcClient>,
+    program_id: Pubkey,
+    markets: HashMap<String, Pubkey>,
+    // Priority fee bidding of the user whose orders this adapter sends
+    fees: PriorityFeePolicy,
+}
+
+impl OpenBookV2 {
+    pub fn new(config: &Config, fees: PriorityFeePolicy) -> Result<Self, ServiceError> {
+        let mut markets = HashMap::new();
+        for (pair, address) in &config.openbook_markets {
+            let market = Pubkey::from_str(address).map_err(|_| {
+                ServiceError::InternalServerError(format!("Invalid OpenBook market address for {}", pair))
+            })?;
+            markets.insert(pair.clone(), market);
+        }
+
+        Ok(Self {
+            rpc: Arc::new(RpcClient::new(config.solana_rpc_url.clone())),
+            program_id: Pubkey::from_str(OPENBOOK_V2_PROGRAM_ID).unwrap(),
+            markets,
+            fees,
+        })
+    }
+
+    async fn load_market(&self, market_pair: &str) -> Result<MarketState> {
+        let address = self
+            .markets
+            .get(market_pair)
+            .ok_or_else(|| anyhow!("No OpenBook v2 market configured for {}", market_pair))?;
+        let data = self.rpc.get_account_data(address).await?;
+        MarketState::parse(*address, &data)
+    }
+
+    /// Finds the owner's open orders account for `market` through their indexer account.
+    async fn open_orders_account(&self, owner: &Pubkey, market: &Pubkey) -> Result<Pubkey> {
+        let (indexer, _) =
+            Pubkey::find_program_address(&[b"OpenOrdersIndexer", owner.as_ref()], &self.program_id);
+        let data = self
+            .rpc
+            .get_account_data(&indexer)
+            .await
+            .map_err(|_| anyhow!("{} has no OpenBook v2 open orders indexer", owner))?;
+        let addresses = indexed_open_orders(&data)
+            .ok_or_else(|| anyhow!("Invalid OpenBook v2 open orders indexer for {}", owner))?;
+
+        let accounts = self.rpc.get_multiple_accounts(&addresses).await?;
+        for (address, account) in addresses.iter().zip(accounts) {
+            if let Some(account) = account {
+                if account.data.len() >= 72 && read_pubkey(&account.data, 40) == *market {
+                    return Ok(*address);
+                }
+            }
+        }
+
+        Err(anyhow!("{} has no OpenBook v2 open orders account for market {}", owner, market))
+    }
+
+    fn optional(&self, account: Pubkey) -> Pubkey {
+        // Anchor encodes an absent optional account as the program id
+        if account == Pubkey::default() {
+            self.program_id
+        } else {
+            account
+        }
+    }
+
+    async fn send(&self, owner: &dyn TransactionSigner, instructions: &[Instruction], purpose: SigningPurpose) -> Result<Signature> {
+        let message = VersionedMessage::Legacy(Message::new(instructions, Some(&owner.pubkey())));
+        let (mut message, _) = with_priority_fee(&self.rpc, message, &self.fees).await?;
+        message.set_recent_blockhash(self.rpc.get_latest_blockhash().await?);
+        let transaction = sign_transaction(owner, message, purpose).await?;
+        Ok(self.rpc.send_and_confirm_transaction(&transaction).await?)
+    }
+}
+
+#[async_trait]
+impl ExecutionVenue for OpenBookV2 {
+    fn name(&self) -> &'static str {
+        super::OPENBOOK_V2
+    }
+
+    async fn place_limit_order(&self, owner: &dyn TransactionSigner, order: &LimitOrder) -> Result<PlacedOrder> {
+        let market = self.load_market(&order.market_pair).await?;
+        let open_orders = self.open_orders_account(&owner.pubkey(), &market.address).await?;
+
+        let price_lots = market.price_to_lots(order.price);
+        let max_base_lots = market.size_to_base_lots(order.size);
+        if price_lots <= 0 || max_base_lots <= 0 {
+            return Err(anyhow!("Order is smaller than the market's lot size"));
+        }
+
+        let (side, user_token_account, market_vault, max_quote_lots) = match order.side {
+            OrderSide::Buy => {
+                // Bids must also lock enough quote to pay the taker fee
+                let fee_factor = 1.0 + market.taker_fee.max(0) as f64 / FEE_SCALE;
+                let max_quote_lots = (price_lots as f64 * max_base_lots as f64 * fee_factor).ceil() as i64;
+                (
+                    0u8,
+                    associated_token_address(&owner.pubkey(), &market.quote_mint),
+                    market.market_quote_vault,
+                    max_quote_lots,
+                )
+            }
+            OrderSide::Sell => (
+                1u8,
+                associated_token_address(&owner.pubkey(), &market.base_mint),
+                market.market_base_vault,
+                i64::MAX,
+            ),
+        };
+
+        let mut data = PLACE_ORDER_DISCRIMINATOR.to_vec();
+        data.push(side);
+        data.extend_from_slice(&price_lots.to_le_bytes());
+        data.extend_from_slice(&max_base_lots.to_le_bytes());
+        data.extend_from_slice(&max_quote_lots.to_le_bytes());
+        data.extend_from_slice(&order.client_order_id.to_le_bytes());
+        data.push(if order.post_only { ORDER_TYPE_POST_ONLY } else { ORDER_TYPE_LIMIT });
+        data.extend_from_slice(&0u64.to_le_bytes()); // no expiry
+        data.push(SELF_TRADE_CANCEL_PROVIDE);
+        data.push(MATCH_LIMIT);
+
+        let instruction = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(owner.pubkey(), true),
+                AccountMeta::new(open_orders, false),
+                AccountMeta::new_readonly(self.program_id, false), // open_orders_admin
+                AccountMeta::new(user_token_account, false),
+                AccountMeta::new(market.address, false),
+                AccountMeta::new(market.bids, false),
+                AccountMeta::new(market.asks, false),
+                AccountMeta::new(market.event_heap, false),
+                AccountMeta::new(market_vault, false),
+                AccountMeta::new_readonly(self.optional(market.oracle_a), false),
+                AccountMeta::new_readonly(self.optional(market.oracle_b), false),
+                AccountMeta::new_readonly(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?, false),
+            ],
+            data,
+        };
+
+        let signature = self.send(owner, &[instruction], SigningPurpose::PlaceOrder).await?;
+        Ok(PlacedOrder {
+            client_order_id: order.client_order_id,
+            signature: signature.to_string(),
+        })
+    }
+
+    async fn cancel_order(&self, owner: &dyn TransactionSigner, market_pair: &str, order_id: u128) -> Result<Signature> {
+        let market = self.load_market(market_pair).await?;
+        let open_orders = self.open_orders_account(&owner.pubkey(), &market.address).await?;
+
+        let mut data = CANCEL_ORDER_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&order_id.to_le_bytes());
+
+        let instruction = Instruction {
+            program_id: self.program_id,
+            accounts: cancel_accounts(owner, open_orders, &market),
+            data,
+        };
+        self.send(owner, &[instruction], SigningPurpose::CancelOrder).await
+    }
+
+    async fn cancel_orders(&self, owner: &dyn TransactionSigner, market_pair: &str, order_ids: &[u128]) -> Result<Vec<Signature>> {
+        let market = self.load_market(market_pair).await?;
+        let open_orders = self.open_orders_account(&owner.pubkey(), &market.address).await?;
+
+        let mut signatures = Vec::new();
+        for batch in order_ids.chunks(CANCEL_BATCH_SIZE) {
+            let instructions: Vec<Instruction> = batch
+                .iter()
+                .map(|order_id| {
+                    let mut data = CANCEL_ORDER_DISCRIMINATOR.to_vec();
+                    data.extend_from_slice(&order_id.to_le_bytes());
+                    Instruction {
+                        program_id: self.program_id,
+                        accounts: cancel_accounts(owner, open_orders, &market),
+                        data,
+                    }
+                })
+                .collect();
+            signatures.push(self.send(owner, &instructions, SigningPurpose::CancelOrder).await?);
+        }
+        Ok(signatures)
+    }
+
+    async fn open_orders(&self, owner: &Pubkey, market_pair: &str) -> Result<Vec<OpenOrder>> {
+        let market = self.load_market(market_pair).await?;
+        let open_orders = self.open_orders_account(owner, &market.address).await?;
+        let data = self.rpc.get_account_data(&open_orders).await?;
+
+        // OpenOrdersAccount header and position precede 24 fixed 40-byte order slots
+        const ORDERS_OFFSET: usize = 304;
+        const SLOT_SIZE: usize = 40;
+        let mut orders = Vec::new();
+        for slot in 0..OPEN_ORDER_SLOTS {
+            let offset = ORDERS_OFFSET + slot * SLOT_SIZE;
+            if data.len() < offset + SLOT_SIZE {
+                break;
+            }
+            let is_free = data[offset + 32] != 0;
+            if is_free {
+                continue;
+            }
+
+            let side_and_tree = data[offset + 33];
+            orders.push(OpenOrder {
+                order_id: u128::from_le_bytes(data[offset..offset + 16].try_into()?),
+                client_order_id: u64::from_le_bytes(data[offset + 16..offset + 24].try_into()?),
+                side: if side_and_tree % 2 == 0 { OrderSide::Buy } else { OrderSide::Sell },
+                price: market.lots_to_price(read_i64(&data, offset + 24)),
+            });
+        }
+
+        Ok(orders)
+    }
+
+    async fn fills(&self, owner: &Pubkey, market_pair: &str) -> Result<Vec<VenueFill>> {
+        let market = self.load_market(market_pair).await?;
+        let open_orders = self.open_orders_account(owner, &market.address).await?;
+        let data = self.rpc.get_account_data(&market.event_heap).await?;
+        maker_fills(&market, &data, &open_orders)
+    }
+
+    async fn top_of_book(&self, market_pair: &str) -> Result<BookTop> {
+        let market = self.load_market(market_pair).await?;
+        let response = self
+            .rpc
+            .get_multiple_accounts_with_commitment(&[market.bids, market.asks], CommitmentConfig::confirmed())
+            .await?;
+        // The book is only as fresh as the slot the node read it at
+        let block_time = self.rpc.get_block_time(response.context.slot).await?;
+        let observed_at = Utc
+            .timestamp_opt(block_time, 0)
+            .single()
+            .ok_or_else(|| anyhow!("Invalid block time {}", block_time))?;
+
+        let (Some(Some(bids)), Some(Some(asks))) = (response.value.first(), response.value.get(1)) else {
+            return Err(anyhow!("Order book accounts of {} are missing", market_pair));
+        };
+        let now_ts = block_time.max(0) as u64;
+        let best_bid = book_side_prices(&bids.data, now_ts)?.into_iter().max();
+        let best_ask = book_side_prices(&asks.data, now_ts)?.into_iter().min();
+
+        Ok(BookTop {
+            best_bid: best_bid.map(|lots| market.lots_to_price(lots)),
+            best_ask: best_ask.map(|lots| market.lots_to_price(lots)),
+            observed_at,
+        })
+    }
+
+    async fn settle_funds(&self, owner: &dyn TransactionSigner, market_pair: &str) -> Result<Signature> {
+        let market = self.load_market(market_pair).await?;
+        let open_orders = self.open_orders_account(&owner.pubkey(), &market.address).await?;
+
+        let instruction = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(owner.pubkey(), true),
+                AccountMeta::new(owner.pubkey(), true), // penalty_payer
+                AccountMeta::new(open_orders, false),
+                AccountMeta::new(market.address, false),
+                AccountMeta::new_readonly(market.market_authority, false),
+                AccountMeta::new(market.market_base_vault, false),
+                AccountMeta::new(market.market_quote_vault, false),
+                AccountMeta::new(associated_token_address(&owner.pubkey(), &market.base_mint), false),
+                AccountMeta::new(associated_token_address(&owner.pubkey(), &market.quote_mint), false),
+                AccountMeta::new_readonly(self.program_id, false), // referrer_account
+                AccountMeta::new_readonly(Pubkey::from_str(SPL_TOKEN_PROGRAM_ID)?, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: SETTLE_FUNDS_DISCRIMINATOR.to_vec(),
+        };
+        self.send(owner, &[instruction], SigningPurpose::SettleFunds).await
+    }
+}
+
+fn cancel_accounts(owner: &dyn TransactionSigner, open_orders: Pubkey, market: &MarketState) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(owner.pubkey(), true),
+        AccountMeta::new(open_orders, false),
+        AccountMeta::new_readonly(market.address, false),
+        AccountMeta::new(market.bids, false),
+        AccountMeta::new(market.asks, false),
+    ]
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Pubkey {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&data[offset..offset + 32]);
+    Pubkey::new_from_array(bytes)
+}
+
+fn read_i64(data: &[u8], offset: usize) -> i64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    i64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SOL/USDC: 0.001 SOL base lots and 0.000001 USDC quote lots
+    fn market() -> MarketState {
+        MarketState {
+            address: Pubkey::new_unique(),
+            base_decimals: 9,
+            quote_decimals: 6,
+            market_authority: Pubkey::new_unique(),
+            bids: Pubkey::new_unique(),
+            asks: Pubkey::new_unique(),
+            event_heap: Pubkey::new_unique(),
+            oracle_a: Pubkey::default(),
+            oracle_b: Pubkey::default(),
+            quote_lot_size: 1,
+            base_lot_size: 1_000_000,
+            taker_fee: 0,
+            base_mint: Pubkey::new_unique(),
+            quote_mint: Pubkey::new_unique(),
+            market_base_vault: Pubkey::new_unique(),
+            market_quote_vault: Pubkey::new_unique(),
+        }
+    }
+
+    fn book_side() -> Vec<u8> {
+        vec![0u8; BOOK_NODES_OFFSET + BOOK_MAX_NODES * BOOK_NODE_SIZE]
+    }
+
+    fn set_root(data: &mut [u8], root: u32, leaf_count: u32) {
+        data[BOOK_FIXED_ROOT_OFFSET..BOOK_FIXED_ROOT_OFFSET + 4].copy_from_slice(&root.to_le_bytes());
+        data[BOOK_FIXED_ROOT_OFFSET + 4..BOOK_FIXED_ROOT_OFFSET + 8].copy_from_slice(&leaf_count.to_le_bytes());
+    }
+
+    fn book_node(data: &mut [u8], handle: usize) -> &mut [u8] {
+        &mut data[BOOK_NODES_OFFSET + handle * BOOK_NODE_SIZE..][..BOOK_NODE_SIZE]
+    }
+
+    fn set_inner(data: &mut [u8], handle: usize, children: [u32; 2]) {
+        let node = book_node(data, handle);
+        node[0] = NODE_TAG_INNER;
+        node[24..28].copy_from_slice(&children[0].to_le_bytes());
+        node[28..32].copy_from_slice(&children[1].to_le_bytes());
+    }
+
+    fn set_leaf(data: &mut [u8], handle: usize, price_lots: i64, time_in_force: u16, timestamp: u64) {
+        let node = book_node(data, handle);
+        node[0] = NODE_TAG_LEAF;
+        node[2..4].copy_from_slice(&time_in_force.to_le_bytes());
+        node[8..24].copy_from_slice(&(((price_lots as u128) << 64) | 7).to_le_bytes());
+        node[64..72].copy_from_slice(&timestamp.to_le_bytes());
+    }
+
+    // Uses the node index as the event's sequence number
+    fn set_fill_event(data: &mut [u8], index: usize, next: u16, maker: &Pubkey, price_lots: i64, quantity: i64, client_order_id: u64) {
+        let node = &mut data[EVENT_HEAP_NODES_OFFSET + index * EVENT_NODE_SIZE..][..EVENT_NODE_SIZE];
+        node[0..2].copy_from_slice(&next.to_le_bytes());
+        let event = &mut node[8..];
+        event[0] = EVENT_TYPE_FILL;
+        event[16..24].copy_from_slice(&(index as u64).to_le_bytes());
+        event[24..56].copy_from_slice(maker.as_ref());
+        event[104..112].copy_from_slice(&price_lots.to_le_bytes());
+        event[120..128].copy_from_slice(&quantity.to_le_bytes());
+        event[128..136].copy_from_slice(&client_order_id.to_le_bytes());
+    }
+
+    #[test]
+    fn prices_convert_to_lots_and_back() {
+        let market = market();
+        assert_eq!(market.price_to_lots(150.0), 150_000);
+        assert!((market.lots_to_price(150_000) - 150.0).abs() < 1e-9);
+        assert!((market.lots_to_price(market.price_to_lots(123.4567)) - 123.457).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sizes_round_down_to_whole_base_lots() {
+        let market = market();
+        assert_eq!(market.size_to_base_lots(2.5), 2_500);
+        assert_eq!(market.size_to_base_lots(0.0015), 1);
+        assert!((market.base_lots_to_size(2_500) - 2.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn book_side_lists_unexpired_leaves() {
+        let mut data = book_side();
+        set_root(&mut data, 0, 3);
+        set_inner(&mut data, 0, [1, 2]);
+        set_inner(&mut data, 2, [3, 4]);
+        set_leaf(&mut data, 1, 150_000, 0, 50);
+        set_leaf(&mut data, 3, 151_000, 0, 50);
+        // Expired at 110
+        set_leaf(&mut data, 4, 152_000, 10, 100);
+
+        let mut prices = book_side_prices(&data, 200).unwrap();
+        prices.sort();
+        assert_eq!(prices, vec![150_000, 151_000]);
+    }
+
+    #[test]
+    fn empty_book_side_has_no_prices() {
+        assert!(book_side_prices(&book_side(), 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn cyclic_order_tree_is_rejected() {
+        let mut data = book_side();
+        set_root(&mut data, 0, 1);
+        set_inner(&mut data, 0, [0, 0]);
+        assert!(book_side_prices(&data, 0).is_err());
+    }
+
+    #[test]
+    fn short_book_side_is_rejected() {
+        assert!(book_side_prices(&[0u8; 64], 0).is_err());
+    }
+
+    #[test]
+    fn maker_fills_keep_only_our_fills_in_heap_order() {
+        let market = market();
+        let (ours, theirs) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = vec![0u8; EVENT_HEAP_NODES_OFFSET + EVENT_HEAP_MAX_EVENTS * EVENT_NODE_SIZE];
+        let header = &mut data[EVENT_HEAP_HEADER_OFFSET..EVENT_HEAP_NODES_OFFSET];
+        header[2..4].copy_from_slice(&5u16.to_le_bytes());
+        header[4..6].copy_from_slice(&3u16.to_le_bytes());
+        set_fill_event(&mut data, 5, 2, &ours, 150_000, 1_500, 9);
+        set_fill_event(&mut data, 2, 8, &theirs, 150_100, 700, 10);
+        set_fill_event(&mut data, 8, 0, &ours, 149_900, 250, 11);
+
+        let fills = maker_fills(&market, &data, &ours).unwrap();
+        assert_eq!(fills.len(), 2);
+        assert_eq!(fills[0].fill_id, "5");
+        assert_eq!(fills[0].client_order_id, 9);
+        assert!((fills[0].price - 150.0).abs() < 1e-9);
+        assert!((fills[0].size - 1.5).abs() < 1e-12);
+        assert_eq!(fills[1].fill_id, "8");
+        assert!((fills[1].size - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn maker_fills_reject_links_outside_the_heap() {
+        let mut data = vec![0u8; EVENT_HEAP_NODES_OFFSET + EVENT_HEAP_MAX_EVENTS * EVENT_NODE_SIZE];
+        let header = &mut data[EVENT_HEAP_HEADER_OFFSET..EVENT_HEAP_NODES_OFFSET];
+        header[2..4].copy_from_slice(&0u16.to_le_bytes());
+        header[4..6].copy_from_slice(&2u16.to_le_bytes());
+        set_fill_event(&mut data, 0, EVENT_HEAP_MAX_EVENTS as u16, &Pubkey::new_unique(), 1, 1, 1);
+        assert!(maker_fills(&market(), &data, &Pubkey::new_unique()).is_err());
+    }
+
+    fn indexer(count: u32, listed: &[Pubkey]) -> Vec<u8> {
+        let mut data = vec![0u8; 13];
+        data.extend_from_slice(&count.to_le_bytes());
+        for key in listed {
+            data.extend_from_slice(key.as_ref());
+        }
+        data
+    }
+
+    #[test]
+    fn indexer_lists_its_open_orders_accounts() {
+        let keys = [Pubkey::new_unique(), Pubkey::new_unique()];
+        assert_eq!(indexed_open_orders(&indexer(2, &keys)), Some(keys.to_vec()));
+        assert_eq!(indexed_open_orders(&indexer(0, &[])), Some(vec![]));
+    }
+
+    #[test]
+    fn indexer_shorter_than_its_count_is_rejected() {
+        let keys = [Pubkey::new_unique(), Pubkey::new_unique()];
+        assert_eq!(indexed_open_orders(&indexer(3, &keys)), None);
+        assert_eq!(indexed_open_orders(&indexer(u32::MAX, &keys)), None);
+        assert_eq!(indexed_open_orders(&[0u8; 10]), None);
+    }
+}
@@ -0,0 +1,551 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use mongodb::Database;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{BookTop, ExecutionVenue, LimitOrder, OpenOrder, OrderSide, PlacedOrder, VenueFill};
+use crate::models::order::{Order, OrderEvent};
+use crate::models::orderbook::{OrderBook, OrderBookEntry};
+use crate::models::strategy_volume::StrategyVolume;
+use crate::models::transaction::Transaction;
+use crate::services::signer::TransactionSigner;
+
+pub const PAPER: &str = "paper";
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PaperFill {
+    pub order_id: u128,
+    pub side: OrderSide,
+    pub price: f64,
+    pub size: f64,
+    pub fee: f64,
+    pub liquidity: Liquidity,
+}
+
+#[derive(Debug, Clone)]
+struct RestingOrder {
+    order_id: u128,
+    owner: Pubkey,
+    order: LimitOrder,
+    remaining: f64,
+}
+
+#[derive(Debug, Default)]
+struct PaperBook {
+    resting: Vec<RestingOrder>,
+    // Latest snapshot resting orders were matched against, less what they took from it, so an
+    // unchanged snapshot is not filled against twice
+    liquidity: Option<OrderBook>,
+}
+
+/// Simulated venue that matches our orders against `OrderBook` snapshots instead of a real market.
+/// Fills are written as simulated `Transaction`s so the rest of the pipeline treats them like real trades.
+pub struct PaperVenue {
+    db: Database,
+    maker_fee_bps: f64,
+    taker_fee_bps: f64,
+    books: Mutex<HashMap<String, PaperBook>>,
+    next_order_id: Mutex<u128>,
+}
+
+impl PaperVenue {
+    pub fn new(db: Database, maker_fee_bps: f64, taker_fee_bps: f64) -> Self {
+        Self {
+            db,
+            maker_fee_bps,
+            taker_fee_bps,
+            books: Mutex::new(HashMap::new()),
+            next_order_id: Mutex::new(1),
+        }
+    }
+
+    async fn snapshot(&self, market_pair: &str) -> Result<OrderBook> {
+        OrderBook::get_order_book(&self.db, market_pair).await
+    }
+
+    /// Average price a market order of `size` would get against the current snapshot.
+    pub async fn estimate_market_price(&self, market_pair: &str, side: OrderSide, size: f64) -> Result<f64> {
+        let snapshot = self.snapshot(market_pair).await?;
+        let fills = take_all(&snapshot, side, size)
+            .ok_or_else(|| anyhow!("Not enough liquidity in {} to fill {}", market_pair, size))?;
+        average_price(&fills).ok_or_else(|| anyhow!("Not enough liquidity in {} to fill {}", market_pair, size))
+    }
+
+    /// Fills a market order immediately against the current snapshot, unless the snapshot cannot
+    /// fill all of it or its average price would be more than `max_slippage_bps` worse than the mid.
+    pub async fn execute_market(
+        &self,
+        user_id: Uuid,
+        wallet_id: Uuid,
+        strategy_id: Option<Uuid>,
+        market_pair: &str,
+        side: OrderSide,
+        size: f64,
+        max_slippage_bps: u16,
+        parent_order_id: Option<Uuid>,
+    ) -> Result<Transaction> {
+        let snapshot = self.snapshot(market_pair).await?;
+        let mid = book_top(&snapshot).mid()
+            .ok_or_else(|| anyhow!("Order book for {} is empty", market_pair))?;
+        // Nothing is filled rather than part of the order, which the caller reserved volume for in full
+        let fills = take_all(&snapshot, side, size)
+            .ok_or_else(|| anyhow!("Not enough liquidity in {} to fill {}", market_pair, size))?;
+        let filled: f64 = fills.iter().map(|(_, s)| s).sum();
+        let price = average_price(&fills)
+            .ok_or_else(|| anyhow!("Not enough liquidity in {} to fill {}", market_pair, size))?;
+
+        let slippage_bps = match side {
+            OrderSide::Buy => (price - mid) / mid * 10_000.0,
+            OrderSide::Sell => (mid - price) / mid * 10_000.0,
+        };
+        if slippage_bps > max_slippage_bps as f64 {
+            return Err(anyhow!(
+                "Filling {} would slip {:.1} bps from the mid, beyond the {} bps limit",
+                size, slippage_bps, max_slippage_bps
+            ));
+        }
+        let fee = price * filled * self.taker_fee_bps / 10_000.0;
+
+        let transaction = Transaction::create_simulated(
+            &self.db,
+            user_id,
+            wallet_id,
+            strategy_id,
+            side_action(side),
+            filled,
+            base_symbol(market_pair),
+            price,
+            fee,
+            parent_order_id,
+        )
+        .await?;
+        Ok(transaction)
+    }
+
+    /// Crosses resting orders against the latest snapshot, filling any the market has traded through
+    /// up to the size the snapshot shows at prices they cross.
+    pub async fn match_resting(&self, market_pair: &str) -> Result<Vec<PaperFill>> {
+        let snapshot = self.snapshot(market_pair).await?;
+
+        let matched = {
+            let mut books = self.books.lock().await;
+            let book = books.entry(market_pair.to_string()).or_default();
+            let mut liquidity = match book.liquidity.take() {
+                Some(depleted) if depleted.timestamp == snapshot.timestamp => depleted,
+                _ => snapshot,
+            };
+            let matched = match_orders(&mut book.resting, &mut liquidity);
+            book.liquidity = Some(liquidity);
+            matched
+        };
+
+        // Resting orders fill at their own price as the maker
+        let mut fills = Vec::new();
+        for (resting, size) in matched {
+            let fill = PaperFill {
+                order_id: resting.order_id,
+                side: resting.order.side,
+                price: resting.order.price,
+                size,
+                fee: resting.order.price * size * self.maker_fee_bps / 10_000.0,
+                liquidity: Liquidity::Maker,
+            };
+            self.record_fill(&resting.owner, &resting.order, &fill).await?;
+            fills.push(fill);
+        }
+
+        Ok(fills)
+    }
+
+    async fn record_fill(&self, owner: &Pubkey, order: &LimitOrder, fill: &PaperFill) -> Result<Transaction> {
+        let transaction = Transaction::create_simulated(
+            &self.db,
+            order.user_id,
+            order.wallet_id,
+            order.strategy_id,
+            side_action(fill.side),
+            fill.size,
+            base_symbol(&order.market_pair),
+            fill.price,
+            fill.fee,
+            None,
+        )
+        .await?;
+
+        if let Some(strategy_id) = order.strategy_id {
+            let day = StrategyVolume::day_key(transaction.created_at);
+            StrategyVolume::record(&self.db, strategy_id, order.user_id, &day, transaction.notional()).await?;
+        }
+
+        // Report the fill against the order it belongs to
+        if let Some(record) = Order::find_by_client_order_id(&self.db, &owner.to_string(), PAPER, order.client_order_id).await? {
+            let event = OrderEvent::Fill { size: fill.size, price: fill.price, fill_id: None };
+            Order::apply_event(&self.db, &record, &event).await?;
+        }
+
+        Ok(transaction)
+    }
+
+    async fn next_order_id(&self) -> u128 {
+        let mut next = self.next_order_id.lock().await;
+        let id = *next;
+        *next += 1;
+        id
+    }
+}
+
+#[async_trait]
+impl ExecutionVenue for PaperVenue {
+    fn name(&self) -> &'static str {
+        PAPER
+    }
+
+    async fn place_limit_order(&self, owner: &dyn TransactionSigner, order: &LimitOrder) -> Result<PlacedOrder> {
+        let snapshot = self.snapshot(&order.market_pair).await?;
+        let order_id = self.next_order_id().await;
+
+        // Take whatever the snapshot offers at or better than our limit
+        let taken = take_liquidity(&snapshot, order.side, Some(order.price), order.size);
+        if order.post_only && !taken.is_empty() {
+            return Err(anyhow!("Post-only order would cross the book"));
+        }
+
+        let filled: f64 = taken.iter().map(|(_, s)| s).sum();
+        if let Some(price) = average_price(&taken) {
+            let fill = PaperFill {
+                order_id,
+                side: order.side,
+                price,
+                size: filled,
+                fee: price * filled * self.taker_fee_bps / 10_000.0,
+                liquidity: Liquidity::Taker,
+            };
+            self.record_fill(&owner.pubkey(), order, &fill).await?;
+        }
+
+        let remaining = order.size - filled;
+        if remaining > f64::EPSILON {
+            let mut books = self.books.lock().await;
+            books.entry(order.market_pair.clone()).or_default().resting.push(RestingOrder {
+                order_id,
+                owner: owner.pubkey(),
+                order: order.clone(),
+                remaining,
+            });
+        }
+
+        Ok(PlacedOrder {
+            client_order_id: order.client_order_id,
+            signature: format!("paper-{}", order_id),
+        })
+    }
+
+    async fn cancel_order(&self, owner: &dyn TransactionSigner, market_pair: &str, order_id: u128) -> Result<Signature> {
+        let mut books = self.books.lock().await;
+        if let Some(book) = books.get_mut(market_pair) {
+            book.resting
+                .retain(|resting| !(resting.order_id == order_id && resting.owner == owner.pubkey()));
+        }
+        Ok(Signature::default())
+    }
+
+    async fn cancel_orders(&self, owner: &dyn TransactionSigner, market_pair: &str, order_ids: &[u128]) -> Result<Vec<Signature>> {
+        let mut books = self.books.lock().await;
+        if let Some(book) = books.get_mut(market_pair) {
+            book.resting
+                .retain(|resting| !(order_ids.contains(&resting.order_id) && resting.owner == owner.pubkey()));
+        }
+        Ok(vec![Signature::default()])
+    }
+
+    async fn open_orders(&self, owner: &Pubkey, market_pair: &str) -> Result<Vec<OpenOrder>> {
+        self.match_resting(market_pair).await?;
+
+        let books = self.books.lock().await;
+        let orders = books
+            .get(market_pair)
+            .map(|book| {
+                book.resting
+                    .iter()
+                    .filter(|resting| resting.owner == *owner)
+                    .map(|resting| OpenOrder {
+                        order_id: resting.order_id,
+                        client_order_id: resting.order.client_order_id,
+                        side: resting.order.side,
+                        price: resting.order.price,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(orders)
+    }
+
+    async fn fills(&self, _owner: &Pubkey, _market_pair: &str) -> Result<Vec<VenueFill>> {
+        // Paper fills are applied to their orders as they happen
+        Ok(Vec::new())
+    }
+
+    async fn top_of_book(&self, market_pair: &str) -> Result<BookTop> {
+        Ok(book_top(&self.snapshot(market_pair).await?))
+    }
+
+    async fn settle_funds(&self, _owner: &dyn TransactionSigner, _market_pair: &str) -> Result<Signature> {
+        // Simulated fills never leave funds in the venue
+        Ok(Signature::default())
+    }
+}
+
+fn book_top(snapshot: &OrderBook) -> BookTop {
+    BookTop {
+        best_bid: snapshot.bids.iter().map(|e| e.price).reduce(f64::max),
+        best_ask: snapshot.asks.iter().map(|e| e.price).reduce(f64::min),
+        observed_at: snapshot.timestamp,
+    }
+}
+
+/// Walks the opposite side of `snapshot` best price first, up to `size` and an optional limit price.
+fn take_liquidity(snapshot: &OrderBook, side: OrderSide, limit: Option<f64>, size: f64) -> Vec<(f64, f64)> {
+    let mut levels: Vec<&OrderBookEntry> = match side {
+        OrderSide::Buy => snapshot.asks.iter().collect(),
+        OrderSide::Sell => snapshot.bids.iter().collect(),
+    };
+    match side {
+        OrderSide::Buy => levels.sort_by(|a, b| a.price.total_cmp(&b.price)),
+        OrderSide::Sell => levels.sort_by(|a, b| b.price.total_cmp(&a.price)),
+    }
+
+    let mut remaining = size;
+    let mut fills = Vec::new();
+    for level in levels {
+        if remaining <= f64::EPSILON {
+            break;
+        }
+        let marketable = match (side, limit) {
+            (_, None) => true,
+            (OrderSide::Buy, Some(limit)) => level.price <= limit,
+            (OrderSide::Sell, Some(limit)) => level.price >= limit,
+        };
+        if !marketable {
+            break;
+        }
+        let taken = remaining.min(level.size);
+        fills.push((level.price, taken));
+        remaining -= taken;
+    }
+
+    fills
+}
+
+/// Like `take_liquidity` without a limit, but `None` unless the snapshot can fill all of `size`.
+fn take_all(snapshot: &OrderBook, side: OrderSide, size: f64) -> Option<Vec<(f64, f64)>> {
+    let fills = take_liquidity(snapshot, side, None, size);
+    let filled: f64 = fills.iter().map(|(_, s)| s).sum();
+    (filled >= size - size * 1e-9).then_some(fills)
+}
+
+/// Fills `resting` orders against `liquidity`, best priced first, each for no more than is left at
+/// prices it crosses. What they take is removed from `liquidity` and fully filled orders from
+/// `resting`. Returns the orders that filled, as they were before, with the size each filled.
+fn match_orders(resting: &mut Vec<RestingOrder>, liquidity: &mut OrderBook) -> Vec<(RestingOrder, f64)> {
+    let mut priority: Vec<usize> = (0..resting.len()).collect();
+    // Stable, so orders at the same price keep their time priority
+    priority.sort_by(|a, b| {
+        let key = |order: &RestingOrder| match order.order.side {
+            OrderSide::Buy => -order.order.price,
+            OrderSide::Sell => order.order.price,
+        };
+        key(&resting[*a]).total_cmp(&key(&resting[*b]))
+    });
+
+    let mut matched = Vec::new();
+    for index in priority {
+        let order = &mut resting[index];
+        let taken = take_liquidity(liquidity, order.order.side, Some(order.order.price), order.remaining);
+        let size: f64 = taken.iter().map(|(_, s)| s).sum();
+        if size <= f64::EPSILON {
+            continue;
+        }
+        consume(liquidity, order.order.side, &taken);
+        matched.push((order.clone(), size));
+        order.remaining -= size;
+    }
+
+    resting.retain(|order| order.remaining > f64::EPSILON);
+    matched
+}
+
+/// Removes liquidity `taken` by an order on `side` from the levels it was taken from.
+fn consume(liquidity: &mut OrderBook, side: OrderSide, taken: &[(f64, f64)]) {
+    let levels = match side {
+        OrderSide::Buy => &mut liquidity.asks,
+        OrderSide::Sell => &mut liquidity.bids,
+    };
+    for &(price, size) in taken {
+        let mut left = size;
+        for level in levels.iter_mut().filter(|level| level.price == price) {
+            let used = left.min(level.size);
+            level.size -= used;
+            level.total = level.price * level.size;
+            left -= used;
+        }
+    }
+    levels.retain(|level| level.size > f64::EPSILON);
+}
+
+fn average_price(fills: &[(f64, f64)]) -> Option<f64> {
+    let size: f64 = fills.iter().map(|(_, s)| s).sum();
+    if size <= 0.0 {
+        return None;
+    }
+    Some(fills.iter().map(|(p, s)| p * s).sum::<f64>() / size)
+}
+
+fn side_action(side: OrderSide) -> &'static str {
+    match side {
+        OrderSide::Buy => "buy",
+        OrderSide::Sell => "sell",
+    }
+}
+
+// "SOL/USDC" trades SOL
+fn base_symbol(market_pair: &str) -> &str {
+    market_pair.split('/').next().unwrap_or(market_pair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn entry(price: f64, size: f64) -> OrderBookEntry {
+        OrderBookEntry { price, size, total: price * size, is_bot: false }
+    }
+
+    // Levels are deliberately out of order
+    fn book() -> OrderBook {
+        OrderBook {
+            market_pair: "SOL/USDC".to_string(),
+            bids: vec![entry(99.0, 2.0), entry(100.0, 1.0), entry(98.0, 5.0)],
+            asks: vec![entry(102.0, 2.0), entry(101.0, 1.0), entry(103.0, 5.0)],
+            last_price: 100.5,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn buy_walks_the_asks_from_the_lowest() {
+        let fills = take_liquidity(&book(), OrderSide::Buy, None, 2.5);
+        assert_eq!(fills, vec![(101.0, 1.0), (102.0, 1.5)]);
+        let expected = (101.0 * 1.0 + 102.0 * 1.5) / 2.5;
+        assert!((average_price(&fills).unwrap() - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sell_walks_the_bids_from_the_highest() {
+        let fills = take_liquidity(&book(), OrderSide::Sell, None, 3.0);
+        assert_eq!(fills, vec![(100.0, 1.0), (99.0, 2.0)]);
+    }
+
+    #[test]
+    fn limit_price_stops_the_walk() {
+        assert_eq!(take_liquidity(&book(), OrderSide::Buy, Some(101.5), 5.0), vec![(101.0, 1.0)]);
+        assert_eq!(take_liquidity(&book(), OrderSide::Sell, Some(99.0), 5.0), vec![(100.0, 1.0), (99.0, 2.0)]);
+        assert!(take_liquidity(&book(), OrderSide::Buy, Some(100.0), 5.0).is_empty());
+    }
+
+    #[test]
+    fn order_larger_than_the_book_takes_all_of_it() {
+        let fills = take_liquidity(&book(), OrderSide::Buy, None, 100.0);
+        let filled: f64 = fills.iter().map(|(_, s)| s).sum();
+        assert_eq!(fills.len(), 3);
+        assert_eq!(filled, 8.0);
+    }
+
+    #[test]
+    fn market_orders_are_filled_in_full_or_not_at_all() {
+        assert_eq!(take_all(&book(), OrderSide::Buy, 2.0), Some(vec![(101.0, 1.0), (102.0, 1.0)]));
+        assert_eq!(take_all(&book(), OrderSide::Sell, 8.0).map(|fills| fills.len()), Some(3));
+        assert_eq!(take_all(&book(), OrderSide::Sell, 8.5), None);
+    }
+
+    fn resting(order_id: u128, side: OrderSide, price: f64, size: f64) -> RestingOrder {
+        RestingOrder {
+            order_id,
+            owner: Pubkey::new_unique(),
+            order: LimitOrder {
+                market_pair: "SOL/USDC".to_string(),
+                side,
+                price,
+                size,
+                client_order_id: order_id as u64,
+                post_only: true,
+                user_id: Uuid::new_v4(),
+                wallet_id: Uuid::new_v4(),
+                strategy_id: None,
+            },
+            remaining: size,
+        }
+    }
+
+    fn filled(matched: &[(RestingOrder, f64)]) -> Vec<(u128, f64)> {
+        matched.iter().map(|(order, size)| (order.order_id, *size)).collect()
+    }
+
+    #[test]
+    fn resting_orders_fill_only_what_the_book_shows() {
+        // Asks of 1 at 101 and 2 at 102 cross a bid at 102 for 5
+        let mut orders = vec![resting(1, OrderSide::Buy, 102.0, 5.0)];
+        let mut liquidity = book();
+
+        let matched = match_orders(&mut orders, &mut liquidity);
+
+        assert_eq!(filled(&matched), vec![(1, 3.0)]);
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].remaining, 2.0);
+        assert_eq!(liquidity.asks.iter().map(|level| level.price).collect::<Vec<_>>(), vec![103.0]);
+
+        // The same snapshot has nothing more to give
+        assert!(match_orders(&mut orders, &mut liquidity).is_empty());
+    }
+
+    #[test]
+    fn better_priced_orders_take_the_liquidity_first() {
+        let mut orders = vec![
+            resting(1, OrderSide::Sell, 99.0, 2.0),
+            resting(2, OrderSide::Sell, 98.5, 2.0),
+            resting(3, OrderSide::Sell, 105.0, 1.0),
+        ];
+        let mut liquidity = book();
+
+        let matched = match_orders(&mut orders, &mut liquidity);
+
+        // Bids of 1 at 100 and 2 at 99 go to the lowest ask first; the order at 105 is not crossed
+        assert_eq!(filled(&matched), vec![(2, 2.0), (1, 1.0)]);
+        assert_eq!(orders.iter().map(|o| (o.order_id, o.remaining)).collect::<Vec<_>>(), vec![(1, 1.0), (3, 1.0)]);
+    }
+
+    #[test]
+    fn no_fills_have_no_average_price() {
+        assert_eq!(average_price(&[]), None);
+    }
+
+    #[test]
+    fn book_top_reads_the_best_levels() {
+        let top = book_top(&book());
+        assert_eq!(top.best_bid, Some(100.0));
+        assert_eq!(top.best_ask, Some(101.0));
+        assert_eq!(top.mid(), Some(100.5));
+    }
+}
@@ -1,11 +1,18 @@
 use mongodb::Database;
 use uuid::Uuid;
-use solana_client::rpc_client::RpcClient;
+use solana_client::nonblocking::rpc_client::RpcClient as NonblockingRpcClient;
 use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
 
-use crate::models::wallet::Wallet;
+use crate::models::keystore::SealedKey;
+use crate::api::wallets::UpdateWalletRequest;
+use crate::models::wallet::{Wallet, WalletStatus};
+use crate::models::wallet_allocation::{WalletAllocation, ALLOCATION_TOLERANCE};
+use crate::services::market_maker::MarketMakingEngine;
+use crate::services::portfolio::is_empty;
 use crate::utils::errors::ServiceError;
 use crate::config::Config;
+use crate::utils::tokens::{associated_token_address, from_base_units, token_by_mint, TokenInfo, NATIVE_MINT};
 
 pub async fn get_wallets(
     db: &Database,
@@ -27,27 +34,164 @@ pub async fn add_wallet(
         return Err(ServiceError::BadRequest("Invalid Solana address".into()));
     }
     
+    let allocation = allocation_percentage.unwrap_or(0.0);
+    reserve_allocation(db, user_id, 0.0, allocation).await?;
+    
     // Create the wallet
-    let wallet = Wallet::create(db, user_id, address, label, allocation_percentage).await?;
-    Ok(wallet)
+    match Wallet::create(db, user_id, address, label, allocation_percentage, None).await {
+        Ok(wallet) => Ok(wallet),
+        Err(e) => {
+            WalletAllocation::release(db, user_id, allocation).await?;
+            Err(e.into())
+        }
+    }
+}
+
+pub async fn update_wallet(
+    db: &Database,
+    engine: &MarketMakingEngine,
+    user_id: Uuid,
+    wallet_id: Uuid,
+    req: UpdateWalletRequest,
+) -> Result<Wallet, ServiceError> {
+    // Check if wallet exists and belongs to user
+    let wallet = Wallet::find_by_id(db, wallet_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Wallet not found".into()))?;
+    
+    if let Some(status) = req.status {
+        if status != wallet.status && !wallet.status.can_transition_to(status) {
+            return Err(ServiceError::BadRequest(format!(
+                "A {} wallet cannot become {}",
+                wallet.status.as_str(),
+                status.as_str()
+            )));
+        }
+    }
+    
+    // Archived wallets give up their allocation
+    let status = req.status.unwrap_or(wallet.status);
+    let mut allocation_percentage = req.allocation_percentage;
+    if status == WalletStatus::Archived {
+        if allocation_percentage.map_or(false, |a| a > 0.0) {
+            return Err(ServiceError::BadRequest("Archived wallets cannot have an allocation".into()));
+        }
+        allocation_percentage = Some(0.0);
+    }
+    if let Some(allocation) = allocation_percentage {
+        let previous = wallet.allocation_percentage;
+        reserve_allocation(db, user_id, previous, allocation).await?;
+        // Only the update that still sees the allocation it was checked against may write it
+        let written = Wallet::set_allocation(db, wallet_id, user_id, previous, allocation).await;
+        if !matches!(written, Ok(true)) {
+            WalletAllocation::release(db, user_id, allocation - previous).await?;
+            written?;
+            return Err(ServiceError::Conflict("The wallet's allocation changed concurrently; try again".into()));
+        }
+    }
+    
+    let updated = Wallet::update(
+        db,
+        wallet_id,
+        user_id,
+        req.label.as_deref(),
+        None,
+        req.status,
+    ).await?
+        .ok_or_else(|| ServiceError::NotFound("Wallet not found".into()))?;
+    
+    // Move strategies off a wallet that stopped being active, or back onto one, now rather than on the next tick
+    if status != wallet.status {
+        engine.sync_workers().await?;
+    }
+    
+    Ok(updated)
+}
+
+/// Refuses an allocation outside 0-100%, or a change from `previous` to `allocation` that would
+/// take the user's combined allocation, now `allocated`, past 100%. Changes that do not raise the
+/// total are always allowed, so a user who is over 100% can work their way back down.
+pub fn validate_allocation(allocated: f64, previous: f64, allocation: f64) -> Result<(), ServiceError> {
+    if !(0.0..=100.0).contains(&allocation) {
+        return Err(ServiceError::BadRequest("Allocation percentage must be between 0 and 100".into()));
+    }
+    
+    let others = allocated - previous;
+    if allocation > previous && others + allocation > 100.0 + ALLOCATION_TOLERANCE {
+        return Err(ServiceError::BadRequest(format!(
+            "Allocations would total {:.2}%; only {:.2}% is left to allocate",
+            others + allocation,
+            (100.0 - others).max(0.0)
+        )));
+    }
+    Ok(())
+}
+
+/// Claims the change from `previous` to `allocation` against the user's combined allocation,
+/// atomically, so concurrent requests cannot together take it past 100%. Give it back with
+/// `WalletAllocation::release` if the wallets it was for are not written.
+pub async fn reserve_allocation(
+    db: &Database,
+    user_id: Uuid,
+    previous: f64,
+    allocation: f64,
+) -> Result<(), ServiceError> {
+    let allocated = match WalletAllocation::find_by_user(db, user_id).await? {
+        Some(total) => total.allocated,
+        None => Wallet::total_allocation(db, user_id, None).await?,
+    };
+    validate_allocation(allocated, previous, allocation)?;
+    
+    if !WalletAllocation::reserve(db, user_id, allocation - previous).await? {
+        // Another request took the room between the check and the reservation
+        let allocated = WalletAllocation::find_by_user(db, user_id).await?.map_or(allocated, |t| t.allocated);
+        validate_allocation(allocated, previous, allocation)?;
+        return Err(ServiceError::Conflict("Allocations changed concurrently; try again".into()));
+    }
+    Ok(())
 }
 
+/// Refuses to trade from a wallet that is paused, draining or archived.
+pub fn ensure_wallet_active(wallet: &Wallet) -> Result<(), ServiceError> {
+    if wallet.status != WalletStatus::Active {
+        return Err(ServiceError::BadRequest(format!(
+            "Wallet is {}; only active wallets can trade",
+            wallet.status.as_str()
+        )));
+    }
+    Ok(())
+}
+
+/// Deletes an archived wallet along with its sealed key. Only empty wallets can go, since their
+/// funds could not be moved again once the key is gone.
 pub async fn remove_wallet(
     db: &Database,
+    config: &Config,
     user_id: Uuid,
     wallet_id: Uuid,
 ) -> Result<(), ServiceError> {
     // Check if wallet exists and belongs to user
     let wallet = Wallet::find_by_id(db, wallet_id, user_id).await?
         .ok_or_else(|| ServiceError::NotFound("Wallet not found".into()))?;
+    if wallet.status != WalletStatus::Archived {
+        return Err(ServiceError::BadRequest("Only archived wallets can be removed".into()));
+    }
+    let client = NonblockingRpcClient::new(config.solana_rpc_url.clone());
+    if !is_empty(&client, &wallet.address).await? {
+        return Err(ServiceError::BadRequest("Wallet still holds funds; move them out before removing it".into()));
+    }
     
-    // Delete the wallet
+    // Key first, so a failure part way never leaves a key behind for a wallet that is gone
+    SealedKey::delete(db, wallet_id).await?;
     Wallet::delete(db, wallet_id, user_id).await?;
+    if wallet.allocation_percentage != 0.0 {
+        WalletAllocation::release(db, user_id, wallet.allocation_percentage).await?;
+    }
     Ok(())
 }
 
 pub async fn get_wallet_balance(
     db: &Database,
+    config: &Config,
     user_id: Uuid,
     wallet_id: Uuid,
 ) -> Result<f64, ServiceError> {
@@ -55,27 +199,75 @@ pub async fn get_wallet_balance(
     let wallet = Wallet::find_by_id(db, wallet_id, user_id).await?
         .ok_or_else(|| ServiceError::NotFound("Wallet not found".into()))?;
     
-    // In a real implementation, we would query the Solana blockchain
-    // For now, we'll return a mock balance
-    let config = Config::from_env();
-    let client = RpcClient::new(config.solana_rpc_url);
-    
     // Parse the wallet address
     let pubkey = wallet.address.parse::<Pubkey>()
         .map_err(|_| ServiceError::BadRequest("Invalid Solana address".into()))?;
     
-    // Get the balance
-    match client.get_balance(&pubkey) {
-        Ok(lamports) => {
-            // Convert lamports to SOL (1 SOL = 1,000,000,000 lamports)
-            let sol_balance = lamports as f64 / 1_000_000_000.0;
-            Ok(sol_balance)
-        },
-        Err(e) => {
-            // For demo purposes, return a mock balance if there's an error
-            // In production, you might want to propagate the error
-            let mock_balance = 35.42;
-            Ok(mock_balance)
-        }
+    // SOL balance only; the portfolio lists every token
+    let sol = token_by_mint(NATIVE_MINT)
+        .ok_or_else(|| ServiceError::InternalServerError("SOL is not configured".into()))?;
+    let client = NonblockingRpcClient::new(config.solana_rpc_url.clone());
+    token_balance(&client, &pubkey, &sol).await
+}
+
+/// Balance of `token` held by `owner`, reading the associated token account for SPL tokens.
+pub async fn token_balance(rpc: &NonblockingRpcClient, owner: &Pubkey, token: &TokenInfo) -> Result<f64, ServiceError> {
+    if token.mint == NATIVE_MINT {
+        let lamports = rpc.get_balance(owner).await
+            .map_err(|e| ServiceError::InternalServerError(format!("Failed to fetch SOL balance: {}", e)))?;
+        return Ok(from_base_units(lamports, token.decimals));
+    }
+    
+    let mint = Pubkey::from_str(token.mint)
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))?;
+    let address = associated_token_address(owner, &mint);
+    let account = rpc.get_account_with_commitment(&address, rpc.commitment()).await
+        .map_err(|e| ServiceError::InternalServerError(format!("Failed to fetch {} balance: {}", token.symbol, e)))?
+        .value;
+    // The token account is created by the swap if it does not exist yet; any other failure is
+    // reported rather than read as an empty balance
+    let Some(account) = account else {
+        return Ok(0.0);
+    };
+    // Token accounts start with the mint and owner, then the amount in base units
+    let amount = account.data.get(64..72)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or_else(|| ServiceError::InternalServerError(format!("{} is not a token account", address)))?;
+    Ok(from_base_units(amount, token.decimals))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocation_may_fill_up_to_the_cap() {
+        assert!(validate_allocation(40.0, 0.0, 60.0).is_ok());
+        assert!(validate_allocation(50.0, 0.0, 60.0).is_err());
+    }
+
+    #[test]
+    fn a_wallets_own_share_is_not_counted_twice() {
+        // 40% of the 90% already allocated belongs to the wallet being changed
+        assert!(validate_allocation(90.0, 40.0, 50.0).is_ok());
+        assert!(validate_allocation(90.0, 40.0, 60.0).is_err());
+    }
+
+    #[test]
+    fn users_over_the_cap_can_lower_allocations() {
+        assert!(validate_allocation(130.0, 50.0, 40.0).is_ok());
+        assert!(validate_allocation(130.0, 40.0, 50.0).is_err());
+    }
+
+    #[test]
+    fn allocation_must_be_a_percentage() {
+        assert!(validate_allocation(0.0, 0.0, -1.0).is_err());
+        assert!(validate_allocation(0.0, 0.0, 101.0).is_err());
+    }
+
+    #[test]
+    fn rounding_in_thirds_still_adds_up_to_100() {
+        assert!(validate_allocation(200.0 / 3.0, 0.0, 100.0 / 3.0).is_ok());
     }
-} 
\ No newline at end of file
+}
@@ -1,5 +1,5 @@
 use actix_web::{error::ResponseError, http::StatusCode, HttpResponse};
-use mongodb::error::{Error as MongoError, ErrorKind};
+use mongodb::error::{Error as MongoError, ErrorKind, WriteFailure};
 use serde::Serialize;
 use std::fmt;
 use thiserror::Error;
@@ -20,6 +20,12 @@ pub enum ServiceError {
     
     #[error("Conflict: {0}")]
     Conflict(String),
+    
+    #[error("Transaction did not land after {attempts} attempts: {reason}")]
+    RetriesExhausted { attempts: u32, reason: String },
+    
+    #[error("Transaction {signature} was sent but its status could not be determined; it is still being tracked")]
+    StatusUnknown { signature: String },
 }
 
 #[derive(Serialize)]
@@ -45,6 +51,8 @@ impl ResponseError for ServiceError {
             ServiceError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
             ServiceError::NotFound(_) => StatusCode::NOT_FOUND,
             ServiceError::Conflict(_) => StatusCode::CONFLICT,
+            ServiceError::RetriesExhausted { .. } => StatusCode::GATEWAY_TIMEOUT,
+            ServiceError::StatusUnknown { .. } => StatusCode::GATEWAY_TIMEOUT,
         }
     }
 }
@@ -52,8 +60,11 @@ impl ResponseError for ServiceError {
 impl From<MongoError> for ServiceError {
     fn from(error: MongoError) -> ServiceError {
         match *error.kind {
+            // Duplicate key error, from a command or from a single-document write
             ErrorKind::Command(ref cmd_err) if cmd_err.code == 11000 => {
-                // Duplicate key error
+                ServiceError::Conflict("Resource already exists".into())
+            }
+            ErrorKind::Write(WriteFailure::WriteError(ref write_err)) if write_err.code == 11000 => {
                 ServiceError::Conflict("Resource already exists".into())
             }
             _ => ServiceError::InternalServerError(error.to_string()),
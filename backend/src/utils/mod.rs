@@ -1,2 +1,3 @@
 pub mod auth;
-pub mod errors; 
\ No newline at end of file
+pub mod errors;
+pub mod tokens;
\ No newline at end of file
@@ -0,0 +1,102 @@
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{system_instruction, system_program};
+use std::str::FromStr;
+
+pub const SPL_TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+pub const NATIVE_MINT: &str = "So11111111111111111111111111111111111111112";
+
+// TransferChecked is instruction 12 of the token program, followed by a u64 amount and the mint's decimals
+const TRANSFER_CHECKED_TAG: u8 = 12;
+// CreateIdempotent is instruction 1 of the associated token account program
+const CREATE_IDEMPOTENT_TAG: u8 = 1;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TokenInfo {
+    pub symbol: &'static str,
+    pub mint: &'static str,
+    pub decimals: u8,
+}
+
+// Tokens the backend knows how to trade
+pub const KNOWN_TOKENS: &[TokenInfo] = &[
+    TokenInfo { symbol: "SOL", mint: NATIVE_MINT, decimals: 9 },
+    TokenInfo { symbol: "USDC", mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", decimals: 6 },
+    TokenInfo { symbol: "USDT", mint: "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB", decimals: 6 },
+    TokenInfo { symbol: "BTC", mint: "3NZ9JMVBmGAqocybic2c8LQCJScmgsAZ6vQqTDzcqmJh", decimals: 8 },
+];
+
+pub fn token_by_symbol(symbol: &str) -> Option<TokenInfo> {
+    KNOWN_TOKENS
+        .iter()
+        .find(|t| t.symbol.eq_ignore_ascii_case(symbol))
+        .copied()
+}
+
+pub fn token_by_mint(mint: &str) -> Option<TokenInfo> {
+    KNOWN_TOKENS.iter().find(|t| t.mint == mint).copied()
+}
+
+/// Converts a UI amount (e.g. 1.5 SOL) into the token's base units.
+pub fn to_base_units(amount: f64, decimals: u8) -> u64 {
+    (amount * 10f64.powi(decimals as i32)).round() as u64
+}
+
+pub fn from_base_units(amount: u64, decimals: u8) -> f64 {
+    amount as f64 / 10f64.powi(decimals as i32)
+}
+
+pub fn associated_token_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    let token_program = Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap();
+    let ata_program = Pubkey::from_str(ASSOCIATED_TOKEN_PROGRAM_ID).unwrap();
+    Pubkey::find_program_address(
+        &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ata_program,
+    )
+    .0
+}
+
+/// Instructions moving `amount` base units of `token` from `owner` to `recipient`. SPL tokens go
+/// between associated token accounts, creating the recipient's at `owner`'s expense if needed.
+pub fn transfer_instructions(owner: &Pubkey, recipient: &Pubkey, token: &TokenInfo, amount: u64) -> Vec<Instruction> {
+    if token.mint == NATIVE_MINT {
+        return vec![system_instruction::transfer(owner, recipient, amount)];
+    }
+
+    let token_program = Pubkey::from_str(SPL_TOKEN_PROGRAM_ID).unwrap();
+    let ata_program = Pubkey::from_str(ASSOCIATED_TOKEN_PROGRAM_ID).unwrap();
+    let mint = Pubkey::from_str(token.mint).unwrap();
+    let source = associated_token_address(owner, &mint);
+    let destination = associated_token_address(recipient, &mint);
+
+    let create_destination = Instruction {
+        program_id: ata_program,
+        accounts: vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(destination, false),
+            AccountMeta::new_readonly(*recipient, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(token_program, false),
+        ],
+        data: vec![CREATE_IDEMPOTENT_TAG],
+    };
+
+    let mut data = vec![TRANSFER_CHECKED_TAG];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(token.decimals);
+    let transfer = Instruction {
+        program_id: token_program,
+        accounts: vec![
+            AccountMeta::new(source, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new(destination, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data,
+    };
+
+    vec![create_destination, transfer]
+}
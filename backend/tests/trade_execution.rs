@@ -0,0 +1,285 @@
+//! Runs a live trade through `execute_trade` with the aggregator and the Solana RPC node both
+//! stubbed by a local HTTP server. Needs a MongoDB instance:
+//!
+//!     TEST_MONGODB_URI=mongodb://localhost:27017 cargo test --test trade_execution -- --ignored
+
+use actix_web::{web, App, HttpResponse, HttpServer};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde_json::{json, Value};
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::message::{Message, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{write_keypair_file, Keypair, Signature, Signer};
+use solana_sdk::transaction::VersionedTransaction;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use solana_market_nexus_backend::config::Config;
+use solana_market_nexus_backend::db::init_db;
+use solana_market_nexus_backend::models::transaction::TransactionStatus;
+use solana_market_nexus_backend::models::wallet::Wallet;
+use solana_market_nexus_backend::services::aggregator::JupiterClient;
+use solana_market_nexus_backend::services::keystore::Keystore;
+use solana_market_nexus_backend::services::market_maker::MarketMakingEngine;
+use solana_market_nexus_backend::services::self_trade::SelfTradePolicy;
+use solana_market_nexus_backend::services::signer::{Signers, JUPITER_PROGRAM_ID};
+use solana_market_nexus_backend::services::trading::execute_trade;
+use solana_market_nexus_backend::services::venues::paper::PaperVenue;
+use solana_market_nexus_backend::utils::tokens::{NATIVE_MINT, SPL_TOKEN_PROGRAM_ID};
+
+const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+const LAST_VALID_BLOCK_HEIGHT: u64 = 1_000;
+const NETWORK_FEE: u64 = 5_000;
+
+// Selling 1 SOL is quoted at 150 USDC and lands at 149.8
+const QUOTED_OUT: u64 = 150_000_000;
+const RECEIVED_OUT: u64 = 149_800_000;
+
+/// What the stub server was asked, for the test to check afterwards.
+struct Stub {
+    owner: Pubkey,
+    quote_queries: Mutex<Vec<HashMap<String, String>>>,
+    swap_requests: Mutex<Vec<Value>>,
+    sent: Mutex<Vec<VersionedTransaction>>,
+}
+
+async fn quote(query: web::Query<HashMap<String, String>>, stub: web::Data<Stub>) -> HttpResponse {
+    stub.quote_queries.lock().unwrap().push(query.into_inner());
+    HttpResponse::Ok().json(json!({
+        "inputMint": NATIVE_MINT,
+        "outputMint": USDC_MINT,
+        "inAmount": "1000000000",
+        "outAmount": QUOTED_OUT.to_string(),
+        "otherAmountThreshold": "149250000",
+        "swapMode": "ExactIn",
+        "slippageBps": 50,
+        "priceImpactPct": "0.01",
+        "routePlan": [],
+    }))
+}
+
+async fn swap(body: web::Json<Value>, stub: web::Data<Stub>) -> HttpResponse {
+    let user = Pubkey::from_str(body["userPublicKey"].as_str().unwrap_or_default()).unwrap();
+    stub.swap_requests.lock().unwrap().push(body.into_inner());
+
+    let jupiter = Pubkey::from_str(JUPITER_PROGRAM_ID).unwrap();
+    let instruction = Instruction::new_with_bytes(jupiter, &[1], vec![AccountMeta::new(user, true)]);
+    let transaction = VersionedTransaction {
+        signatures: vec![Signature::default()],
+        message: VersionedMessage::Legacy(Message::new(&[instruction], Some(&user))),
+    };
+    HttpResponse::Ok().json(json!({
+        "swapTransaction": BASE64.encode(bincode::serialize(&transaction).unwrap()),
+        "lastValidBlockHeight": LAST_VALID_BLOCK_HEIGHT,
+    }))
+}
+
+fn token_balance(owner: &Pubkey, amount: u64) -> Value {
+    json!({
+        "accountIndex": 1,
+        "mint": USDC_MINT,
+        "owner": owner.to_string(),
+        "programId": SPL_TOKEN_PROGRAM_ID,
+        "uiTokenAmount": {
+            "uiAmount": amount as f64 / 1e6,
+            "decimals": 6,
+            "amount": amount.to_string(),
+            "uiAmountString": (amount as f64 / 1e6).to_string(),
+        },
+    })
+}
+
+async fn rpc(request: web::Json<Value>, stub: web::Data<Stub>) -> HttpResponse {
+    let params = &request["params"];
+    let result = match request["method"].as_str().unwrap_or_default() {
+        "getVersion" => json!({ "solana-core": "1.18.26", "feature-set": 0 }),
+        "simulateTransaction" => json!({
+            "context": { "slot": 1 },
+            "value": { "err": null, "logs": [], "accounts": null, "unitsConsumed": 100_000, "returnData": null },
+        }),
+        "getRecentPrioritizationFees" => json!([{ "slot": 1, "prioritizationFee": 1_000 }]),
+        "getLatestBlockhash" => json!({
+            "context": { "slot": 1 },
+            "value": { "blockhash": Hash::new_unique().to_string(), "lastValidBlockHeight": LAST_VALID_BLOCK_HEIGHT },
+        }),
+        "sendTransaction" => {
+            let bytes = BASE64.decode(params[0].as_str().unwrap_or_default()).unwrap();
+            let transaction: VersionedTransaction = bincode::deserialize(&bytes).unwrap();
+            let signature = transaction.signatures[0].to_string();
+            stub.sent.lock().unwrap().push(transaction);
+            json!(signature)
+        }
+        "getSignatureStatuses" => {
+            let count = params[0].as_array().map_or(0, |signatures| signatures.len());
+            let landed = json!({
+                "slot": 1,
+                "confirmations": null,
+                "err": null,
+                "status": { "Ok": null },
+                "confirmationStatus": "finalized",
+            });
+            json!({ "context": { "slot": 1 }, "value": vec![landed; count] })
+        }
+        "getBlockHeight" => json!(10),
+        "getTransaction" => {
+            let sent = stub.sent.lock().unwrap();
+            let encoded = sent.last().map(|tx| BASE64.encode(bincode::serialize(tx).unwrap())).unwrap_or_default();
+            // 1 SOL sold, on top of the network fee
+            json!({
+                "slot": 1,
+                "blockTime": null,
+                "transaction": [encoded, "base64"],
+                "meta": {
+                    "err": null,
+                    "status": { "Ok": null },
+                    "fee": NETWORK_FEE,
+                    "preBalances": [2_000_000_000u64, 0],
+                    "postBalances": [1_000_000_000u64 - NETWORK_FEE, 0],
+                    "preTokenBalances": [token_balance(&stub.owner, 0)],
+                    "postTokenBalances": [token_balance(&stub.owner, RECEIVED_OUT)],
+                    "logMessages": [],
+                    "rewards": [],
+                },
+            })
+        }
+        method => {
+            return HttpResponse::Ok().json(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": { "code": -32601, "message": format!("{} is not stubbed", method) },
+            }))
+        }
+    };
+    HttpResponse::Ok().json(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }))
+}
+
+fn test_config(mongodb_uri: String, stub_url: &str, keypair_path: &str) -> Config {
+    Config {
+        mongodb_uri,
+        mongodb_database: format!("trade_execution_{}", Uuid::new_v4().simple()),
+        jwt_secret: "test".to_string(),
+        jwt_expiration: 3600,
+        solana_rpc_url: stub_url.to_string(),
+        engine_sync_interval_secs: 10,
+        quote_spread_bps: 20.0,
+        aggregator_url: stub_url.to_string(),
+        price_max_age_secs: 30,
+        default_slippage_bps: 50,
+        trading_keypair_path: Some(keypair_path.to_string()),
+        openbook_markets: HashMap::new(),
+        confirmation_poll_interval_secs: 5,
+        paper_trading: false,
+        paper_maker_fee_bps: 0.0,
+        paper_taker_fee_bps: 10.0,
+        circuit_breaker_interval_secs: 15,
+        breaker_max_consecutive_failures: 3,
+        breaker_max_daily_loss: 0.0,
+        breaker_max_market_data_age_secs: 60,
+        breaker_max_rpc_failures: 3,
+        market_data_record_interval_secs: 60,
+        self_trade_policy: SelfTradePolicy::Reject,
+        parent_order_interval_secs: 5,
+        submit_max_attempts: 1,
+        rebroadcast_interval_ms: 100,
+        resubmit_fee_bump_pct: 0.0,
+        keystore_export_enabled: false,
+        rebalance_interval_secs: 3600,
+        rebalance_threshold_pct: 5.0,
+        top_up_interval_secs: 60,
+        top_up_daily_cap: 1.0,
+        remote_signer_url: None,
+        remote_signer_ca_cert: None,
+        remote_signer_client_cert: None,
+        remote_signer_client_key: None,
+    }
+}
+
+#[actix_web::test]
+#[ignore = "needs MongoDB at TEST_MONGODB_URI"]
+async fn sell_is_quoted_built_signed_and_settled_from_the_landed_balances() {
+    let mongodb_uri = std::env::var("TEST_MONGODB_URI").expect("TEST_MONGODB_URI must be set");
+
+    let keypair = Keypair::new();
+    let keypair_path = std::env::temp_dir().join(format!("trade-execution-{}.json", Uuid::new_v4()));
+    write_keypair_file(&keypair, &keypair_path).unwrap();
+
+    let stub = web::Data::new(Stub {
+        owner: keypair.pubkey(),
+        quote_queries: Mutex::new(Vec::new()),
+        swap_requests: Mutex::new(Vec::new()),
+        sent: Mutex::new(Vec::new()),
+    });
+    let server_stub = stub.clone();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(server_stub.clone())
+            .route("/", web::post().to(rpc))
+            .route("/quote", web::get().to(quote))
+            .route("/swap", web::post().to(swap))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let stub_url = format!("http://{}", server.addrs()[0]);
+    actix_web::rt::spawn(server.run());
+
+    let config = test_config(mongodb_uri, &stub_url, keypair_path.to_str().unwrap());
+    let db = init_db(&config).await;
+    let paper = Arc::new(PaperVenue::new(db.clone(), config.paper_maker_fee_bps, config.paper_taker_fee_bps));
+    let keystore = Arc::new(Keystore::locked(db.clone()));
+    let signers = Arc::new(Signers::new(keystore, &config).unwrap());
+    let engine = MarketMakingEngine::new(db.clone(), config.clone(), paper.clone(), signers);
+    let aggregator = JupiterClient::new(&config.aggregator_url);
+
+    let user_id = Uuid::new_v4();
+    let wallet = Wallet::create(&db, user_id, &keypair.pubkey().to_string(), Some("Trading"), None, None).await.unwrap();
+    // Built the way the API receives it
+    let request = serde_json::from_value(json!({
+        "wallet_id": wallet.id,
+        "action": "sell",
+        "amount": 1.0,
+        "token": "SOL",
+        "price": null,
+        "strategy_id": null,
+    })).unwrap();
+
+    let result = execute_trade(&db, &config, &engine, &aggregator, &paper, user_id, request).await;
+    db.drop(None).await.unwrap();
+    std::fs::remove_file(&keypair_path).ok();
+    let transaction = result.expect("trade should execute");
+
+    // Sells spend exactly the amount, within the configured slippage
+    let queries = stub.quote_queries.lock().unwrap();
+    assert_eq!(queries.len(), 1);
+    assert_eq!(queries[0]["inputMint"], NATIVE_MINT);
+    assert_eq!(queries[0]["outputMint"], USDC_MINT);
+    assert_eq!(queries[0]["amount"], "1000000000");
+    assert_eq!(queries[0]["swapMode"], "ExactIn");
+    assert_eq!(queries[0]["slippageBps"], "50");
+
+    // The swap is built from the aggregator's own quote for the wallet's key
+    let swaps = stub.swap_requests.lock().unwrap();
+    assert_eq!(swaps.len(), 1);
+    assert_eq!(swaps[0]["userPublicKey"], keypair.pubkey().to_string());
+    assert_eq!(swaps[0]["quoteResponse"]["outAmount"], QUOTED_OUT.to_string());
+
+    // Exactly one transaction went out, signed by the wallet
+    let sent = stub.sent.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].verify_with_results().into_iter().all(|valid| valid));
+    assert_eq!(sent[0].message.static_account_keys()[0], keypair.pubkey());
+
+    assert_eq!(transaction.status, TransactionStatus::Finalized);
+    assert_eq!(transaction.transaction_hash, Some(sent[0].signatures[0].to_string()));
+    assert_eq!(transaction.attempts.len(), 1);
+    assert_eq!(transaction.network_fee, Some(NETWORK_FEE));
+    assert_eq!(transaction.compute_unit_price, Some(1_000));
+
+    // Price and slippage come from what moved on-chain, not from the quote
+    assert!((transaction.price - 149.8).abs() < 1e-9, "executed price {}", transaction.price);
+    let slippage = transaction.slippage.expect("slippage should be measured");
+    assert!((slippage - (150.0 - 149.8) / 150.0 * 100.0).abs() < 1e-9, "slippage {}", slippage);
+}
@@ -20,14 +20,16 @@ export function WalletStrategyPanel() {
               <div className="space-y-6">
                 <div className="space-y-2">
                   <Label>Strategy Type</Label>
-                  <Select defaultValue="market-making">
+                  <Select defaultValue="fixed-spread">
                     <SelectTrigger>
                       <SelectValue placeholder="Select a strategy" />
                     </SelectTrigger>
                     <SelectContent>
-                      <SelectItem value="market-making">Market Making</SelectItem>
-                      <SelectItem value="price-influence">Price Influence</SelectItem>
-                      <SelectItem value="stealth-trading">Stealth Trading</SelectItem>
+                      <SelectItem value="fixed-spread">Fixed Spread</SelectItem>
+                      <SelectItem value="grid">Grid</SelectItem>
+                      <SelectItem value="inventory-skewed">Inventory Skewed</SelectItem>
+                      <SelectItem value="avellaneda-stoikov">Avellaneda–Stoikov</SelectItem>
+                      <SelectItem value="twap">TWAP</SelectItem>
                     </SelectContent>
                   </Select>
                 </div>
@@ -47,14 +49,12 @@ export function WalletStrategyPanel() {
                 
                 <div className="space-y-2">
                   <Label>Execution Platform</Label>
-                  <Select defaultValue="raydium">
+                  <Select defaultValue="openbook-v2">
                     <SelectTrigger>
                       <SelectValue placeholder="Select platform" />
                     </SelectTrigger>
                     <SelectContent>
-                      <SelectItem value="raydium">Raydium</SelectItem>
-                      <SelectItem value="orca">Orca</SelectItem>
-                      <SelectItem value="jupiter">Jupiter</SelectItem>
+                      <SelectItem value="openbook-v2">OpenBook v2</SelectItem>
                     </SelectContent>
                   </Select>
                 </div>